@@ -0,0 +1,20 @@
+//! FFmpeg throughput per pipeline stage on synthetic frames: `cargo bench`
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use media_processing_service::services::benchmark::{self, Synthetic, CASES};
+
+fn pipeline(c: &mut Criterion) {
+    let input = Synthetic { width: 1280, height: 720, frames: 30 };
+    let mut group = c.benchmark_group("ffmpeg_720p");
+    // Each iteration spawns FFmpeg, so keep the sample count low
+    group.sample_size(10);
+    group.throughput(Throughput::Elements(input.frames as u64));
+    for case in CASES {
+        group.bench_function(case.name, |b| {
+            b.iter(|| benchmark::run_case(case, &input).expect("ffmpeg benchmark run failed"))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, pipeline);
+criterion_main!(benches);
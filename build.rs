@@ -0,0 +1,7 @@
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // Protobuf code generation is only needed for the optional gRPC server
+    #[cfg(feature = "grpc")]
+    tonic_build::compile_protos("proto/media.proto")?;
+
+    Ok(())
+}
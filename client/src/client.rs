@@ -0,0 +1,148 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::time::Duration;
+
+use crate::error::{ClientError, Problem};
+use crate::models::{
+    ExtractAudioRequest, Job, MirrorSyncRequest, ProcessingResult, TranscodeAudioRequest, TranscodeVideoRequest,
+};
+
+/// Client for the `/api/v2` endpoints of the media processing service
+#[derive(Debug, Clone)]
+pub struct Client {
+    base_url: String,
+    http: reqwest::Client,
+    api_key: Option<String>,
+}
+
+impl Client {
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self::with_http_client(base_url, reqwest::Client::new())
+    }
+
+    /// Use a preconfigured `reqwest::Client` (timeouts, proxies, TLS roots)
+    pub fn with_http_client(base_url: impl Into<String>, http: reqwest::Client) -> Self {
+        let base_url = base_url.into().trim_end_matches('/').to_string();
+        Self { base_url, http, api_key: None }
+    }
+
+    /// Send `X-API-Key` with every request, for services that have `API_KEYS` configured
+    pub fn api_key(mut self, api_key: impl Into<String>) -> Self {
+        self.api_key = Some(api_key.into());
+        self
+    }
+
+    fn request(&self, method: reqwest::Method, url: String) -> reqwest::RequestBuilder {
+        let request = self.http.request(method, url);
+        match &self.api_key {
+            Some(api_key) => request.header("x-api-key", api_key),
+            None => request,
+        }
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}/api/v2{}", self.base_url, path)
+    }
+
+    pub async fn transcode_video(&self, request: &TranscodeVideoRequest) -> Result<ProcessingResult, ClientError> {
+        self.post("/video/transcode", request).await
+    }
+
+    /// Transcode into every quality profile and package an HLS master playlist
+    pub async fn transcode_hls(&self, request: &TranscodeVideoRequest) -> Result<ProcessingResult, ClientError> {
+        self.post("/video/hls", request).await
+    }
+
+    pub async fn extract_audio(&self, request: &ExtractAudioRequest) -> Result<ProcessingResult, ClientError> {
+        self.post("/audio/extract", request).await
+    }
+
+    pub async fn transcode_audio(&self, request: &TranscodeAudioRequest) -> Result<ProcessingResult, ClientError> {
+        self.post("/audio/transcode", request).await
+    }
+
+    /// ffprobe metadata is returned in `ProcessingResult::metadata`
+    pub async fn extract_metadata(&self, file_path: &str) -> Result<ProcessingResult, ClientError> {
+        self.post("/metadata/extract", &serde_json::json!({ "file_path": file_path })).await
+    }
+
+    pub async fn mirror(&self, request: &MirrorSyncRequest) -> Result<ProcessingResult, ClientError> {
+        self.post("/sync/mirror", request).await
+    }
+
+    pub async fn job(&self, job_id: &str) -> Result<Job, ClientError> {
+        let response = self.request(reqwest::Method::GET, self.url(&format!("/jobs/{}", job_id))).send().await?;
+        Self::parse(response).await
+    }
+
+    /// Poll a job every `interval` until it completes or fails; check `Job::status` for which
+    pub async fn wait_for_job(&self, job_id: &str, interval: Duration) -> Result<Job, ClientError> {
+        loop {
+            let job = self.job(job_id).await?;
+            if job.is_finished() {
+                return Ok(job);
+            }
+            tokio::time::sleep(interval).await;
+        }
+    }
+
+    pub async fn capabilities(&self) -> Result<serde_json::Value, ClientError> {
+        let response = self.request(reqwest::Method::GET, self.url("/capabilities")).send().await?;
+        Self::parse(response).await
+    }
+
+    pub async fn health(&self) -> Result<serde_json::Value, ClientError> {
+        let response = self.request(reqwest::Method::GET, format!("{}/health", self.base_url)).send().await?;
+        Self::parse(response).await
+    }
+
+    async fn post<B: Serialize + ?Sized, T: DeserializeOwned>(&self, path: &str, body: &B) -> Result<T, ClientError> {
+        let response = self.request(reqwest::Method::POST, self.url(path)).json(body).send().await?;
+        Self::parse(response).await
+    }
+
+    /// Decode a success body, or turn the service's problem+json into `ClientError::Api`
+    async fn parse<T: DeserializeOwned>(response: reqwest::Response) -> Result<T, ClientError> {
+        if response.status().is_success() {
+            return Ok(response.json().await?);
+        }
+
+        let status = response.status();
+        let body = response.text().await?;
+        let problem = serde_json::from_str::<Problem>(&body).unwrap_or_else(|_| Problem {
+            problem_type: "about:blank".to_string(),
+            title: status.canonical_reason().unwrap_or("Unknown").to_string(),
+            status: status.as_u16(),
+            code: "unknown".to_string(),
+            detail: (!body.is_empty()).then_some(body),
+            violations: None,
+        });
+        Err(ClientError::Api(problem))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builder_skips_unset_fields() {
+        let request = TranscodeVideoRequest::new("in.mp4", "out.mp4").codec("libx264").resolution(1280, 720);
+        let body = serde_json::to_value(&request).unwrap();
+        assert_eq!(
+            body,
+            serde_json::json!({
+                "input_path": "in.mp4",
+                "output_path": "out.mp4",
+                "codec": "libx264",
+                "resolution": "1280x720",
+            })
+        );
+    }
+
+    #[test]
+    fn test_base_url_trailing_slash() {
+        let client = Client::new("http://localhost:8082/");
+        assert_eq!(client.url("/video/transcode"), "http://localhost:8082/api/v2/video/transcode");
+    }
+}
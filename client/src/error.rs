@@ -0,0 +1,41 @@
+use derive_more::{Display, From};
+use serde::Deserialize;
+
+/// RFC 7807 problem body returned by the service for every error
+#[derive(Debug, Clone, Deserialize)]
+pub struct Problem {
+    #[serde(rename = "type")]
+    pub problem_type: String,
+    pub title: String,
+    pub status: u16,
+    pub code: String,
+    pub detail: Option<String>,
+    pub violations: Option<Vec<Violation>>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Violation {
+    pub field: String,
+    pub message: String,
+}
+
+#[derive(Debug, Display, From)]
+pub enum ClientError {
+    #[display(fmt = "HTTP error: {}", _0)]
+    Http(reqwest::Error),
+
+    #[display(fmt = "API error {} ({}): {}", "_0.status", "_0.code", "_0.detail.as_deref().unwrap_or(&_0.title)")]
+    Api(Problem),
+}
+
+impl std::error::Error for ClientError {}
+
+impl ClientError {
+    /// Stable service error code (e.g. `codec_unsupported`), if the service answered
+    pub fn code(&self) -> Option<&str> {
+        match self {
+            ClientError::Api(problem) => Some(&problem.code),
+            ClientError::Http(_) => None,
+        }
+    }
+}
@@ -0,0 +1,22 @@
+//! Typed async client for the media processing service.
+//!
+//! ```no_run
+//! use photo_rust_client::{Client, models::TranscodeVideoRequest};
+//!
+//! # async fn run() -> Result<(), photo_rust_client::ClientError> {
+//! let client = Client::new("http://localhost:8082");
+//! let request = TranscodeVideoRequest::new("/data/in.mp4", "/data/out.webm")
+//!     .codec("libvpx")
+//!     .bitrate("1M");
+//! let result = client.transcode_video(&request).await?;
+//! println!("{} -> {:?}", result.job_id, result.outputs);
+//! # Ok(())
+//! # }
+//! ```
+
+mod client;
+mod error;
+pub mod models;
+
+pub use client::Client;
+pub use error::{ClientError, Problem};
@@ -0,0 +1,286 @@
+use serde::{Deserialize, Serialize};
+
+/// Response shape shared by every v2 processing endpoint
+#[derive(Debug, Clone, Deserialize)]
+pub struct ProcessingResult {
+    /// Schema revision; results from services predating the field count as version 1
+    #[serde(default = "default_schema_version")]
+    pub schema_version: u32,
+    pub job_id: String,
+    pub operation: String,
+    pub status: String,
+    pub outputs: Vec<String>,
+    /// Signed download links, present when the service has `SIGNED_URL_SECRET` set
+    #[serde(default)]
+    pub output_urls: Option<Vec<String>>,
+    pub processing_time_ms: u64,
+    pub metadata: Option<serde_json::Value>,
+}
+
+fn default_schema_version() -> u32 {
+    1
+}
+
+/// A job as tracked by the service, from `GET /jobs/{job_id}`
+#[derive(Debug, Clone, Deserialize)]
+pub struct Job {
+    pub job_id: String,
+    #[serde(rename = "type")]
+    pub job_type: String,
+    /// `running`, `completed` or `failed`
+    pub status: String,
+    pub input_path: String,
+    pub output_path: String,
+    pub created_at: String,
+    pub finished_at: Option<String>,
+    pub processing_time_ms: Option<u64>,
+    pub error: Option<String>,
+    #[serde(default)]
+    pub progress: Option<JobProgress>,
+    /// FFmpeg runs that failed transiently and were retried
+    #[serde(default)]
+    pub attempts: Vec<JobAttempt>,
+}
+
+impl Job {
+    pub fn is_finished(&self) -> bool {
+        matches!(self.status.as_str(), "completed" | "failed" | "cancelled")
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct JobAttempt {
+    pub attempt: u32,
+    pub operation: String,
+    pub started_at: String,
+    pub failed_at: String,
+    pub error: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct JobProgress {
+    pub percent: f64,
+    pub completed: Option<usize>,
+    pub total: Option<usize>,
+    pub current_file: Option<String>,
+    pub eta_secs: Option<f64>,
+    pub bitrate: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TranscodeVideoRequest {
+    pub input_path: String,
+    pub output_path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub format: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub codec: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bitrate: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resolution: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fps: Option<u32>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub filters: Vec<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub priority: Option<String>,
+}
+
+impl TranscodeVideoRequest {
+    pub fn new(input_path: impl Into<String>, output_path: impl Into<String>) -> Self {
+        Self {
+            input_path: input_path.into(),
+            output_path: output_path.into(),
+            format: None,
+            codec: None,
+            bitrate: None,
+            resolution: None,
+            fps: None,
+            filters: Vec::new(),
+            priority: None,
+        }
+    }
+
+    pub fn format(mut self, format: impl Into<String>) -> Self {
+        self.format = Some(format.into());
+        self
+    }
+
+    pub fn codec(mut self, codec: impl Into<String>) -> Self {
+        self.codec = Some(codec.into());
+        self
+    }
+
+    pub fn bitrate(mut self, bitrate: impl Into<String>) -> Self {
+        self.bitrate = Some(bitrate.into());
+        self
+    }
+
+    /// Output size as `WIDTHxHEIGHT`
+    pub fn resolution(mut self, width: u32, height: u32) -> Self {
+        self.resolution = Some(format!("{}x{}", width, height));
+        self
+    }
+
+    pub fn fps(mut self, fps: u32) -> Self {
+        self.fps = Some(fps);
+        self
+    }
+
+    /// Append a filter step, e.g. `json!({"type": "eq", "contrast": 1.1})`; steps run in the order added
+    pub fn filter(mut self, filter: serde_json::Value) -> Self {
+        self.filters.push(filter);
+        self
+    }
+
+    /// `low`, `normal` (default) or `high`; interactive requests should use `high`
+    pub fn priority(mut self, priority: impl Into<String>) -> Self {
+        self.priority = Some(priority.into());
+        self
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ExtractAudioRequest {
+    pub input_path: String,
+    pub output_path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub format: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bitrate: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub priority: Option<String>,
+}
+
+impl ExtractAudioRequest {
+    pub fn new(input_path: impl Into<String>, output_path: impl Into<String>) -> Self {
+        Self {
+            input_path: input_path.into(),
+            output_path: output_path.into(),
+            format: None,
+            bitrate: None,
+            priority: None,
+        }
+    }
+
+    /// Audio encoder, e.g. `libmp3lame` or `aac`
+    pub fn format(mut self, format: impl Into<String>) -> Self {
+        self.format = Some(format.into());
+        self
+    }
+
+    pub fn bitrate(mut self, bitrate: impl Into<String>) -> Self {
+        self.bitrate = Some(bitrate.into());
+        self
+    }
+
+    pub fn priority(mut self, priority: impl Into<String>) -> Self {
+        self.priority = Some(priority.into());
+        self
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TranscodeAudioRequest {
+    pub input_path: String,
+    pub output_path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub format: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub priority: Option<String>,
+}
+
+impl TranscodeAudioRequest {
+    pub fn new(input_path: impl Into<String>, output_path: impl Into<String>) -> Self {
+        Self {
+            input_path: input_path.into(),
+            output_path: output_path.into(),
+            format: None,
+            priority: None,
+        }
+    }
+
+    pub fn format(mut self, format: impl Into<String>) -> Self {
+        self.format = Some(format.into());
+        self
+    }
+
+    pub fn priority(mut self, priority: impl Into<String>) -> Self {
+        self.priority = Some(priority.into());
+        self
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DerivativeSpec {
+    pub format: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_dimension: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quality: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub colors: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dither: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MirrorSyncRequest {
+    pub source_dir: String,
+    pub output_dir: String,
+    pub derivative: DerivativeSpec,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prune: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub priority: Option<String>,
+}
+
+impl MirrorSyncRequest {
+    pub fn new(source_dir: impl Into<String>, output_dir: impl Into<String>, format: impl Into<String>) -> Self {
+        Self {
+            source_dir: source_dir.into(),
+            output_dir: output_dir.into(),
+            derivative: DerivativeSpec {
+                format: format.into(),
+                max_dimension: None,
+                quality: None,
+                colors: None,
+                dither: None,
+            },
+            prune: None,
+            priority: None,
+        }
+    }
+
+    pub fn max_dimension(mut self, max_dimension: u32) -> Self {
+        self.derivative.max_dimension = Some(max_dimension);
+        self
+    }
+
+    pub fn quality(mut self, quality: u32) -> Self {
+        self.derivative.quality = Some(quality);
+        self
+    }
+
+    /// Palette size for GIF, or for PNG to get an indexed PNG8
+    pub fn colors(mut self, colors: u32) -> Self {
+        self.derivative.colors = Some(colors);
+        self
+    }
+
+    pub fn dither(mut self, dither: impl Into<String>) -> Self {
+        self.derivative.dither = Some(dither.into());
+        self
+    }
+
+    pub fn prune(mut self, prune: bool) -> Self {
+        self.prune = Some(prune);
+        self
+    }
+
+    pub fn priority(mut self, priority: impl Into<String>) -> Self {
+        self.priority = Some(priority.into());
+        self
+    }
+}
@@ -0,0 +1,14 @@
+//! EXIF blocks of uploaded JPEGs, read for their GPS position and blanked in place when it
+//! falls inside a privacy zone
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use media_processing_service::services::exif;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = exif::gps(data);
+    let mut stripped = data.to_vec();
+    if exif::strip_gps(&mut stripped) {
+        assert_eq!(stripped.len(), data.len());
+        assert_eq!(exif::gps(&stripped), None);
+    }
+});
@@ -0,0 +1,20 @@
+//! Parsers of FFmpeg and whisper.cpp log output. Filenames and metadata end up in those logs,
+//! so their lines are partly input-controlled
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use media_processing_service::models::image::parse_crop;
+use media_processing_service::services::{autotrim, faces, frames, hls, language, quality, scenes};
+use media_processing_service::utils::validation::parse_resolution;
+
+fuzz_target!(|text: &str| {
+    let _ = autotrim::parse_cropdetect(text);
+    let _ = frames::parse_showinfo(text, 1.5);
+    let _ = faces::parse_detections(text);
+    let _ = language::parse_detected(text);
+    let _ = quality::parse_ssim(text);
+    let _ = quality::parse_vmaf(text);
+    let _ = scenes::thin(scenes::parse_cuts(text), 1.0, 10);
+    let _ = hls::parse_bitrate(text);
+    let _ = parse_crop(text);
+    let _ = parse_resolution(text);
+});
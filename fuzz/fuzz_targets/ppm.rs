@@ -0,0 +1,22 @@
+//! The frame loader behind watermark embedding and detection and scan orientation, fed what a
+//! hostile input could make FFmpeg write: any PPM it parses must survive a round trip and both
+//! analyses
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use media_processing_service::services::orientation;
+use media_processing_service::services::watermark::{self, Rgb};
+
+fuzz_target!(|data: &[u8]| {
+    let Some(image) = Rgb::from_ppm(data) else {
+        return;
+    };
+    assert_eq!(image.pixels.len(), image.width * image.height * 3);
+    assert_eq!(Rgb::from_ppm(&image.to_ppm()).as_ref(), Some(&image));
+    if watermark::fits(&image) {
+        let detection = watermark::detect(&image);
+        assert!((0.0..=1.0).contains(&detection.confidence));
+    }
+    if let Some(found) = orientation::detect(&image) {
+        assert!(matches!(found.rotation, 0 | 90 | 180 | 270));
+    }
+});
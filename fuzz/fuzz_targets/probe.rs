@@ -0,0 +1,24 @@
+//! ffprobe JSON, which carries whatever the container and EXIF headers claim: stream sizes,
+//! bit depths, rates, tags. Estimates must stay sane for absurd values
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use media_processing_service::services::hls::{self, Rendition};
+use media_processing_service::services::memory::{Footprint, MemoryBudget};
+use media_processing_service::services::probe;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(value) = serde_json::from_slice::<serde_json::Value>(data) else {
+        return;
+    };
+    let _ = probe::duration(&value);
+    let _ = probe::dimensions(&value);
+    let _ = probe::has_rotation(&value);
+    let _ = hls::audio_tracks(&value);
+    let _ = Rendition::from_probe("fuzz.m3u8".to_string(), &value, Some(1 << 20));
+    if let Some(footprint) = Footprint::from_probe(&value) {
+        let budget = MemoryBudget::new(Some(256 << 20), true);
+        let plan = budget.plan_image(&footprint);
+        assert!(plan.lowres <= 3);
+        let _ = budget.plan_video(&footprint);
+    }
+});
@@ -0,0 +1,59 @@
+//! Request bodies as clients send them: the first byte picks the request type, the rest is
+//! its JSON. Anything may be rejected; nothing may panic
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use media_processing_service::models::image::*;
+use media_processing_service::models::job::JobQuery;
+use media_processing_service::models::sync::MirrorSyncRequest;
+use media_processing_service::models::video::*;
+use media_processing_service::services::fixtures::{ImageFixture, VideoFixture};
+use media_processing_service::utils::validation::Validate;
+use serde::de::DeserializeOwned;
+
+fn check<T: DeserializeOwned + Validate>(json: &[u8]) {
+    if let Ok(request) = serde_json::from_slice::<T>(json) {
+        let _ = request.validate();
+    }
+}
+
+const CHECKS: &[fn(&[u8])] = &[
+    check::<VideoTranscodeRequest>,
+    check::<AudioExtractRequest>,
+    check::<AudioTranscodeRequest>,
+    check::<CoverExtractRequest>,
+    check::<CoverAttachRequest>,
+    check::<SlideshowRequest>,
+    check::<AnimationRequest>,
+    check::<SceneDetectRequest>,
+    check::<SpriteSheetRequest>,
+    check::<ThumbnailRequest>,
+    check::<TrimRequest>,
+    check::<EncodeCompareRequest>,
+    check::<FrameExportRequest>,
+    check::<FaceIndexRequest>,
+    check::<FingerprintRequest>,
+    check::<FingerprintCompareRequest>,
+    check::<VideoInfoRequest>,
+    check::<LosslessJpegRequest>,
+    check::<AutotrimRequest>,
+    check::<WatermarkEmbedRequest>,
+    check::<BlurRequest>,
+    check::<StickerRequest>,
+    check::<WatermarkDetectRequest>,
+    check::<JobQuery>,
+    check::<MirrorSyncRequest>,
+    check::<ImageFixture>,
+    check::<VideoFixture>,
+    check::<VideoWatermarkRequest>,
+    check::<SubtitleBurnRequest>,
+    check::<SubtitleMuxRequest>,
+    check::<SubtitleExtractRequest>,
+    check::<PanoramaAnalyzeRequest>,
+    check::<UpscaleRequest>,
+];
+
+fuzz_target!(|data: &[u8]| {
+    if let Some((&selector, json)) = data.split_first() {
+        CHECKS[usize::from(selector) % CHECKS.len()](json);
+    }
+});
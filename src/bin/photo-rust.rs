@@ -0,0 +1,272 @@
+use std::path::Path;
+use std::process::ExitCode;
+
+use anyhow::Result;
+use clap::{Parser, Subcommand};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+use media_processing_service::models::sync::{DerivativeSpec, MirrorSyncRequest};
+use media_processing_service::models::video::{AudioExtractRequest, ProgressEvent, VideoTranscodeRequest};
+use media_processing_service::services::hwaccel::Accelerator;
+use media_processing_service::services::sync_processor::{SyncProcessor, DERIVATIVE_FORMATS};
+use media_processing_service::services::tonemap::ToneMap;
+use media_processing_service::services::video_processor::{ProgressSender, VideoProcessor};
+
+/// Run the media processing core from scripts and CI without starting the HTTP server
+#[derive(Parser)]
+#[command(name = "photo-rust", version, about)]
+struct Cli {
+    #[command(subcommand)]
+    command: Commands,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Transcode a video (same options as POST /api/v1/video/transcode)
+    Transcode {
+        #[arg(short, long)]
+        input: String,
+        #[arg(short, long)]
+        output: String,
+        #[arg(long)]
+        format: Option<String>,
+        #[arg(long)]
+        codec: Option<String>,
+        #[arg(long)]
+        bitrate: Option<String>,
+        #[arg(long)]
+        resolution: Option<String>,
+        #[arg(long)]
+        fps: Option<u32>,
+        /// Encode on auto, nvenc, vaapi or videotoolbox, falling back to the CPU
+        #[arg(long, value_parser = parse_accelerator)]
+        hardware_acceleration: Option<Accelerator>,
+        /// Tone map HDR input to SDR with hable, mobius, reinhard or clip
+        #[arg(long, value_parser = parse_tone_map)]
+        tone_map: Option<ToneMap>,
+    },
+    /// Extract the audio track of a video (POST /api/v1/video/extract-audio)
+    ExtractAudio {
+        #[arg(short, long)]
+        input: String,
+        #[arg(short, long)]
+        output: String,
+        #[arg(long)]
+        format: Option<String>,
+        #[arg(long)]
+        bitrate: Option<String>,
+    },
+    /// Grab a single frame from a video as an image
+    Thumbnail {
+        #[arg(short, long)]
+        input: String,
+        #[arg(short, long)]
+        output: String,
+        /// Timestamp in seconds (defaults to the first frame)
+        #[arg(long)]
+        at: Option<f64>,
+        #[arg(long)]
+        width: Option<u32>,
+    },
+    /// Re-encode an image as an optimized derivative; the format follows the output extension
+    Optimize {
+        #[arg(short, long)]
+        input: String,
+        #[arg(short, long)]
+        output: String,
+        #[arg(long)]
+        max_dimension: Option<u32>,
+        #[arg(long)]
+        quality: Option<u32>,
+        /// Palette size for GIF, or for PNG to write an indexed PNG8
+        #[arg(long)]
+        colors: Option<u32>,
+        /// Palette dithering: none, bayer, floyd_steinberg, sierra2, sierra2_4a
+        #[arg(long)]
+        dither: Option<String>,
+    },
+    /// Transcode the quality ladder and package it as HLS (POST /api/v1/video/multi-quality-hls)
+    Hls {
+        #[arg(short, long)]
+        input: String,
+        #[arg(short, long)]
+        output: String,
+        #[arg(long)]
+        codec: Option<String>,
+        /// Encode the ladder on auto, nvenc, vaapi or videotoolbox, falling back to the CPU
+        #[arg(long, value_parser = parse_accelerator)]
+        hardware_acceleration: Option<Accelerator>,
+        /// Leave out renditions larger than the input
+        #[arg(long)]
+        per_title: bool,
+    },
+    /// Print ffprobe metadata as JSON (POST /api/v1/video/info)
+    Info {
+        file: String,
+    },
+    /// Mirror a directory tree into derivatives (POST /api/v1/sync/mirror)
+    Mirror {
+        #[arg(short, long)]
+        source: String,
+        #[arg(short, long)]
+        output: String,
+        #[arg(long, default_value = "webp")]
+        format: String,
+        #[arg(long)]
+        max_dimension: Option<u32>,
+        #[arg(long)]
+        quality: Option<u32>,
+        #[arg(long)]
+        colors: Option<u32>,
+        #[arg(long)]
+        dither: Option<String>,
+        /// Remove derivatives whose source file was deleted
+        #[arg(long)]
+        prune: bool,
+    },
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn")).init();
+
+    let cli = Cli::parse();
+    match run(cli.command).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {:#}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run(command: Commands) -> Result<()> {
+    match command {
+        Commands::Transcode { input, output, format, codec, bitrate, resolution, fps, hardware_acceleration, tone_map } => {
+            let processor = VideoProcessor::new()?;
+            let request = VideoTranscodeRequest {
+                input_path: input,
+                output_path: output,
+                format,
+                codec,
+                bitrate,
+                resolution,
+                fps,
+                filters: None,
+                hls: None,
+                hardware_acceleration,
+                rate_control: None,
+                ladder: None,
+                per_title: None,
+                tone_map,
+                priority: None,
+            };
+            let (progress, printer) = progress_printer();
+            let result = processor.transcode_video(&request, Some(&progress)).await;
+            drop(progress);
+            printer.await?;
+            println!("{}", result?);
+        }
+        Commands::ExtractAudio { input, output, format, bitrate } => {
+            let processor = VideoProcessor::new()?;
+            let request = AudioExtractRequest {
+                input_path: input,
+                output_path: output,
+                format,
+                bitrate,
+                priority: None,
+            };
+            let (progress, printer) = progress_printer();
+            let result = processor.extract_audio(&request, Some(&progress)).await;
+            drop(progress);
+            printer.await?;
+            println!("{}", result?);
+        }
+        Commands::Thumbnail { input, output, at, width } => {
+            let processor = VideoProcessor::new()?;
+            processor.extract_thumbnail(&input, &output, at, width).await?;
+            println!("{}", output);
+        }
+        Commands::Optimize { input, output, max_dimension, quality, colors, dither } => {
+            let format = Path::new(&output)
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .map(|ext| ext.to_lowercase())
+                .unwrap_or_default();
+            if !DERIVATIVE_FORMATS.contains(&format.as_str()) {
+                return Err(anyhow::anyhow!("Unsupported output format: {:?}", format));
+            }
+            let spec = DerivativeSpec { format: format.clone(), max_dimension, quality, colors, dither };
+            SyncProcessor::render_derivative(Path::new(&input), Path::new(&output), &spec, &format).await?;
+            println!("{}", output);
+        }
+        Commands::Hls { input, output, codec, hardware_acceleration, per_title } => {
+            let processor = VideoProcessor::new()?;
+            let request = VideoTranscodeRequest {
+                input_path: input,
+                output_path: output,
+                format: None,
+                codec,
+                bitrate: None,
+                resolution: None,
+                fps: None,
+                filters: None,
+                hls: None,
+                hardware_acceleration,
+                rate_control: None,
+                ladder: None,
+                per_title: Some(per_title),
+                tone_map: None,
+                priority: None,
+            };
+            let response = processor.transcode_multi_quality_and_hls(&request).await?;
+            println!("{}", serde_json::to_string_pretty(&response)?);
+        }
+        Commands::Info { file } => {
+            let processor = VideoProcessor::new()?;
+            let info = processor.get_video_info(&file).await?;
+            println!("{}", serde_json::to_string_pretty(&info)?);
+        }
+        Commands::Mirror { source, output, format, max_dimension, quality, colors, dither, prune } => {
+            let request = MirrorSyncRequest {
+                source_dir: source,
+                output_dir: output,
+                derivative: DerivativeSpec { format, max_dimension, quality, colors, dither },
+                prune: Some(prune),
+                priority: None,
+            };
+            let response = SyncProcessor::new().mirror(&request).await?;
+            println!("{}", serde_json::to_string_pretty(&response)?);
+        }
+    }
+
+    Ok(())
+}
+
+/// Same names as the request field, e.g. `auto`
+fn parse_accelerator(value: &str) -> Result<Accelerator, String> {
+    serde_json::from_value(serde_json::Value::String(value.to_string()))
+        .map_err(|_| format!("expected auto, nvenc, vaapi or videotoolbox, got {:?}", value))
+}
+
+fn parse_tone_map(value: &str) -> Result<ToneMap, String> {
+    serde_json::from_value(serde_json::Value::String(value.to_string()))
+        .map_err(|_| format!("expected hable, mobius, reinhard or clip, got {:?}", value))
+}
+
+/// Print FFmpeg progress on a single stderr line until the sender is dropped
+fn progress_printer() -> (ProgressSender, JoinHandle<()>) {
+    let (sender, mut receiver) = mpsc::unbounded_channel::<ProgressEvent>();
+    let printer = tokio::spawn(async move {
+        let mut printed = false;
+        while let Some(event) = receiver.recv().await {
+            eprint!("\r{:5.1}% ({:.1}s/{:.1}s)", event.percent, event.current_time, event.duration);
+            printed = true;
+        }
+        if printed {
+            eprintln!();
+        }
+    });
+    (sender, printer)
+}
@@ -0,0 +1,251 @@
+use std::future::Future;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use log::{error, info};
+use tokio::sync::mpsc;
+use tokio_stream::{wrappers::ReceiverStream, Stream};
+use tonic::{transport::Server, Request, Response, Status};
+
+use crate::middleware::auth::{self, ApiKeys, Role};
+use crate::models::job::Priority;
+use crate::models::video::{AudioExtractRequest, VideoInfoRequest, VideoTranscodeRequest};
+use crate::services::queue::JobQueue;
+use crate::services::video_processor::{ProgressSender, VideoProcessor};
+use crate::services::{remote, storage};
+use crate::utils::error::ServiceError;
+use crate::utils::validation::Validate;
+
+pub mod proto {
+    tonic::include_proto!("media.v1");
+}
+
+use proto::media_processing_server::{MediaProcessing, MediaProcessingServer};
+use proto::{
+    ExtractAudioRequest, JobEvent, JobState, MediaInfoRequest, MediaInfoResponse,
+    TranscodeAudioRequest, TranscodeVideoRequest,
+};
+
+type JobEventStream = Pin<Box<dyn Stream<Item = Result<JobEvent, Status>> + Send>>;
+
+pub struct MediaGrpcService {
+    video_processor: Arc<VideoProcessor>,
+    queue: Arc<JobQueue>,
+    keys: Arc<ApiKeys>,
+}
+
+impl MediaGrpcService {
+    pub fn new(video_processor: Arc<VideoProcessor>, queue: Arc<JobQueue>, keys: Arc<ApiKeys>) -> Self {
+        Self { video_processor, queue, keys }
+    }
+
+    /// Check the caller's key (`x-api-key` or `authorization: Bearer` metadata) like the HTTP
+    /// middleware does, returning its tenant
+    fn authorize<T>(&self, request: &Request<T>, required: Role) -> Result<Option<String>, Status> {
+        let metadata = request.metadata();
+        let key = metadata
+            .get(auth::API_KEY_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .or_else(|| {
+                metadata
+                    .get("authorization")
+                    .and_then(|value| value.to_str().ok())
+                    .and_then(|value| value.strip_prefix("Bearer "))
+            })
+            .map(str::trim);
+        self.keys.authorize(key, required).map_err(|e| match e {
+            ServiceError::Unauthorized(_) => Status::unauthenticated(e.to_string()),
+            _ => Status::permission_denied(e.to_string()),
+        })
+    }
+
+    /// Queue a job on behalf of `tenant`, sharing the HTTP jobs' workers and quotas, and forward
+    /// its progress and terminal state as a stream
+    fn job_stream<F, Fut>(
+        &self,
+        tenant: Option<String>,
+        job_type: &'static str,
+        input_path: String,
+        output_path: String,
+        job: F,
+    ) -> JobEventStream
+    where
+        F: FnOnce(ProgressSender) -> Fut + Send + 'static,
+        Fut: Future<Output = anyhow::Result<String>> + Send + 'static,
+    {
+        let (progress_tx, mut progress_rx) = mpsc::unbounded_channel();
+        let (tx, rx) = mpsc::channel(32);
+        let queue = self.queue.clone();
+
+        tokio::spawn(async move {
+            let queued = auth::with_tenant(tenant, async move {
+                queue.run(job_type, Priority::default(), &input_path, &output_path, job(progress_tx)).await
+            });
+            let handle = tokio::spawn(queued);
+
+            // The progress channel closes once the job drops its sender
+            while let Some(event) = progress_rx.recv().await {
+                let event = JobEvent {
+                    job_id: event.job_id,
+                    state: JobState::Running as i32,
+                    percent: event.percent,
+                    current_time: event.current_time,
+                    duration: event.duration,
+                    error: String::new(),
+                };
+                if tx.send(Ok(event)).await.is_err() {
+                    // Client went away, let the job finish on its own
+                    break;
+                }
+            }
+
+            let terminal = match handle.await {
+                Ok(Ok(job_id)) => Ok(JobEvent {
+                    job_id,
+                    state: JobState::Completed as i32,
+                    percent: 100.0,
+                    ..Default::default()
+                }),
+                Ok(Err(e)) => {
+                    error!("gRPC job failed: {}", e);
+                    Ok(JobEvent {
+                        state: JobState::Failed as i32,
+                        error: e.to_string(),
+                        ..Default::default()
+                    })
+                }
+                Err(e) => Err(Status::internal(format!("Task join error: {e}"))),
+            };
+            let _ = tx.send(terminal).await;
+        });
+
+        Box::pin(ReceiverStream::new(rx))
+    }
+}
+
+#[tonic::async_trait]
+impl MediaProcessing for MediaGrpcService {
+    type TranscodeVideoStream = JobEventStream;
+    type ExtractAudioStream = JobEventStream;
+    type TranscodeAudioStream = JobEventStream;
+
+    async fn transcode_video(
+        &self,
+        request: Request<TranscodeVideoRequest>,
+    ) -> Result<Response<Self::TranscodeVideoStream>, Status> {
+        let tenant = self.authorize(&request, Role::Process)?;
+        let req = request.into_inner();
+        info!("Received gRPC video transcode request: {}", req.input_path);
+
+        let transcode = VideoTranscodeRequest {
+            input_path: req.input_path,
+            output_path: req.output_path,
+            format: req.format,
+            codec: req.codec,
+            bitrate: req.bitrate,
+            resolution: req.resolution,
+            fps: req.fps,
+            filters: None,
+            hls: None,
+            hardware_acceleration: None,
+            rate_control: None,
+            ladder: None,
+            per_title: None,
+            tone_map: None,
+            priority: None,
+        };
+        let (input_path, output_path) = (transcode.input_path.clone(), transcode.output_path.clone());
+        let processor = self.video_processor.clone();
+
+        Ok(Response::new(self.job_stream(tenant, "video.transcode", input_path, output_path, move |progress| async move {
+            let mut transcode = transcode;
+            let _download = remote::localize(&mut transcode.input_path).await?;
+            let upload = storage::stage(&mut transcode.output_path)?;
+            let job_id = processor.transcode_video(&transcode, Some(&progress)).await?;
+            if let Some(upload) = upload {
+                upload.finish().await?;
+            }
+            Ok(job_id)
+        })))
+    }
+
+    async fn extract_audio(
+        &self,
+        request: Request<ExtractAudioRequest>,
+    ) -> Result<Response<Self::ExtractAudioStream>, Status> {
+        let tenant = self.authorize(&request, Role::Process)?;
+        let req = request.into_inner();
+        info!("Received gRPC audio extraction request: {}", req.input_path);
+
+        let extract = AudioExtractRequest {
+            input_path: req.input_path,
+            output_path: req.output_path,
+            format: req.format,
+            bitrate: req.bitrate,
+            priority: None,
+        };
+        let (input_path, output_path) = (extract.input_path.clone(), extract.output_path.clone());
+        let processor = self.video_processor.clone();
+
+        Ok(Response::new(self.job_stream(tenant, "audio.extract", input_path, output_path, move |progress| async move {
+            processor.extract_audio(&extract, Some(&progress)).await
+        })))
+    }
+
+    async fn transcode_audio(
+        &self,
+        request: Request<TranscodeAudioRequest>,
+    ) -> Result<Response<Self::TranscodeAudioStream>, Status> {
+        let tenant = self.authorize(&request, Role::Process)?;
+        let req = request.into_inner();
+        info!("Received gRPC audio transcode request: {}", req.input_path);
+
+        let (input_path, output_path) = (req.input_path.clone(), req.output_path.clone());
+        let processor = self.video_processor.clone();
+
+        Ok(Response::new(self.job_stream(tenant, "audio.transcode", input_path, output_path, move |progress| async move {
+            processor
+                .transcode_audio(&req.input_path, &req.output_path, req.format.as_deref(), Some(&progress))
+                .await
+        })))
+    }
+
+    async fn get_media_info(
+        &self,
+        request: Request<MediaInfoRequest>,
+    ) -> Result<Response<MediaInfoResponse>, Status> {
+        let tenant = self.authorize(&request, Role::Read)?;
+        let req = request.into_inner();
+        info!("Received gRPC media info request: {}", req.file_path);
+
+        // Answered inline like `POST /video/info`, inside the tenant's workspace
+        let info_request = VideoInfoRequest { file_path: req.file_path, detect_language: None };
+        let info = auth::with_tenant(tenant, async {
+            info_request.validate().map_err(|e| Status::invalid_argument(e.to_string()))?;
+            Ok::<_, Status>(self.video_processor.get_video_info(&info_request.file_path).await)
+        });
+        match info.await? {
+            Ok(info) => Ok(Response::new(MediaInfoResponse { json: info.to_string() })),
+            Err(e) => {
+                error!("Failed to get video info: {}", e);
+                Err(Status::internal(e.to_string()))
+            }
+        }
+    }
+}
+
+/// Serve the gRPC API until the process exits
+pub async fn serve(
+    addr: SocketAddr,
+    video_processor: Arc<VideoProcessor>,
+    queue: Arc<JobQueue>,
+    keys: Arc<ApiKeys>,
+) -> Result<(), tonic::transport::Error> {
+    info!("gRPC server starting on {}", addr);
+
+    Server::builder()
+        .add_service(MediaProcessingServer::new(MediaGrpcService::new(video_processor, queue, keys)))
+        .serve(addr)
+        .await
+}
@@ -0,0 +1,158 @@
+use actix_web::{web, HttpResponse, Result};
+use log::info;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use crate::logging;
+use crate::models::job::Priority;
+use crate::middleware::auth;
+use crate::models::admin::{
+    BenchmarkQuery, DiskUsage, LogFilterRequest, QueueResponse, StatsResponse, UsageQuery, UsageResponse, WorkersResponse,
+};
+use crate::services::benchmark::{self, Synthetic};
+use crate::services::fixtures::{self, ImageFixture, VideoFixture};
+use crate::services::queue::JobQueue;
+use crate::services::video_processor::VideoProcessor;
+use crate::utils::error::ServiceError;
+use crate::utils::fs;
+use crate::utils::validation::{FieldViolation, Validate, Violations};
+
+/// Job throughput and timings plus disk usage of the workspace (and `CACHE_DIR` when set)
+pub async fn stats(video_processor: web::Data<VideoProcessor>) -> Result<HttpResponse, ServiceError> {
+    let mut dirs = vec![fs::workspace_dir()];
+    if let Ok(cache_dir) = std::env::var("CACHE_DIR") {
+        dirs.push(PathBuf::from(cache_dir));
+    }
+
+    // Walking the directories is blocking IO
+    let disk = web::block(move || {
+        dirs.iter()
+            .map(|dir| {
+                let (bytes, files) = fs::dir_usage(dir);
+                DiskUsage { path: dir.display().to_string(), bytes, files }
+            })
+            .collect::<Vec<_>>()
+    })
+    .await
+    .map_err(|_| ServiceError::InternalError)?;
+
+    Ok(HttpResponse::Ok().json(StatsResponse {
+        jobs: video_processor.jobs().stats(),
+        processing_times: video_processor.metrics().snapshot(),
+        disk,
+    }))
+}
+
+/// Bytes read and written per tenant and day, for chargeback and capacity planning
+pub async fn usage(
+    query: web::Query<UsageQuery>,
+    video_processor: web::Data<VideoProcessor>,
+) -> Result<HttpResponse, ServiceError> {
+    let mut query = query.into_inner();
+    if let (Some(from), Some(to)) = (query.from, query.to) {
+        if from > to {
+            let mut violations = Violations::new();
+            violations.add("to", "must not be before from");
+            violations.into_result()?;
+        }
+    }
+    if let Some(tenant) = auth::current_tenant() {
+        query.tenant = Some(tenant);
+    }
+    Ok(HttpResponse::Ok().json(UsageResponse { usage: video_processor.jobs().usage(&query) }))
+}
+
+pub async fn queue(
+    video_processor: web::Data<VideoProcessor>,
+    queue: web::Data<JobQueue>,
+) -> Result<HttpResponse, ServiceError> {
+    Ok(HttpResponse::Ok().json(QueueResponse {
+        queued: queue.depth(),
+        capacity: queue.config().capacity,
+        queued_by_priority: Priority::ALL.into_iter().zip(queue.depth_by_priority()).collect(),
+        waiting: video_processor.jobs().queued(),
+        running: video_processor.jobs().running(),
+    }))
+}
+
+pub async fn workers(
+    video_processor: web::Data<VideoProcessor>,
+    queue: web::Data<JobQueue>,
+) -> Result<HttpResponse, ServiceError> {
+    let running = video_processor.jobs().running();
+    let mut running_by_type = BTreeMap::new();
+    for job in &running {
+        *running_by_type.entry(job.job_type.clone()).or_insert(0) += 1;
+    }
+
+    Ok(HttpResponse::Ok().json(WorkersResponse {
+        // actix starts one worker per available core by default
+        http_workers: std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1),
+        queue_workers: queue.config().workers,
+        busy: running.len(),
+        running_by_type,
+    }))
+}
+
+pub async fn get_log_filter() -> Result<HttpResponse, ServiceError> {
+    let filter = logging::current_filter().ok_or(ServiceError::InternalError)?;
+    Ok(HttpResponse::Ok().json(LogFilterRequest { filter }))
+}
+
+/// Change log levels at runtime; takes effect for the next record logged
+pub async fn set_log_filter(req: web::Json<LogFilterRequest>) -> Result<HttpResponse, ServiceError> {
+    let filter = logging::set_filter(&req.filter).map_err(|message| {
+        ServiceError::ValidationError(vec![FieldViolation {
+            field: "filter".to_string(),
+            message,
+        }])
+    })?;
+    info!("Log filter changed to: {}", filter);
+    Ok(HttpResponse::Ok().json(LogFilterRequest { filter }))
+}
+
+/// Time resize, effect and encode on synthetic frames, to compare builds and hosts before release
+pub async fn run_benchmark(query: web::Query<BenchmarkQuery>) -> Result<HttpResponse, ServiceError> {
+    let mut violations = Violations::new();
+    violations.range("width", query.width, 16, 7680);
+    violations.range("height", query.height, 16, 4320);
+    violations.range("frames", query.frames, 1, 600);
+    violations.into_result()?;
+
+    let defaults = Synthetic::default();
+    let input = Synthetic {
+        width: query.width.unwrap_or(defaults.width),
+        height: query.height.unwrap_or(defaults.height),
+        frames: query.frames.unwrap_or(defaults.frames),
+    };
+    info!("Running benchmark on {}x{}, {} frames", input.width, input.height, input.frames);
+
+    let report = web::block(move || benchmark::run(&input))
+        .await
+        .map_err(|_| ServiceError::InternalError)??;
+    Ok(HttpResponse::Ok().json(report))
+}
+
+/// Synthesize a solid, gradient or noise still, so tests need no binary fixtures. Only
+/// routed with `DEBUG_ENDPOINTS`
+pub async fn fixture_image(
+    req: web::Json<ImageFixture>,
+    video_processor: web::Data<VideoProcessor>,
+) -> Result<HttpResponse, ServiceError> {
+    req.validate()?;
+    info!("Generating {:?} fixture image {}", req.pattern.unwrap_or_default(), req.output_path);
+    video_processor.temp_files().output_parent(std::path::Path::new(&req.output_path))?;
+    let response = fixtures::generate(&req.output_path, req.command()).await?;
+    Ok(HttpResponse::Ok().json(response))
+}
+
+/// Synthesize a color-bars video with a test tone. Only routed with `DEBUG_ENDPOINTS`
+pub async fn fixture_video(
+    req: web::Json<VideoFixture>,
+    video_processor: web::Data<VideoProcessor>,
+) -> Result<HttpResponse, ServiceError> {
+    req.validate()?;
+    info!("Generating fixture video {}", req.output_path);
+    video_processor.temp_files().output_parent(std::path::Path::new(&req.output_path))?;
+    let response = fixtures::generate(&req.output_path, req.command()).await?;
+    Ok(HttpResponse::Ok().json(response))
+}
@@ -0,0 +1,9 @@
+use actix_web::{web, HttpResponse};
+use log::info;
+use crate::services::capabilities::Capabilities;
+
+pub async fn get_capabilities(capabilities: web::Data<Capabilities>) -> HttpResponse {
+    info!("Capabilities endpoint called");
+
+    HttpResponse::Ok().json(capabilities.get_ref())
+}
@@ -0,0 +1,114 @@
+use actix_web::{http::header, web, HttpRequest, HttpResponse, Result};
+use log::{info, warn};
+use std::io::SeekFrom;
+use std::path::Path;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use tokio_util::io::ReaderStream;
+use crate::services::url_signer::UrlSigner;
+use crate::utils::error::ServiceError;
+
+/// Serve an output through a signed `/files/{token}` link; the token stands in for an API key.
+/// The file is streamed from disk in chunks, and a single `Range: bytes=` span is honoured so
+/// players can seek and interrupted downloads can resume
+pub async fn serve_file(
+    req: HttpRequest,
+    token: web::Path<String>,
+    signer: web::Data<UrlSigner>,
+) -> Result<HttpResponse, ServiceError> {
+    let path = signer.verify(&token).inspect_err(|e| warn!("Rejected file link: {}", e))?;
+
+    let mut file = tokio::fs::File::open(&path)
+        .await
+        .map_err(|_| ServiceError::FileNotFound("file is no longer available".to_string()))?;
+    let length = file.metadata().await.map_err(|_| ServiceError::InternalError)?.len();
+
+    let range = req
+        .headers()
+        .get(header::RANGE)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| parse_range(value, length));
+    let (start, end) = match range {
+        None => (0, length),
+        Some(Some(range)) => range,
+        Some(None) => {
+            return Ok(HttpResponse::RangeNotSatisfiable()
+                .insert_header((header::CONTENT_RANGE, format!("bytes */{}", length)))
+                .finish());
+        }
+    };
+    info!("Serving signed file: {} (bytes {}-{} of {})", path.display(), start, end, length);
+
+    if start > 0 {
+        file.seek(SeekFrom::Start(start)).await.map_err(|_| ServiceError::InternalError)?;
+    }
+    let file_name = path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+    let mut response = if range.is_some() {
+        let mut partial = HttpResponse::PartialContent();
+        partial.insert_header((header::CONTENT_RANGE, format!("bytes {}-{}/{}", start, end - 1, length)));
+        partial
+    } else {
+        HttpResponse::Ok()
+    };
+    Ok(response
+        .content_type(content_type(&path))
+        .insert_header((header::ACCEPT_RANGES, "bytes"))
+        .insert_header((header::CONTENT_LENGTH, end - start))
+        .insert_header((header::CONTENT_DISPOSITION, format!("inline; filename=\"{}\"", file_name.replace('"', ""))))
+        .streaming(ReaderStream::new(file.take(end - start))))
+}
+
+/// Half-open byte span for `bytes=first-last`, `bytes=first-` or `bytes=-suffix`; multipart
+/// ranges are not supported and, like spans past the end, yield `None` (416)
+fn parse_range(value: &str, length: u64) -> Option<(u64, u64)> {
+    let spec = value.trim().strip_prefix("bytes=")?;
+    let (first, last) = spec.split_once('-')?;
+    let (first, last) = (first.trim(), last.trim());
+    let (start, end) = if first.is_empty() {
+        let suffix: u64 = last.parse().ok()?;
+        (length.saturating_sub(suffix), length)
+    } else {
+        let start: u64 = first.parse().ok()?;
+        let end = if last.is_empty() { length } else { last.parse::<u64>().ok()?.saturating_add(1).min(length) };
+        (start, end)
+    };
+    (start < end).then_some((start, end))
+}
+
+fn content_type(path: &Path) -> &'static str {
+    let extension = path.extension().and_then(|ext| ext.to_str()).unwrap_or("").to_lowercase();
+    match extension.as_str() {
+        "mp4" | "m4v" => "video/mp4",
+        "webm" => "video/webm",
+        "mkv" => "video/x-matroska",
+        "mov" => "video/quicktime",
+        "ts" => "video/mp2t",
+        "m3u8" => "application/vnd.apple.mpegurl",
+        "mp3" => "audio/mpeg",
+        "aac" => "audio/aac",
+        "m4a" => "audio/mp4",
+        "ogg" | "opus" => "audio/ogg",
+        "wav" => "audio/wav",
+        "flac" => "audio/flac",
+        "jpg" | "jpeg" => "image/jpeg",
+        "png" => "image/png",
+        "webp" => "image/webp",
+        "gif" => "image/gif",
+        _ => "application/octet-stream",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_range() {
+        assert_eq!(parse_range("bytes=0-99", 1000), Some((0, 100)));
+        assert_eq!(parse_range("bytes=900-", 1000), Some((900, 1000)));
+        assert_eq!(parse_range("bytes=-100", 1000), Some((900, 1000)));
+        assert_eq!(parse_range("bytes=500-5000", 1000), Some((500, 1000)));
+        assert_eq!(parse_range("bytes=1000-", 1000), None);
+        assert_eq!(parse_range("bytes=0-1,5-9", 1000), None);
+        assert_eq!(parse_range("items=0-1", 1000), None);
+    }
+}
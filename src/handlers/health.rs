@@ -1,14 +1,47 @@
-use actix_web::HttpResponse;
+use actix_web::{web, HttpResponse};
 use chrono::Utc;
-use log::info;
+use log::{info, warn};
+use crate::services::health;
+use crate::services::queue::JobQueue;
+use crate::services::video_processor::VideoProcessor;
 
-pub async fn health_check() -> HttpResponse {
+pub async fn health_check(video_processor: web::Data<VideoProcessor>) -> HttpResponse {
     info!("Health check endpoint called at {}", Utc::now().to_rfc3339());
-    
+    let temp = video_processor.temp_files().clone();
+    let storage = web::block(move || temp.usage()).await.ok();
+
     HttpResponse::Ok().json(serde_json::json!({
         "status": "service is running",
         "service": "media-processing-service",
         "version": "1.0.0",
+        "timestamp": Utc::now().to_rfc3339(),
+        "storage": storage
+    }))
+}
+
+/// Liveness: the process is up and serving requests; never touches dependencies
+pub async fn liveness() -> HttpResponse {
+    HttpResponse::Ok().json(serde_json::json!({
+        "status": "alive",
         "timestamp": Utc::now().to_rfc3339()
     }))
-} 
\ No newline at end of file
+}
+
+/// Readiness: 200 when every dependency check passes, 503 otherwise
+pub async fn readiness(queue: web::Data<JobQueue>) -> HttpResponse {
+    let load = (queue.depth(), queue.config().capacity);
+    let report = match web::block(move || health::readiness(load)).await {
+        Ok(report) => report,
+        Err(e) => {
+            warn!("Readiness checks could not run: {}", e);
+            return HttpResponse::ServiceUnavailable().json(serde_json::json!({ "ready": false }));
+        }
+    };
+
+    if report.ready {
+        HttpResponse::Ok().json(report)
+    } else {
+        warn!("Readiness check failed: {:?}", report.checks);
+        HttpResponse::ServiceUnavailable().json(report)
+    }
+}
@@ -0,0 +1,242 @@
+use actix_web::{web, HttpResponse, Result};
+use crate::models::image::{
+    AutotrimRequest, BlurRequest, LosslessJpegRequest, LosslessJpegResponse, PanoramaAnalyzeRequest, StickerRequest, UpscaleRequest, WatermarkDetectRequest,
+    WatermarkEmbedRequest,
+};
+use crate::handlers::video::accepted;
+use crate::services::queue::JobQueue;
+use crate::services::remote;
+use crate::services::sync_processor::SyncProcessor;
+use crate::services::video_processor::VideoProcessor;
+use crate::utils::error::ServiceError;
+use crate::utils::validation::Validate;
+use log::{error, info};
+
+pub async fn lossless_jpeg(
+    req: web::Json<LosslessJpegRequest>,
+    sync_processor: web::Data<SyncProcessor>,
+    queue: web::Data<JobQueue>,
+) -> Result<HttpResponse, ServiceError> {
+    info!("Received lossless JPEG request: {}", req.input_path);
+    req.validate()?;
+
+    let mut request = req.into_inner();
+    let (input_path, output_path) = (request.input_path.clone(), request.output_path.clone());
+    let processor = sync_processor.into_inner();
+    let priority = request.priority.unwrap_or_default();
+    let task = queue.keeping_result(async move {
+        let _download = remote::localize(&mut request.input_path).await?;
+        let output_path = request.output_path.clone();
+        processor.lossless_jpeg(&request).await.map(|job_id| LosslessJpegResponse { job_id, output_path })
+    });
+    match queue.submit("image.lossless_jpeg", priority, &input_path, &output_path, task) {
+        Ok(job_id) => Ok(accepted(job_id, "Lossless JPEG transform job queued", None)),
+        Err(e) => {
+            error!("Failed to queue lossless JPEG transform: {}", e);
+            Err(e.into())
+        }
+    }
+}
+
+/// Crop scan margins and other uniform borders off an image
+pub async fn autotrim(
+    req: web::Json<AutotrimRequest>,
+    video_processor: web::Data<VideoProcessor>,
+    queue: web::Data<JobQueue>,
+) -> Result<HttpResponse, ServiceError> {
+    info!("Received autotrim request: {}", req.input_path);
+    run_autotrim(req.into_inner(), video_processor, queue, true).await
+}
+
+/// Crop letterbox/pillarbox bars off a video, detected over its first ten minutes
+pub async fn autocrop_video(
+    req: web::Json<AutotrimRequest>,
+    video_processor: web::Data<VideoProcessor>,
+    queue: web::Data<JobQueue>,
+) -> Result<HttpResponse, ServiceError> {
+    info!("Received video autocrop request: {}", req.input_path);
+    run_autotrim(req.into_inner(), video_processor, queue, false).await
+}
+
+/// Blur an image; with a mask, only its background, or only the masked area for privacy blurs
+pub async fn blur(
+    req: web::Json<BlurRequest>,
+    video_processor: web::Data<VideoProcessor>,
+    queue: web::Data<JobQueue>,
+) -> Result<HttpResponse, ServiceError> {
+    info!("Received blur request: {}", req.input_path);
+    req.validate()?;
+
+    let mut request = req.into_inner();
+    let (input_path, output_path) = (request.input_path.clone(), request.output_path.clone());
+    let processor = video_processor.into_inner();
+    let priority = request.priority.unwrap_or_default();
+    let task = queue.keeping_result(async move {
+        let _download = remote::localize(&mut request.input_path).await?;
+        processor.blur(&request).await
+    });
+    match queue.submit("image.blur", priority, &input_path, &output_path, task) {
+        Ok(job_id) => Ok(accepted(job_id, "Blur job queued", None)),
+        Err(e) => {
+            error!("Failed to queue blur: {}", e);
+            Err(e.into())
+        }
+    }
+}
+
+/// Turn a keyed video or a frame sequence into an animated sticker with a transparent background
+pub async fn sticker(
+    req: web::Json<StickerRequest>,
+    video_processor: web::Data<VideoProcessor>,
+    queue: web::Data<JobQueue>,
+) -> Result<HttpResponse, ServiceError> {
+    info!("Received sticker request: {}", req.output_path);
+    req.validate()?;
+
+    let mut request = req.into_inner();
+    let input_path = request.input_path.clone().or_else(|| request.frames.as_ref()?.first().cloned()).unwrap_or_default();
+    let output_path = request.output_path.clone();
+    let processor = video_processor.into_inner();
+    let priority = request.priority.unwrap_or_default();
+    let task = queue.keeping_result(async move {
+        let _download = match request.input_path.as_mut() {
+            Some(input_path) => remote::localize(input_path).await?,
+            None => None,
+        };
+        processor.sticker(&request).await
+    });
+    match queue.submit("image.sticker", priority, &input_path, &output_path, task) {
+        Ok(job_id) => Ok(accepted(job_id, "Sticker job queued", None)),
+        Err(e) => {
+            error!("Failed to queue sticker: {}", e);
+            Err(e.into())
+        }
+    }
+}
+
+/// Hide a numeric id in an image, invisible to viewers, to trace where a copy leaked from
+pub async fn embed_watermark(
+    req: web::Json<WatermarkEmbedRequest>,
+    video_processor: web::Data<VideoProcessor>,
+    queue: web::Data<JobQueue>,
+) -> Result<HttpResponse, ServiceError> {
+    info!("Received invisible watermark request: {}", req.input_path);
+    req.validate()?;
+
+    let mut request = req.into_inner();
+    let (input_path, output_path) = (request.input_path.clone(), request.output_path.clone());
+    let processor = video_processor.into_inner();
+    let priority = request.priority.unwrap_or_default();
+    let task = queue.keeping_result(async move {
+        let _download = remote::localize(&mut request.input_path).await?;
+        processor.embed_watermark(&request).await
+    });
+    match queue.submit("image.watermark", priority, &input_path, &output_path, task) {
+        Ok(job_id) => Ok(accepted(job_id, "Invisible watermark job queued", None)),
+        Err(e) => {
+            error!("Failed to queue invisible watermark: {}", e);
+            Err(e.into())
+        }
+    }
+}
+
+/// Read back the id `embed_watermark` hid in an image
+pub async fn detect_watermark(
+    req: web::Json<WatermarkDetectRequest>,
+    video_processor: web::Data<VideoProcessor>,
+    queue: web::Data<JobQueue>,
+) -> Result<HttpResponse, ServiceError> {
+    info!("Received watermark detection request: {}", req.input_path);
+    req.validate()?;
+
+    let mut request = req.into_inner();
+    let input_path = request.input_path.clone();
+    let processor = video_processor.into_inner();
+    let priority = request.priority.unwrap_or_default();
+    let task = queue.keeping_result(async move {
+        let _download = remote::localize(&mut request.input_path).await?;
+        processor.detect_watermark(&request).await
+    });
+    match queue.submit("image.watermark_detect", priority, &input_path, "", task) {
+        Ok(job_id) => Ok(accepted(job_id, "Watermark detection job queued", None)),
+        Err(e) => {
+            error!("Failed to queue watermark detection: {}", e);
+            Err(e.into())
+        }
+    }
+}
+
+/// Check whether shots form a panorama and put them in stitching order
+pub async fn analyze_panorama(
+    req: web::Json<PanoramaAnalyzeRequest>,
+    video_processor: web::Data<VideoProcessor>,
+    queue: web::Data<JobQueue>,
+) -> Result<HttpResponse, ServiceError> {
+    info!("Received panorama analysis request: {} images", req.input_paths.len());
+    req.validate()?;
+
+    let request = req.into_inner();
+    let input_path = request.input_paths[0].clone();
+    let processor = video_processor.into_inner();
+    let priority = request.priority.unwrap_or_default();
+    let task = queue.keeping_result(async move {
+        processor.analyze_panorama(&request).await
+    });
+    match queue.submit("image.panorama_analyze", priority, &input_path, "", task) {
+        Ok(job_id) => Ok(accepted(job_id, "Panorama analysis job queued", None)),
+        Err(e) => {
+            error!("Failed to queue panorama analysis: {}", e);
+            Err(e.into())
+        }
+    }
+}
+
+/// Enlarge an image, deblocking JPEG artifacts first when they show
+pub async fn upscale(
+    req: web::Json<UpscaleRequest>,
+    video_processor: web::Data<VideoProcessor>,
+    queue: web::Data<JobQueue>,
+) -> Result<HttpResponse, ServiceError> {
+    info!("Received upscale request: {}", req.input_path);
+    req.validate()?;
+
+    let mut request = req.into_inner();
+    let (input_path, output_path) = (request.input_path.clone(), request.output_path.clone());
+    let processor = video_processor.into_inner();
+    let priority = request.priority.unwrap_or_default();
+    let task = queue.keeping_result(async move {
+        let _download = remote::localize(&mut request.input_path).await?;
+        processor.upscale(&request).await
+    });
+    match queue.submit("image.upscale", priority, &input_path, &output_path, task) {
+        Ok(job_id) => Ok(accepted(job_id, "Upscale job queued", None)),
+        Err(e) => {
+            error!("Failed to queue upscale: {}", e);
+            Err(e.into())
+        }
+    }
+}
+
+async fn run_autotrim(
+    mut request: AutotrimRequest,
+    video_processor: web::Data<VideoProcessor>,
+    queue: web::Data<JobQueue>,
+    still: bool,
+) -> Result<HttpResponse, ServiceError> {
+    request.validate()?;
+    let job_type = if still { "image.autotrim" } else { "video.autocrop" };
+    let (input_path, output_path) = (request.input_path.clone(), request.output_path.clone());
+    let processor = video_processor.into_inner();
+    let priority = request.priority.unwrap_or_default();
+    let task = queue.keeping_result(async move {
+        let _download = remote::localize(&mut request.input_path).await?;
+        processor.autotrim(&request, still).await
+    });
+    match queue.submit(job_type, priority, &input_path, &output_path, task) {
+        Ok(job_id) => Ok(accepted(job_id, if still { "Autotrim job queued" } else { "Autocrop job queued" }, None)),
+        Err(e) => {
+            error!("Failed to queue autotrim: {}", e);
+            Err(e.into())
+        }
+    }
+}
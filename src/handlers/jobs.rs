@@ -0,0 +1,107 @@
+use actix_web::http::header;
+use actix_web::web::Bytes;
+use actix_web::{web, HttpResponse, Result};
+use futures_util::stream;
+use std::convert::Infallible;
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+use crate::middleware::auth;
+use crate::models::job::{JobQuery, ProgressUpdate};
+use crate::services::video_processor::VideoProcessor;
+use crate::utils::error::ServiceError;
+use crate::utils::validation::Validate;
+
+/// A comment line is sent when a progress stream has been idle this long
+const KEEP_ALIVE: Duration = Duration::from_secs(15);
+
+pub async fn list_jobs(
+    query: web::Query<JobQuery>,
+    video_processor: web::Data<VideoProcessor>,
+) -> Result<HttpResponse, ServiceError> {
+    query.validate()?;
+    let mut query = query.into_inner();
+    if let Some(tenant) = auth::current_tenant() {
+        query.tenant = Some(tenant);
+    }
+    Ok(HttpResponse::Ok().json(video_processor.jobs().query(&query)))
+}
+
+/// Current status, progress and outcome of one job, for clients polling long transcodes
+pub async fn get_job(
+    path: web::Path<String>,
+    video_processor: web::Data<VideoProcessor>,
+) -> Result<HttpResponse, ServiceError> {
+    let job_id = path.into_inner();
+    let job = video_processor
+        .jobs()
+        .get(&job_id)
+        // Another tenant's job is reported as missing rather than forbidden, so ids can't be probed
+        .filter(|job| auth::current_tenant().is_none_or(|tenant| job.tenant.as_ref() == Some(&tenant)))
+        .ok_or_else(|| ServiceError::JobNotFound(format!("Job not found: {}", job_id)))?;
+    Ok(HttpResponse::Ok().json(job))
+}
+
+/// Stop a queued or running job; the record is returned as it stands once cancellation was requested
+pub async fn cancel_job(
+    path: web::Path<String>,
+    video_processor: web::Data<VideoProcessor>,
+) -> Result<HttpResponse, ServiceError> {
+    let job_id = path.into_inner();
+    video_processor
+        .jobs()
+        .get(&job_id)
+        .filter(|job| auth::current_tenant().is_none_or(|tenant| job.tenant.as_ref() == Some(&tenant)))
+        .ok_or_else(|| ServiceError::JobNotFound(format!("Job not found: {}", job_id)))?;
+    video_processor.cancel_job(&job_id)?;
+    Ok(HttpResponse::Accepted().json(video_processor.jobs().get(&job_id)))
+}
+
+/// Server-sent events with the job's progress (percent, ETA, bit rate) as FFmpeg reports it.
+/// The stream ends with a `done` event once the job has finished
+pub async fn job_progress(
+    path: web::Path<String>,
+    video_processor: web::Data<VideoProcessor>,
+) -> Result<HttpResponse, ServiceError> {
+    let job_id = path.into_inner();
+    let jobs = video_processor.shared_jobs();
+    // Subscribed before reading the record, so no update can slip in between
+    let updates = jobs.subscribe();
+    let job = jobs
+        .get(&job_id)
+        .filter(|job| auth::current_tenant().is_none_or(|tenant| job.tenant.as_ref() == Some(&tenant)))
+        .ok_or_else(|| ServiceError::JobNotFound(format!("Job not found: {}", job_id)))?;
+
+    let events = stream::unfold((Some(job), updates, false), move |(pending, mut updates, done)| {
+        let jobs = jobs.clone();
+        let job_id = job_id.clone();
+        async move {
+            if done {
+                return None;
+            }
+            let job = match pending {
+                Some(job) => job,
+                None => loop {
+                    match tokio::time::timeout(KEEP_ALIVE, updates.recv()).await {
+                        Ok(Ok(job)) if job.job_id == job_id => break job,
+                        Ok(Ok(_)) => continue,
+                        // Fell behind; the record itself has the latest state
+                        Ok(Err(RecvError::Lagged(_))) => break jobs.get(&job_id)?,
+                        Ok(Err(RecvError::Closed)) => return None,
+                        // Queued jobs can wait a long time; keep proxies from closing the stream
+                        Err(_) => return Some((Ok(Bytes::from_static(b": keep-alive\n\n")), (None, updates, false))),
+                    }
+                },
+            };
+            let finished = job.finished_at.is_some();
+            let event = if finished { "done" } else { "progress" };
+            let data = serde_json::to_string(&ProgressUpdate::from(&job)).unwrap_or_default();
+            let frame = Bytes::from(format!("event: {}\ndata: {}\n\n", event, data));
+            Some((Ok::<_, Infallible>(frame), (None, updates, finished)))
+        }
+    });
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header((header::CACHE_CONTROL, "no-cache"))
+        .streaming(events))
+}
@@ -1,2 +1,9 @@
 pub mod video;
-pub mod health; 
\ No newline at end of file
+pub mod health;
+pub mod sync;
+pub mod v2;
+pub mod capabilities;
+pub mod jobs;
+pub mod admin;
+pub mod files;
+pub mod image;
\ No newline at end of file
@@ -0,0 +1,30 @@
+use actix_web::{web, HttpResponse, Result};
+use crate::handlers::video::accepted;
+use crate::models::sync::MirrorSyncRequest;
+use crate::services::queue::JobQueue;
+use crate::services::sync_processor::SyncProcessor;
+use crate::utils::error::ServiceError;
+use crate::utils::validation::Validate;
+use log::{error, info};
+
+pub async fn mirror_directory(
+    req: web::Json<MirrorSyncRequest>,
+    sync_processor: web::Data<SyncProcessor>,
+    queue: web::Data<JobQueue>,
+) -> Result<HttpResponse, ServiceError> {
+    info!("Received mirror sync request: {} -> {}", req.source_dir, req.output_dir);
+    req.validate()?;
+
+    let request = req.into_inner();
+    let (source_dir, output_dir) = (request.source_dir.clone(), request.output_dir.clone());
+    let processor = sync_processor.into_inner();
+    let priority = request.priority.unwrap_or_default();
+    let task = queue.keeping_result(async move { processor.mirror(&request).await });
+    match queue.submit("sync.mirror", priority, &source_dir, &output_dir, task) {
+        Ok(job_id) => Ok(accepted(job_id, "Mirror sync job queued", None)),
+        Err(e) => {
+            error!("Failed to queue mirror sync: {}", e);
+            Err(e.into())
+        }
+    }
+}
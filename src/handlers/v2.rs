@@ -0,0 +1,230 @@
+use std::time::Instant;
+
+use actix_web::{web, HttpResponse, Result};
+use log::{error, info};
+use uuid::Uuid;
+
+use crate::handlers::video::media_info;
+use crate::models::processing::ProcessingResult;
+use crate::models::sync::MirrorSyncRequest;
+use crate::models::video::{AudioExtractRequest, AudioTranscodeRequest, VideoInfoRequest, VideoTranscodeRequest};
+use crate::services::queue::JobQueue;
+use crate::services::{remote, storage};
+use crate::services::sync_processor::SyncProcessor;
+use crate::services::url_signer::UrlSigner;
+use crate::services::video_processor::VideoProcessor;
+use crate::utils::error::ServiceError;
+use crate::utils::validation::Validate;
+
+fn elapsed_ms(started: Instant) -> u64 {
+    started.elapsed().as_millis() as u64
+}
+
+pub async fn transcode_video(
+    req: web::Json<VideoTranscodeRequest>,
+    video_processor: web::Data<VideoProcessor>,
+    queue: web::Data<JobQueue>,
+    signer: web::Data<UrlSigner>,
+) -> Result<HttpResponse, ServiceError> {
+    info!("Received v2 video transcode request");
+    req.validate()?;
+    let started = Instant::now();
+    let mut request = req.into_inner();
+    let (input_path, output_path) = (request.input_path.clone(), request.output_path.clone());
+    let processor = video_processor.into_inner();
+
+    let result = queue
+        .run("video.transcode", request.priority.unwrap_or_default(), &input_path, &output_path, async move {
+            let _download = remote::localize(&mut request.input_path).await?;
+            let upload = storage::stage(&mut request.output_path)?;
+            let job_id = processor.transcode_video(&request, None).await?;
+            if let Some(upload) = upload {
+                upload.finish().await?;
+            }
+            Ok(job_id)
+        })
+        .await;
+    match result {
+        Ok(job_id) => Ok(HttpResponse::Ok().json(ProcessingResult::completed(
+            job_id,
+            "video.transcode",
+            vec![output_path],
+            elapsed_ms(started),
+        )
+        .with_signed_urls(&signer))),
+        Err(e) => {
+            error!("Video transcode failed: {}", e);
+            Err(e.into())
+        }
+    }
+}
+
+pub async fn transcode_hls(
+    req: web::Json<VideoTranscodeRequest>,
+    video_processor: web::Data<VideoProcessor>,
+    queue: web::Data<JobQueue>,
+    signer: web::Data<UrlSigner>,
+) -> Result<HttpResponse, ServiceError> {
+    info!("Received v2 multi-quality HLS request");
+    req.validate()?;
+    let started = Instant::now();
+    let mut request = req.into_inner();
+    let (input_path, output_path) = (request.input_path.clone(), request.output_path.clone());
+    let processor = video_processor.into_inner();
+
+    let result = queue
+        .run("video.hls", request.priority.unwrap_or_default(), &input_path, &output_path, async move {
+            let _download = remote::localize(&mut request.input_path).await?;
+            processor.transcode_multi_quality_and_hls(&request).await
+        })
+        .await;
+    match result {
+        Ok(hls) => {
+            let mut outputs = hls.outputs;
+            outputs.push(hls.master_playlist.clone());
+            let result = ProcessingResult::completed(
+                hls.job_id,
+                "video.hls",
+                outputs,
+                elapsed_ms(started),
+            )
+            .with_signed_urls(&signer)
+            .with_metadata(serde_json::json!({ "master_playlist": hls.master_playlist }));
+            Ok(HttpResponse::Ok().json(result))
+        }
+        Err(e) => {
+            error!("Multi-quality HLS failed: {}", e);
+            Err(e.into())
+        }
+    }
+}
+
+pub async fn extract_audio(
+    req: web::Json<AudioExtractRequest>,
+    video_processor: web::Data<VideoProcessor>,
+    queue: web::Data<JobQueue>,
+    signer: web::Data<UrlSigner>,
+) -> Result<HttpResponse, ServiceError> {
+    info!("Received v2 audio extraction request");
+    req.validate()?;
+    let started = Instant::now();
+    let request = req.into_inner();
+    let (input_path, output_path) = (request.input_path.clone(), request.output_path.clone());
+    let processor = video_processor.into_inner();
+
+    let result = queue
+        .run("audio.extract", request.priority.unwrap_or_default(), &input_path, &output_path, async move {
+            processor.extract_audio(&request, None).await
+        })
+        .await;
+    match result {
+        Ok(job_id) => Ok(HttpResponse::Ok().json(ProcessingResult::completed(
+            job_id,
+            "audio.extract",
+            vec![output_path],
+            elapsed_ms(started),
+        )
+        .with_signed_urls(&signer))),
+        Err(e) => {
+            error!("Audio extraction failed: {}", e);
+            Err(e.into())
+        }
+    }
+}
+
+pub async fn transcode_audio(
+    req: web::Json<AudioTranscodeRequest>,
+    video_processor: web::Data<VideoProcessor>,
+    queue: web::Data<JobQueue>,
+    signer: web::Data<UrlSigner>,
+) -> Result<HttpResponse, ServiceError> {
+    info!("Received v2 audio transcode request");
+    req.validate()?;
+    let started = Instant::now();
+    let request = req.into_inner();
+    let (input_path, output_path) = (request.input_path.clone(), request.output_path.clone());
+    let processor = video_processor.into_inner();
+
+    let result = queue
+        .run("audio.transcode", request.priority.unwrap_or_default(), &input_path, &output_path, async move {
+            processor
+                .transcode_audio(&request.input_path, &request.output_path, request.format.as_deref(), None)
+                .await
+        })
+        .await;
+    match result {
+        Ok(job_id) => Ok(HttpResponse::Ok().json(ProcessingResult::completed(
+            job_id,
+            "audio.transcode",
+            vec![output_path],
+            elapsed_ms(started),
+        )
+        .with_signed_urls(&signer))),
+        Err(e) => {
+            error!("Audio transcode failed: {}", e);
+            Err(e.into())
+        }
+    }
+}
+
+pub async fn extract_metadata(
+    req: web::Json<VideoInfoRequest>,
+    video_processor: web::Data<VideoProcessor>,
+    queue: web::Data<JobQueue>,
+) -> Result<HttpResponse, ServiceError> {
+    info!("Received v2 metadata request for: {}", req.file_path);
+    req.validate()?;
+    let started = Instant::now();
+
+    match media_info(&req, video_processor, &queue).await {
+        Ok(info) => {
+            let result = ProcessingResult::completed(
+                Uuid::new_v4().to_string(),
+                "metadata.extract",
+                Vec::new(),
+                elapsed_ms(started),
+            )
+            .with_metadata(info);
+            Ok(HttpResponse::Ok().json(result))
+        }
+        Err(e) => {
+            error!("Failed to get video info: {}", e);
+            Err(e.into())
+        }
+    }
+}
+
+pub async fn mirror_directory(
+    req: web::Json<MirrorSyncRequest>,
+    sync_processor: web::Data<SyncProcessor>,
+    queue: web::Data<JobQueue>,
+) -> Result<HttpResponse, ServiceError> {
+    info!("Received v2 mirror sync request: {} -> {}", req.source_dir, req.output_dir);
+    req.validate()?;
+    let started = Instant::now();
+    let request = req.into_inner();
+    let (source_dir, output_dir) = (request.source_dir.clone(), request.output_dir.clone());
+    let processor = sync_processor.into_inner();
+
+    let result = queue
+        .run("sync.mirror", request.priority.unwrap_or_default(), &source_dir, &output_dir, async move { processor.mirror(&request).await })
+        .await;
+    match result {
+        Ok(sync) => {
+            let status = if sync.failed.is_empty() { "completed" } else { "completed_with_errors" };
+            let metadata = serde_json::json!({
+                "skipped": sync.skipped,
+                "removed": sync.removed,
+                "failed": sync.failed,
+            });
+            let mut result = ProcessingResult::completed(sync.job_id, "sync.mirror", sync.processed, elapsed_ms(started))
+                .with_metadata(metadata);
+            result.status = status.to_string();
+            Ok(HttpResponse::Ok().json(result))
+        }
+        Err(e) => {
+            error!("Mirror sync failed: {}", e);
+            Err(e.into())
+        }
+    }
+}
@@ -1,34 +1,39 @@
 use actix_web::{web, HttpResponse, Result};
-use crate::models::video::{VideoTranscodeRequest, VideoTranscodeResponse, AudioExtractRequest, VideoInfoRequest};
+use crate::models::job::Priority;
+use crate::models::video::{
+    VideoTranscodeRequest, VideoTranscodeResponse, AudioExtractRequest, AudioTranscodeRequest, CoverAttachRequest,
+    CoverExtractRequest, FaceIndexRequest, FingerprintCompareRequest, FingerprintCompareResponse, FingerprintRequest,
+    AnimationRequest, EncodeCompareRequest, FrameExportRequest, MultiQualityHlsResponse, SceneDetectRequest, SlideshowRequest, SpriteSheetRequest, ThumbnailRequest, TrimRequest, VideoInfoRequest,
+    VideoWatermarkRequest, SubtitleBurnRequest, SubtitleExtractRequest, SubtitleMuxRequest,
+};
+use crate::services::fingerprint;
+use crate::services::queue::JobQueue;
+use crate::services::{remote, storage};
+use crate::services::url_signer::UrlSigner;
 use crate::services::video_processor::VideoProcessor;
 use crate::utils::error::ServiceError;
-use log::{error, info};
-use serde::Serialize;
-
-#[derive(Serialize)]
-pub struct MultiQualityHlsResponse {
-    pub outputs: Vec<String>,
-    pub master_playlist: String,
-}
+use crate::utils::validation::Validate;
+use log::{error, info, warn};
+use std::sync::Arc;
 
 pub async fn transcode_video(
     req: web::Json<VideoTranscodeRequest>,
     video_processor: web::Data<VideoProcessor>,
+    queue: web::Data<JobQueue>,
+    signer: web::Data<UrlSigner>,
 ) -> Result<HttpResponse, ServiceError> {
     info!("Received video transcode request");
+    req.validate()?;
     
-    match video_processor.transcode_video(&req.into_inner()).await {
-        Ok(job_id) => {
-            let response = VideoTranscodeResponse {
-                job_id,
-                status: "processing".to_string(),
-                message: "Video transcode job started successfully".to_string(),
-            };
-            Ok(HttpResponse::Ok().json(response))
-        }
+    let request = req.into_inner();
+    let (input_path, output_path) = (request.input_path.clone(), request.output_path.clone());
+    let task = transcode_task(video_processor.into_inner(), request.clone());
+    let queued = queue.submit_request("video.transcode", request.priority.unwrap_or_default(), &request, &input_path, &output_path, task);
+    match queued {
+        Ok(job_id) => Ok(accepted(job_id, "Video transcode job queued", signer.sign(&output_path))),
         Err(e) => {
-            error!("Video transcode failed: {}", e);
-            Err(ServiceError::FFmpegError(e.to_string()))
+            error!("Failed to queue video transcode: {}", e);
+            Err(e.into())
         }
     }
 }
@@ -36,119 +41,550 @@ pub async fn transcode_video(
 pub async fn extract_audio(
     req: web::Json<AudioExtractRequest>,
     video_processor: web::Data<VideoProcessor>,
+    queue: web::Data<JobQueue>,
 ) -> Result<HttpResponse, ServiceError> {
     info!("Received audio extraction request");
+    req.validate()?;
     
-    match video_processor.extract_audio(&req.into_inner()).await {
-        Ok(job_id) => {
-            let response = VideoTranscodeResponse {
-                job_id,
-                status: "processing".to_string(),
-                message: "Audio extraction job started successfully".to_string(),
-            };
-            Ok(HttpResponse::Ok().json(response))
-        }
+    let request = req.into_inner();
+    let (input_path, output_path) = (request.input_path.clone(), request.output_path.clone());
+    let task = extract_audio_task(video_processor.into_inner(), request.clone());
+    let queued = queue.submit_request("audio.extract", request.priority.unwrap_or_default(), &request, &input_path, &output_path, task);
+    match queued {
+        Ok(job_id) => Ok(accepted(job_id, "Audio extraction job queued", None)),
         Err(e) => {
-            error!("Audio extraction failed: {}", e);
-            Err(ServiceError::FFmpegError(e.to_string()))
+            error!("Failed to queue audio extraction: {}", e);
+            Err(e.into())
         }
     }
 }
 
 pub async fn transcode_audio(
-    req: web::Json<serde_json::Value>,
+    req: web::Json<AudioTranscodeRequest>,
     video_processor: web::Data<VideoProcessor>,
+    queue: web::Data<JobQueue>,
 ) -> Result<HttpResponse, ServiceError> {
     info!("Received audio transcode request");
+    req.validate()?;
     
-    let input_path = req["input_path"].as_str().unwrap_or("");
-    let output_path = req["output_path"].as_str().unwrap_or("");
-    let format = req["format"].as_str();
-    
-    if input_path.is_empty() || output_path.is_empty() {
-        return Err(ServiceError::BadRequest("Missing input_path or output_path".to_string()));
-    }
-    
-    match video_processor.transcode_audio(input_path, output_path, format).await {
-        Ok(job_id) => {
-            let response = VideoTranscodeResponse {
-                job_id,
-                status: "processing".to_string(),
-                message: "Audio transcode job started successfully".to_string(),
-            };
-            Ok(HttpResponse::Ok().json(response))
-        }
+    let request = req.into_inner();
+    let (input_path, output_path) = (request.input_path.clone(), request.output_path.clone());
+    let task = transcode_audio_task(video_processor.into_inner(), request.clone());
+    let queued = queue.submit_request("audio.transcode", request.priority.unwrap_or_default(), &request, &input_path, &output_path, task);
+    match queued {
+        Ok(job_id) => Ok(accepted(job_id, "Audio transcode job queued", None)),
         Err(e) => {
-            error!("Audio transcode failed: {}", e);
-            Err(ServiceError::FFmpegError(e.to_string()))
+            error!("Failed to queue audio transcode: {}", e);
+            Err(e.into())
+        }
+    }
+}
+
+async fn transcode_task(processor: Arc<VideoProcessor>, mut request: VideoTranscodeRequest) -> anyhow::Result<()> {
+    let _download = remote::localize(&mut request.input_path).await?;
+    let upload = storage::stage(&mut request.output_path)?;
+    processor.transcode_video(&request, None).await?;
+    if let Some(upload) = upload {
+        upload.finish().await?;
+    }
+    Ok(())
+}
+
+async fn hls_task(
+    processor: Arc<VideoProcessor>,
+    mut request: VideoTranscodeRequest,
+) -> anyhow::Result<MultiQualityHlsResponse> {
+    let _download = remote::localize(&mut request.input_path).await?;
+    processor.transcode_multi_quality_and_hls(&request).await
+}
+
+async fn extract_audio_task(processor: Arc<VideoProcessor>, request: AudioExtractRequest) -> anyhow::Result<()> {
+    processor.extract_audio(&request, None).await.map(|_| ())
+}
+
+async fn transcode_audio_task(processor: Arc<VideoProcessor>, request: AudioTranscodeRequest) -> anyhow::Result<()> {
+    processor
+        .transcode_audio(&request.input_path, &request.output_path, request.format.as_deref(), None)
+        .await
+        .map(|_| ())
+}
+
+/// Queue again the jobs above that a restart interrupted, from the requests kept on their
+/// records; returns how many were queued. Jobs whose callers waited for the result are gone
+/// with the connection and stay failed
+pub fn resume_interrupted(queue: &JobQueue, processor: Arc<VideoProcessor>) -> usize {
+    let mut resumed = 0;
+    for job in processor.jobs().take_interrupted() {
+        let Some(request) = job.request.clone() else {
+            continue;
+        };
+        let queued = match job.job_type.as_str() {
+            "video.transcode" => serde_json::from_value(request)
+                .map_err(anyhow::Error::from)
+                .and_then(|request| queue.resume(&job, transcode_task(processor.clone(), request))),
+            "video.hls" => serde_json::from_value(request)
+                .map_err(anyhow::Error::from)
+                .and_then(|request| queue.resume(&job, queue.keeping_result(hls_task(processor.clone(), request)))),
+            "audio.extract" => serde_json::from_value(request)
+                .map_err(anyhow::Error::from)
+                .and_then(|request| queue.resume(&job, extract_audio_task(processor.clone(), request))),
+            "audio.transcode" => serde_json::from_value(request)
+                .map_err(anyhow::Error::from)
+                .and_then(|request| queue.resume(&job, transcode_audio_task(processor.clone(), request))),
+            other => Err(anyhow::anyhow!("{} jobs can't be re-queued", other)),
+        };
+        match queued {
+            Ok(()) => {
+                info!("[{}] Re-queued {} job interrupted by the restart", job.job_id, job.job_type);
+                resumed += 1;
+            }
+            Err(e) => warn!("[{}] Could not re-queue interrupted job: {}", job.job_id, e),
         }
     }
+    resumed
+}
+
+/// `202 Accepted` for a job now waiting in the queue; clients poll `/jobs/{job_id}`, whose
+/// `result` holds what the endpoint produced once the job completes
+pub(crate) fn accepted(job_id: String, message: &str, output_url: Option<String>) -> HttpResponse {
+    HttpResponse::Accepted().json(VideoTranscodeResponse {
+        job_id,
+        status: "queued".to_string(),
+        message: message.to_string(),
+        output_url,
+    })
 }
 
 pub async fn transcode_multi_quality_and_hls(
     req: web::Json<VideoTranscodeRequest>,
     video_processor: web::Data<VideoProcessor>,
+    queue: web::Data<JobQueue>,
 ) -> Result<HttpResponse, ServiceError> {
     info!("Received multi-quality HLS transcode request");
-    let input_path = &req.input_path;
-    let output_prefix = req.output_path.trim_end_matches(".mp4");
-    let codec = req.codec.as_deref().unwrap_or("libx264");
-    let format = req.format.as_deref().unwrap_or("mp4");
-    let output_dir = std::path::Path::new(output_prefix).parent().unwrap_or_else(|| std::path::Path::new("output")).to_str().unwrap_or("output");
-    let master_playlist = "master.m3u8";
-
-    // 1. Transcode song song nhiều chất lượng
-    let outputs = video_processor.transcode_multi_quality(
-        input_path,
-        output_prefix,
-        codec,
-        format,
-    ).await.map_err(|e| ServiceError::FFmpegError(e.to_string()))?;
-
-    // 2. Đóng gói HLS
-    video_processor.package_hls(&outputs, output_dir, master_playlist)
-        .await.map_err(|e| ServiceError::FFmpegError(e.to_string()))?;
-
-    // 3. Trả về metadata
-    let response = MultiQualityHlsResponse {
-        outputs: outputs.clone(),
-        master_playlist: format!("{}/{}", output_dir, master_playlist),
-    };
-    Ok(HttpResponse::Ok().json(response))
+    req.validate()?;
+
+    let request = req.into_inner();
+    let (input_path, output_path) = (request.input_path.clone(), request.output_path.clone());
+    let task = queue.keeping_result(hls_task(video_processor.into_inner(), request.clone()));
+    let job_id = queue
+        .submit_request("video.hls", request.priority.unwrap_or_default(), &request, &input_path, &output_path, task)
+        .map_err(ServiceError::from)?;
+    Ok(accepted(job_id, "Multi-quality HLS job queued", None))
+}
+
+/// Save a video's embedded cover art as an image
+pub async fn extract_cover(
+    req: web::Json<CoverExtractRequest>,
+    video_processor: web::Data<VideoProcessor>,
+    queue: web::Data<JobQueue>,
+) -> Result<HttpResponse, ServiceError> {
+    info!("Received cover extraction request: {}", req.input_path);
+    req.validate()?;
+
+    let request = req.into_inner();
+    let (input_path, output_path) = (request.input_path.clone(), request.output_path.clone());
+    let processor = video_processor.into_inner();
+    let priority = request.priority.unwrap_or_default();
+    let task = queue.keeping_result(async move { processor.extract_cover(&request).await });
+    let job_id = queue.submit("video.cover_extract", priority, &input_path, &output_path, task).map_err(ServiceError::from)?;
+    Ok(accepted(job_id, "Cover extraction job queued", None))
+}
+
+/// Encode one source two ways and report size, timing and SSIM/VMAF of each, for tuning presets
+pub async fn compare(
+    req: web::Json<EncodeCompareRequest>,
+    video_processor: web::Data<VideoProcessor>,
+    queue: web::Data<JobQueue>,
+) -> Result<HttpResponse, ServiceError> {
+    info!("Received encode comparison request: {}", req.input_path);
+    req.validate()?;
+
+    let request = req.into_inner();
+    let (input_path, output_dir) = (request.input_path.clone(), request.output_dir.clone());
+    let processor = video_processor.into_inner();
+    let priority = request.priority.unwrap_or_default();
+    let task = queue.keeping_result(async move { processor.compare_encodes(&request).await });
+    let job_id = queue.submit("video.compare", priority, &input_path, &output_dir, task).map_err(ServiceError::from)?;
+    Ok(accepted(job_id, "Encode comparison job queued", None))
+}
+
+/// A time range as an animated GIF or WebP preview
+pub async fn animation(
+    req: web::Json<AnimationRequest>,
+    video_processor: web::Data<VideoProcessor>,
+    queue: web::Data<JobQueue>,
+) -> Result<HttpResponse, ServiceError> {
+    info!("Received animation request: {}", req.input_path);
+    req.validate()?;
+
+    let request = req.into_inner();
+    let (input_path, output_path) = (request.input_path.clone(), request.output_path.clone());
+    let processor = video_processor.into_inner();
+    let priority = request.priority.unwrap_or_default();
+    let task = queue.keeping_result(async move { processor.animate(&request).await });
+    let job_id = queue.submit("video.animation", priority, &input_path, &output_path, task).map_err(ServiceError::from)?;
+    Ok(accepted(job_id, "Animation job queued", None))
+}
+
+/// A logo or text burned into the video, for its whole length or a time window
+pub async fn watermark(
+    req: web::Json<VideoWatermarkRequest>,
+    video_processor: web::Data<VideoProcessor>,
+    queue: web::Data<JobQueue>,
+) -> Result<HttpResponse, ServiceError> {
+    info!("Received video watermark request: {}", req.input_path);
+    req.validate()?;
+
+    let mut request = req.into_inner();
+    let (input_path, output_path) = (request.input_path.clone(), request.output_path.clone());
+    let processor = video_processor.into_inner();
+    let priority = request.priority.unwrap_or_default();
+    let task = queue.keeping_result(async move {
+        let _download = remote::localize(&mut request.input_path).await?;
+        let destination = request.output_path.clone();
+        let upload = storage::stage(&mut request.output_path)?;
+        let mut response = processor.watermark_video(&request).await?;
+        if let Some(upload) = upload {
+            upload.finish().await?;
+            response.output_path = destination;
+        }
+        Ok(response)
+    });
+    let job_id = queue.submit("video.watermark", priority, &input_path, &output_path, task).map_err(ServiceError::from)?;
+    Ok(accepted(job_id, "Video watermark job queued", None))
+}
+
+/// Subtitles rendered onto the frames, from a file or one of the input's tracks
+pub async fn subtitles_burn(
+    req: web::Json<SubtitleBurnRequest>,
+    video_processor: web::Data<VideoProcessor>,
+    queue: web::Data<JobQueue>,
+) -> Result<HttpResponse, ServiceError> {
+    info!("Received subtitle burn request: {}", req.input_path);
+    req.validate()?;
+
+    let mut request = req.into_inner();
+    let (input_path, output_path) = (request.input_path.clone(), request.output_path.clone());
+    let processor = video_processor.into_inner();
+    let priority = request.priority.unwrap_or_default();
+    let task = queue.keeping_result(async move {
+        let _download = remote::localize(&mut request.input_path).await?;
+        let destination = request.output_path.clone();
+        let upload = storage::stage(&mut request.output_path)?;
+        let mut response = processor.burn_subtitles(&request).await?;
+        if let Some(upload) = upload {
+            upload.finish().await?;
+            response.output_path = destination;
+        }
+        Ok(response)
+    });
+    let job_id = queue.submit("video.subtitles_burn", priority, &input_path, &output_path, task).map_err(ServiceError::from)?;
+    Ok(accepted(job_id, "Subtitle burn-in job queued", None))
+}
+
+/// Subtitle files added as selectable tracks, without re-encoding
+pub async fn subtitles_mux(
+    req: web::Json<SubtitleMuxRequest>,
+    video_processor: web::Data<VideoProcessor>,
+    queue: web::Data<JobQueue>,
+) -> Result<HttpResponse, ServiceError> {
+    info!("Received subtitle mux request: {}", req.input_path);
+    req.validate()?;
+
+    let mut request = req.into_inner();
+    let (input_path, output_path) = (request.input_path.clone(), request.output_path.clone());
+    let processor = video_processor.into_inner();
+    let priority = request.priority.unwrap_or_default();
+    let task = queue.keeping_result(async move {
+        let _download = remote::localize(&mut request.input_path).await?;
+        let destination = request.output_path.clone();
+        let upload = storage::stage(&mut request.output_path)?;
+        let mut response = processor.mux_subtitles(&request).await?;
+        if let Some(upload) = upload {
+            upload.finish().await?;
+            response.output_path = destination;
+        }
+        Ok(response)
+    });
+    let job_id = queue.submit("video.subtitles_mux", priority, &input_path, &output_path, task).map_err(ServiceError::from)?;
+    Ok(accepted(job_id, "Subtitle mux job queued", None))
+}
+
+/// A text subtitle track saved as SRT, ASS or WebVTT
+pub async fn subtitles_extract(
+    req: web::Json<SubtitleExtractRequest>,
+    video_processor: web::Data<VideoProcessor>,
+    queue: web::Data<JobQueue>,
+) -> Result<HttpResponse, ServiceError> {
+    info!("Received subtitle extraction request: {}", req.input_path);
+    req.validate()?;
+
+    let mut request = req.into_inner();
+    let (input_path, output_path) = (request.input_path.clone(), request.output_path.clone());
+    let processor = video_processor.into_inner();
+    let priority = request.priority.unwrap_or_default();
+    let task = queue.keeping_result(async move {
+        let _download = remote::localize(&mut request.input_path).await?;
+        let destination = request.output_path.clone();
+        let upload = storage::stage(&mut request.output_path)?;
+        let mut response = processor.extract_subtitles(&request).await?;
+        if let Some(upload) = upload {
+            upload.finish().await?;
+            response.output_path = destination;
+        }
+        Ok(response)
+    });
+    let job_id = queue.submit("video.subtitles_extract", priority, &input_path, &output_path, task).map_err(ServiceError::from)?;
+    Ok(accepted(job_id, "Subtitle extraction job queued", None))
+}
+
+/// Timestamps and scores of scene cuts, for picking thumbnails or chapters
+pub async fn scenes(
+    req: web::Json<SceneDetectRequest>,
+    video_processor: web::Data<VideoProcessor>,
+    queue: web::Data<JobQueue>,
+) -> Result<HttpResponse, ServiceError> {
+    info!("Received scene detection request: {}", req.input_path);
+    req.validate()?;
+
+    let request = req.into_inner();
+    let input_path = request.input_path.clone();
+    let processor = video_processor.into_inner();
+    let priority = request.priority.unwrap_or_default();
+    let task = queue.keeping_result(async move { processor.detect_scenes(&request).await });
+    let job_id = queue.submit("video.scenes", priority, &input_path, "", task).map_err(ServiceError::from)?;
+    Ok(accepted(job_id, "Scene detection job queued", None))
+}
+
+/// Sprite sheets and a WebVTT track for players' scrub previews
+pub async fn sprites(
+    req: web::Json<SpriteSheetRequest>,
+    video_processor: web::Data<VideoProcessor>,
+    queue: web::Data<JobQueue>,
+) -> Result<HttpResponse, ServiceError> {
+    info!("Received sprite sheet request: {}", req.input_path);
+    req.validate()?;
+
+    let request = req.into_inner();
+    let (input_path, output_dir) = (request.input_path.clone(), request.output_dir.clone());
+    let processor = video_processor.into_inner();
+    let priority = request.priority.unwrap_or_default();
+    let task = queue.keeping_result(async move { processor.generate_sprite_sheet(&request).await });
+    let job_id = queue.submit("video.sprites", priority, &input_path, &output_dir, task).map_err(ServiceError::from)?;
+    Ok(accepted(job_id, "Sprite sheet job queued", None))
+}
+
+/// Stills at given timestamps or percentage positions, e.g. for scrubbing previews
+pub async fn thumbnails(
+    req: web::Json<ThumbnailRequest>,
+    video_processor: web::Data<VideoProcessor>,
+    queue: web::Data<JobQueue>,
+) -> Result<HttpResponse, ServiceError> {
+    info!("Received thumbnail request: {}", req.input_path);
+    req.validate()?;
+
+    let request = req.into_inner();
+    let (input_path, output_dir) = (request.input_path.clone(), request.output_dir.clone());
+    let processor = video_processor.into_inner();
+    let priority = request.priority.unwrap_or_default();
+    let task = queue.keeping_result(async move { processor.extract_thumbnails(&request).await });
+    let job_id = queue.submit("video.thumbnail", priority, &input_path, &output_dir, task).map_err(ServiceError::from)?;
+    Ok(accepted(job_id, "Thumbnail job queued", None))
+}
+
+/// Cut a clip out of a video, fast by stream copy or frame-accurate by re-encoding
+pub async fn trim(
+    req: web::Json<TrimRequest>,
+    video_processor: web::Data<VideoProcessor>,
+    queue: web::Data<JobQueue>,
+) -> Result<HttpResponse, ServiceError> {
+    info!("Received trim request: {}", req.input_path);
+    req.validate()?;
+
+    let request = req.into_inner();
+    let (input_path, output_path) = (request.input_path.clone(), request.output_path.clone());
+    let processor = video_processor.into_inner();
+    let priority = request.priority.unwrap_or_default();
+    let task = queue.keeping_result(async move { processor.trim(&request).await });
+    let job_id = queue.submit("video.trim", priority, &input_path, &output_path, task).map_err(ServiceError::from)?;
+    Ok(accepted(job_id, "Trim job queued", None))
+}
+
+/// Embed a poster image in a video so players and file browsers show it as the thumbnail
+pub async fn attach_cover(
+    req: web::Json<CoverAttachRequest>,
+    video_processor: web::Data<VideoProcessor>,
+    queue: web::Data<JobQueue>,
+) -> Result<HttpResponse, ServiceError> {
+    info!("Received cover attach request: {}", req.input_path);
+    req.validate()?;
+
+    let request = req.into_inner();
+    let (input_path, output_path) = (request.input_path.clone(), request.output_path.clone());
+    let processor = video_processor.into_inner();
+    let priority = request.priority.unwrap_or_default();
+    let task = queue.keeping_result(async move { processor.attach_cover(&request).await });
+    let job_id = queue.submit("video.cover_attach", priority, &input_path, &output_path, task).map_err(ServiceError::from)?;
+    Ok(accepted(job_id, "Cover attach job queued", None))
+}
+
+/// Dump frames as a numbered image sequence with a manifest, e.g. for training data
+pub async fn export_frames(
+    req: web::Json<FrameExportRequest>,
+    video_processor: web::Data<VideoProcessor>,
+    queue: web::Data<JobQueue>,
+) -> Result<HttpResponse, ServiceError> {
+    info!("Received frame export request: {}", req.input_path);
+    req.validate()?;
+
+    let request = req.into_inner();
+    let (input_path, output_dir) = (request.input_path.clone(), request.output_dir.clone());
+    let processor = video_processor.into_inner();
+    let priority = request.priority.unwrap_or_default();
+    let task = queue.keeping_result(async move { processor.export_frames(&request).await });
+    let job_id = queue.submit("video.frames", priority, &input_path, &output_dir, task).map_err(ServiceError::from)?;
+    Ok(accepted(job_id, "Frame export job queued", None))
+}
+
+/// Encode an ordered list (or wildcard pattern) of images into a slideshow or timelapse
+pub async fn slideshow(
+    req: web::Json<SlideshowRequest>,
+    video_processor: web::Data<VideoProcessor>,
+    queue: web::Data<JobQueue>,
+) -> Result<HttpResponse, ServiceError> {
+    info!("Received slideshow request: {}", req.output_path);
+    req.validate()?;
+
+    let request = req.into_inner();
+    let input_path = request
+        .pattern
+        .clone()
+        .or_else(|| request.images.as_ref()?.first().cloned())
+        .unwrap_or_default();
+    let output_path = request.output_path.clone();
+    let processor = video_processor.into_inner();
+    let priority = request.priority.unwrap_or_default();
+    let task = queue.keeping_result(async move { processor.slideshow(&request).await });
+    let job_id = queue.submit("video.slideshow", priority, &input_path, &output_path, task).map_err(ServiceError::from)?;
+    Ok(accepted(job_id, "Slideshow job queued", None))
+}
+
+/// Build a face-track index with representative crops, for people-based navigation
+pub async fn index_faces(
+    req: web::Json<FaceIndexRequest>,
+    video_processor: web::Data<VideoProcessor>,
+    queue: web::Data<JobQueue>,
+) -> Result<HttpResponse, ServiceError> {
+    info!("Received face index request: {}", req.input_path);
+    req.validate()?;
+
+    let request = req.into_inner();
+    let (input_path, output_dir) = (request.input_path.clone(), request.output_dir.clone());
+    let processor = video_processor.into_inner();
+    let priority = request.priority.unwrap_or_default();
+    let task = queue.keeping_result(async move { processor.index_faces(&request).await });
+    let job_id = queue.submit("video.faces", priority, &input_path, &output_dir, task).map_err(ServiceError::from)?;
+    Ok(accepted(job_id, "Face indexing job queued", None))
+}
+
+/// Fingerprint a clip so later uploads can be checked against it
+pub async fn fingerprint(
+    req: web::Json<FingerprintRequest>,
+    video_processor: web::Data<VideoProcessor>,
+    queue: web::Data<JobQueue>,
+) -> Result<HttpResponse, ServiceError> {
+    info!("Received fingerprint request: {}", req.input_path);
+    req.validate()?;
+
+    let request = req.into_inner();
+    let input_path = request.input_path.clone();
+    let processor = video_processor.into_inner();
+    let priority = request.priority.unwrap_or_default();
+    let task = queue.keeping_result(async move { processor.fingerprint(&request).await });
+    let job_id = queue.submit("video.fingerprint", priority, &input_path, "", task).map_err(ServiceError::from)?;
+    Ok(accepted(job_id, "Fingerprint job queued", None))
+}
+
+/// Find the best alignment of two fingerprints; cheap enough to answer without queueing
+pub async fn compare_fingerprints(req: web::Json<FingerprintCompareRequest>) -> Result<HttpResponse, ServiceError> {
+    req.validate()?;
+    let threshold = req.threshold.unwrap_or(fingerprint::DEFAULT_THRESHOLD);
+    let comparison = fingerprint::compare(&req.a, &req.b, threshold);
+    Ok(HttpResponse::Ok().json(FingerprintCompareResponse { comparison }))
 }
 
 pub async fn get_video_info(
     req: web::Json<VideoInfoRequest>,
     video_processor: web::Data<VideoProcessor>,
+    queue: web::Data<JobQueue>,
 ) -> Result<HttpResponse, ServiceError> {
     info!("Received video info request for: {}", req.file_path);
+    req.validate()?;
     
-    match video_processor.get_video_info(&req.file_path).await {
+    match media_info(&req, video_processor, &queue).await {
         Ok(info) => Ok(HttpResponse::Ok().json(info)),
         Err(e) => {
             error!("Failed to get video info: {}", e);
-            Err(ServiceError::FFmpegError(e.to_string()))
+            Err(e.into())
         }
     }
 }
 
-pub async fn get_video_info_from_json(
-    req: web::Json<serde_json::Value>,
+/// Probe `request.file_path`, queueing language detection on top when asked for. Answered
+/// inline unlike the processing endpoints: the response is the information itself and nothing
+/// is written, so there is no output to poll for
+pub(crate) async fn media_info(
+    request: &VideoInfoRequest,
     video_processor: web::Data<VideoProcessor>,
-) -> Result<HttpResponse, ServiceError> {
-    let file_path = req["file_path"].as_str().unwrap_or("");
-    
-    if file_path.is_empty() {
-        return Err(ServiceError::BadRequest("Missing file_path".to_string()));
+    queue: &JobQueue,
+) -> anyhow::Result<serde_json::Value> {
+    let info = video_processor.get_video_info(&request.file_path).await?;
+    if request.detect_language != Some(true) {
+        return Ok(info);
     }
-    
-    info!("Received video info request for: {}", file_path);
-    
-    match video_processor.get_video_info(file_path).await {
-        Ok(info) => Ok(HttpResponse::Ok().json(info)),
-        Err(e) => {
-            error!("Failed to get video info: {}", e);
-            Err(ServiceError::FFmpegError(e.to_string()))
-        }
+    let file_path = request.file_path.clone();
+    let processor = video_processor.into_inner();
+    queue
+        .run("audio.language", Priority::default(), &request.file_path, "", async move {
+            processor.detect_languages(&file_path, info).await
+        })
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::middleware::auth;
+    use crate::services::tenants;
+
+    #[tokio::test]
+    async fn test_tenant_job_passes_validation_after_localizing() {
+        let tenant = "localize-test";
+        let workspace = tenants::workspace(tenant);
+        std::fs::create_dir_all(&workspace).unwrap();
+        std::fs::write(workspace.join("in.mp4"), b"not a video").unwrap();
+
+        auth::with_tenant(Some(tenant.to_string()), async {
+            let mut request: VideoTranscodeRequest = serde_json::from_value(serde_json::json!({
+                "input_path": "file://tenants/localize-test/in.mp4",
+                "output_path": "file://tenants/localize-test/out.mp4",
+            }))
+            .unwrap();
+            request.validate().unwrap();
+
+            // What transcode_task does before handing the request to the processor
+            let download = remote::localize(&mut request.input_path).await.unwrap();
+            let upload = storage::stage(&mut request.output_path).unwrap();
+            assert!(!tenants::is_within(&workspace, std::path::Path::new(&request.input_path)));
+            assert!(!tenants::is_within(&workspace, std::path::Path::new(&request.output_path)));
+            request.validate().unwrap();
+
+            // Another tenant can't point at them, nor this one once the job is done
+            let other = auth::with_tenant(Some("other-tenant".to_string()), async { request.validate() }).await;
+            assert!(other.is_err());
+            drop((download, upload));
+            assert!(request.validate().is_err());
+        })
+        .await;
     }
-} 
\ No newline at end of file
+}
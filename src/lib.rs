@@ -0,0 +1,9 @@
+pub mod handlers;
+pub mod services;
+pub mod models;
+pub mod utils;
+pub mod logging;
+pub mod listener;
+pub mod middleware;
+#[cfg(feature = "grpc")]
+pub mod grpc;
@@ -0,0 +1,111 @@
+use std::io;
+
+/// Certificate chain and private key, both PEM
+#[derive(Debug, Clone, PartialEq)]
+pub struct TlsPaths {
+    pub cert_path: String,
+    pub key_path: String,
+}
+
+/// Where the HTTP server accepts connections
+#[derive(Debug, Clone, PartialEq)]
+pub struct ListenerConfig {
+    /// `None` when only a Unix socket was requested
+    pub tcp_address: Option<String>,
+    /// Serve HTTPS on `tcp_address` instead of plain HTTP
+    pub tls: Option<TlsPaths>,
+    pub unix_socket: Option<String>,
+}
+
+impl ListenerConfig {
+    /// Read `BIND_ADDRESS`, `PORT`, `TLS_CERT_PATH`, `TLS_KEY_PATH` and `UNIX_SOCKET`.
+    /// With `UNIX_SOCKET` set, TCP is only bound when `BIND_ADDRESS` or `PORT` is set as well
+    pub fn from_env() -> io::Result<Self> {
+        Self::from_lookup(|name| std::env::var(name).ok())
+    }
+
+    fn from_lookup(lookup: impl Fn(&str) -> Option<String>) -> io::Result<Self> {
+        let var = |name: &str| lookup(name).map(|value| value.trim().to_string()).filter(|value| !value.is_empty());
+        let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidInput, message.to_string());
+
+        let unix_socket = var("UNIX_SOCKET");
+        let host = var("BIND_ADDRESS");
+        let port = var("PORT");
+        let tcp_address = if unix_socket.is_some() && host.is_none() && port.is_none() {
+            None
+        } else {
+            Some(format!(
+                "{}:{}",
+                host.as_deref().unwrap_or("127.0.0.1"),
+                port.as_deref().unwrap_or("8082")
+            ))
+        };
+
+        let tls = match (var("TLS_CERT_PATH"), var("TLS_KEY_PATH")) {
+            (None, None) => None,
+            (Some(cert_path), Some(key_path)) => Some(TlsPaths { cert_path, key_path }),
+            _ => return Err(invalid("TLS_CERT_PATH and TLS_KEY_PATH must be set together")),
+        };
+        if tls.is_some() && !cfg!(feature = "tls") {
+            return Err(invalid("TLS_CERT_PATH is set but the service was built without the `tls` feature"));
+        }
+        if tls.is_some() && tcp_address.is_none() {
+            return Err(invalid("TLS needs a TCP listener; set BIND_ADDRESS or PORT alongside UNIX_SOCKET"));
+        }
+
+        Ok(Self { tcp_address, tls, unix_socket })
+    }
+}
+
+/// Load the certificate chain and key into a rustls server config
+#[cfg(feature = "tls")]
+pub fn rustls_config(tls: &TlsPaths) -> io::Result<rustls::ServerConfig> {
+    use std::fs::File;
+    use std::io::BufReader;
+
+    let certs = rustls_pemfile::certs(&mut BufReader::new(File::open(&tls.cert_path)?))
+        .collect::<Result<Vec<_>, _>>()?;
+    if certs.is_empty() {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, format!("no certificates in {}", tls.cert_path)));
+    }
+    let key = rustls_pemfile::private_key(&mut BufReader::new(File::open(&tls.key_path)?))?
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, format!("no private key in {}", tls.key_path)))?;
+
+    rustls::ServerConfig::builder()
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .map_err(io::Error::other)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(vars: &[(&str, &str)]) -> io::Result<ListenerConfig> {
+        ListenerConfig::from_lookup(|name| {
+            vars.iter().find(|(key, _)| *key == name).map(|(_, value)| value.to_string())
+        })
+    }
+
+    #[test]
+    fn test_tcp_by_default() {
+        let listener = config(&[]).unwrap();
+        assert_eq!(listener.tcp_address.as_deref(), Some("127.0.0.1:8082"));
+        assert_eq!((listener.tls, listener.unix_socket), (None, None));
+
+        let listener = config(&[("BIND_ADDRESS", "0.0.0.0"), ("PORT", "9000")]).unwrap();
+        assert_eq!(listener.tcp_address.as_deref(), Some("0.0.0.0:9000"));
+    }
+
+    #[test]
+    fn test_unix_socket_replaces_default_tcp() {
+        let listener = config(&[("UNIX_SOCKET", "/run/photo-rust.sock")]).unwrap();
+        assert_eq!(listener.tcp_address, None);
+        assert_eq!(listener.unix_socket.as_deref(), Some("/run/photo-rust.sock"));
+
+        let listener = config(&[("UNIX_SOCKET", "/run/photo-rust.sock"), ("PORT", "9000")]).unwrap();
+        assert_eq!(listener.tcp_address.as_deref(), Some("127.0.0.1:9000"));
+
+        assert!(config(&[("TLS_CERT_PATH", "cert.pem")]).is_err());
+    }
+}
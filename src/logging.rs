@@ -1,89 +1,202 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use flate2::{write::GzEncoder, Compression};
 use log::{LevelFilter, Log, Metadata, Record};
 use std::{
     fs::{File, OpenOptions},
     io::{self, Write},
     path::Path,
-    sync::{Arc, Mutex},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc::{self, Receiver, SyncSender, TrySendError},
+        Arc, OnceLock, RwLock,
+    },
+    thread,
+    time::{Duration, SystemTime},
 };
 
-/// Simple text logger implementation with file rotation
-pub struct Logger {
-    file: Arc<Mutex<File>>,
-    level: LevelFilter,
-    log_dir: String,
-    max_file_size: u64,
-    max_files: usize,
+/// Lines buffered between callers and the writer thread before new records are dropped
+const CHANNEL_CAPACITY: usize = 8192;
+
+/// Upper bound of records written (and flushed) together
+const MAX_BATCH: usize = 256;
+
+/// Rotation and retention settings for the log files
+#[derive(Debug, Clone)]
+pub struct LogConfig {
+    /// Rotate once `app.log` grows past this many bytes
+    pub max_file_size: u64,
+    /// Rotated files kept (`app.log.0` is the newest)
+    pub max_files: usize,
+    /// Delete rotated files older than this, regardless of count
+    pub max_age_days: Option<u64>,
+    /// Also rotate when the UTC date changes
+    pub daily: bool,
+    /// Gzip rotated files (`app.log.0.gz`)
+    pub compress: bool,
 }
 
-impl Logger {
-    /// Create a new logger instance
-    pub fn new(log_dir: &str, level: LevelFilter) -> io::Result<Self> {
-        // Create log directory if it doesn't exist
-        std::fs::create_dir_all(log_dir)?;
-        
-        let log_file_path = format!("{}/app.log", log_dir);
-        let file = OpenOptions::new()
-            .create(true)
-            .append(true)
-            .open(&log_file_path)?;
-        
-        Ok(Self {
-            file: Arc::new(Mutex::new(file)),
-            level,
-            log_dir: log_dir.to_string(),
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self {
             max_file_size: 10 * 1024 * 1024, // 10MB
             max_files: 5,
-        })
-    }
-    
-    /// Rotate log files if current file is too large
-    fn rotate_if_needed(&self) -> io::Result<()> {
-        let log_file_path = format!("{}/app.log", self.log_dir);
-        
-        if let Ok(metadata) = std::fs::metadata(&log_file_path) {
-            if metadata.len() > self.max_file_size {
-                self.rotate_log_files()?;
-            }
+            max_age_days: None,
+            daily: false,
+            compress: false,
         }
-        
-        Ok(())
     }
-    
-    /// Rotate log files by moving existing files
-    fn rotate_log_files(&self) -> io::Result<()> {
-        let log_file_path = format!("{}/app.log", self.log_dir);
-        
-        // Remove oldest log file if we have too many
-        let oldest_log = format!("{}/app.log.{}", self.log_dir, self.max_files - 1);
-        if Path::new(&oldest_log).exists() {
-            std::fs::remove_file(&oldest_log)?;
+}
+
+impl LogConfig {
+    /// Read `LOG_MAX_FILE_SIZE`, `LOG_MAX_FILES`, `LOG_MAX_AGE_DAYS`, `LOG_ROTATE_DAILY`
+    /// and `LOG_COMPRESS`, falling back to the defaults for anything unset or invalid
+    pub fn from_env() -> Self {
+        fn var<T: std::str::FromStr>(name: &str) -> Option<T> {
+            std::env::var(name).ok()?.trim().parse().ok()
         }
-        
-        // Shift existing log files
-        for i in (1..self.max_files).rev() {
-            let src = format!("{}/app.log.{}", self.log_dir, i - 1);
-            let dst = format!("{}/app.log.{}", self.log_dir, i);
-            if Path::new(&src).exists() {
-                std::fs::rename(&src, &dst)?;
+        fn flag(name: &str) -> Option<bool> {
+            let value = std::env::var(name).ok()?;
+            Some(matches!(value.trim().to_lowercase().as_str(), "1" | "true" | "yes" | "on"))
+        }
+
+        let defaults = Self::default();
+        Self {
+            max_file_size: var("LOG_MAX_FILE_SIZE").unwrap_or(defaults.max_file_size),
+            max_files: var::<usize>("LOG_MAX_FILES").unwrap_or(defaults.max_files).max(1),
+            max_age_days: var("LOG_MAX_AGE_DAYS").or(defaults.max_age_days),
+            daily: flag("LOG_ROTATE_DAILY").unwrap_or(defaults.daily),
+            compress: flag("LOG_COMPRESS").unwrap_or(defaults.compress),
+        }
+    }
+}
+
+/// `RUST_LOG`-style filter: a default level plus per-target overrides,
+/// e.g. `info,video_processor=debug,actix_web=warn`
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogFilter {
+    default: LevelFilter,
+    directives: Vec<(String, LevelFilter)>,
+}
+
+impl LogFilter {
+    pub fn new(default: LevelFilter) -> Self {
+        Self { default, directives: Vec::new() }
+    }
+
+    /// Parse a comma-separated spec; a bare level replaces `default`
+    pub fn parse(spec: &str, default: LevelFilter) -> Result<Self, String> {
+        let mut filter = Self::new(default);
+        for directive in spec.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+            match directive.split_once('=') {
+                Some((target, level)) => {
+                    let target = target.trim();
+                    if target.is_empty() {
+                        return Err(format!("missing target in '{}'", directive));
+                    }
+                    filter.directives.push((target.to_string(), parse_level(level)?));
+                }
+                None => filter.default = parse_level(directive)?,
             }
         }
-        
-        // Rename current log file
-        let rotated_log = format!("{}/app.log.0", self.log_dir);
-        if Path::new(&log_file_path).exists() {
-            std::fs::rename(&log_file_path, &rotated_log)?;
+        Ok(filter)
+    }
+
+    /// `video_processor` matches the target itself, a path prefix or any path segment
+    /// (`media_processing_service::services::video_processor`); the longest directive wins
+    pub fn level_for(&self, target: &str) -> LevelFilter {
+        self.directives
+            .iter()
+            .filter(|(name, _)| {
+                target == name
+                    || target.starts_with(&format!("{}::", name))
+                    || target.ends_with(&format!("::{}", name))
+                    || target.contains(&format!("::{}::", name))
+            })
+            .max_by_key(|(name, _)| name.len())
+            .map(|(_, level)| *level)
+            .unwrap_or(self.default)
+    }
+
+    /// Most verbose level any target can log at, for `log::set_max_level`
+    pub fn max_level(&self) -> LevelFilter {
+        self.directives
+            .iter()
+            .map(|(_, level)| *level)
+            .fold(self.default, Ord::max)
+    }
+}
+
+impl std::fmt::Display for LogFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.default.as_str().to_lowercase())?;
+        for (target, level) in &self.directives {
+            write!(f, ",{}={}", target, level.as_str().to_lowercase())?;
         }
-        
-        // Create new log file
-        let new_file = OpenOptions::new()
-            .create(true)
-            .append(true)
-            .open(&log_file_path)?;
-        
-        *self.file.lock().unwrap() = new_file;
-        
         Ok(())
     }
+}
+
+fn parse_level(level: &str) -> Result<LevelFilter, String> {
+    level
+        .trim()
+        .parse()
+        .map_err(|_| format!("unknown log level '{}'", level.trim()))
+}
+
+/// Filter of the installed logger, shared so it can be changed at runtime
+static ACTIVE_FILTER: OnceLock<Arc<RwLock<LogFilter>>> = OnceLock::new();
+
+/// Current filter spec, or `None` before `init_logger` ran
+pub fn current_filter() -> Option<String> {
+    ACTIVE_FILTER.get().map(|filter| filter.read().unwrap().to_string())
+}
+
+/// Replace the installed logger's filter without restarting; returns the new spec
+pub fn set_filter(spec: &str) -> Result<String, String> {
+    let active = ACTIVE_FILTER.get().ok_or("logger is not initialized")?;
+    let mut filter = active.write().unwrap();
+    *filter = LogFilter::parse(spec, filter.default)?;
+    log::set_max_level(filter.max_level());
+    Ok(filter.to_string())
+}
+
+enum LogMessage {
+    Line(String),
+    Flush(SyncSender<()>),
+}
+
+/// Simple text logger implementation with file rotation.
+///
+/// Records are formatted on the calling thread and handed to a dedicated writer
+/// thread, so logging never waits on file IO or rotation.
+pub struct Logger {
+    sender: SyncSender<LogMessage>,
+    filter: Arc<RwLock<LogFilter>>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl Logger {
+    /// Create a new logger instance
+    pub fn new(log_dir: &str, level: LevelFilter) -> io::Result<Self> {
+        Self::with_config(log_dir, LogFilter::new(level), LogConfig::default())
+    }
+
+    pub fn with_config(log_dir: &str, filter: LogFilter, config: LogConfig) -> io::Result<Self> {
+        let writer = LogWriter::open(log_dir, config)?;
+        let (sender, receiver) = mpsc::sync_channel(CHANNEL_CAPACITY);
+        let dropped = Arc::new(AtomicU64::new(0));
+
+        let writer_dropped = dropped.clone();
+        thread::Builder::new()
+            .name("log-writer".to_string())
+            .spawn(move || writer.run(receiver, writer_dropped))?;
+
+        Ok(Self {
+            sender,
+            filter: Arc::new(RwLock::new(filter)),
+            dropped,
+        })
+    }
     
     /// Format log record as simple text
     fn format_log(&self, record: &Record) -> String {
@@ -93,7 +206,7 @@ impl Logger {
         let target = if record.target() == "media_processing_service" {
             "main"
         } else if record.target().contains("::") {
-            record.target().split("::").last().unwrap_or(record.target())
+            record.target().rsplit("::").next().unwrap_or(record.target())
         } else {
             record.target()
         };
@@ -106,65 +219,253 @@ impl Logger {
             module_path.to_string()
         };
 
+        // Correlate every line logged while serving a request
+        let request_id = crate::middleware::request_id::current()
+            .map(|id| format!(" [req={}]", id))
+            .unwrap_or_default();
+
         format!(
-            "[{}] {} [{}] {} - {}\n",
+            "[{}] {}{} [{}] {} - {}\n",
             timestamp,
             record.level(),
+            request_id,
             target,
             short_module,
             record.args()
         )
     }
-    
-    /// Write log to file
-    fn write_log(&self, record: &Record) -> io::Result<()> {
-        let log_line = self.format_log(record);
-        
-        let mut file = self.file.lock().unwrap();
-        file.write_all(log_line.as_bytes())?;
-        file.flush()?;
-        
-        Ok(())
-    }
 }
 
 impl Log for Logger {
     fn enabled(&self, metadata: &Metadata) -> bool {
-        metadata.level() <= self.level
+        metadata.level() <= self.filter.read().unwrap().level_for(metadata.target())
     }
     
     fn log(&self, record: &Record) {
         if self.enabled(record.metadata()) {
-            // Rotate logs if needed
-            if let Err(e) = self.rotate_if_needed() {
-                eprintln!("Failed to rotate log files: {}", e);
+            // A full channel means the disk can't keep up; drop rather than stall the caller
+            if let Err(TrySendError::Full(_)) = self.sender.try_send(LogMessage::Line(self.format_log(record))) {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+    
+    /// Block until every record queued so far has been written
+    fn flush(&self) {
+        let (ack, done) = mpsc::sync_channel(1);
+        if self.sender.send(LogMessage::Flush(ack)).is_ok() {
+            let _ = done.recv();
+        }
+    }
+}
+
+/// Owns the log file on the writer thread
+struct LogWriter {
+    file: File,
+    log_dir: String,
+    config: LogConfig,
+    /// UTC date the current `app.log` was started on, for daily rotation
+    opened_on: NaiveDate,
+}
+
+impl LogWriter {
+    fn open(log_dir: &str, config: LogConfig) -> io::Result<Self> {
+        // Create log directory if it doesn't exist
+        std::fs::create_dir_all(log_dir)?;
+        
+        let log_file_path = format!("{}/app.log", log_dir);
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&log_file_path)?;
+
+        // An app.log left over from a previous day is rotated on the first write
+        let opened_on = file
+            .metadata()
+            .and_then(|metadata| metadata.modified())
+            .map(|modified| DateTime::<Utc>::from(modified).date_naive())
+            .unwrap_or_else(|_| Utc::now().date_naive());
+        
+        Ok(Self {
+            file,
+            log_dir: log_dir.to_string(),
+            config,
+            opened_on,
+        })
+    }
+
+    /// Write queued records in batches until every `Logger` handle is gone
+    fn run(mut self, receiver: Receiver<LogMessage>, dropped: Arc<AtomicU64>) {
+        let mut batch = String::new();
+        let mut acks = Vec::new();
+
+        while let Ok(message) = receiver.recv() {
+            let mut pending = Some(message);
+            let mut count = 0;
+            while let Some(message) = pending.take() {
+                match message {
+                    LogMessage::Line(line) => batch.push_str(&line),
+                    LogMessage::Flush(ack) => acks.push(ack),
+                }
+                count += 1;
+                // Drain whatever queued up meanwhile so a burst costs one write and one flush
+                if count < MAX_BATCH {
+                    pending = receiver.try_recv().ok();
+                }
+            }
+
+            let lost = dropped.swap(0, Ordering::Relaxed);
+            if lost > 0 {
+                batch.push_str(&format!(
+                    "[{}] WARN [logging] logging - {} log record(s) dropped, writer fell behind\n",
+                    chrono::Utc::now().format("%Y-%m-%d %H:%M:%S%.3f"),
+                    lost
+                ));
             }
-            
-            // Write to file
-            if let Err(e) = self.write_log(record) {
-                eprintln!("Failed to write log: {}", e);
+
+            if !batch.is_empty() {
+                if let Err(e) = self.write_batch(&batch) {
+                    eprintln!("Failed to write log: {}", e);
+                }
+                batch.clear();
             }
-            
-            // Also print to stderr for development
-            if cfg!(debug_assertions) {
-                eprintln!("{}", self.format_log(record).trim());
+            for ack in acks.drain(..) {
+                let _ = ack.send(());
             }
         }
     }
+
+    fn write_batch(&mut self, batch: &str) -> io::Result<()> {
+        // Rotate logs if needed
+        if let Err(e) = self.rotate_if_needed() {
+            eprintln!("Failed to rotate log files: {}", e);
+        }
+
+        self.file.write_all(batch.as_bytes())?;
+        self.file.flush()?;
+
+        // Also print to stderr for development
+        if cfg!(debug_assertions) {
+            eprint!("{}", batch);
+        }
+        Ok(())
+    }
     
-    fn flush(&self) {
-        if let Ok(mut file) = self.file.lock() {
-            let _ = file.flush();
+    /// Rotate log files if current file is too large or, with daily rotation, from a past day
+    fn rotate_if_needed(&mut self) -> io::Result<()> {
+        let log_file_path = format!("{}/app.log", self.log_dir);
+        let today = Utc::now().date_naive();
+        
+        let too_large = std::fs::metadata(&log_file_path)
+            .map(|metadata| metadata.len() > self.config.max_file_size)
+            .unwrap_or(false);
+        let new_day = self.config.daily && today != self.opened_on;
+
+        if too_large || new_day {
+            self.rotate_log_files()?;
+            self.opened_on = today;
         }
+        
+        Ok(())
+    }
+    
+    /// Rotate log files by moving existing files
+    fn rotate_log_files(&mut self) -> io::Result<()> {
+        let log_file_path = format!("{}/app.log", self.log_dir);
+        
+        let max_files = self.config.max_files.max(1);
+        
+        // Remove oldest log file if we have too many
+        for suffix in ROTATED_SUFFIXES {
+            let oldest_log = format!("{}/app.log.{}{}", self.log_dir, max_files - 1, suffix);
+            if Path::new(&oldest_log).exists() {
+                std::fs::remove_file(&oldest_log)?;
+            }
+        }
+        
+        // Shift existing log files, compressed or not
+        for i in (1..max_files).rev() {
+            for suffix in ROTATED_SUFFIXES {
+                let src = format!("{}/app.log.{}{}", self.log_dir, i - 1, suffix);
+                let dst = format!("{}/app.log.{}{}", self.log_dir, i, suffix);
+                if Path::new(&src).exists() {
+                    std::fs::rename(&src, &dst)?;
+                }
+            }
+        }
+        
+        // Rename current log file
+        let rotated_log = format!("{}/app.log.0", self.log_dir);
+        if Path::new(&log_file_path).exists() {
+            std::fs::rename(&log_file_path, &rotated_log)?;
+            if self.config.compress {
+                compress_file(&rotated_log)?;
+            }
+        }
+        
+        // Create new log file
+        let new_file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&log_file_path)?;
+        
+        self.file = new_file;
+
+        self.remove_expired()
+    }
+
+    /// Enforce `max_age_days` on the rotated files
+    fn remove_expired(&self) -> io::Result<()> {
+        let Some(max_age_days) = self.config.max_age_days else {
+            return Ok(());
+        };
+        let max_age = Duration::from_secs(max_age_days * 24 * 60 * 60);
+        let now = SystemTime::now();
+
+        for i in 0..self.config.max_files.max(1) {
+            for suffix in ROTATED_SUFFIXES {
+                let path = format!("{}/app.log.{}{}", self.log_dir, i, suffix);
+                let Ok(modified) = std::fs::metadata(&path).and_then(|metadata| metadata.modified()) else {
+                    continue;
+                };
+                if now.duration_since(modified).unwrap_or_default() > max_age {
+                    std::fs::remove_file(&path)?;
+                }
+            }
+        }
+        Ok(())
     }
 }
 
-/// Initialize the custom logger
+/// Extensions a rotated `app.log.N` may carry
+const ROTATED_SUFFIXES: [&str; 2] = ["", ".gz"];
+
+/// Replace `path` with a gzipped `path.gz`
+fn compress_file(path: &str) -> io::Result<()> {
+    let mut input = File::open(path)?;
+    let output = File::create(format!("{}.gz", path))?;
+    let mut encoder = GzEncoder::new(output, Compression::default());
+    io::copy(&mut input, &mut encoder)?;
+    encoder.finish()?;
+    std::fs::remove_file(path)
+}
+
+/// Initialize the custom logger; `RUST_LOG` may override `level` and add per-target filters
 pub fn init_logger(log_dir: &str, level: LevelFilter) -> io::Result<()> {
-    let logger = Logger::new(log_dir, level)?;
+    let filter = match std::env::var("RUST_LOG") {
+        Ok(spec) => LogFilter::parse(&spec, level).unwrap_or_else(|e| {
+            eprintln!("Ignoring invalid RUST_LOG: {}", e);
+            LogFilter::new(level)
+        }),
+        Err(_) => LogFilter::new(level),
+    };
+    let max_level = filter.max_level();
+
+    let logger = Logger::with_config(log_dir, filter, LogConfig::from_env())?;
+    let _ = ACTIVE_FILTER.set(logger.filter.clone());
     log::set_boxed_logger(Box::new(logger))
-        .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
-    log::set_max_level(level);
+        .map_err(io::Error::other)?;
+    log::set_max_level(max_level);
     Ok(())
 }
 
@@ -206,4 +507,76 @@ mod tests {
         assert!(formatted.contains("Test message"));
         assert!(formatted.contains("INFO"));
     }
+
+    #[test]
+    fn test_filter_per_target() {
+        let filter = LogFilter::parse("warn,video_processor=debug,actix_web=error", LevelFilter::Info).unwrap();
+        assert_eq!(filter.level_for("media_processing_service::services::video_processor"), LevelFilter::Debug);
+        assert_eq!(filter.level_for("actix_web::middleware::logger"), LevelFilter::Error);
+        assert_eq!(filter.level_for("media_processing_service::handlers::video"), LevelFilter::Warn);
+        assert_eq!(filter.max_level(), LevelFilter::Debug);
+        assert_eq!(filter.to_string(), "warn,video_processor=debug,actix_web=error");
+        assert!(LogFilter::parse("video_processor=loud", LevelFilter::Info).is_err());
+    }
+
+    #[test]
+    fn test_flush_waits_for_writer() {
+        let temp_dir = tempdir().unwrap();
+        let log_dir = temp_dir.path().to_str().unwrap();
+        let logger = Logger::new(log_dir, LevelFilter::Debug).unwrap();
+
+        for i in 0..3 {
+            logger.log(
+                &log::Record::builder()
+                    .level(Level::Info)
+                    .target("test_target")
+                    .args(format_args!("queued message {}", i))
+                    .build(),
+            );
+        }
+        logger.flush();
+
+        let contents = std::fs::read_to_string(format!("{}/app.log", log_dir)).unwrap();
+        assert_eq!(contents.lines().count(), 3);
+        assert!(contents.contains("queued message 2"));
+    }
+
+    #[test]
+    fn test_rotation_compresses_and_keeps_max_files() {
+        let temp_dir = tempdir().unwrap();
+        let log_dir = temp_dir.path().to_str().unwrap();
+        let config = LogConfig {
+            max_file_size: 8,
+            max_files: 2,
+            compress: true,
+            ..LogConfig::default()
+        };
+        let mut writer = LogWriter::open(log_dir, config).unwrap();
+        for i in 0..4 {
+            writer.write_batch(&format!("batch number {}\n", i)).unwrap();
+        }
+
+        let mut rotated = String::new();
+        let gz = File::open(format!("{}/app.log.0.gz", log_dir)).unwrap();
+        io::Read::read_to_string(&mut flate2::read::GzDecoder::new(gz), &mut rotated).unwrap();
+        assert_eq!(rotated, "batch number 2\n");
+        assert!(Path::new(&format!("{}/app.log.1.gz", log_dir)).exists());
+        assert!(!Path::new(&format!("{}/app.log.2.gz", log_dir)).exists());
+        assert!(!Path::new(&format!("{}/app.log.0", log_dir)).exists());
+    }
+
+    #[test]
+    fn test_daily_rotation_on_date_change() {
+        let temp_dir = tempdir().unwrap();
+        let log_dir = temp_dir.path().to_str().unwrap();
+        let config = LogConfig { daily: true, ..LogConfig::default() };
+        let mut writer = LogWriter::open(log_dir, config).unwrap();
+        writer.write_batch("today\n").unwrap();
+        assert!(!Path::new(&format!("{}/app.log.0", log_dir)).exists());
+
+        writer.opened_on = writer.opened_on.pred_opt().unwrap();
+        writer.write_batch("tomorrow\n").unwrap();
+        assert_eq!(std::fs::read_to_string(format!("{}/app.log.0", log_dir)).unwrap(), "today\n");
+        assert_eq!(std::fs::read_to_string(format!("{}/app.log", log_dir)).unwrap(), "tomorrow\n");
+    }
 } 
\ No newline at end of file
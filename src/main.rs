@@ -1,13 +1,21 @@
-mod handlers;
-mod services;
-mod models;
-mod utils;
-mod logging;
-
-use actix_web::{web, App, HttpServer};
+use actix_web::{middleware::from_fn, web, App, HttpServer};
+use std::sync::Arc;
 use log::info;
-use services::video_processor::VideoProcessor;
-use logging::{init_logger, levels};
+use media_processing_service::handlers;
+use media_processing_service::middleware::auth::{self, ApiKeys};
+use media_processing_service::middleware::cors::CorsConfig;
+use media_processing_service::middleware::{api_version, cors, request_id};
+use media_processing_service::utils::{audit, sandbox};
+use media_processing_service::utils::validation::{json_error_handler, query_error_handler};
+use media_processing_service::services::video_processor::VideoProcessor;
+use media_processing_service::services::sync_processor::SyncProcessor;
+use media_processing_service::services::queue::{JobQueue, QueueConfig};
+use media_processing_service::services::capabilities::{self, Capabilities};
+use media_processing_service::services::url_signer::UrlSigner;
+use media_processing_service::listener::ListenerConfig;
+use media_processing_service::logging::{init_logger, levels};
+#[cfg(feature = "grpc")]
+use media_processing_service::grpc;
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
@@ -20,31 +28,125 @@ async fn main() -> std::io::Result<()> {
     };
     
     init_logger(&log_dir, log_level)?;
+    audit::init(&log_dir)?;
+    sandbox::init(sandbox::SandboxConfig::from_env());
     
     info!("Starting Media Processing Service...");
     
     // Initialize video processor
-    let video_processor = VideoProcessor::new()
-        .expect("Failed to initialize video processor");
+    let video_processor = Arc::new(VideoProcessor::new()
+        .expect("Failed to initialize video processor"));
+    
+    let video_processor_data = web::Data::from(video_processor.clone());
+    let sync_processor_data = web::Data::new(SyncProcessor::with_jobs(video_processor.shared_jobs()));
+    let queue_data = web::Data::new(JobQueue::start(QueueConfig::from_env(), video_processor.shared_jobs()));
+    
+    // Working directories of crashed runs and unfetched outputs under TEMP_DIR expire
+    video_processor.temp_files().spawn_collector();
+    
+    // Without this, jobs the last run left unfinished stay failed as "interrupted"
+    let requeue = std::env::var("JOB_REQUEUE")
+        .map(|v| matches!(v.trim().to_lowercase().as_str(), "1" | "true" | "yes" | "on"))
+        .unwrap_or(false);
+    if requeue {
+        let resumed = handlers::video::resume_interrupted(&queue_data, video_processor.clone());
+        info!("Re-queued {} interrupted jobs", resumed);
+    }
+    
+    // Probe FFmpeg once so capability queries never spawn processes and requests for
+    // encoders this build lacks are rejected during validation
+    let capabilities_data = web::Data::new(capabilities::init(Capabilities::probe()).clone());
+    
+    let api_keys = ApiKeys::from_env()
+        .unwrap_or_else(|e| panic!("Invalid API_KEYS: {}", e));
+    if !api_keys.is_enabled() {
+        log::warn!("API_KEYS not set, every endpoint is open");
+    }
+    let api_keys_data = web::Data::new(api_keys);
+    let cors_data = web::Data::new(CorsConfig::from_env());
+    let url_signer_data = web::Data::new(UrlSigner::from_env());
+    
+    // Request body limits; actix's own defaults are 2MB for JSON and 256KB for raw payloads
+    let body_limit = |name: &str, default: usize| {
+        std::env::var(name).ok().and_then(|v| v.trim().parse().ok()).unwrap_or(default)
+    };
+    let json_limit = body_limit("JSON_MAX_BYTES", 2 * 1024 * 1024);
+    let payload_limit = body_limit("PAYLOAD_MAX_BYTES", 256 * 1024);
     
-    let video_processor_data = web::Data::new(video_processor);
+    // Test media generators; they write wherever they are told, so they stay off in production
+    let debug_endpoints = std::env::var("DEBUG_ENDPOINTS")
+        .map(|v| matches!(v.trim().to_lowercase().as_str(), "1" | "true" | "yes" | "on"))
+        .unwrap_or(false);
+    if debug_endpoints {
+        log::warn!("DEBUG_ENDPOINTS set, fixture generators are routed under /admin/fixtures");
+    }
     
-    let port = std::env::var("PORT").unwrap_or_else(|_| "8082".to_string());
-    let bind_address = format!("127.0.0.1:{}", port);
+    let listener = ListenerConfig::from_env()?;
     
-    info!("Server starting on {}", bind_address);
+    // gRPC runs on its own runtime so long encodes can't starve the HTTP workers
+    #[cfg(feature = "grpc")]
+    {
+        let grpc_port = std::env::var("GRPC_PORT").unwrap_or_else(|_| "50051".to_string());
+        let grpc_address = format!("127.0.0.1:{}", grpc_port)
+            .parse()
+            .expect("Invalid gRPC bind address");
+        let grpc_processor = video_processor.clone();
+        let (grpc_queue, grpc_keys) = (queue_data.clone().into_inner(), api_keys_data.clone().into_inner());
+        std::thread::spawn(move || {
+            let runtime = tokio::runtime::Runtime::new().expect("Failed to start gRPC runtime");
+            if let Err(e) = runtime.block_on(grpc::serve(grpc_address, grpc_processor, grpc_queue, grpc_keys)) {
+                log::error!("gRPC server stopped: {}", e);
+            }
+        });
+    }
     
-    HttpServer::new(move || {   
+    let mut server = HttpServer::new(move || {   
         App::new()
+            .wrap(from_fn(auth::authorize))
+            .wrap(from_fn(request_id::request_id))
+            .wrap(from_fn(cors::cors))
+            .app_data(web::JsonConfig::default().limit(json_limit).error_handler(json_error_handler))
+            .app_data(web::PayloadConfig::new(payload_limit))
+            .app_data(web::QueryConfig::default().error_handler(query_error_handler))
             .app_data(video_processor_data.clone())
+            .app_data(sync_processor_data.clone())
+            .app_data(queue_data.clone())
+            .app_data(capabilities_data.clone())
+            .app_data(api_keys_data.clone())
+            .app_data(cors_data.clone())
+            .app_data(url_signer_data.clone())
             .service(
                 web::scope("/api/v1")
+                    .wrap(from_fn(api_version::v1))
+                    .route("/capabilities", web::get().to(handlers::capabilities::get_capabilities))
+                    .route("/jobs", web::get().to(handlers::jobs::list_jobs))
+                    .route("/jobs/{job_id}", web::get().to(handlers::jobs::get_job))
+                    .route("/jobs/{job_id}/progress", web::get().to(handlers::jobs::job_progress))
+                    .route("/jobs/{job_id}", web::delete().to(handlers::jobs::cancel_job))
                     .service(
                         web::scope("/video")
                             .route("/transcode", web::post().to(handlers::video::transcode_video))
                             .route("/extract-audio", web::post().to(handlers::video::extract_audio))
                             .route("/info", web::post().to(handlers::video::get_video_info))
                             .route("/multi-quality-hls", web::post().to(handlers::video::transcode_multi_quality_and_hls))
+                            .route("/autocrop", web::post().to(handlers::image::autocrop_video))
+                            .route("/trim", web::post().to(handlers::video::trim))
+                            .route("/thumbnail", web::post().to(handlers::video::thumbnails))
+                            .route("/compare", web::post().to(handlers::video::compare))
+                            .route("/sprites", web::post().to(handlers::video::sprites))
+                            .route("/scenes", web::post().to(handlers::video::scenes))
+                            .route("/gif", web::post().to(handlers::video::animation))
+                            .route("/watermark", web::post().to(handlers::video::watermark))
+                            .route("/subtitles/burn", web::post().to(handlers::video::subtitles_burn))
+                            .route("/subtitles/mux", web::post().to(handlers::video::subtitles_mux))
+                            .route("/subtitles/extract", web::post().to(handlers::video::subtitles_extract))
+                            .route("/cover/extract", web::post().to(handlers::video::extract_cover))
+                            .route("/cover/attach", web::post().to(handlers::video::attach_cover))
+                            .route("/slideshow", web::post().to(handlers::video::slideshow))
+                            .route("/frames", web::post().to(handlers::video::export_frames))
+                            .route("/faces", web::post().to(handlers::video::index_faces))
+                            .route("/fingerprint", web::post().to(handlers::video::fingerprint))
+                            .route("/fingerprint/compare", web::post().to(handlers::video::compare_fingerprints))
                     )
                     .service(
                         web::scope("/audio")
@@ -53,12 +155,102 @@ async fn main() -> std::io::Result<()> {
                     )
                     .service(
                         web::scope("/metadata")
-                            .route("/extract", web::post().to(handlers::video::get_video_info_from_json))
+                            .route("/extract", web::post().to(handlers::video::get_video_info))
+                    )
+                    .service(
+                        web::scope("/sync")
+                            .route("/mirror", web::post().to(handlers::sync::mirror_directory))
+                    )
+                    .service(
+                        web::scope("/image")
+                            .route("/lossless-jpeg", web::post().to(handlers::image::lossless_jpeg))
+                            .route("/autotrim", web::post().to(handlers::image::autotrim))
+                            .route("/blur", web::post().to(handlers::image::blur))
+                            .route("/sticker", web::post().to(handlers::image::sticker))
+                            .route("/watermark/invisible", web::post().to(handlers::image::embed_watermark))
+                            .route("/watermark/detect", web::post().to(handlers::image::detect_watermark))
+                            .route("/panorama/analyze", web::post().to(handlers::image::analyze_panorama))
+                            .route("/upscale", web::post().to(handlers::image::upscale))
+                    )
+            )
+            .service(
+                web::scope("/api/v2")
+                    .wrap(from_fn(api_version::v2))
+                    .route("/capabilities", web::get().to(handlers::capabilities::get_capabilities))
+                    .route("/jobs/{job_id}", web::get().to(handlers::jobs::get_job))
+                    .route("/jobs/{job_id}", web::delete().to(handlers::jobs::cancel_job))
+                    .service(
+                        web::scope("/video")
+                            .route("/transcode", web::post().to(handlers::v2::transcode_video))
+                            .route("/hls", web::post().to(handlers::v2::transcode_hls))
+                    )
+                    .service(
+                        web::scope("/audio")
+                            .route("/extract", web::post().to(handlers::v2::extract_audio))
+                            .route("/transcode", web::post().to(handlers::v2::transcode_audio))
+                    )
+                    .service(
+                        web::scope("/metadata")
+                            .route("/extract", web::post().to(handlers::v2::extract_metadata))
+                    )
+                    .service(
+                        web::scope("/sync")
+                            .route("/mirror", web::post().to(handlers::v2::mirror_directory))
                     )
             )
-            .route("/health", web::get().to(handlers::health::health_check))
-    })
-    .bind(&bind_address)?
-    .run()
-    .await
+            .service(
+                web::scope("/admin")
+                    .route("/stats", web::get().to(handlers::admin::stats))
+                    .route("/usage", web::get().to(handlers::admin::usage))
+                    .route("/queue", web::get().to(handlers::admin::queue))
+                    .route("/workers", web::get().to(handlers::admin::workers))
+                    .route("/logging", web::get().to(handlers::admin::get_log_filter))
+                    .route("/logging", web::put().to(handlers::admin::set_log_filter))
+                    .route("/benchmark", web::post().to(handlers::admin::run_benchmark))
+                    .configure(|config| {
+                        if debug_endpoints {
+                            config
+                                .route("/fixtures/image", web::post().to(handlers::admin::fixture_image))
+                                .route("/fixtures/video", web::post().to(handlers::admin::fixture_video));
+                        }
+                    })
+            )
+            .route("/files/{token}", web::get().to(handlers::files::serve_file))
+            .service(
+                web::scope("/health")
+                    .route("", web::get().to(handlers::health::health_check))
+                    .route("/live", web::get().to(handlers::health::liveness))
+                    .route("/ready", web::get().to(handlers::health::readiness))
+            )
+    });
+
+    if let Some(socket) = &listener.unix_socket {
+        // A socket left behind by a previous run would make the bind fail
+        if std::path::Path::new(socket).exists() {
+            std::fs::remove_file(socket)?;
+        }
+        info!("Server starting on unix:{}", socket);
+        server = server.bind_uds(socket)?;
+    }
+    if let Some(address) = &listener.tcp_address {
+        #[cfg(feature = "tls")]
+        if let Some(tls) = &listener.tls {
+            info!("Server starting on https://{}", address);
+            server = server.bind_rustls_0_23(address, media_processing_service::listener::rustls_config(tls)?)?;
+        } else {
+            info!("Server starting on {}", address);
+            server = server.bind(address)?;
+        }
+        #[cfg(not(feature = "tls"))]
+        {
+            info!("Server starting on {}", address);
+            server = server.bind(address)?;
+        }
+    }
+
+    let server = server.run().await;
+
+    // Drain the background log writer before exiting
+    log::logger().flush();
+    server
 } 
\ No newline at end of file
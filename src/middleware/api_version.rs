@@ -0,0 +1,99 @@
+use actix_web::{
+    body::{EitherBody, MessageBody},
+    dev::{ServiceRequest, ServiceResponse},
+    http::header::{HeaderName, HeaderValue},
+    middleware::Next,
+    Error, HttpResponse,
+};
+use log::warn;
+
+/// Request header clients use to state which API versions they accept (e.g. `2` or `1, 2`)
+pub const ACCEPT_VERSION_HEADER: &str = "accept-version";
+
+/// Response header carrying the API version that served the request
+pub const API_VERSION_HEADER: &str = "api-version";
+
+pub const SUPPORTED_VERSIONS: &[u32] = &[1, 2];
+
+/// Middleware for the deprecated `/api/v1` scope
+pub async fn v1(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, Error> {
+    warn!("Deprecated API v1 call: {} {}", req.method(), req.path());
+
+    let mut res = negotiate(req, next, 1).await?;
+    let headers = res.headers_mut();
+    headers.insert(HeaderName::from_static("deprecation"), HeaderValue::from_static("true"));
+    headers.insert(
+        HeaderName::from_static("link"),
+        HeaderValue::from_static("</api/v2>; rel=\"successor-version\""),
+    );
+    headers.insert(
+        HeaderName::from_static("warning"),
+        HeaderValue::from_static("299 - \"API v1 is deprecated, migrate to /api/v2\""),
+    );
+    Ok(res)
+}
+
+/// Middleware for the current `/api/v2` scope
+pub async fn v2(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, Error> {
+    negotiate(req, next, 2).await
+}
+
+/// Reject requests whose `Accept-Version` excludes the scope's version, and stamp `API-Version`
+async fn negotiate<B: MessageBody + 'static>(
+    req: ServiceRequest,
+    next: Next<B>,
+    version: u32,
+) -> Result<ServiceResponse<EitherBody<B>>, Error> {
+    let accepted = req
+        .headers()
+        .get(ACCEPT_VERSION_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(parse_accept_version);
+
+    let mut res = match accepted {
+        Some(versions) if !versions.contains(&version) => {
+            let response = HttpResponse::NotAcceptable().json(serde_json::json!({
+                "error": "Not Acceptable",
+                "message": format!("This endpoint is served by API v{}", version),
+                "supported_versions": SUPPORTED_VERSIONS,
+            }));
+            req.into_response(response).map_into_right_body()
+        }
+        _ => next.call(req).await?.map_into_left_body(),
+    };
+
+    res.headers_mut().insert(
+        HeaderName::from_static(API_VERSION_HEADER),
+        HeaderValue::from(version),
+    );
+    Ok(res)
+}
+
+/// Parse a list such as `2`, `v2` or `1, 2.0` into major version numbers
+fn parse_accept_version(value: &str) -> Vec<u32> {
+    value
+        .split(',')
+        .filter_map(|part| {
+            let part = part.trim().trim_start_matches(['v', 'V']);
+            part.split('.').next()?.parse().ok()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_accept_version() {
+        assert_eq!(parse_accept_version("2"), vec![2]);
+        assert_eq!(parse_accept_version("v1, 2.0"), vec![1, 2]);
+        assert!(parse_accept_version("latest").is_empty());
+    }
+}
@@ -0,0 +1,222 @@
+use actix_web::{
+    body::{EitherBody, MessageBody},
+    dev::{ServiceRequest, ServiceResponse},
+    http::Method,
+    middleware::Next,
+    web, Error, ResponseError,
+};
+use log::warn;
+use std::collections::HashMap;
+use crate::utils::error::ServiceError;
+
+pub const API_KEY_HEADER: &str = "x-api-key";
+
+/// Roles are ordered: each one includes the permissions of those below it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Role {
+    /// Info, metadata, capabilities and job listings
+    Read,
+    /// Everything that spawns FFmpeg or writes files
+    Process,
+    /// `/admin` endpoints
+    Admin,
+}
+
+impl Role {
+    fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "read" => Some(Role::Read),
+            "process" => Some(Role::Process),
+            "admin" => Some(Role::Admin),
+            _ => None,
+        }
+    }
+}
+
+tokio::task_local! {
+    static TENANT: Option<String>;
+}
+
+/// Tenant of the API key behind the current request; `None` for untenanted keys and outside requests
+pub fn current_tenant() -> Option<String> {
+    TENANT.try_with(|tenant| tenant.clone()).ok().flatten()
+}
+
+/// Run `future` on behalf of `tenant`, so work done off the request task keeps its paths and quotas
+pub async fn with_tenant<F: std::future::Future>(tenant: Option<String>, future: F) -> F::Output {
+    TENANT.scope(tenant, future).await
+}
+
+/// Tenant names become workspace directory names
+fn is_valid_tenant(tenant: &str) -> bool {
+    !tenant.is_empty()
+        && tenant.len() <= 64
+        && tenant.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_'))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Grant {
+    role: Role,
+    tenant: Option<String>,
+}
+
+/// API keys with their roles and optional tenant, from
+/// `API_KEYS="monitor-key=read,ci-key=process@team-a,ops-key=admin"`
+#[derive(Debug, Default)]
+pub struct ApiKeys(HashMap<String, Grant>);
+
+impl ApiKeys {
+    pub fn from_env() -> Result<Self, String> {
+        match std::env::var("API_KEYS") {
+            Ok(spec) => Self::parse(&spec),
+            Err(_) => Ok(Self::default()),
+        }
+    }
+
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let mut keys = HashMap::new();
+        for entry in spec.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let (key, grant) = entry
+                .rsplit_once('=')
+                .ok_or_else(|| format!("expected KEY=ROLE[@TENANT], got '{}'", entry))?;
+            let (role, tenant) = match grant.split_once('@') {
+                Some((role, tenant)) => (role, Some(tenant.trim())),
+                None => (grant, None),
+            };
+            let role = Role::parse(role).ok_or_else(|| format!("unknown role '{}' (read, process, admin)", role))?;
+            if let Some(tenant) = tenant.filter(|tenant| !is_valid_tenant(tenant)) {
+                return Err(format!("invalid tenant '{}' (letters, digits, '-' and '_')", tenant));
+            }
+            let tenant = tenant.map(str::to_string);
+            keys.insert(key.trim().to_string(), Grant { role, tenant });
+        }
+        Ok(Self(keys))
+    }
+
+    /// No keys configured means authentication is disabled
+    pub fn is_enabled(&self) -> bool {
+        !self.0.is_empty()
+    }
+
+    fn grant_of(&self, key: &str) -> Option<&Grant> {
+        self.0.get(key)
+    }
+
+    /// Tenant of `key` if its role covers `required`, for callers outside the HTTP middleware
+    /// such as gRPC. With authentication disabled everyone passes, untenanted
+    pub fn authorize(&self, key: Option<&str>, required: Role) -> Result<Option<String>, ServiceError> {
+        if !self.is_enabled() {
+            return Ok(None);
+        }
+        let grant = key
+            .and_then(|key| self.grant_of(key))
+            .ok_or_else(|| ServiceError::Unauthorized("missing or unknown API key".to_string()))?;
+        if grant.role < required {
+            return Err(ServiceError::Forbidden(format!("{:?} role required", required)));
+        }
+        Ok(grant.tenant.clone())
+    }
+}
+
+/// Role needed for a route; `None` for public routes such as the health probes
+pub fn required_role(method: &Method, path: &str) -> Option<Role> {
+    if path.starts_with("/admin") {
+        return Some(Role::Admin);
+    }
+    if !path.starts_with("/api/") {
+        return None;
+    }
+    let read_only_post = path.ends_with("/video/info")
+        || path.ends_with("/metadata/extract")
+        || path.ends_with("/watermark/detect")
+        || path.ends_with("/panorama/analyze");
+    if method == Method::GET || method == Method::HEAD || read_only_post {
+        Some(Role::Read)
+    } else {
+        Some(Role::Process)
+    }
+}
+
+/// Check the caller's API key (`X-API-Key` or `Authorization: Bearer`) against the route's role,
+/// and expose the key's tenant to the rest of the request
+pub async fn authorize(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, Error> {
+    let keys = req.app_data::<web::Data<ApiKeys>>().filter(|keys| keys.is_enabled());
+    let required = required_role(req.method(), req.path());
+
+    let grant = keys.and_then(|keys| presented_key(&req).and_then(|key| keys.grant_of(&key).cloned()));
+    let denied = match (keys, required, &grant) {
+        (Some(_), Some(_), None) => Some(ServiceError::Unauthorized("missing or unknown API key".to_string())),
+        (Some(_), Some(required), Some(grant)) if grant.role < required => Some(ServiceError::Forbidden(format!(
+            "{:?} role required for {} {}",
+            required,
+            req.method(),
+            req.path()
+        ))),
+        _ => None,
+    };
+
+    match denied {
+        Some(error) => {
+            warn!("Denied {} {}: {}", req.method(), req.path(), error);
+            Ok(req.into_response(error.error_response()).map_into_right_body())
+        }
+        None => {
+            let tenant = grant.and_then(|grant| grant.tenant);
+            Ok(TENANT.scope(tenant, next.call(req)).await?.map_into_left_body())
+        }
+    }
+}
+
+fn presented_key(req: &ServiceRequest) -> Option<String> {
+    let headers = req.headers();
+    if let Some(key) = headers.get(API_KEY_HEADER).and_then(|value| value.to_str().ok()) {
+        return Some(key.trim().to_string());
+    }
+    headers
+        .get("authorization")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(|key| key.trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_api_keys() {
+        let keys = ApiKeys::parse("monitor=read, ci=process@team-a,ops=ADMIN").unwrap();
+        assert_eq!(keys.grant_of("monitor").map(|grant| grant.role), Some(Role::Read));
+        assert_eq!(keys.grant_of("ops").map(|grant| grant.role), Some(Role::Admin));
+        let ci = keys.grant_of("ci").unwrap();
+        assert_eq!((ci.role, ci.tenant.as_deref()), (Role::Process, Some("team-a")));
+        assert!(ApiKeys::parse("ci=root").is_err());
+        assert!(ApiKeys::parse("ci=process@../etc").is_err());
+        assert!(!ApiKeys::parse("").unwrap().is_enabled());
+    }
+
+    #[test]
+    fn test_authorize_outside_http() {
+        let keys = ApiKeys::parse("monitor=read,ci=process@team-a").unwrap();
+        assert_eq!(keys.authorize(Some("ci"), Role::Process).unwrap().as_deref(), Some("team-a"));
+        assert_eq!(keys.authorize(Some("monitor"), Role::Read).unwrap(), None);
+        assert_eq!(keys.authorize(Some("monitor"), Role::Process).unwrap_err().code(), "forbidden");
+        assert_eq!(keys.authorize(None, Role::Read).unwrap_err().code(), "unauthorized");
+        assert_eq!(ApiKeys::default().authorize(None, Role::Admin).unwrap(), None);
+    }
+
+    #[test]
+    fn test_required_role() {
+        assert_eq!(required_role(&Method::GET, "/health/ready"), None);
+        assert_eq!(required_role(&Method::GET, "/api/v1/jobs"), Some(Role::Read));
+        assert_eq!(required_role(&Method::POST, "/api/v2/metadata/extract"), Some(Role::Read));
+        assert_eq!(required_role(&Method::POST, "/api/v1/image/watermark/detect"), Some(Role::Read));
+        assert_eq!(required_role(&Method::POST, "/api/v1/image/panorama/analyze"), Some(Role::Read));
+        assert_eq!(required_role(&Method::POST, "/api/v1/video/transcode"), Some(Role::Process));
+        assert_eq!(required_role(&Method::PUT, "/admin/logging"), Some(Role::Admin));
+        assert!(Role::Admin > Role::Process && Role::Process > Role::Read);
+    }
+}
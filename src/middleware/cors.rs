@@ -0,0 +1,157 @@
+use actix_web::{
+    body::{EitherBody, MessageBody},
+    dev::{ServiceRequest, ServiceResponse},
+    http::{
+        header::{self, HeaderMap, HeaderName, HeaderValue},
+        Method,
+    },
+    middleware::Next,
+    web, Error, HttpResponse,
+};
+use crate::middleware::request_id::REQUEST_ID_HEADER;
+use crate::middleware::api_version::API_VERSION_HEADER;
+
+const DEFAULT_METHODS: &str = "GET, POST, PUT, DELETE, OPTIONS";
+const DEFAULT_HEADERS: &str = "content-type, authorization, x-api-key, x-request-id, accept-version";
+
+/// Cross-origin rules for browser frontends, from `CORS_*` env vars; no origins means CORS is off
+#[derive(Debug, Clone, Default)]
+pub struct CorsConfig {
+    /// Exact origins such as `https://app.example.com`, or `*` for any
+    pub allowed_origins: Vec<String>,
+    pub allowed_methods: String,
+    pub allowed_headers: String,
+    pub allow_credentials: bool,
+    pub max_age_secs: u64,
+}
+
+impl CorsConfig {
+    /// Read `CORS_ALLOWED_ORIGINS`, `CORS_ALLOWED_METHODS`, `CORS_ALLOWED_HEADERS`,
+    /// `CORS_ALLOW_CREDENTIALS` and `CORS_MAX_AGE`
+    pub fn from_env() -> Self {
+        let var = |name: &str| std::env::var(name).ok().map(|v| v.trim().to_string()).filter(|v| !v.is_empty());
+        Self {
+            allowed_origins: var("CORS_ALLOWED_ORIGINS")
+                .map(|origins| Self::parse_origins(&origins))
+                .unwrap_or_default(),
+            allowed_methods: var("CORS_ALLOWED_METHODS").unwrap_or_else(|| DEFAULT_METHODS.to_string()),
+            allowed_headers: var("CORS_ALLOWED_HEADERS").unwrap_or_else(|| DEFAULT_HEADERS.to_string()),
+            allow_credentials: var("CORS_ALLOW_CREDENTIALS")
+                .map(|v| matches!(v.to_lowercase().as_str(), "1" | "true" | "yes" | "on"))
+                .unwrap_or(false),
+            max_age_secs: var("CORS_MAX_AGE").and_then(|v| v.parse().ok()).unwrap_or(3600),
+        }
+    }
+
+    fn parse_origins(spec: &str) -> Vec<String> {
+        spec.split(',')
+            .map(|origin| origin.trim().trim_end_matches('/').to_string())
+            .filter(|origin| !origin.is_empty())
+            .collect()
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.allowed_origins.is_empty()
+    }
+
+    /// Value for `Access-Control-Allow-Origin`, or `None` when `origin` is not allowed.
+    /// Credentialed requests can't use `*`, so the origin is echoed back instead
+    fn allow_origin(&self, origin: &str) -> Option<String> {
+        if self.allowed_origins.iter().any(|allowed| allowed == origin) {
+            return Some(origin.to_string());
+        }
+        if self.allowed_origins.iter().any(|allowed| allowed == "*") {
+            return Some(if self.allow_credentials { origin.to_string() } else { "*".to_string() });
+        }
+        None
+    }
+
+    fn apply(&self, headers: &mut HeaderMap, allow_origin: &str) {
+        let mut insert = |name: HeaderName, value: &str| {
+            if let Ok(value) = HeaderValue::from_str(value) {
+                headers.insert(name, value);
+            }
+        };
+        insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, allow_origin);
+        insert(header::VARY, "Origin");
+        insert(
+            header::ACCESS_CONTROL_EXPOSE_HEADERS,
+            &format!("{}, {}", REQUEST_ID_HEADER, API_VERSION_HEADER),
+        );
+        if self.allow_credentials {
+            insert(header::ACCESS_CONTROL_ALLOW_CREDENTIALS, "true");
+        }
+    }
+}
+
+/// Answer preflight requests and add CORS headers to responses for allowed origins.
+/// Wrapped outermost so preflights never reach API key checks
+pub async fn cors(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, Error> {
+    let config = req.app_data::<web::Data<CorsConfig>>().filter(|config| config.is_enabled()).cloned();
+    let origin = req
+        .headers()
+        .get(header::ORIGIN)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+    let (Some(config), Some(origin)) = (config, origin) else {
+        return Ok(next.call(req).await?.map_into_left_body());
+    };
+    let allow_origin = config.allow_origin(&origin);
+
+    let is_preflight =
+        req.method() == Method::OPTIONS && req.headers().contains_key(header::ACCESS_CONTROL_REQUEST_METHOD);
+    if is_preflight {
+        let mut response = match &allow_origin {
+            Some(allow_origin) => {
+                let mut response = HttpResponse::NoContent().finish();
+                let headers = response.headers_mut();
+                config.apply(headers, allow_origin);
+                for (name, value) in [
+                    (header::ACCESS_CONTROL_ALLOW_METHODS, config.allowed_methods.clone()),
+                    (header::ACCESS_CONTROL_ALLOW_HEADERS, config.allowed_headers.clone()),
+                    (header::ACCESS_CONTROL_MAX_AGE, config.max_age_secs.to_string()),
+                ] {
+                    if let Ok(value) = HeaderValue::from_str(&value) {
+                        headers.insert(name, value);
+                    }
+                }
+                response
+            }
+            None => HttpResponse::Forbidden().finish(),
+        };
+        response.headers_mut().insert(header::VARY, HeaderValue::from_static("Origin"));
+        return Ok(req.into_response(response).map_into_right_body());
+    }
+
+    let mut res = next.call(req).await?;
+    if let Some(allow_origin) = allow_origin {
+        config.apply(res.headers_mut(), &allow_origin);
+    }
+    Ok(res.map_into_left_body())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_allow_origin() {
+        let config = CorsConfig {
+            allowed_origins: CorsConfig::parse_origins("https://app.example.com/, http://localhost:3000"),
+            ..CorsConfig::default()
+        };
+        assert_eq!(config.allow_origin("https://app.example.com").as_deref(), Some("https://app.example.com"));
+        assert_eq!(config.allow_origin("https://evil.example.com"), None);
+
+        let any = CorsConfig { allowed_origins: vec!["*".to_string()], ..CorsConfig::default() };
+        assert_eq!(any.allow_origin("https://evil.example.com").as_deref(), Some("*"));
+        let credentialed = CorsConfig { allow_credentials: true, ..any };
+        assert_eq!(
+            credentialed.allow_origin("https://app.example.com").as_deref(),
+            Some("https://app.example.com")
+        );
+    }
+}
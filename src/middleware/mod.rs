@@ -0,0 +1,4 @@
+pub mod api_version;
+pub mod request_id;
+pub mod auth;
+pub mod cors;
\ No newline at end of file
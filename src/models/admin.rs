@@ -0,0 +1,116 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use crate::models::job::{JobRecord, Priority};
+use crate::services::metrics::BucketStats;
+
+/// Body of `PUT /admin/logging`, e.g. `{"filter": "info,video_processor=debug"}`
+#[derive(Debug, Deserialize, Serialize)]
+pub struct LogFilterRequest {
+    pub filter: String,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct OperationStats {
+    pub count: usize,
+    pub completed: usize,
+    pub failed: usize,
+    pub avg_processing_time_ms: Option<u64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SlowJob {
+    pub job_id: String,
+    #[serde(rename = "type")]
+    pub job_type: String,
+    pub input_path: String,
+    pub processing_time_ms: u64,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Aggregates over the job history for `/admin/stats`
+#[derive(Debug, Serialize)]
+pub struct JobStats {
+    pub total: usize,
+    pub queued: usize,
+    pub running: usize,
+    pub completed: usize,
+    pub failed: usize,
+    pub cancelled: usize,
+    /// Jobs finished per minute over the last hour
+    pub throughput_per_minute: f64,
+    pub operations: BTreeMap<String, OperationStats>,
+    /// Same breakdown per tenant, for jobs started with a tenant's API key
+    pub tenants: BTreeMap<String, OperationStats>,
+    pub slowest_recent: Vec<SlowJob>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DiskUsage {
+    pub path: String,
+    pub bytes: u64,
+    pub files: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct StatsResponse {
+    pub jobs: JobStats,
+    /// Percentiles by operation, resolution, codec and input size
+    pub processing_times: Vec<BucketStats>,
+    pub disk: Vec<DiskUsage>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct QueueResponse {
+    /// Jobs waiting for a queue worker, out of `capacity`
+    pub queued: usize,
+    pub capacity: usize,
+    /// `queued` split by priority class
+    pub queued_by_priority: BTreeMap<Priority, usize>,
+    pub waiting: Vec<JobRecord>,
+    pub running: Vec<JobRecord>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WorkersResponse {
+    pub http_workers: usize,
+    /// Size of the job queue's worker pool; at most this many jobs run at once
+    pub queue_workers: usize,
+    pub busy: usize,
+    pub running_by_type: BTreeMap<String, usize>,
+}
+
+/// Query of `POST /admin/benchmark`; omitted fields use the 1080p, 60 frame default
+#[derive(Debug, Deserialize)]
+pub struct BenchmarkQuery {
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub frames: Option<u32>,
+}
+
+/// Query of `GET /admin/usage`, e.g. `?from=2024-05-01&to=2024-05-31&tenant=team-a`; both
+/// dates are inclusive
+#[derive(Debug, Default, Deserialize)]
+pub struct UsageQuery {
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+    /// Tenanted callers only ever see their own usage, whatever is passed here
+    pub tenant: Option<String>,
+}
+
+/// Jobs one tenant finished on one UTC day and the bytes they read and wrote
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DailyUsage {
+    pub date: NaiveDate,
+    /// `None` for jobs started without a tenanted API key
+    pub tenant: Option<String>,
+    pub jobs: usize,
+    pub completed: usize,
+    pub input_bytes: u64,
+    pub output_bytes: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct UsageResponse {
+    pub usage: Vec<DailyUsage>,
+}
@@ -0,0 +1,367 @@
+use serde::{Deserialize, Serialize};
+use crate::models::job::Priority;
+use crate::services::autotrim::CropRect;
+use crate::services::capabilities;
+use crate::services::orientation::Orientation;
+use crate::services::panorama::{self, Overlap};
+use crate::services::sequence::MAX_FRAMES;
+use crate::services::sticker::{self, StickerFormat, STICKER_EXTENSIONS};
+use crate::services::upscale::{self, Deblock};
+use crate::services::watermark::Detection;
+use crate::utils::error::ServiceError;
+use crate::utils::validation::{Validate, Violations};
+
+/// jpegtran transforms; together they cover all eight EXIF orientations
+pub static JPEG_TRANSFORMS: &[&str] = &[
+    "rotate90", "rotate180", "rotate270", "flip_horizontal", "flip_vertical", "transpose", "transverse",
+];
+
+/// Rotate, flip and/or crop a JPEG by rearranging its DCT blocks, without decoding or re-encoding
+#[derive(Debug, Clone, Deserialize)]
+pub struct LosslessJpegRequest {
+    pub input_path: String,
+    pub output_path: String,
+    pub transform: Option<String>,
+    /// `WIDTHxHEIGHT+X+Y`; the offset is rounded down to the JPEG block grid
+    pub crop: Option<String>,
+    /// Drop the partial edge blocks a transform can't move losslessly instead of failing
+    pub trim: Option<bool>,
+    /// Queue class: `low`, `normal` (default) or `high`
+    pub priority: Option<Priority>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LosslessJpegResponse {
+    pub job_id: String,
+    pub output_path: String,
+}
+
+/// Colors `AutotrimRequest::border` may name; `auto` tries both and keeps the tighter crop
+pub static BORDER_MODES: &[&str] = &["auto", "black", "white"];
+
+/// Detect uniform borders (scan margins, letterboxing) and crop them off
+#[derive(Debug, Clone, Deserialize)]
+pub struct AutotrimRequest {
+    pub input_path: String,
+    pub output_path: String,
+    pub border: Option<String>,
+    /// Luma distance from the border color still counted as border, 0-255 (default: 24)
+    pub threshold: Option<u32>,
+    /// Images only: turn pages scanned sideways or upside down upright, judged by their text
+    /// lines. Inputs carrying an orientation of their own are left alone
+    pub auto_rotate: Option<bool>,
+    pub priority: Option<Priority>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AutotrimResponse {
+    pub job_id: String,
+    pub output_path: String,
+    /// What was kept of the input; `None` when no border was found and the input was re-encoded as is
+    pub crop: Option<CropRect>,
+    /// Rotation applied after the crop; `None` without `auto_rotate` or when the text didn't show
+    /// which way is up
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub orientation: Option<Orientation>,
+}
+
+/// Hide `watermark_id` in the image's DCT coefficients, to trace copies that leak
+#[derive(Debug, Clone, Deserialize)]
+pub struct WatermarkEmbedRequest {
+    pub input_path: String,
+    pub output_path: String,
+    pub watermark_id: u32,
+    /// 1-100 (default: 20); higher survives harder recompression but starts to show in flat areas
+    pub strength: Option<u32>,
+    pub priority: Option<Priority>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WatermarkEmbedResponse {
+    pub job_id: String,
+    pub output_path: String,
+    pub watermark_id: u32,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct WatermarkDetectRequest {
+    pub input_path: String,
+    pub priority: Option<Priority>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WatermarkDetectResponse {
+    pub job_id: String,
+    pub detected: bool,
+    #[serde(flatten)]
+    pub detection: Detection,
+}
+
+/// Work out whether a set of shots, e.g. a burst taken while panning, forms a panorama and in
+/// which order they join
+#[derive(Debug, Clone, Deserialize)]
+pub struct PanoramaAnalyzeRequest {
+    /// 2-12 images, in any order
+    pub input_paths: Vec<String>,
+    /// Correlation, 0-1, every neighbouring pair needs to count as stitchable (default: 0.5)
+    pub min_score: Option<f64>,
+    pub priority: Option<Priority>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PanoramaLink {
+    pub from: String,
+    pub to: String,
+    #[serde(flatten)]
+    pub overlap: Option<Overlap>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PanoramaAnalysisResponse {
+    pub job_id: String,
+    pub stitchable: bool,
+    /// `input_paths` left to right, as a stitcher takes them
+    pub order: Vec<String>,
+    /// Each neighbouring pair of `order` and how the right one continues the left
+    pub links: Vec<PanoramaLink>,
+}
+
+/// Channels `BlurRequest::channels` may name
+pub static BLUR_CHANNELS: &[&str] = &["r", "g", "b"];
+
+/// Gaussian blur, optionally limited to some channels and, through a mask, to part of the image
+#[derive(Debug, Clone, Deserialize)]
+pub struct BlurRequest {
+    pub input_path: String,
+    pub output_path: String,
+    /// 1-100 pixels (default: 8)
+    pub sigma: Option<u32>,
+    /// Grayscale image, stretched to the input: white stays sharp, black is blurred, gray blends
+    pub mask_path: Option<String>,
+    /// Blur the white areas of the mask instead, e.g. for privacy masks over faces
+    pub invert_mask: Option<bool>,
+    /// Blur only these of `r`, `g` and `b` (default: all)
+    pub channels: Option<Vec<String>>,
+    pub priority: Option<Priority>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BlurResponse {
+    pub job_id: String,
+    pub output_path: String,
+}
+
+/// Animated sticker with a transparent background, as VP9 WebM (`.webm`) or APNG (`.png`/`.apng`)
+#[derive(Debug, Clone, Deserialize)]
+pub struct StickerRequest {
+    /// Video or animated image to convert; exclusive with `frames`
+    pub input_path: Option<String>,
+    /// Ordered frame images, e.g. PNGs that already carry alpha
+    pub frames: Option<Vec<String>>,
+    pub output_path: String,
+    /// 1-60; defaults to 15 for `frames` and to the input's own rate otherwise
+    pub fps: Option<u32>,
+    /// Background keyed out to transparent, `#RRGGBB`, e.g. a green screen
+    pub key_color: Option<String>,
+    /// How far from `key_color` still counts as background, 1-100 (default: 10)
+    pub key_tolerance: Option<u32>,
+    /// Longest side, 16-2048 (default: 512)
+    pub max_dimension: Option<u32>,
+    pub priority: Option<Priority>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct StickerResponse {
+    pub job_id: String,
+    pub output_path: String,
+}
+
+/// Enlarge an image with Lanczos resampling, removing JPEG compression artifacts first so
+/// they aren't enlarged along with it
+#[derive(Debug, Clone, Deserialize)]
+pub struct UpscaleRequest {
+    pub input_path: String,
+    pub output_path: String,
+    /// 2-4 (default: 2)
+    pub scale: Option<u32>,
+    /// `auto` (default) deblocks inputs that measure as blocky, `always` or `never`
+    pub deblock: Option<Deblock>,
+    pub priority: Option<Priority>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct UpscaleResponse {
+    pub job_id: String,
+    pub output_path: String,
+    pub width: u32,
+    pub height: u32,
+    /// Step across the 8x8 block grid relative to steps inside blocks; about 1 for clean
+    /// images. `None` with `deblock: never` or for images too small to measure
+    pub blockiness: Option<f64>,
+    pub deblocked: bool,
+}
+
+/// Parse `WIDTHxHEIGHT+X+Y` into `(width, height, x, y)`
+pub fn parse_crop(value: &str) -> Option<(u32, u32, u32, u32)> {
+    let (size, offset) = value.split_once('+')?;
+    let (x, y) = offset.split_once('+')?;
+    let (width, height) = size.split_once('x')?;
+    let width: u32 = width.parse().ok()?;
+    let height: u32 = height.parse().ok()?;
+    if width == 0 || height == 0 {
+        return None;
+    }
+    Some((width, height, x.parse().ok()?, y.parse().ok()?))
+}
+
+impl Validate for LosslessJpegRequest {
+    fn validate(&self) -> Result<(), ServiceError> {
+        let mut violations = Violations::new();
+        violations.input("input_path", &self.input_path);
+        violations.path("output_path", &self.output_path);
+        if self.input_path == self.output_path {
+            violations.add("output_path", "must differ from input_path");
+        }
+        violations.one_of("transform", self.transform.as_deref(), JPEG_TRANSFORMS);
+        if let Some(crop) = &self.crop {
+            if parse_crop(crop).is_none() {
+                violations.add("crop", "must look like WIDTHxHEIGHT+X+Y, e.g. 800x600+16+0");
+            }
+        }
+        if self.transform.is_none() && self.crop.is_none() {
+            violations.add("transform", "a transform or a crop is required");
+        }
+        violations.into_result()
+    }
+}
+
+impl Validate for AutotrimRequest {
+    fn validate(&self) -> Result<(), ServiceError> {
+        let mut violations = Violations::new();
+        violations.input("input_path", &self.input_path);
+        violations.path("output_path", &self.output_path);
+        if self.input_path == self.output_path {
+            violations.add("output_path", "must differ from input_path");
+        }
+        violations.one_of("border", self.border.as_deref(), BORDER_MODES);
+        violations.range("threshold", self.threshold, 0, 255);
+        violations.into_result()
+    }
+}
+impl Validate for WatermarkEmbedRequest {
+    fn validate(&self) -> Result<(), ServiceError> {
+        let mut violations = Violations::new();
+        violations.input("input_path", &self.input_path);
+        violations.path("output_path", &self.output_path);
+        if self.input_path == self.output_path {
+            violations.add("output_path", "must differ from input_path");
+        }
+        violations.range("strength", self.strength, 1, 100);
+        violations.into_result()
+    }
+}
+
+impl Validate for BlurRequest {
+    fn validate(&self) -> Result<(), ServiceError> {
+        let mut violations = Violations::new();
+        violations.input("input_path", &self.input_path);
+        violations.path("output_path", &self.output_path);
+        if self.input_path == self.output_path {
+            violations.add("output_path", "must differ from input_path");
+        }
+        violations.range("sigma", self.sigma, 1, 100);
+        if let Some(mask_path) = &self.mask_path {
+            violations.path("mask_path", mask_path);
+        } else if self.invert_mask.is_some() {
+            violations.add("invert_mask", "requires mask_path");
+        }
+        if let Some(channels) = &self.channels {
+            if channels.is_empty() {
+                violations.add("channels", "must name at least one channel");
+            }
+            for channel in channels {
+                violations.one_of("channels", Some(channel.as_str()), BLUR_CHANNELS);
+            }
+        }
+        violations.into_result()
+    }
+}
+
+impl Validate for StickerRequest {
+    fn validate(&self) -> Result<(), ServiceError> {
+        let mut violations = Violations::new();
+        match (&self.input_path, &self.frames) {
+            (Some(input_path), None) => {
+                violations.input("input_path", input_path);
+                if *input_path == self.output_path {
+                    violations.add("output_path", "must differ from input_path");
+                }
+            }
+            (None, Some(frames)) => {
+                if frames.is_empty() || frames.len() > MAX_FRAMES {
+                    violations.add("frames", format!("must list 1 to {} images", MAX_FRAMES));
+                }
+                for frame in frames {
+                    violations.path("frames", frame);
+                }
+            }
+            _ => violations.add("input_path", "exactly one of input_path and frames is required"),
+        }
+        violations.path("output_path", &self.output_path);
+        match StickerFormat::from_path(&self.output_path) {
+            None => violations.add("output_path", format!("must end in one of: {}", STICKER_EXTENSIONS.join(", "))),
+            Some(StickerFormat::Webm) if capabilities::has_encoder("libvpx-vp9") == Some(false) => {
+                violations.add("output_path", "WebM stickers need libvpx-vp9, which this FFmpeg build lacks")
+            }
+            Some(_) => {}
+        }
+        violations.range("fps", self.fps, 1, 60);
+        if let Some(color) = &self.key_color {
+            if !sticker::is_hex_color(color) {
+                violations.add("key_color", "must look like #RRGGBB");
+            }
+        } else if self.key_tolerance.is_some() {
+            violations.add("key_tolerance", "requires key_color");
+        }
+        violations.range("key_tolerance", self.key_tolerance, 1, 100);
+        violations.range("max_dimension", self.max_dimension, 16, 2048);
+        violations.into_result()
+    }
+}
+
+impl Validate for WatermarkDetectRequest {
+    fn validate(&self) -> Result<(), ServiceError> {
+        let mut violations = Violations::new();
+        violations.input("input_path", &self.input_path);
+        violations.into_result()
+    }
+}
+
+impl Validate for PanoramaAnalyzeRequest {
+    fn validate(&self) -> Result<(), ServiceError> {
+        let mut violations = Violations::new();
+        if self.input_paths.len() < 2 || self.input_paths.len() > panorama::MAX_IMAGES {
+            violations.add("input_paths", format!("must list 2 to {} images", panorama::MAX_IMAGES));
+        }
+        for path in &self.input_paths {
+            violations.path("input_paths", path);
+        }
+        if self.min_score.is_some_and(|score| !(0.0..=1.0).contains(&score)) {
+            violations.add("min_score", "must be between 0 and 1");
+        }
+        violations.into_result()
+    }
+}
+
+impl Validate for UpscaleRequest {
+    fn validate(&self) -> Result<(), ServiceError> {
+        let mut violations = Violations::new();
+        violations.input("input_path", &self.input_path);
+        violations.path("output_path", &self.output_path);
+        if self.input_path == self.output_path {
+            violations.add("output_path", "must differ from input_path");
+        }
+        violations.range("scale", self.scale, 2, upscale::MAX_SCALE);
+        violations.into_result()
+    }
+}
\ No newline at end of file
@@ -0,0 +1,176 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use crate::utils::audit::CommandAudit;
+use crate::utils::error::ServiceError;
+use crate::utils::validation::{Validate, Violations};
+
+pub const DEFAULT_PAGE_SIZE: usize = 20;
+pub const MAX_PAGE_SIZE: usize = 100;
+pub static JOB_SORT_KEYS: &[&str] = &["created_at", "-created_at", "finished_at", "-finished_at"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobStatus {
+    /// Accepted and waiting for a queue worker
+    Queued,
+    Running,
+    Completed,
+    Failed,
+    /// Stopped through `DELETE /jobs/{job_id}`
+    Cancelled,
+}
+
+/// Scheduling class of a queued job; interactive requests should ask for `high`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Priority {
+    Low,
+    #[default]
+    Normal,
+    High,
+}
+
+impl Priority {
+    pub const ALL: [Priority; 3] = [Priority::Low, Priority::Normal, Priority::High];
+}
+
+/// Latest progress of a job; batch jobs also count files
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct JobProgress {
+    pub percent: f64,
+    /// Files finished (rendered, skipped or failed) out of `total`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub completed: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub current_file: Option<String>,
+    /// Seconds left at FFmpeg's current `speed`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub eta_secs: Option<f64>,
+    /// Output bit rate FFmpeg reports, e.g. `1048.6kbits/s`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bitrate: Option<String>,
+}
+
+impl JobProgress {
+    pub fn batch(completed: usize, total: usize, current_file: Option<String>) -> Self {
+        let percent = if total == 0 { 100.0 } else { completed as f64 * 100.0 / total as f64 };
+        Self { percent, completed: Some(completed), total: Some(total), current_file, ..Self::default() }
+    }
+}
+
+/// One `data:` payload of the `GET /jobs/{job_id}/progress` event stream
+#[derive(Debug, Clone, Serialize)]
+pub struct ProgressUpdate {
+    pub job_id: String,
+    pub status: JobStatus,
+    #[serde(flatten)]
+    pub progress: Option<JobProgress>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl From<&JobRecord> for ProgressUpdate {
+    fn from(job: &JobRecord) -> Self {
+        Self {
+            job_id: job.job_id.clone(),
+            status: job.status,
+            progress: job.progress.clone(),
+            error: job.error.clone(),
+        }
+    }
+}
+
+/// An FFmpeg run of a job that failed for a transient reason and was repeated
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JobAttempt {
+    /// 1 for the first run
+    pub attempt: u32,
+    pub operation: String,
+    pub started_at: DateTime<Utc>,
+    pub failed_at: DateTime<Utc>,
+    pub error: String,
+}
+
+/// One processing run as kept in the job history
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JobRecord {
+    pub job_id: String,
+    #[serde(rename = "type")]
+    pub job_type: String,
+    pub status: JobStatus,
+    #[serde(default)]
+    pub priority: Priority,
+    pub input_path: String,
+    pub output_path: String,
+    /// `x-request-id` of the HTTP request that started the job
+    pub request_id: Option<String>,
+    /// Tenant of the API key that started the job
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tenant: Option<String>,
+    pub created_at: DateTime<Utc>,
+    /// Body of the request that queued the job, kept so it can be re-queued after a restart
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request: Option<serde_json::Value>,
+    /// When a worker picked the job up; later than `created_at` for queued jobs
+    pub started_at: Option<DateTime<Utc>>,
+    pub finished_at: Option<DateTime<Utc>>,
+    pub processing_time_ms: Option<u64>,
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub progress: Option<JobProgress>,
+    /// Size of the input when the job started; directories count everything below them
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub input_bytes: Option<u64>,
+    /// Size of the output once the job completed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_bytes: Option<u64>,
+    /// What a completed job produced, for endpoints that answer `202` and leave the outcome
+    /// to be polled, e.g. the playlists of an HLS job
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<serde_json::Value>,
+    /// FFmpeg/ffprobe invocations made for this job
+    pub commands: Vec<CommandAudit>,
+    /// Failed runs that were retried; the final run's outcome is `status`/`error`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attempts: Vec<JobAttempt>,
+}
+
+/// Query string of `GET /jobs`, e.g. `?status=failed&type=video.transcode&sort=-created_at&page=2`
+#[derive(Debug, Default, Deserialize)]
+pub struct JobQuery {
+    pub status: Option<JobStatus>,
+    #[serde(rename = "type")]
+    pub job_type: Option<String>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    /// Tenanted callers only ever see their own jobs, whatever is passed here
+    pub tenant: Option<String>,
+    pub sort: Option<String>,
+    pub page: Option<u32>,
+    pub per_page: Option<u32>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct JobListResponse {
+    pub jobs: Vec<JobRecord>,
+    pub page: usize,
+    pub per_page: usize,
+    pub total: usize,
+}
+
+impl Validate for JobQuery {
+    fn validate(&self) -> Result<(), ServiceError> {
+        let mut violations = Violations::new();
+        violations.range("page", self.page, 1, u32::MAX);
+        violations.range("per_page", self.per_page, 1, MAX_PAGE_SIZE as u32);
+        violations.one_of("sort", self.sort.as_deref(), JOB_SORT_KEYS);
+        if let (Some(from), Some(to)) = (self.from, self.to) {
+            if from > to {
+                violations.add("to", "must not be before from");
+            }
+        }
+        violations.into_result()
+    }
+}
@@ -1 +1,6 @@
-pub mod video; 
\ No newline at end of file
+pub mod video;
+pub mod sync;
+pub mod processing;
+pub mod job;
+pub mod admin;
+pub mod image;
\ No newline at end of file
@@ -0,0 +1,47 @@
+use serde::{Deserialize, Serialize};
+use crate::services::url_signer::UrlSigner;
+
+/// Bumped on any breaking change to [`ProcessingResult`]; additive fields keep the version
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Common response shape returned by every v2 processing endpoint
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ProcessingResult {
+    pub schema_version: u32,
+    pub job_id: String,
+    pub operation: String,
+    pub status: String,
+    pub outputs: Vec<String>,
+    /// Signed `/files/{token}` links, or presigned URLs for outputs in object storage, one per
+    /// output, when every output could be linked
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_urls: Option<Vec<String>>,
+    pub processing_time_ms: u64,
+    pub metadata: Option<serde_json::Value>,
+}
+
+impl ProcessingResult {
+    pub fn completed(job_id: String, operation: &str, outputs: Vec<String>, processing_time_ms: u64) -> Self {
+        Self {
+            schema_version: SCHEMA_VERSION,
+            job_id,
+            operation: operation.to_string(),
+            status: "completed".to_string(),
+            outputs,
+            output_urls: None,
+            processing_time_ms,
+            metadata: None,
+        }
+    }
+
+    /// Attach signed links for `outputs`; a no-op unless each of them has one
+    pub fn with_signed_urls(mut self, signer: &UrlSigner) -> Self {
+        self.output_urls = self.outputs.iter().map(|output| signer.sign(output)).collect();
+        self
+    }
+
+    pub fn with_metadata(mut self, metadata: serde_json::Value) -> Self {
+        self.metadata = Some(metadata);
+        self
+    }
+}
@@ -0,0 +1,61 @@
+use serde::{Deserialize, Serialize};
+use crate::models::job::Priority;
+use crate::services::sync_processor::{DERIVATIVE_FORMATS, DITHER_MODES};
+use crate::utils::error::ServiceError;
+use crate::utils::validation::{Validate, Violations};
+
+/// Describes the derivative produced for every source image (e.g. 1024px WebP)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DerivativeSpec {
+    pub format: String,
+    pub max_dimension: Option<u32>,
+    pub quality: Option<u32>,
+    /// Palette size for GIF output (default 256); on PNG output it produces an indexed PNG8
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub colors: Option<u32>,
+    /// Dithering used when mapping onto the palette (default `sierra2_4a`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dither: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MirrorSyncRequest {
+    pub source_dir: String,
+    pub output_dir: String,
+    pub derivative: DerivativeSpec,
+    pub prune: Option<bool>,
+    /// Queue class: `low`, `normal` (default) or `high`
+    pub priority: Option<Priority>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SyncFailure {
+    pub path: String,
+    pub error: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MirrorSyncResponse {
+    pub job_id: String,
+    pub processed: Vec<String>,
+    pub skipped: usize,
+    pub removed: Vec<String>,
+    pub failed: Vec<SyncFailure>,
+}
+
+impl Validate for MirrorSyncRequest {
+    fn validate(&self) -> Result<(), ServiceError> {
+        let mut violations = Violations::new();
+        violations.path("source_dir", &self.source_dir);
+        violations.path("output_dir", &self.output_dir);
+        if self.source_dir == self.output_dir {
+            violations.add("output_dir", "must differ from source_dir");
+        }
+        violations.one_of("derivative.format", Some(&self.derivative.format), DERIVATIVE_FORMATS);
+        violations.range("derivative.max_dimension", self.derivative.max_dimension, 1, 16384);
+        violations.range("derivative.quality", self.derivative.quality, 1, 100);
+        violations.range("derivative.colors", self.derivative.colors, 2, 256);
+        violations.one_of("derivative.dither", self.derivative.dither.as_deref(), DITHER_MODES);
+        violations.into_result()
+    }
+}
@@ -1,6 +1,26 @@
 use serde::{Deserialize, Serialize};
+use crate::models::job::Priority;
+use crate::services::animation::{ANIMATION_EXTENSIONS, MAX_DURATION};
+use crate::services::cover::{self, COVER_CONTAINERS, COVER_EXTENSIONS, POSTER_EXTENSIONS};
+use crate::services::faces::FaceModel;
+use crate::services::filters::{VideoFilter, FILTER_PATH_RESERVED, MAX_FILTERS};
+use crate::services::fingerprint::{Comparison, Fingerprint, MAX_HASHES};
+use crate::services::frames::FRAME_FORMATS;
+use crate::services::hls::HlsOptions;
+use crate::services::hwaccel::{self, Accelerator};
+use crate::services::ladder::{self, Rung};
+use crate::services::language::WhisperConfig;
+use crate::services::overlay::{WatermarkPosition, MAX_TEXT_LENGTH};
+use crate::services::quality::{EncodeParams, Scores, COMPOSITES};
+use crate::services::ratecontrol::RateControl;
+use crate::services::scenes::{SceneCut, MAX_SCENES};
+use crate::services::slideshow::{DEFAULT_IMAGE_SECS, MAX_SLIDES, TRANSITIONS};
+use crate::services::subtitles::{EXTRACT_EXTENSIONS, MAX_TRACKS, SUBTITLE_EXTENSIONS};
+use crate::services::tonemap::ToneMap;
+use crate::utils::error::ServiceError;
+use crate::utils::validation::{Validate, Violations, AUDIO_CODECS, CONTAINER_FORMATS, VIDEO_CODECS};
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct VideoTranscodeRequest {
     pub input_path: String,
     pub output_path: String,
@@ -9,6 +29,26 @@ pub struct VideoTranscodeRequest {
     pub bitrate: Option<String>,
     pub resolution: Option<String>,
     pub fps: Option<u32>,
+    /// Applied in order before encoding, e.g. `[{"type": "scale", "width": 1280}]`
+    pub filters: Option<Vec<VideoFilter>>,
+    /// Segmenting of multi-quality HLS output, e.g. `{"segment_duration": 6, "segment_type": "fmp4"}`
+    pub hls: Option<HlsOptions>,
+    /// Encode H.264/HEVC on `nvenc`, `vaapi` or `videotoolbox`, or `auto` for whichever this
+    /// host has; falls back to the CPU when none is usable or the hardware encode fails
+    pub hardware_acceleration: Option<Accelerator>,
+    /// `{"mode": "crf", "value": 23}`, `{"mode": "two_pass", "value": "3M"}` or `{"mode": "cbr", "value": "2M"}`;
+    /// `bitrate` is CRF's cap. Ladder renditions keep the mode and aim at their own bit rate
+    pub rate_control: Option<RateControl>,
+    /// Renditions of `multi-quality-hls`, e.g. `[{"label": "720p", "resolution": "1280x720", "bitrate": "2.5M"}]`;
+    /// the 1080p/720p/480p profiles when unset
+    pub ladder: Option<Vec<Rung>>,
+    /// Fit the ladder to the probed source: no rendition larger than the input
+    pub per_title: Option<bool>,
+    /// Tone map HDR10 and HLG inputs to SDR BT.709 with `hable`, `mobius`, `reinhard` or `clip`;
+    /// SDR inputs pass through unchanged
+    pub tone_map: Option<ToneMap>,
+    /// Queue class: `low`, `normal` (default) or `high`
+    pub priority: Option<Priority>,
 }
 
 #[derive(Debug, Serialize)]
@@ -16,6 +56,19 @@ pub struct VideoTranscodeResponse {
     pub job_id: String,
     pub status: String,
     pub message: String,
+    /// Signed or presigned link to the output, which serves it once the job completed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output_url: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MultiQualityHlsResponse {
+    pub job_id: String,
+    pub outputs: Vec<String>,
+    pub master_playlist: String,
+    /// WebVTT track of the scrub preview sprites, when `hls.sprites` asked for them
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sprites: Option<String>,
 }
 
 #[derive(Debug, Serialize)]
@@ -27,15 +80,951 @@ pub struct JobStatusResponse {
     pub error: Option<String>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AudioExtractRequest {
     pub input_path: String,
     pub output_path: String,
     pub format: Option<String>,
     pub bitrate: Option<String>,
+    pub priority: Option<Priority>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AudioTranscodeRequest {
+    pub input_path: String,
+    pub output_path: String,
+    pub format: Option<String>,
+    pub priority: Option<Priority>,
+}
+
+/// Write a video's embedded cover art out as an image, re-encoded to `output_path`'s format
+#[derive(Debug, Clone, Deserialize)]
+pub struct CoverExtractRequest {
+    pub input_path: String,
+    pub output_path: String,
+    pub priority: Option<Priority>,
+}
+
+/// Copy a video with `image_path` embedded as its cover, replacing any existing one
+#[derive(Debug, Clone, Deserialize)]
+pub struct CoverAttachRequest {
+    pub input_path: String,
+    pub image_path: String,
+    pub output_path: String,
+    pub priority: Option<Priority>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CoverResponse {
+    pub job_id: String,
+    pub output_path: String,
+}
+
+/// Encode a slideshow or timelapse from still images
+#[derive(Debug, Clone, Deserialize)]
+pub struct SlideshowRequest {
+    /// Images in display order; exclusive with `pattern`
+    pub images: Option<Vec<String>>,
+    /// `*`/`?` wildcard over file names, e.g. `/shoots/day1/*.jpg`; matches are sorted by name
+    pub pattern: Option<String>,
+    pub output_path: String,
+    /// Music laid under the video, cut (with a fade-out) where the video ends
+    pub audio_path: Option<String>,
+    /// Seconds each image is shown, 0.04-60 (default: 3); short values make a timelapse
+    pub image_duration: Option<f64>,
+    /// `xfade` transition between images (default: none)
+    pub transition: Option<String>,
+    /// Seconds each transition overlaps the next image (default: 1)
+    pub transition_duration: Option<f64>,
+    /// Slow zoom into each image
+    pub ken_burns: Option<bool>,
+    /// Output frame rate, 1-60 (default: 30)
+    pub fps: Option<u32>,
+    /// `WIDTHxHEIGHT` every image is fitted to (default: 1920x1080)
+    pub resolution: Option<String>,
+    pub codec: Option<String>,
+    pub priority: Option<Priority>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SlideshowResponse {
+    pub job_id: String,
+    pub output_path: String,
+    pub images: usize,
+    pub duration_secs: f64,
+}
+
+/// Turn a stretch of a video into an animated GIF or WebP preview
+#[derive(Debug, Clone, Deserialize)]
+pub struct AnimationRequest {
+    pub input_path: String,
+    /// `.gif` or `.webp`; the extension picks the format
+    pub output_path: String,
+    /// Seconds into the video (default: 0)
+    pub start_time: Option<f64>,
+    /// Seconds converted, up to 60 (default: 5)
+    pub duration: Option<f64>,
+    /// 1-50 (default: 10)
+    pub fps: Option<u32>,
+    /// 16-1920 (default: 480); the height follows the aspect ratio
+    pub width: Option<u32>,
+    /// WebP quality, 0-100 (default: 75)
+    pub quality: Option<u32>,
+    /// Times the animation repeats; 0 (default) loops forever
+    pub loops: Option<u32>,
+    pub priority: Option<Priority>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AnimationResponse {
+    pub job_id: String,
+    pub output_path: String,
+    pub start_time: f64,
+    pub duration: f64,
+}
+
+/// Burn a visible logo or text into a video, optionally only for part of it
+#[derive(Debug, Clone, Deserialize)]
+pub struct VideoWatermarkRequest {
+    pub input_path: String,
+    pub output_path: String,
+    /// Logo to overlay, e.g. a PNG with alpha; exclusive with `text`
+    pub image_path: Option<String>,
+    /// Line of text to draw, up to 200 characters
+    pub text: Option<String>,
+    /// Corner or center to anchor to (default: bottom_right)
+    pub position: Option<WatermarkPosition>,
+    /// 0-1 (default: 0.8)
+    pub opacity: Option<f64>,
+    /// Size relative to the video: the logo's width (default: 0.15) or the text's line
+    /// height (default: 0.05), 0.01-1
+    pub scale: Option<f64>,
+    /// Pixels from the anchored edges, up to 1000 (default: 16)
+    pub margin: Option<u32>,
+    /// Text color, an FFmpeg color name or `#RRGGBB` (default: white)
+    pub font_color: Option<String>,
+    /// Seconds into the video the mark appears (default: from the start)
+    pub start_time: Option<f64>,
+    /// Seconds into the video the mark disappears (default: at the end)
+    pub end_time: Option<f64>,
+    /// Video encoder; audio is copied as is
+    pub codec: Option<String>,
+    pub priority: Option<Priority>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct VideoWatermarkResponse {
+    pub job_id: String,
+    pub output_path: String,
+}
+
+/// Render subtitles onto the frames, from a file or from a track of the input itself
+#[derive(Debug, Clone, Deserialize)]
+pub struct SubtitleBurnRequest {
+    pub input_path: String,
+    pub output_path: String,
+    /// `.srt`, `.ass`, `.ssa` or `.vtt` file; without it a subtitle track of the input is burned
+    pub subtitle_path: Option<String>,
+    /// Which of the input's subtitle tracks to burn, counting from 0 (default: 0)
+    pub stream: Option<u32>,
+    /// Video encoder; audio is copied as is
+    pub codec: Option<String>,
+    pub priority: Option<Priority>,
+}
+
+/// A subtitle file to add as a selectable track
+#[derive(Debug, Clone, Deserialize)]
+pub struct SubtitleTrack {
+    pub path: String,
+    /// ISO 639-2 code, e.g. `eng`
+    pub language: Option<String>,
+    pub title: Option<String>,
+    /// Players show this track unless told otherwise
+    pub default: Option<bool>,
+}
+
+/// Add subtitle files to a video as streams of their own, without re-encoding it
+#[derive(Debug, Clone, Deserialize)]
+pub struct SubtitleMuxRequest {
+    pub input_path: String,
+    /// MP4 and MOV tracks are converted to `mov_text`, WebM tracks to WebVTT; Matroska keeps them
+    pub output_path: String,
+    pub subtitles: Vec<SubtitleTrack>,
+    pub priority: Option<Priority>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SubtitleResponse {
+    pub job_id: String,
+    pub output_path: String,
+}
+
+/// Save a text subtitle track of a container as `.srt`, `.ass` or `.vtt`
+#[derive(Debug, Clone, Deserialize)]
+pub struct SubtitleExtractRequest {
+    pub input_path: String,
+    pub output_path: String,
+    /// Which of the input's subtitle tracks to save, counting from 0 (default: 0)
+    pub stream: Option<u32>,
+    pub priority: Option<Priority>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SubtitleExtractResponse {
+    pub job_id: String,
+    pub output_path: String,
+    /// Codec of the track in the input
+    pub codec: String,
+    pub language: Option<String>,
+}
+
+/// Find the cuts between scenes, e.g. to pick thumbnails or chapter marks
+#[derive(Debug, Clone, Deserialize)]
+pub struct SceneDetectRequest {
+    pub input_path: String,
+    /// Scene score a cut must exceed, 0.01-1 (default: 0.4); lower finds softer cuts
+    pub threshold: Option<f64>,
+    /// Seconds a scene lasts at least (default: 1); cuts closer than this merge
+    pub min_interval: Option<f64>,
+    /// Keep at most this many of the strongest cuts, 1-1000 (default: 1000)
+    pub max_scenes: Option<u32>,
+    pub priority: Option<Priority>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SceneDetectResponse {
+    pub job_id: String,
+    pub duration: f64,
+    /// In time order; the first scene starts at 0 with no cut
+    pub scenes: Vec<SceneCut>,
+}
+
+/// Tile frames sampled every `interval` seconds into sprite sheets, with a WebVTT track that
+/// maps each stretch of the video to its tile, for players' scrub previews
+#[derive(Debug, Clone, Deserialize)]
+pub struct SpriteSheetRequest {
+    pub input_path: String,
+    /// Created if missing; gets `sprite_000.jpg` and up and `sprite.vtt`
+    pub output_dir: String,
+    /// Seconds between tiles, 0.5-600 (default: 10)
+    pub interval: Option<f64>,
+    /// Tile width, 32-640 (default: 160); the height follows the aspect ratio
+    pub tile_width: Option<u32>,
+    /// Tiles per row, 1-20 (default: 10); sheets hold up to 10 rows
+    pub columns: Option<u32>,
+    pub priority: Option<Priority>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SpriteSheetResponse {
+    pub job_id: String,
+    pub vtt_path: String,
+    pub sheets: Vec<String>,
+    pub tiles: u32,
+}
+
+/// Stills one `/video/thumbnail` request may ask for
+pub const MAX_THUMBNAILS: usize = 100;
+
+/// Grab stills at given timestamps, or at positions in percent of the duration
+#[derive(Debug, Clone, Deserialize)]
+pub struct ThumbnailRequest {
+    pub input_path: String,
+    /// Created if missing; stills are written as `thumb_000.jpg` and up
+    pub output_dir: String,
+    /// Seconds into the video, e.g. `[1.5, 30]`; give this or `percentages`
+    pub timestamps: Option<Vec<f64>>,
+    /// Positions from 0 to 100, e.g. `[10, 50, 90]`
+    pub percentages: Option<Vec<f64>>,
+    /// `jpg` (default) or `png`
+    pub format: Option<String>,
+    /// Scale to this width, keeping the aspect ratio, 16-7680
+    pub width: Option<u32>,
+    pub priority: Option<Priority>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Thumbnail {
+    /// Seconds into the video the still was taken at
+    pub timestamp: f64,
+    pub path: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ThumbnailResponse {
+    pub job_id: String,
+    pub thumbnails: Vec<Thumbnail>,
+}
+
+/// How `/video/trim` cuts a clip
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TrimMode {
+    /// Stream copy: fast and lossless, but starts at the keyframe at or before `start_time`
+    #[default]
+    Copy,
+    /// Decode and encode again: starts on the exact frame, at encoding cost
+    Reencode,
+}
+
+/// Cut the part between `start_time` and `end_time` (or `start_time` + `duration`) out of a video
+#[derive(Debug, Clone, Deserialize)]
+pub struct TrimRequest {
+    pub input_path: String,
+    pub output_path: String,
+    /// Seconds into the video the clip starts at
+    pub start_time: f64,
+    /// Seconds into the video the clip ends at; give this or `duration`
+    pub end_time: Option<f64>,
+    /// Seconds the clip runs
+    pub duration: Option<f64>,
+    /// `copy` (default) or `reencode`
+    pub mode: Option<TrimMode>,
+    /// Video encoder for `reencode` (default: libx264)
+    pub codec: Option<String>,
+    pub priority: Option<Priority>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TrimResponse {
+    pub job_id: String,
+    pub output_path: String,
+    pub start_time: f64,
+    /// Requested length; a stream copy comes out longer by however far its keyframe lies back
+    pub duration: f64,
+    pub mode: TrimMode,
+}
+
+/// Export a video's frames as a numbered image sequence with an `index.json` manifest
+#[derive(Debug, Clone, Deserialize)]
+pub struct FrameExportRequest {
+    pub input_path: String,
+    /// Created if missing; frames are written as `frame_000000.png` and up
+    pub output_dir: String,
+    /// `png` (default) or `jpg`
+    pub format: Option<String>,
+    /// Seconds into the video to start at (default: 0)
+    pub start: Option<f64>,
+    /// Seconds into the video to stop at (default: the end)
+    pub end: Option<f64>,
+    /// Keep every Nth frame, 1-10000 (default: 1)
+    pub every: Option<u32>,
+    /// Stop after this many frames, 1-100000 (default: 10000)
+    pub max_frames: Option<u32>,
+    pub priority: Option<Priority>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FrameExportResponse {
+    pub job_id: String,
+    pub output_dir: String,
+    pub manifest_path: String,
+    pub frames: usize,
+}
+
+/// Encode one source with two parameter sets to compare their size, speed and quality
+#[derive(Debug, Clone, Deserialize)]
+pub struct EncodeCompareRequest {
+    pub input_path: String,
+    /// Created if missing; gets `a.mkv`, `b.mkv` and the composite
+    pub output_dir: String,
+    pub a: EncodeParams,
+    pub b: EncodeParams,
+    /// Seconds encoded from the start (default: the whole video)
+    pub duration: Option<f64>,
+    /// Score VMAF besides SSIM (default: true); it is the slower of the two
+    pub vmaf: Option<bool>,
+    /// `image` (default; the middle frame as `compare.png`), `video` (`compare.mp4`) or `none`
+    pub composite: Option<String>,
+    pub priority: Option<Priority>,
+}
+
+/// How one side of a comparison did
+#[derive(Debug, Serialize)]
+pub struct EncodeOutcome {
+    pub output_path: String,
+    pub size_bytes: u64,
+    /// Average bits per second of the encode
+    pub bitrate: u64,
+    /// Wall time of the encode alone, without scoring
+    pub encode_ms: u64,
+    #[serde(flatten)]
+    pub scores: Scores,
+}
+
+#[derive(Debug, Serialize)]
+pub struct EncodeCompareResponse {
+    pub job_id: String,
+    /// Seconds of the source both sides encoded
+    pub duration: f64,
+    pub a: EncodeOutcome,
+    pub b: EncodeOutcome,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub composite_path: Option<String>,
+}
+
+/// Detect and track faces across a video, saving one representative crop per track
+#[derive(Debug, Clone, Deserialize)]
+pub struct FaceIndexRequest {
+    pub input_path: String,
+    /// Created if missing; receives `faces.json` and `face_0000.jpg` and up
+    pub output_dir: String,
+    /// Seconds between sampled frames, 0.1-10 (default: 0.5)
+    pub interval: Option<f64>,
+    /// Detector confidence in percent, 1-100 (default: 60)
+    pub confidence: Option<u32>,
+    /// Samples a face must be seen on to be indexed, 1-100 (default: 2)
+    pub min_appearances: Option<u32>,
+    pub priority: Option<Priority>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FaceIndexResponse {
+    pub job_id: String,
+    pub output_dir: String,
+    pub index_path: String,
+    pub tracks: usize,
+}
+
+/// Perceptual fingerprint of a clip, to find re-uploads and trimmed copies of it later
+#[derive(Debug, Clone, Deserialize)]
+pub struct FingerprintRequest {
+    pub input_path: String,
+    /// Seconds between hashed frames, 0.1-10 (default: 1); compared fingerprints must share it
+    pub interval: Option<f64>,
+    pub priority: Option<Priority>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FingerprintResponse {
+    pub job_id: String,
+    pub fingerprint: Fingerprint,
+}
+
+/// Two fingerprints from `/video/fingerprint`, `b` usually the newer upload
+#[derive(Debug, Deserialize)]
+pub struct FingerprintCompareRequest {
+    pub a: Fingerprint,
+    pub b: Fingerprint,
+    /// Similarity from 0.5 to 1 at which the clips count as duplicates (default: 0.9)
+    pub threshold: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FingerprintCompareResponse {
+    #[serde(flatten)]
+    pub comparison: Comparison,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct VideoInfoRequest {
     pub file_path: String,
-} 
\ No newline at end of file
+    /// Identify the language spoken on each audio stream, added to it as `detected_language`;
+    /// queued, since it runs Whisper on a sample of every track
+    pub detect_language: Option<bool>,
+}
+
+/// Progress snapshot published while an FFmpeg job is running
+#[derive(Debug, Clone, Serialize)]
+pub struct ProgressEvent {
+    pub job_id: String,
+    pub percent: f64,
+    pub current_time: f64,
+    pub duration: f64,
+}
+
+impl Validate for VideoTranscodeRequest {
+    fn validate(&self) -> Result<(), ServiceError> {
+        let mut violations = Violations::new();
+        violations.input("input_path", &self.input_path);
+        violations.output("output_path", &self.output_path);
+        if self.input_path == self.output_path {
+            violations.add("output_path", "must differ from input_path");
+        }
+        violations.ffmpeg_token("format", self.format.as_deref(), CONTAINER_FORMATS);
+        violations.encoder("codec", self.codec.as_deref(), VIDEO_CODECS);
+        violations.bitrate("bitrate", self.bitrate.as_deref());
+        violations.resolution("resolution", self.resolution.as_deref());
+        violations.range("fps", self.fps, 1, 240);
+        if let Some(filters) = &self.filters {
+            if filters.len() > MAX_FILTERS {
+                violations.add("filters", format!("at most {} filters can be chained", MAX_FILTERS));
+            }
+            if !filters.is_empty() && self.codec.as_deref() == Some("copy") {
+                violations.add("filters", "filtering needs re-encoding, so codec can't be copy");
+            }
+            for (index, filter) in filters.iter().enumerate() {
+                filter.validate(index, &mut violations);
+            }
+        }
+        if let Some(hls) = &self.hls {
+            hls.validate(&mut violations);
+        }
+        if self.hardware_acceleration.is_some() && !hwaccel::supports(self.codec.as_deref()) {
+            violations.add("hardware_acceleration", "only applies to H.264 and HEVC (libx264, libx265, h264, hevc)");
+        }
+        if let Some(rate_control) = &self.rate_control {
+            rate_control.validate("rate_control.value", &mut violations);
+            if self.codec.as_deref() == Some("copy") {
+                violations.add("rate_control", "needs re-encoding, so codec can't be copy");
+            }
+            if rate_control.bitrate().is_some() && self.bitrate.is_some() {
+                violations.add("bitrate", "two_pass and cbr take theirs from rate_control.value");
+            }
+            if self.hardware_acceleration.is_some() && !matches!(rate_control, RateControl::Cbr(_)) {
+                violations.add("rate_control", "only cbr applies to hardware encoders");
+            }
+        }
+        if self.tone_map.is_some() && self.codec.as_deref() == Some("copy") {
+            violations.add("tone_map", "needs re-encoding, so codec can't be copy");
+        }
+        if let Some(rungs) = &self.ladder {
+            ladder::validate(rungs, &mut violations);
+            let software = rungs.iter().any(|rung| rung.codec.is_some() && !hwaccel::supports(rung.codec.as_deref()));
+            if self.hardware_acceleration.is_some() && software {
+                violations.add("hardware_acceleration", "every ladder codec must be H.264 or HEVC");
+            }
+        }
+        violations.into_result()
+    }
+}
+
+impl Validate for AudioExtractRequest {
+    fn validate(&self) -> Result<(), ServiceError> {
+        let mut violations = Violations::new();
+        violations.path("input_path", &self.input_path);
+        violations.path("output_path", &self.output_path);
+        if self.input_path == self.output_path {
+            violations.add("output_path", "must differ from input_path");
+        }
+        // `format` is the audio encoder here
+        violations.encoder("format", self.format.as_deref(), AUDIO_CODECS);
+        violations.bitrate("bitrate", self.bitrate.as_deref());
+        violations.into_result()
+    }
+}
+
+impl Validate for AudioTranscodeRequest {
+    fn validate(&self) -> Result<(), ServiceError> {
+        let mut violations = Violations::new();
+        violations.path("input_path", &self.input_path);
+        violations.path("output_path", &self.output_path);
+        if self.input_path == self.output_path {
+            violations.add("output_path", "must differ from input_path");
+        }
+        violations.ffmpeg_token("format", self.format.as_deref(), CONTAINER_FORMATS);
+        violations.into_result()
+    }
+}
+
+impl Validate for CoverExtractRequest {
+    fn validate(&self) -> Result<(), ServiceError> {
+        let mut violations = Violations::new();
+        violations.path("input_path", &self.input_path);
+        violations.path("output_path", &self.output_path);
+        if !cover::extension_in(&self.output_path, POSTER_EXTENSIONS) {
+            violations.add("output_path", format!("must end in one of: {}", POSTER_EXTENSIONS.join(", ")));
+        }
+        violations.into_result()
+    }
+}
+
+impl Validate for CoverAttachRequest {
+    fn validate(&self) -> Result<(), ServiceError> {
+        let mut violations = Violations::new();
+        violations.path("input_path", &self.input_path);
+        violations.path("image_path", &self.image_path);
+        violations.path("output_path", &self.output_path);
+        if self.input_path == self.output_path {
+            violations.add("output_path", "must differ from input_path");
+        }
+        if !cover::extension_in(&self.image_path, COVER_EXTENSIONS) {
+            violations.add("image_path", format!("must end in one of: {}", COVER_EXTENSIONS.join(", ")));
+        }
+        if !cover::extension_in(&self.output_path, COVER_CONTAINERS) {
+            violations.add("output_path", format!("must end in one of: {}", COVER_CONTAINERS.join(", ")));
+        }
+        violations.into_result()
+    }
+}
+
+impl Validate for SlideshowRequest {
+    fn validate(&self) -> Result<(), ServiceError> {
+        let mut violations = Violations::new();
+        match (&self.images, &self.pattern) {
+            (Some(images), None) => {
+                if images.is_empty() || images.len() > MAX_SLIDES {
+                    violations.add("images", format!("must list 1 to {} images", MAX_SLIDES));
+                }
+                for image in images {
+                    violations.path("images", image);
+                }
+            }
+            (None, Some(pattern)) => {
+                violations.path("pattern", pattern);
+                let dir = std::path::Path::new(pattern).parent().map(|dir| dir.to_string_lossy().into_owned());
+                if dir.is_some_and(|dir| dir.contains(['*', '?'])) {
+                    violations.add("pattern", "wildcards are only supported in the file name");
+                }
+            }
+            _ => violations.add("images", "exactly one of images and pattern is required"),
+        }
+        violations.path("output_path", &self.output_path);
+        if let Some(audio_path) = &self.audio_path {
+            violations.path("audio_path", audio_path);
+        }
+        let image_duration = self.image_duration.unwrap_or(DEFAULT_IMAGE_SECS);
+        if !(0.04..=60.0).contains(&image_duration) {
+            violations.add("image_duration", "must be between 0.04 and 60");
+        }
+        violations.one_of("transition", self.transition.as_deref(), TRANSITIONS);
+        if let Some(transition_duration) = self.transition_duration {
+            if transition_duration <= 0.0 || transition_duration >= image_duration {
+                violations.add("transition_duration", "must be positive and shorter than image_duration");
+            }
+        }
+        violations.range("fps", self.fps, 1, 60);
+        violations.resolution("resolution", self.resolution.as_deref());
+        violations.encoder("codec", self.codec.as_deref(), VIDEO_CODECS);
+        if self.codec.as_deref() == Some("copy") {
+            violations.add("codec", "slideshows are always encoded");
+        }
+        violations.into_result()
+    }
+}
+
+impl Validate for AnimationRequest {
+    fn validate(&self) -> Result<(), ServiceError> {
+        let mut violations = Violations::new();
+        violations.path("input_path", &self.input_path);
+        violations.path("output_path", &self.output_path);
+        let extension = std::path::Path::new(&self.output_path)
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_lowercase());
+        if !extension.is_some_and(|ext| ANIMATION_EXTENSIONS.contains(&ext.as_str())) {
+            violations.add("output_path", format!("must end in one of: {}", ANIMATION_EXTENSIONS.join(", ")));
+        }
+        if self.start_time.is_some_and(|start| !start.is_finite() || start < 0.0) {
+            violations.add("start_time", "must not be negative");
+        }
+        if self.duration.is_some_and(|duration| duration.is_nan() || duration <= 0.0 || duration > MAX_DURATION) {
+            violations.add("duration", format!("must be between 0 and {}", MAX_DURATION));
+        }
+        violations.range("fps", self.fps, 1, 50);
+        violations.range("width", self.width, 16, 1920);
+        violations.range("quality", self.quality, 0, 100);
+        violations.range("loops", self.loops, 0, 65535);
+        violations.into_result()
+    }
+}
+
+impl Validate for SubtitleBurnRequest {
+    fn validate(&self) -> Result<(), ServiceError> {
+        let mut violations = Violations::new();
+        violations.input("input_path", &self.input_path);
+        violations.output("output_path", &self.output_path);
+        if self.input_path == self.output_path {
+            violations.add("output_path", "must differ from input_path");
+        }
+        // The file the filter reads goes into the filtergraph
+        let source = match &self.subtitle_path {
+            Some(subtitle_path) => {
+                subtitle_file(&mut violations, "subtitle_path", subtitle_path);
+                if self.stream.is_some() {
+                    violations.add("stream", "only applies without subtitle_path");
+                }
+                ("subtitle_path", subtitle_path)
+            }
+            None => ("input_path", &self.input_path),
+        };
+        if source.1.contains(FILTER_PATH_RESERVED) {
+            violations.add(source.0, "must not contain any of ' \\ : , ; [ ] to be burned in");
+        }
+        violations.encoder("codec", self.codec.as_deref(), VIDEO_CODECS);
+        if self.codec.as_deref() == Some("copy") {
+            violations.add("codec", "burning in needs re-encoding, so codec can't be copy");
+        }
+        violations.into_result()
+    }
+}
+
+impl Validate for SubtitleMuxRequest {
+    fn validate(&self) -> Result<(), ServiceError> {
+        let mut violations = Violations::new();
+        violations.input("input_path", &self.input_path);
+        violations.output("output_path", &self.output_path);
+        if self.input_path == self.output_path {
+            violations.add("output_path", "must differ from input_path");
+        }
+        if self.subtitles.is_empty() || self.subtitles.len() > MAX_TRACKS {
+            violations.add("subtitles", format!("must list 1-{} files", MAX_TRACKS));
+        }
+        for (index, track) in self.subtitles.iter().enumerate() {
+            subtitle_file(&mut violations, &format!("subtitles[{}].path", index), &track.path);
+            let language_ok = |language: &String| (2..=3).contains(&language.len()) && language.chars().all(|c| c.is_ascii_lowercase());
+            if track.language.as_ref().is_some_and(|language| !language_ok(language)) {
+                violations.add(&format!("subtitles[{}].language", index), "must be an ISO 639 code, e.g. eng");
+            }
+            if track.title.as_ref().is_some_and(|title| title.len() > 256 || title.contains('\0')) {
+                violations.add(&format!("subtitles[{}].title", index), "must be at most 256 bytes without NUL");
+            }
+        }
+        if self.subtitles.iter().filter(|track| track.default.unwrap_or(false)).count() > 1 {
+            violations.add("subtitles", "only one track can be the default");
+        }
+        violations.into_result()
+    }
+}
+
+impl Validate for SubtitleExtractRequest {
+    fn validate(&self) -> Result<(), ServiceError> {
+        let mut violations = Violations::new();
+        violations.input("input_path", &self.input_path);
+        violations.output("output_path", &self.output_path);
+        let extension = std::path::Path::new(&self.output_path)
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_lowercase());
+        if !extension.is_some_and(|ext| EXTRACT_EXTENSIONS.contains(&ext.as_str())) {
+            violations.add("output_path", format!("must end in one of: {}", EXTRACT_EXTENSIONS.join(", ")));
+        }
+        violations.into_result()
+    }
+}
+
+/// A subtitle file in one of the formats FFmpeg reads as text
+fn subtitle_file(violations: &mut Violations, field: &str, path: &str) {
+    violations.path(field, path);
+    let extension = std::path::Path::new(path).extension().map(|ext| ext.to_string_lossy().to_lowercase());
+    if !extension.is_some_and(|ext| SUBTITLE_EXTENSIONS.contains(&ext.as_str())) {
+        violations.add(field, format!("must end in one of: {}", SUBTITLE_EXTENSIONS.join(", ")));
+    }
+}
+
+impl Validate for VideoWatermarkRequest {
+    fn validate(&self) -> Result<(), ServiceError> {
+        let mut violations = Violations::new();
+        violations.input("input_path", &self.input_path);
+        violations.output("output_path", &self.output_path);
+        if self.input_path == self.output_path {
+            violations.add("output_path", "must differ from input_path");
+        }
+        match (&self.image_path, &self.text) {
+            (Some(image_path), None) => {
+                violations.path("image_path", image_path);
+                if self.font_color.is_some() {
+                    violations.add("font_color", "only applies to text");
+                }
+            }
+            (None, Some(text)) => {
+                if text.trim().is_empty() || text.chars().count() > MAX_TEXT_LENGTH {
+                    violations.add("text", format!("must be 1-{} characters", MAX_TEXT_LENGTH));
+                }
+            }
+            _ => violations.add("image_path", "exactly one of image_path and text is required"),
+        }
+        if self.opacity.is_some_and(|opacity| !(0.0..=1.0).contains(&opacity)) {
+            violations.add("opacity", "must be between 0 and 1");
+        }
+        if self.scale.is_some_and(|scale| !(0.01..=1.0).contains(&scale)) {
+            violations.add("scale", "must be between 0.01 and 1");
+        }
+        violations.range("margin", self.margin, 0, 1000);
+        // Goes into the filtergraph, so nothing that could end the option or the filter
+        let color_ok = |color: &String| {
+            !color.is_empty() && color.len() <= 32 && color.chars().all(|c| c.is_ascii_alphanumeric() || c == '#')
+        };
+        if self.font_color.as_ref().is_some_and(|color| !color_ok(color)) {
+            violations.add("font_color", "must be a color name or #RRGGBB");
+        }
+        if self.start_time.is_some_and(|start| !start.is_finite() || start < 0.0) {
+            violations.add("start_time", "must not be negative");
+        }
+        if self.end_time.is_some_and(|end| !end.is_finite() || end <= self.start_time.unwrap_or(0.0)) {
+            violations.add("end_time", "must be after start_time");
+        }
+        violations.encoder("codec", self.codec.as_deref(), VIDEO_CODECS);
+        if self.codec.as_deref() == Some("copy") {
+            violations.add("codec", "watermarking needs re-encoding, so codec can't be copy");
+        }
+        violations.into_result()
+    }
+}
+
+impl Validate for SceneDetectRequest {
+    fn validate(&self) -> Result<(), ServiceError> {
+        let mut violations = Violations::new();
+        violations.path("input_path", &self.input_path);
+        if self.threshold.is_some_and(|threshold| !(0.01..=1.0).contains(&threshold)) {
+            violations.add("threshold", "must be between 0.01 and 1");
+        }
+        if self.min_interval.is_some_and(|interval| !interval.is_finite() || interval < 0.0) {
+            violations.add("min_interval", "must not be negative");
+        }
+        violations.range("max_scenes", self.max_scenes, 1, MAX_SCENES as u32);
+        violations.into_result()
+    }
+}
+
+impl Validate for SpriteSheetRequest {
+    fn validate(&self) -> Result<(), ServiceError> {
+        let mut violations = Violations::new();
+        violations.path("input_path", &self.input_path);
+        violations.path("output_dir", &self.output_dir);
+        if self.interval.is_some_and(|interval| !(0.5..=600.0).contains(&interval)) {
+            violations.add("interval", "must be between 0.5 and 600");
+        }
+        violations.range("tile_width", self.tile_width, 32, 640);
+        violations.range("columns", self.columns, 1, 20);
+        violations.into_result()
+    }
+}
+
+impl Validate for ThumbnailRequest {
+    fn validate(&self) -> Result<(), ServiceError> {
+        let mut violations = Violations::new();
+        violations.path("input_path", &self.input_path);
+        violations.path("output_dir", &self.output_dir);
+        match (&self.timestamps, &self.percentages) {
+            (Some(_), Some(_)) => violations.add("percentages", "give timestamps or percentages, not both"),
+            (None, None) => violations.add("timestamps", "timestamps or percentages is required"),
+            (Some(positions), None) | (None, Some(positions)) => {
+                let (field, max) =
+                    if self.timestamps.is_some() { ("timestamps", f64::MAX) } else { ("percentages", 100.0) };
+                if positions.is_empty() || positions.len() > MAX_THUMBNAILS {
+                    violations.add(field, format!("must have 1 to {} entries", MAX_THUMBNAILS));
+                }
+                if positions.iter().any(|position| !position.is_finite() || !(0.0..=max).contains(position)) {
+                    violations.add(field, if max == 100.0 { "must be between 0 and 100" } else { "must not be negative" });
+                }
+            }
+        }
+        violations.one_of("format", self.format.as_deref(), FRAME_FORMATS);
+        violations.range("width", self.width, 16, 7680);
+        violations.into_result()
+    }
+}
+
+impl TrimRequest {
+    /// Length of the clip: `duration`, or the span up to `end_time`
+    pub fn clip_duration(&self) -> f64 {
+        self.duration.or(self.end_time.map(|end| end - self.start_time)).unwrap_or_default()
+    }
+}
+
+impl Validate for TrimRequest {
+    fn validate(&self) -> Result<(), ServiceError> {
+        let mut violations = Violations::new();
+        violations.path("input_path", &self.input_path);
+        violations.path("output_path", &self.output_path);
+        if !self.start_time.is_finite() || self.start_time < 0.0 {
+            violations.add("start_time", "must not be negative");
+        }
+        match (self.end_time, self.duration) {
+            (Some(_), Some(_)) => violations.add("duration", "give end_time or duration, not both"),
+            (None, None) => violations.add("end_time", "end_time or duration is required"),
+            (Some(end), None) if !end.is_finite() || end <= self.start_time => {
+                violations.add("end_time", "must be after start_time")
+            }
+            (None, Some(duration)) if !duration.is_finite() || duration <= 0.0 => violations.add("duration", "must be positive"),
+            _ => {}
+        }
+        match self.mode.unwrap_or_default() {
+            TrimMode::Copy if self.codec.is_some() => violations.add("codec", "only applies to mode reencode"),
+            TrimMode::Copy => {}
+            TrimMode::Reencode => violations.encoder("codec", self.codec.as_deref(), VIDEO_CODECS),
+        }
+        violations.into_result()
+    }
+}
+
+impl Validate for EncodeCompareRequest {
+    fn validate(&self) -> Result<(), ServiceError> {
+        let mut violations = Violations::new();
+        violations.path("input_path", &self.input_path);
+        violations.path("output_dir", &self.output_dir);
+        self.a.validate("a", &mut violations);
+        self.b.validate("b", &mut violations);
+        if self.duration.is_some_and(|duration| !duration.is_finite() || duration <= 0.0) {
+            violations.add("duration", "must be positive");
+        }
+        violations.one_of("composite", self.composite.as_deref(), COMPOSITES);
+        violations.into_result()
+    }
+}
+
+impl Validate for FrameExportRequest {
+    fn validate(&self) -> Result<(), ServiceError> {
+        let mut violations = Violations::new();
+        violations.path("input_path", &self.input_path);
+        violations.path("output_dir", &self.output_dir);
+        violations.one_of("format", self.format.as_deref(), FRAME_FORMATS);
+        if self.start.is_some_and(|start| start < 0.0) {
+            violations.add("start", "must not be negative");
+        }
+        if let Some(end) = self.end {
+            if end <= self.start.unwrap_or(0.0) {
+                violations.add("end", "must be after start");
+            }
+        }
+        violations.range("every", self.every, 1, 10_000);
+        violations.range("max_frames", self.max_frames, 1, 100_000);
+        violations.into_result()
+    }
+}
+
+impl Validate for FaceIndexRequest {
+    fn validate(&self) -> Result<(), ServiceError> {
+        let mut violations = Violations::new();
+        violations.path("input_path", &self.input_path);
+        violations.path("output_dir", &self.output_dir);
+        if self.interval.is_some_and(|interval| !(0.1..=10.0).contains(&interval)) {
+            violations.add("interval", "must be between 0.1 and 10");
+        }
+        violations.range("confidence", self.confidence, 1, 100);
+        violations.range("min_appearances", self.min_appearances, 1, 100);
+        if FaceModel::from_env().is_none() {
+            violations.add("input_path", "face indexing needs a face detection model in FACE_MODEL or MODEL_DIR");
+        }
+        violations.into_result()
+    }
+}
+
+impl Validate for FingerprintRequest {
+    fn validate(&self) -> Result<(), ServiceError> {
+        let mut violations = Violations::new();
+        violations.path("input_path", &self.input_path);
+        if self.interval.is_some_and(|interval| !(0.1..=10.0).contains(&interval)) {
+            violations.add("interval", "must be between 0.1 and 10");
+        }
+        violations.into_result()
+    }
+}
+
+impl Validate for FingerprintCompareRequest {
+    fn validate(&self) -> Result<(), ServiceError> {
+        let mut violations = Violations::new();
+        for (field, fingerprint) in [("a", &self.a), ("b", &self.b)] {
+            if fingerprint.hashes.is_empty() || fingerprint.hashes.len() > MAX_HASHES {
+                violations.add(field, format!("must hold 1 to {} hashes", MAX_HASHES));
+            }
+        }
+        if self.a.interval != self.b.interval {
+            violations.add("b", "must be sampled at the same interval as a");
+        }
+        if self.threshold.is_some_and(|threshold| !(0.5..=1.0).contains(&threshold)) {
+            violations.add("threshold", "must be between 0.5 and 1");
+        }
+        violations.into_result()
+    }
+}
+
+impl Validate for VideoInfoRequest {
+    fn validate(&self) -> Result<(), ServiceError> {
+        let mut violations = Violations::new();
+        violations.path("file_path", &self.file_path);
+        if self.detect_language == Some(true) && WhisperConfig::from_env().is_none() {
+            violations.add("detect_language", "needs a Whisper model in WHISPER_MODEL or MODEL_DIR");
+        }
+        violations.into_result()
+    }
+}
@@ -0,0 +1,87 @@
+/// Frame rate of previews when the request doesn't set one; GIF delays are in hundredths of a
+/// second, so rates that divide 100 play back evenly
+pub const DEFAULT_FPS: u32 = 10;
+
+/// Preview width when the request doesn't set one; the height follows the aspect ratio
+pub const DEFAULT_WIDTH: u32 = 480;
+
+/// Seconds converted when the request doesn't set a duration
+pub const DEFAULT_DURATION: f64 = 5.0;
+
+/// Longest range one preview may cover; GIFs of more grow to tens of megabytes
+pub const MAX_DURATION: f64 = 60.0;
+
+/// WebP quality when the request doesn't set one
+pub const DEFAULT_QUALITY: u32 = 75;
+
+/// Output extensions and the animated formats they produce
+pub static ANIMATION_EXTENSIONS: &[&str] = &["gif", "webp"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnimationFormat {
+    /// 256 colors from a palette computed over the clip
+    Gif,
+    /// Lossy animated WebP; full color and usually a fraction of the GIF's size
+    Webp,
+}
+
+impl AnimationFormat {
+    pub fn from_path(path: &str) -> Option<Self> {
+        let extension = std::path::Path::new(path).extension()?.to_str()?.to_lowercase();
+        match extension.as_str() {
+            "gif" => Some(AnimationFormat::Gif),
+            "webp" => Some(AnimationFormat::Webp),
+            _ => None,
+        }
+    }
+
+    /// Output options; `loops` of 0 repeats forever
+    pub fn encoder_args(self, quality: u32, loops: u32) -> Vec<String> {
+        let mut args: Vec<String> = match self {
+            AnimationFormat::Gif => vec!["-f".into(), "gif".into()],
+            AnimationFormat::Webp => vec![
+                "-c:v".into(),
+                "libwebp_anim".into(),
+                "-quality".into(),
+                quality.to_string(),
+                "-compression_level".into(),
+                "6".into(),
+                "-f".into(),
+                "webp".into(),
+            ],
+        };
+        args.extend(["-loop".into(), loops.to_string(), "-an".into()]);
+        args
+    }
+}
+
+/// `-vf` graph: resample and scale, then for GIF build a palette from the clip itself and map it
+/// onto every frame. `stats_mode=diff` weights the palette towards what moves, and
+/// `diff_mode=rectangle` only redraws the changed part of each frame, which keeps files small
+pub fn filter(fps: u32, width: u32, format: AnimationFormat) -> String {
+    let scaled = format!("fps={},scale={}:-1:flags=lanczos", fps, width);
+    match format {
+        AnimationFormat::Gif => format!(
+            "{},split[frames][sample];[sample]palettegen=stats_mode=diff[palette];[frames][palette]paletteuse=dither=bayer:bayer_scale=5:diff_mode=rectangle",
+            scaled
+        ),
+        AnimationFormat::Webp => scaled,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_animation_filter_and_format() {
+        assert_eq!(AnimationFormat::from_path("out/preview.GIF"), Some(AnimationFormat::Gif));
+        assert_eq!(AnimationFormat::from_path("out/preview.webp"), Some(AnimationFormat::Webp));
+        assert_eq!(AnimationFormat::from_path("out/preview.mp4"), None);
+
+        assert!(filter(10, 480, AnimationFormat::Gif).starts_with("fps=10,scale=480:-1:flags=lanczos,split[frames][sample];"));
+        assert_eq!(filter(15, 320, AnimationFormat::Webp), "fps=15,scale=320:-1:flags=lanczos");
+        assert_eq!(AnimationFormat::Gif.encoder_args(75, 0).join(" "), "-f gif -loop 0 -an");
+        assert!(AnimationFormat::Webp.encoder_args(60, 3).join(" ").contains("libwebp_anim -quality 60"));
+    }
+}
@@ -0,0 +1,123 @@
+use anyhow::Result;
+use serde::Serialize;
+use std::path::Path;
+use crate::utils::error::{FfmpegErrorKind, FfmpegFailure};
+use crate::utils::{audit, sandbox};
+
+/// cropdetect `limit`: luma at or below this (8-bit scale) counts as border
+pub const DEFAULT_THRESHOLD: u32 = 24;
+
+/// Letterboxing is sampled at this rate over at most the first ten minutes of a video
+const SAMPLE_FPS: u32 = 2;
+const MAX_SAMPLE_SECS: u32 = 600;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Border {
+    Black,
+    /// Scanner beds and paper margins; detected by running cropdetect on the negated frame
+    White,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct CropRect {
+    pub width: u64,
+    pub height: u64,
+    pub x: u64,
+    pub y: u64,
+}
+
+impl CropRect {
+    pub fn filter(&self) -> String {
+        format!("crop={}:{}:{}:{}", self.width, self.height, self.x, self.y)
+    }
+
+    fn area(&self) -> u64 {
+        self.width * self.height
+    }
+}
+
+/// Last `crop=W:H:X:Y` cropdetect logged. With `reset=0` it is the union of the content seen
+/// in every sampled frame, so a bright scene can't cut into a dark one
+pub fn parse_cropdetect(stderr: &str) -> Option<CropRect> {
+    let values = stderr.lines().rev().find_map(|line| line.split("crop=").nth(1))?;
+    let values: Vec<i64> = values
+        .split_whitespace()
+        .next()?
+        .split(':')
+        .map(|value| value.parse().ok())
+        .collect::<Option<_>>()?;
+    let [width, height, x, y] = values[..] else {
+        return None;
+    };
+    // An all-border frame yields negative sizes
+    if width <= 0 || height <= 0 || x < 0 || y < 0 {
+        return None;
+    }
+    Some(CropRect { width: width as u64, height: height as u64, x: x as u64, y: y as u64 })
+}
+
+/// The tightest detected crop that actually trims something off a `width`x`height` frame
+pub fn choose(width: u64, height: u64, candidates: &[CropRect]) -> Option<CropRect> {
+    candidates
+        .iter()
+        .filter(|crop| crop.x + crop.width <= width && crop.y + crop.height <= height)
+        .filter(|crop| crop.width < width || crop.height < height)
+        .min_by_key(|crop| crop.area())
+        .copied()
+}
+
+/// Run cropdetect over `input`; `still` inputs are a single frame, videos are sampled
+pub async fn detect(job_id: &str, input: &Path, border: Border, threshold: u32, still: bool) -> Result<Option<CropRect>> {
+    let mut filters = Vec::new();
+    if border == Border::White {
+        filters.push("negate".to_string());
+    }
+    if !still {
+        filters.push(format!("fps={}", SAMPLE_FPS));
+    }
+    // Even sizes and offsets keep 4:2:0 encoders happy
+    filters.push(format!("cropdetect=limit={}:round=2:reset=0", threshold));
+
+    let mut command = sandbox::command("ffmpeg");
+    command.arg("-hide_banner").arg("-nostdin");
+    if !still {
+        command.arg("-t").arg(MAX_SAMPLE_SECS.to_string());
+    }
+    command.arg("-i").arg(input).arg("-vf").arg(filters.join(","));
+    if still {
+        command.arg("-frames:v").arg("1");
+    }
+    command.arg("-an").arg("-f").arg("null").arg("-");
+
+    let output = audit::output_async(Some(job_id), command).await?;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if !output.status.success() {
+        return Err(FfmpegFailure {
+            kind: FfmpegErrorKind::classify(&stderr),
+            message: format!("Border detection failed: {}", stderr.trim()),
+        }
+        .into());
+    }
+    Ok(parse_cropdetect(&stderr))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_choose_crop() {
+        let stderr = "\
+[Parsed_cropdetect_1 @ 0x55d5] x1:0 x2:1919 y1:138 y2:941 w:1920 h:800 x:0 y:140 pts:0 t:0.000000 limit:0.094118 crop=1920:800:0:140
+[Parsed_cropdetect_1 @ 0x55d5] x1:0 x2:1919 y1:136 y2:943 w:1920 h:804 x:0 y:138 pts:1 t:0.500000 limit:0.094118 crop=1920:804:0:138
+";
+        let letterbox = parse_cropdetect(stderr).unwrap();
+        assert_eq!(letterbox, CropRect { width: 1920, height: 804, x: 0, y: 138 });
+        assert_eq!(letterbox.filter(), "crop=1920:804:0:138");
+        assert_eq!(parse_cropdetect("... crop=-1904:-1064:1912:1072"), None);
+
+        let full = CropRect { width: 1920, height: 1080, x: 0, y: 0 };
+        assert_eq!(choose(1920, 1080, &[full]), None);
+        assert_eq!(choose(1920, 1080, &[full, letterbox]), Some(letterbox));
+    }
+}
@@ -0,0 +1,95 @@
+use anyhow::Result;
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::time::Instant;
+use crate::utils::error::{FfmpegErrorKind, FfmpegFailure};
+use crate::utils::{audit, sandbox};
+
+/// One pipeline stage measured in isolation; output goes to the null muxer so disk speed doesn't count
+pub struct BenchmarkCase {
+    pub name: &'static str,
+    pub args: &'static [&'static str],
+}
+
+pub static CASES: &[BenchmarkCase] = &[
+    // Same filter as the mirror's max_dimension fit
+    BenchmarkCase { name: "resize", args: &["-vf", "scale='min(640,iw)':'min(640,ih)':force_original_aspect_ratio=decrease", "-f", "null"] },
+    BenchmarkCase { name: "effect", args: &["-vf", "boxblur=5:1", "-f", "null"] },
+    BenchmarkCase { name: "encode", args: &["-c:v", "mjpeg", "-q:v", "3", "-f", "null"] },
+];
+
+/// Synthetic `testsrc2` input, generated by FFmpeg itself so no fixture files are needed
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+pub struct Synthetic {
+    pub width: u32,
+    pub height: u32,
+    pub frames: u32,
+}
+
+impl Default for Synthetic {
+    fn default() -> Self {
+        Self { width: 1920, height: 1080, frames: 60 }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CaseResult {
+    pub name: String,
+    pub elapsed_ms: u64,
+    pub frames_per_sec: f64,
+    pub megapixels_per_sec: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BenchmarkReport {
+    pub input: Synthetic,
+    pub cases: Vec<CaseResult>,
+}
+
+/// Run one case to completion; blocking. Times include generating the synthetic frames,
+/// which is the same for every case, so compare runs of one case rather than cases
+pub fn run_case(case: &BenchmarkCase, input: &Synthetic) -> Result<CaseResult> {
+    let mut command = sandbox::command("ffmpeg");
+    command
+        .arg("-hide_banner").arg("-v").arg("error").arg("-nostdin")
+        .arg("-f").arg("lavfi")
+        .arg("-i").arg(format!("testsrc2=size={}x{}:rate=30", input.width, input.height))
+        .arg("-frames:v").arg(input.frames.to_string())
+        .args(case.args)
+        .arg("-");
+
+    let started = Instant::now();
+    let output = audit::output(None, &mut command)?;
+    let elapsed = started.elapsed();
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(FfmpegFailure {
+            kind: FfmpegErrorKind::classify(&stderr),
+            message: format!("Benchmark '{}' failed: {}", case.name, stderr.trim()),
+        }
+        .into());
+    }
+
+    let seconds = elapsed.as_secs_f64().max(f64::EPSILON);
+    let pixels = input.width as f64 * input.height as f64 * input.frames as f64;
+    Ok(CaseResult {
+        name: case.name.to_string(),
+        elapsed_ms: elapsed.as_millis() as u64,
+        frames_per_sec: input.frames as f64 / seconds,
+        megapixels_per_sec: pixels / 1_000_000.0 / seconds,
+    })
+}
+
+/// Every case in [`CASES`], one after another so they don't compete for cores
+pub fn run(input: &Synthetic) -> Result<BenchmarkReport> {
+    let mut cases = Vec::with_capacity(CASES.len());
+    for case in CASES {
+        let result = run_case(case, input)?;
+        info!(
+            "Benchmark {}: {:.1} fps, {:.1} MP/s ({} ms)",
+            result.name, result.frames_per_sec, result.megapixels_per_sec, result.elapsed_ms
+        );
+        cases.push(result);
+    }
+    Ok(BenchmarkReport { input: *input, cases })
+}
@@ -0,0 +1,66 @@
+/// `gblur` sigma, in pixels, when a request doesn't set one
+pub const DEFAULT_SIGMA: u32 = 8;
+
+/// Channels a blur can be limited to, in `gbrp` plane order
+const PLANES: [&str; 3] = ["g", "b", "r"];
+
+/// How much of the input a blur covers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Coverage {
+    Full,
+    /// Only where input 1, a grayscale mask, is black; white areas stay sharp
+    OutsideMask,
+    /// Only where the mask is white, e.g. over faces or plates
+    InsideMask,
+}
+
+/// `gblur` plane bitmask for the named channels; `None` when every channel is blurred
+pub fn planes(channels: &[String]) -> Option<u32> {
+    let mask = PLANES
+        .iter()
+        .enumerate()
+        .filter(|(_, plane)| channels.iter().any(|channel| channel.eq_ignore_ascii_case(plane)))
+        .fold(0, |mask, (index, _)| mask | 1 << index);
+    (mask != 0 && mask != 0b111).then_some(mask)
+}
+
+/// `-filter_complex` graph ending in `[out]`. The frame is converted to planar RGB so
+/// `planes` picks red, green and blue rather than luma and chroma; the mask is stretched
+/// to the frame and `maskedmerge` blends the sharp and blurred copies through it
+pub fn filter_graph(sigma: u32, planes: Option<u32>, coverage: Coverage) -> String {
+    let blur = match planes {
+        Some(planes) => format!("gblur=sigma={}:planes={}", sigma, planes),
+        None => format!("gblur=sigma={}", sigma),
+    };
+    let negate = match coverage {
+        Coverage::Full => return format!("[0:v]format=gbrp,{}[out]", blur),
+        Coverage::OutsideMask => "",
+        Coverage::InsideMask => "negate,",
+    };
+    format!(
+        "[0:v]format=gbrp,split[frame][soft];[soft]{blur}[blurred];\
+         [1:v]format=gray,{negate}format=gbrp[stencil];[stencil][frame]scale2ref[mask][sharp];\
+         [blurred][sharp][mask]maskedmerge[out]"
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_planes_and_filter_graph() {
+        let channels = |names: &[&str]| names.iter().map(|name| name.to_string()).collect::<Vec<_>>();
+        assert_eq!(planes(&channels(&["r"])), Some(4));
+        assert_eq!(planes(&channels(&["G", "b"])), Some(3));
+        assert_eq!(planes(&channels(&["r", "g", "b"])), None);
+        assert_eq!(planes(&[]), None);
+
+        assert_eq!(filter_graph(8, Some(4), Coverage::Full), "[0:v]format=gbrp,gblur=sigma=8:planes=4[out]");
+        let background = filter_graph(12, None, Coverage::OutsideMask);
+        assert!(background.starts_with("[0:v]format=gbrp,split[frame][soft];[soft]gblur=sigma=12[blurred];"));
+        assert!(background.contains("[1:v]format=gray,format=gbrp[stencil]"));
+        assert!(background.ends_with("[blurred][sharp][mask]maskedmerge[out]"));
+        assert!(filter_graph(12, None, Coverage::InsideMask).contains("[1:v]format=gray,negate,format=gbrp[stencil]"));
+    }
+}
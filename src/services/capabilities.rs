@@ -0,0 +1,264 @@
+use log::{info, warn};
+use serde::Serialize;
+use std::process::Command;
+use std::sync::OnceLock;
+use crate::services::faces::FaceModel;
+use crate::services::hwaccel;
+use crate::services::language::WhisperConfig;
+use crate::services::sync_processor::{DERIVATIVE_FORMATS, SOURCE_EXTENSIONS};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CodecInfo {
+    pub name: String,
+    pub description: String,
+    pub kind: String,
+    pub decode: bool,
+    pub encode: bool,
+}
+
+/// An entry of `ffmpeg -encoders`; these are the names `-c:v`/`-c:a` accept
+#[derive(Debug, Clone, Serialize)]
+pub struct EncoderInfo {
+    pub name: String,
+    pub description: String,
+    pub kind: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HardwareAcceleration {
+    pub available: bool,
+    pub methods: Vec<String>,
+    /// Hardware encoders that completed a test encode here, which `hardware_acceleration`
+    /// requests pick from
+    pub encoders: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ImageFormats {
+    pub input: Vec<String>,
+    pub output: Vec<String>,
+}
+
+/// What this deployment can do, probed once at startup so clients can adapt
+#[derive(Debug, Clone, Serialize)]
+pub struct Capabilities {
+    pub ffmpeg_version: Option<String>,
+    pub codecs: Vec<CodecInfo>,
+    pub encoders: Vec<EncoderInfo>,
+    pub hardware_acceleration: HardwareAcceleration,
+    pub image_formats: ImageFormats,
+    /// Image effects are not part of this service yet; kept so clients can feature-detect
+    pub effects: Vec<String>,
+    /// `face-detection` and `language-detection` when their models are installed
+    pub ai_models: Vec<String>,
+}
+
+impl Capabilities {
+    /// Query the local ffmpeg binary for its version, codecs and hwaccel methods
+    pub fn probe() -> Self {
+        let ffmpeg_version = run_ffmpeg(&["-version"])
+            .and_then(|output| parse_version(&output));
+        let codecs = run_ffmpeg(&["-hide_banner", "-codecs"])
+            .map(|output| parse_codecs(&output))
+            .unwrap_or_default();
+        let encoders = run_ffmpeg(&["-hide_banner", "-encoders"])
+            .map(|output| parse_encoders(&output))
+            .unwrap_or_default();
+        let methods = run_ffmpeg(&["-hide_banner", "-hwaccels"])
+            .map(|output| parse_hwaccels(&output))
+            .unwrap_or_default();
+
+        info!(
+            "FFmpeg capabilities: version {}, {} codecs, {} encoders, hwaccels [{}]",
+            ffmpeg_version.as_deref().unwrap_or("unknown"),
+            codecs.len(),
+            encoders.len(),
+            methods.join(", ")
+        );
+
+        Self {
+            ffmpeg_version,
+            codecs,
+            encoders,
+            hardware_acceleration: HardwareAcceleration {
+                available: !methods.is_empty(),
+                methods,
+                encoders: hwaccel::usable().iter().map(|name| name.to_string()).collect(),
+            },
+            image_formats: ImageFormats {
+                input: SOURCE_EXTENSIONS.iter().map(|ext| ext.to_string()).collect(),
+                output: DERIVATIVE_FORMATS.iter().map(|ext| ext.to_string()).collect(),
+            },
+            effects: Vec::new(),
+            ai_models: [
+                FaceModel::from_env().map(|_| "face-detection"),
+                WhisperConfig::from_env().map(|_| "language-detection"),
+            ]
+            .into_iter()
+            .flatten()
+            .map(String::from)
+            .collect(),
+        }
+    }
+
+    /// `None` when the encoder list could not be read, so callers don't reject on missing data
+    pub fn has_encoder(&self, name: &str) -> Option<bool> {
+        if self.encoders.is_empty() {
+            return None;
+        }
+        Some(self.encoders.iter().any(|encoder| encoder.name == name))
+    }
+}
+
+static CAPABILITIES: OnceLock<Capabilities> = OnceLock::new();
+
+/// Keep the startup probe for request validation; returns the stored copy
+pub fn init(capabilities: Capabilities) -> &'static Capabilities {
+    CAPABILITIES.get_or_init(|| capabilities)
+}
+
+/// Whether the probed FFmpeg build has `name`; `None` before [`init`] or when probing failed
+pub fn has_encoder(name: &str) -> Option<bool> {
+    CAPABILITIES.get()?.has_encoder(name)
+}
+
+fn run_ffmpeg(args: &[&str]) -> Option<String> {
+    match Command::new("ffmpeg").args(args).output() {
+        Ok(output) if output.status.success() => Some(String::from_utf8_lossy(&output.stdout).to_string()),
+        Ok(output) => {
+            warn!("ffmpeg {:?} failed: {}", args, String::from_utf8_lossy(&output.stderr).trim());
+            None
+        }
+        Err(e) => {
+            warn!("Could not run ffmpeg {:?}: {}", args, e);
+            None
+        }
+    }
+}
+
+/// Extract the version from the first line of `ffmpeg -version`
+fn parse_version(output: &str) -> Option<String> {
+    output
+        .lines()
+        .next()?
+        .strip_prefix("ffmpeg version ")?
+        .split_whitespace()
+        .next()
+        .map(|version| version.to_string())
+}
+
+/// Parse the codec table printed by `ffmpeg -codecs` (flags, name, description)
+fn parse_codecs(output: &str) -> Vec<CodecInfo> {
+    output
+        .lines()
+        .skip_while(|line| !line.trim_start().starts_with("---"))
+        .skip(1)
+        .filter_map(|line| {
+            let line = line.trim_start();
+            let (flags, rest) = line.split_once(' ')?;
+            let (name, description) = rest.trim_start().split_once(' ').unwrap_or((rest, ""));
+            let flags: Vec<char> = flags.chars().collect();
+            if flags.len() < 3 {
+                return None;
+            }
+            let kind = match flags[2] {
+                'V' => "video",
+                'A' => "audio",
+                'S' => "subtitle",
+                'D' => "data",
+                'T' => "attachment",
+                _ => "unknown",
+            };
+            Some(CodecInfo {
+                name: name.to_string(),
+                description: description.trim().to_string(),
+                kind: kind.to_string(),
+                decode: flags[0] == 'D',
+                encode: flags[1] == 'E',
+            })
+        })
+        .collect()
+}
+
+/// Parse `ffmpeg -encoders`: a legend, a `------` rule, then `V....D name  description` rows
+fn parse_encoders(output: &str) -> Vec<EncoderInfo> {
+    output
+        .lines()
+        .skip_while(|line| !line.trim_start().starts_with("---"))
+        .skip(1)
+        .filter_map(|line| {
+            let (flags, rest) = line.trim_start().split_once(' ')?;
+            let (name, description) = rest.trim_start().split_once(' ').unwrap_or((rest, ""));
+            let kind = match flags.chars().next()? {
+                'V' => "video",
+                'A' => "audio",
+                'S' => "subtitle",
+                _ => "unknown",
+            };
+            Some(EncoderInfo {
+                name: name.to_string(),
+                description: description.trim().to_string(),
+                kind: kind.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Parse `ffmpeg -hwaccels`, which lists one method per line after a header
+fn parse_hwaccels(output: &str) -> Vec<String> {
+    output
+        .lines()
+        .skip_while(|line| !line.starts_with("Hardware acceleration methods"))
+        .skip(1)
+        .map(|line| line.trim().to_string())
+        .filter(|line| !line.is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_codecs() {
+        let output = "Codecs:\n D..... = Decoding supported\n -------\n DEV.LS h264                 H.264 / AVC / MPEG-4 AVC\n D.A.L. mp3                  MP3 (MPEG audio layer 3)\n";
+        let codecs = parse_codecs(output);
+        assert_eq!(codecs.len(), 2);
+        assert_eq!(codecs[0].name, "h264");
+        assert_eq!(codecs[0].kind, "video");
+        assert!(codecs[0].encode);
+        assert_eq!(codecs[1].kind, "audio");
+        assert!(!codecs[1].encode);
+    }
+
+    #[test]
+    fn test_parse_encoders() {
+        let output = "Encoders:\n V..... = Video\n A..... = Audio\n ------\n V....D libx264              libx264 H.264 / AVC (codec h264)\n A....D aac                  AAC (Advanced Audio Coding)\n";
+        let encoders = parse_encoders(output);
+        assert_eq!(encoders.len(), 2);
+        assert_eq!(encoders[0].name, "libx264");
+        assert_eq!(encoders[0].kind, "video");
+        assert_eq!(encoders[1].kind, "audio");
+
+        let capabilities = Capabilities {
+            ffmpeg_version: None,
+            codecs: Vec::new(),
+            encoders,
+            hardware_acceleration: HardwareAcceleration { available: false, methods: Vec::new(), encoders: Vec::new() },
+            image_formats: ImageFormats { input: Vec::new(), output: Vec::new() },
+            effects: Vec::new(),
+            ai_models: Vec::new(),
+        };
+        assert_eq!(capabilities.has_encoder("libx264"), Some(true));
+        assert_eq!(capabilities.has_encoder("hevc_nvenc"), Some(false));
+    }
+
+    #[test]
+    fn test_parse_version_and_hwaccels() {
+        assert_eq!(
+            parse_version("ffmpeg version 5.1.6-0+deb12u1 Copyright (c) 2000-2024").as_deref(),
+            Some("5.1.6-0+deb12u1")
+        );
+        assert_eq!(parse_hwaccels("Hardware acceleration methods:\nvaapi\ncuda\n\n"), vec!["vaapi", "cuda"]);
+    }
+}
@@ -0,0 +1,76 @@
+use std::path::Path;
+
+/// Image formats MP4 and Matroska can carry as an `attached_pic` stream as is
+pub static COVER_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png"];
+
+/// Containers a cover is written into
+pub static COVER_CONTAINERS: &[&str] = &["mp4", "m4v", "mov", "mkv"];
+
+/// Still formats an extracted cover may be re-encoded to
+pub static POSTER_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png", "webp"];
+
+/// Whether the extension of `path`, in any case, is one of `allowed`
+pub fn extension_in(path: &str, allowed: &[&str]) -> bool {
+    Path::new(path)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| allowed.contains(&ext.to_lowercase().as_str()))
+}
+
+/// Stream index of the first embedded cover in an ffprobe result. Matroska image attachments
+/// are exposed as `attached_pic` video streams too
+pub fn cover_stream(probe: &serde_json::Value) -> Option<u64> {
+    probe["streams"]
+        .as_array()?
+        .iter()
+        .find(|stream| stream["codec_type"] == "video" && stream["disposition"]["attached_pic"] == 1)
+        .and_then(|stream| stream["index"].as_u64())
+}
+
+/// Output options that copy every stream of input 0 except its current cover and add input 1
+/// as the new one. The cover becomes the last video stream, so its disposition is set by
+/// its position among the video streams
+pub fn attach_args(probe: &serde_json::Value) -> Vec<String> {
+    let existing = cover_stream(probe);
+    let videos = probe["streams"]
+        .as_array()
+        .map_or(0, |streams| streams.iter().filter(|stream| stream["codec_type"] == "video").count());
+    let kept_videos = videos - usize::from(existing.is_some());
+
+    let mut args = vec!["-map".to_string(), "0".to_string()];
+    if let Some(index) = existing {
+        args.extend(["-map".to_string(), format!("-0:{}", index)]);
+    }
+    args.extend(["-map", "1", "-c", "copy"].map(String::from));
+    args.extend([format!("-disposition:v:{}", kept_videos), "attached_pic".to_string()]);
+    args
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_finds_and_replaces_cover() {
+        let plain = serde_json::json!({ "streams": [
+            { "index": 0, "codec_type": "video", "disposition": { "attached_pic": 0 } },
+            { "index": 1, "codec_type": "audio", "disposition": { "attached_pic": 0 } }
+        ]});
+        assert_eq!(cover_stream(&plain), None);
+        assert_eq!(attach_args(&plain).join(" "), "-map 0 -map 1 -c copy -disposition:v:1 attached_pic");
+
+        let with_cover = serde_json::json!({ "streams": [
+            { "index": 0, "codec_type": "video", "disposition": { "attached_pic": 0 } },
+            { "index": 1, "codec_type": "audio", "disposition": { "attached_pic": 0 } },
+            { "index": 2, "codec_type": "video", "disposition": { "attached_pic": 1 } }
+        ]});
+        assert_eq!(cover_stream(&with_cover), Some(2));
+        assert_eq!(
+            attach_args(&with_cover).join(" "),
+            "-map 0 -map -0:2 -map 1 -c copy -disposition:v:1 attached_pic"
+        );
+
+        assert!(extension_in("/covers/front.JPG", COVER_EXTENSIONS));
+        assert!(!extension_in("/covers/front.gif", COVER_EXTENSIONS));
+    }
+}
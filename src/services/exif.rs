@@ -0,0 +1,242 @@
+/// IFD0 tag pointing at the GPS IFD
+const GPS_IFD_TAG: u16 = 0x8825;
+
+/// `GPSLatitudeRef`, `GPSLatitude`, `GPSLongitudeRef`, `GPSLongitude`
+const LATITUDE_REF: u16 = 1;
+const LATITUDE: u16 = 2;
+const LONGITUDE_REF: u16 = 3;
+const LONGITUDE: u16 = 4;
+
+/// Bytes of an IFD entry: tag, type, count and a value or offset
+const ENTRY_BYTES: usize = 12;
+
+/// The TIFF structure inside a JPEG's `Exif` APP1 segment
+struct Tiff {
+    /// File offset of the TIFF header, which all EXIF offsets count from
+    start: usize,
+    /// File offset the segment ends at
+    end: usize,
+    little_endian: bool,
+}
+
+/// One entry of an IFD, located in the file
+struct Entry {
+    /// File offset of the entry itself
+    at: usize,
+    tag: u16,
+    /// File range of the entry's value, inline or out of line; `None` when it points outside
+    /// the segment or has a type this parser doesn't know
+    value: Option<std::ops::Range<usize>>,
+}
+
+impl Tiff {
+    fn find(jpeg: &[u8]) -> Option<Self> {
+        if !jpeg.starts_with(&[0xFF, 0xD8]) {
+            return None;
+        }
+        let mut pos = 2;
+        loop {
+            if *jpeg.get(pos)? != 0xFF {
+                return None;
+            }
+            let marker = *jpeg.get(pos + 1)?;
+            // Padding before a marker
+            if marker == 0xFF {
+                pos += 1;
+                continue;
+            }
+            // Metadata segments all come before the scan
+            if matches!(marker, 0xD9 | 0xDA) {
+                return None;
+            }
+            let length = usize::from(u16::from_be_bytes([*jpeg.get(pos + 2)?, *jpeg.get(pos + 3)?]));
+            let end = pos + 2 + length;
+            if length < 2 || end > jpeg.len() {
+                return None;
+            }
+            let body = &jpeg[pos + 4..end];
+            if marker == 0xE1 && body.starts_with(b"Exif\0\0") {
+                let start = pos + 10;
+                let little_endian = match jpeg.get(start..start + 2)? {
+                    b"II" => true,
+                    b"MM" => false,
+                    _ => return None,
+                };
+                return Some(Self { start, end, little_endian });
+            }
+            pos = end;
+        }
+    }
+
+    /// File range of `len` bytes at TIFF offset `offset`, if inside the segment
+    fn range(&self, offset: usize, len: usize) -> Option<std::ops::Range<usize>> {
+        let from = self.start.checked_add(offset)?;
+        let to = from.checked_add(len)?;
+        (to <= self.end).then_some(from..to)
+    }
+
+    fn u16(&self, data: &[u8], at: usize) -> Option<u16> {
+        let bytes: [u8; 2] = data.get(at..at + 2)?.try_into().ok()?;
+        Some(if self.little_endian { u16::from_le_bytes(bytes) } else { u16::from_be_bytes(bytes) })
+    }
+
+    fn u32(&self, data: &[u8], at: usize) -> Option<u32> {
+        let bytes: [u8; 4] = data.get(at..at + 4)?.try_into().ok()?;
+        Some(if self.little_endian { u32::from_le_bytes(bytes) } else { u32::from_be_bytes(bytes) })
+    }
+
+    /// File offset of the IFD at TIFF offset `offset` and its entries
+    fn ifd(&self, data: &[u8], offset: u32) -> Option<(usize, Vec<Entry>)> {
+        let at = self.range(offset as usize, 2)?.start;
+        let count = usize::from(self.u16(data, at)?);
+        let entries = self.range((offset as usize).checked_add(2)?, count * ENTRY_BYTES)?;
+        let entries = entries
+            .step_by(ENTRY_BYTES)
+            .map(|at| {
+                let tag = self.u16(data, at).unwrap_or_default();
+                let value = self.value(data, at);
+                Entry { at, tag, value }
+            })
+            .collect();
+        Some((at, entries))
+    }
+
+    fn value(&self, data: &[u8], entry: usize) -> Option<std::ops::Range<usize>> {
+        let size = match self.u16(data, entry + 2)? {
+            1 | 2 | 6 | 7 => 1,
+            3 | 8 => 2,
+            4 | 9 | 11 => 4,
+            5 | 10 | 12 => 8,
+            _ => return None,
+        };
+        let len = (self.u32(data, entry + 4)? as usize).checked_mul(size)?;
+        if len <= 4 {
+            Some(entry + 8..entry + 8 + len)
+        } else {
+            self.range(self.u32(data, entry + 8)? as usize, len)
+        }
+    }
+
+    /// The GPS IFD, if IFD0 links to one
+    fn gps_ifd(&self, data: &[u8]) -> Option<(usize, Vec<Entry>)> {
+        let ifd0 = self.u32(data, self.start + 4)?;
+        let (_, entries) = self.ifd(data, ifd0)?;
+        let link = entries.iter().find(|entry| entry.tag == GPS_IFD_TAG)?;
+        self.ifd(data, self.u32(data, link.at + 8)?)
+    }
+}
+
+/// Latitude and longitude in degrees, south and west negative, from the EXIF GPS tags of a
+/// JPEG. Only the structure leading to the GPS IFD is parsed
+pub fn gps(jpeg: &[u8]) -> Option<(f64, f64)> {
+    let tiff = Tiff::find(jpeg)?;
+    let (_, entries) = tiff.gps_ifd(jpeg)?;
+    let value = |tag: u16| entries.iter().find(|entry| entry.tag == tag)?.value.clone();
+    let degrees = |tag: u16, reference: u16, negative: u8| -> Option<f64> {
+        let range = value(tag)?;
+        // Degrees, minutes and seconds as three RATIONALs
+        if range.len() != 24 {
+            return None;
+        }
+        let mut total = 0.0;
+        for (index, scale) in [1.0, 60.0, 3600.0].into_iter().enumerate() {
+            let at = range.start + index * 8;
+            let (numerator, denominator) = (tiff.u32(jpeg, at)?, tiff.u32(jpeg, at + 4)?);
+            if denominator == 0 {
+                return None;
+            }
+            total += f64::from(numerator) / f64::from(denominator) / scale;
+        }
+        let reference = value(reference).and_then(|range| jpeg.get(range.start).copied());
+        Some(if reference == Some(negative) { -total } else { total })
+    };
+    let latitude = degrees(LATITUDE, LATITUDE_REF, b'S')?;
+    let longitude = degrees(LONGITUDE, LONGITUDE_REF, b'W')?;
+    ((-90.0..=90.0).contains(&latitude) && (-180.0..=180.0).contains(&longitude)).then_some((latitude, longitude))
+}
+
+/// Zero every GPS tag and its value, and empty the GPS IFD, without moving anything else.
+/// Returns whether there was a GPS IFD to blank
+pub fn strip_gps(jpeg: &mut [u8]) -> bool {
+    let Some(tiff) = Tiff::find(jpeg) else {
+        return false;
+    };
+    let Some((at, entries)) = tiff.gps_ifd(jpeg) else {
+        return false;
+    };
+    for entry in &entries {
+        if let Some(value) = &entry.value {
+            jpeg[value.clone()].fill(0);
+        }
+        jpeg[entry.at..entry.at + ENTRY_BYTES].fill(0);
+    }
+    // An entry count of 0 also turns the next-IFD link, now read from the blanked first
+    // entry, into 0, so readers stop here
+    jpeg[at..at + 2].fill(0);
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A JPEG header with an EXIF block holding only a GPS IFD: 52° 31' 12" N, 13° 24' 18" E
+    fn tagged_jpeg(little_endian: bool) -> Vec<u8> {
+        let u16 = |value: u16| if little_endian { value.to_le_bytes() } else { value.to_be_bytes() };
+        let u32 = |value: u32| if little_endian { value.to_le_bytes() } else { value.to_be_bytes() };
+        let mut tiff = Vec::new();
+        tiff.extend_from_slice(if little_endian { b"II" } else { b"MM" });
+        tiff.extend_from_slice(&u16(42));
+        tiff.extend_from_slice(&u32(8));
+        // IFD0 at 8: one entry linking to the GPS IFD at 26
+        tiff.extend_from_slice(&u16(1));
+        tiff.extend_from_slice(&u16(GPS_IFD_TAG));
+        tiff.extend_from_slice(&u16(4));
+        tiff.extend_from_slice(&u32(1));
+        tiff.extend_from_slice(&u32(26));
+        tiff.extend_from_slice(&u32(0));
+        // GPS IFD at 26: four entries, the rationals following at 80 and 104
+        tiff.extend_from_slice(&u16(4));
+        for (tag, kind, count, value) in [(1, 2, 2, *b"N\0\0\0"), (2, 5, 3, u32(80)), (3, 2, 2, *b"E\0\0\0"), (4, 5, 3, u32(104))] {
+            tiff.extend_from_slice(&u16(tag));
+            tiff.extend_from_slice(&u16(kind));
+            tiff.extend_from_slice(&u32(count));
+            tiff.extend_from_slice(&value);
+        }
+        tiff.extend_from_slice(&u32(0));
+        for (numerator, denominator) in [(52, 1), (31, 1), (12, 1), (13, 1), (24, 1), (1800, 100)] {
+            tiff.extend_from_slice(&u32(numerator));
+            tiff.extend_from_slice(&u32(denominator));
+        }
+
+        let mut jpeg = vec![0xFF, 0xD8, 0xFF, 0xE1];
+        jpeg.extend_from_slice(&((tiff.len() + 8) as u16).to_be_bytes());
+        jpeg.extend_from_slice(b"Exif\0\0");
+        jpeg.extend_from_slice(&tiff);
+        jpeg.extend_from_slice(&[0xFF, 0xDA, 0x00, 0x02, 0xFF, 0xD9]);
+        jpeg
+    }
+
+    #[test]
+    fn test_read_and_strip_gps() {
+        for little_endian in [true, false] {
+            let mut jpeg = tagged_jpeg(little_endian);
+            let (latitude, longitude) = gps(&jpeg).unwrap();
+            assert!((latitude - 52.52).abs() < 1e-9 && (longitude - 13.405).abs() < 1e-9);
+
+            let length = jpeg.len();
+            assert!(strip_gps(&mut jpeg));
+            assert_eq!(jpeg.len(), length);
+            assert_eq!(gps(&jpeg), None);
+            assert!(!jpeg.windows(2).any(|pair| pair == b"N\0"));
+            // The structure around it still parses
+            assert!(strip_gps(&mut jpeg));
+        }
+
+        let mut truncated = tagged_jpeg(true);
+        truncated.truncate(40);
+        assert_eq!(gps(&truncated), None);
+        assert!(!strip_gps(&mut truncated));
+        assert!(!strip_gps(&mut [0xFF, 0xD8, 0xFF, 0xDA]));
+    }
+}
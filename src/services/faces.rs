@@ -0,0 +1,301 @@
+use anyhow::Result;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use crate::utils::error::{FfmpegErrorKind, FfmpegFailure};
+use crate::utils::{audit, sandbox};
+
+/// Seconds between sampled frames when the request doesn't set it
+pub const DEFAULT_INTERVAL: f64 = 0.5;
+
+/// Detector confidence in percent below which boxes are dropped
+pub const DEFAULT_CONFIDENCE: u32 = 60;
+
+/// Samples a track needs before it is indexed; single hits are mostly false positives
+pub const DEFAULT_MIN_APPEARANCES: u32 = 2;
+
+/// Tracks one index may hold, each costing an FFmpeg run for its crop
+pub const MAX_TRACKS: usize = 500;
+
+/// Model file `FACE_MODEL` defaults to inside `MODEL_DIR`
+const DEFAULT_MODEL_FILE: &str = "face-detection-retail-0004.xml";
+
+/// Overlap with a track's last box at which a detection continues that track
+const MIN_IOU: f64 = 0.3;
+
+/// Samples a track may go unseen (a turned head, a cut away and back) before it ends
+const MAX_GAP_SAMPLES: f64 = 2.0;
+
+/// Share of the face box added on every side of a crop, so it shows the whole head
+const CROP_MARGIN: f64 = 0.2;
+
+/// OpenVINO face detector run through FFmpeg's `dnn_detect` filter
+#[derive(Debug, Clone, PartialEq)]
+pub struct FaceModel {
+    pub path: PathBuf,
+    pub input: String,
+    pub output: String,
+}
+
+impl FaceModel {
+    /// `FACE_MODEL` (default: `face-detection-retail-0004.xml` in `MODEL_DIR`), with its
+    /// `FACE_MODEL_INPUT`/`FACE_MODEL_OUTPUT` layer names (default: `data`/`detection_out`).
+    /// `None` when the model file doesn't exist or its path can't be passed as a filter option
+    pub fn from_env() -> Option<Self> {
+        let path = match std::env::var("FACE_MODEL") {
+            Ok(path) => PathBuf::from(path),
+            Err(_) => Path::new(&std::env::var("MODEL_DIR").ok()?).join(DEFAULT_MODEL_FILE),
+        };
+        let unquotable = path.to_string_lossy().contains(['\'', '\\', ':', ',', ';', '[', ']']);
+        if unquotable || !path.is_file() {
+            return None;
+        }
+        let layer = |name: &str, default: &str| std::env::var(name).unwrap_or_else(|_| default.to_string());
+        Some(Self { path, input: layer("FACE_MODEL_INPUT", "data"), output: layer("FACE_MODEL_OUTPUT", "detection_out") })
+    }
+
+    /// `-vf` chain sampling a frame every `interval` seconds, detecting faces on it and logging
+    /// the boxes through `showinfo`
+    pub fn filter(&self, interval: f64, confidence: u32) -> String {
+        format!(
+            "fps={},dnn_detect=dnn_backend=openvino:model={}:input={}:output={}:confidence={},showinfo",
+            1.0 / interval,
+            self.path.display(),
+            self.input,
+            self.output,
+            f64::from(confidence) / 100.0
+        )
+    }
+}
+
+/// Face box in pixels of the source frame
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct FaceBox {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+impl FaceBox {
+    fn iou(&self, other: &FaceBox) -> f64 {
+        let overlap_w = (self.x + self.width).min(other.x + other.width) - self.x.max(other.x);
+        let overlap_h = (self.y + self.height).min(other.y + other.height) - self.y.max(other.y);
+        if overlap_w <= 0 || overlap_h <= 0 {
+            return 0.0;
+        }
+        let intersection = f64::from(overlap_w) * f64::from(overlap_h);
+        let union = f64::from(self.width * self.height) + f64::from(other.width * other.height) - intersection;
+        intersection / union
+    }
+
+    /// `crop` filter for this box grown by `CROP_MARGIN`, clamped to the frame by FFmpeg's
+    /// own `iw`/`ih`
+    pub fn crop_filter(&self) -> String {
+        let (margin_x, margin_y) = ((f64::from(self.width) * CROP_MARGIN) as i32, (f64::from(self.height) * CROP_MARGIN) as i32);
+        let (x, y) = ((self.x - margin_x).max(0), (self.y - margin_y).max(0));
+        let (width, height) = (self.width + 2 * margin_x, self.height + 2 * margin_y);
+        format!("crop='min({width},iw-{x})':'min({height},ih-{y})':{x}:{y}")
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Detection {
+    pub time: f64,
+    pub face: FaceBox,
+    pub confidence: f64,
+}
+
+/// Detections `showinfo` logged, timed by the frame line preceding them:
+/// `index: 0, region: (412, 96) -> (508, 220), label: face, confidence: 9876/10000.`
+pub fn parse_detections(stderr: &str) -> Vec<Detection> {
+    let mut time = None;
+    let mut detections = Vec::new();
+    for line in stderr.lines().filter(|line| line.contains("Parsed_showinfo")) {
+        if let Some(pts_time) = line.split_whitespace().find_map(|token| token.strip_prefix("pts_time:")) {
+            time = pts_time.parse::<f64>().ok();
+        } else if let (Some(time), Some(detection)) = (time, parse_region(line)) {
+            detections.push(Detection { time, face: detection.0, confidence: detection.1 });
+        }
+    }
+    detections
+}
+
+fn parse_region(line: &str) -> Option<(FaceBox, f64)> {
+    let region = line.split_once("region: (")?.1;
+    let (from, rest) = region.split_once(") -> (")?;
+    let (to, rest) = rest.split_once(')')?;
+    let point = |text: &str| -> Option<(i32, i32)> {
+        let (x, y) = text.split_once(',')?;
+        Some((x.trim().parse().ok()?, y.trim().parse().ok()?))
+    };
+    let ((x1, y1), (x2, y2)) = (point(from)?, point(to)?);
+    let confidence = rest.split_once("confidence: ").map_or(Some(1.0), |(_, ratio)| {
+        let (num, den) = ratio.trim_end_matches('.').trim().split_once('/')?;
+        Some(num.parse::<f64>().ok()? / den.parse::<f64>().ok()?.max(1.0))
+    })?;
+    (x2 > x1 && y2 > y1).then_some((FaceBox { x: x1, y: y1, width: x2 - x1, height: y2 - y1 }, confidence))
+}
+
+/// One continuous appearance of a face. Tracks are not identities: the same person
+/// leaving and coming back gets a new track
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FaceTrack {
+    pub id: usize,
+    pub start: f64,
+    pub end: f64,
+    /// Samples the face was detected on
+    pub appearances: u32,
+    /// The most confident detection, which the crop is taken from
+    pub best_time: f64,
+    pub best_box: FaceBox,
+    pub confidence: f64,
+    /// Crop file name within the output directory, `face_0000.jpg` and up
+    pub crop: String,
+    #[serde(skip)]
+    last_box: FaceBox,
+}
+
+/// Link detections sampled `interval` seconds apart into tracks by how much each box
+/// overlaps the track's last one, keeping tracks seen at least `min_appearances` times
+pub fn track(detections: &[Detection], interval: f64, min_appearances: u32) -> Vec<FaceTrack> {
+    let mut tracks: Vec<FaceTrack> = Vec::new();
+    let mut index = 0;
+    while index < detections.len() {
+        let time = detections[index].time;
+        let frame_end = detections[index..].iter().position(|d| d.time != time).map_or(detections.len(), |n| index + n);
+        let mut claimed = Vec::new();
+        for detection in &detections[index..frame_end] {
+            let candidate = tracks
+                .iter()
+                .enumerate()
+                .filter(|(i, track)| !claimed.contains(i) && time - track.end <= interval * MAX_GAP_SAMPLES + 1e-9)
+                .map(|(i, track)| (i, track.last_box.iou(&detection.face)))
+                .filter(|(_, iou)| *iou >= MIN_IOU)
+                .max_by(|a, b| a.1.total_cmp(&b.1));
+            match candidate {
+                Some((i, _)) => {
+                    let track = &mut tracks[i];
+                    track.end = time;
+                    track.appearances += 1;
+                    track.last_box = detection.face;
+                    if detection.confidence > track.confidence {
+                        (track.best_time, track.best_box, track.confidence) = (time, detection.face, detection.confidence);
+                    }
+                    claimed.push(i);
+                }
+                None => {
+                    claimed.push(tracks.len());
+                    tracks.push(FaceTrack {
+                        id: 0,
+                        start: time,
+                        end: time,
+                        appearances: 1,
+                        best_time: time,
+                        best_box: detection.face,
+                        confidence: detection.confidence,
+                        crop: String::new(),
+                        last_box: detection.face,
+                    });
+                }
+            }
+        }
+        index = frame_end;
+    }
+
+    tracks.retain(|track| track.appearances >= min_appearances);
+    tracks.truncate(MAX_TRACKS);
+    for (id, track) in tracks.iter_mut().enumerate() {
+        track.id = id;
+        track.crop = format!("face_{:04}.jpg", id);
+    }
+    tracks
+}
+
+/// Manifest written next to the crops as `faces.json`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FaceIndex {
+    pub source: String,
+    pub interval: f64,
+    pub tracks: Vec<FaceTrack>,
+}
+
+/// Run the detector over `input`, sampling a frame every `interval` seconds
+pub async fn detect(job_id: &str, input: &Path, model: &FaceModel, interval: f64, confidence: u32) -> Result<Vec<Detection>> {
+    let mut command = sandbox::command("ffmpeg");
+    command
+        .arg("-hide_banner")
+        .arg("-nostdin")
+        .arg("-i").arg(input)
+        .arg("-map").arg("0:v:0")
+        .arg("-vf").arg(model.filter(interval, confidence))
+        .arg("-an")
+        .arg("-f").arg("null")
+        .arg("-");
+    let output = audit::output_async(Some(job_id), command).await?;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if !output.status.success() {
+        return Err(FfmpegFailure {
+            kind: FfmpegErrorKind::classify(&stderr),
+            message: format!("Face detection failed: {}", stderr.trim()),
+        }
+        .into());
+    }
+    Ok(parse_detections(&stderr))
+}
+
+/// Save the representative crop of `track` into `output_dir`
+pub async fn save_crop(job_id: &str, input: &Path, track: &FaceTrack, output_dir: &Path) -> Result<()> {
+    let mut command = sandbox::command("ffmpeg");
+    command
+        .arg("-y")
+        .arg("-hide_banner")
+        .arg("-nostdin")
+        .arg("-ss").arg(track.best_time.to_string())
+        .arg("-i").arg(input)
+        .arg("-map").arg("0:v:0")
+        .arg("-vf").arg(track.best_box.crop_filter())
+        .arg("-frames:v").arg("1")
+        .arg("-q:v").arg("2")
+        .arg(output_dir.join(&track.crop));
+    let output = audit::output_async(Some(job_id), command).await?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(FfmpegFailure {
+            kind: FfmpegErrorKind::classify(&stderr),
+            message: format!("Face crop failed: {}", stderr.trim()),
+        }
+        .into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_track_faces() {
+        let stderr = "\
+[Parsed_showinfo_2 @ 0x55d1] n:   0 pts:      0 pts_time:0       duration:      1 fmt:yuv420p
+[Parsed_showinfo_2 @ 0x55d1]   detection bounding boxes:
+[Parsed_showinfo_2 @ 0x55d1] source: face-detection-retail-0004.xml
+[Parsed_showinfo_2 @ 0x55d1] index: 0,\tregion: (100, 100) -> (200, 220), label: , confidence: 9000/10000.
+[Parsed_showinfo_2 @ 0x55d1] index: 1,\tregion: (600, 80) -> (680, 180), label: , confidence: 7000/10000.
+[Parsed_showinfo_2 @ 0x55d1] n:   1 pts:      1 pts_time:0.5     duration:      1 fmt:yuv420p
+[Parsed_showinfo_2 @ 0x55d1] index: 0,\tregion: (110, 104) -> (210, 224), label: , confidence: 9500/10000.
+[Parsed_showinfo_2 @ 0x55d1] n:   2 pts:      2 pts_time:1       duration:      1 fmt:yuv420p
+[Parsed_showinfo_2 @ 0x55d1] n:   3 pts:      3 pts_time:1.5     duration:      1 fmt:yuv420p
+[Parsed_showinfo_2 @ 0x55d1] index: 0,\tregion: (118, 110) -> (218, 230), label: , confidence: 8000/10000.
+";
+        let detections = parse_detections(stderr);
+        assert_eq!(detections.len(), 4);
+        assert_eq!(detections[1], Detection { time: 0.0, face: FaceBox { x: 600, y: 80, width: 80, height: 100 }, confidence: 0.7 });
+
+        // The face on the left is followed across the empty sample; the one-off on the right is dropped
+        let tracks = track(&detections, 0.5, 2);
+        assert_eq!(tracks.len(), 1);
+        assert_eq!((tracks[0].start, tracks[0].end, tracks[0].appearances), (0.0, 1.5, 3));
+        assert_eq!((tracks[0].best_time, tracks[0].crop.as_str()), (0.5, "face_0000.jpg"));
+        assert_eq!(tracks[0].best_box.crop_filter(), "crop='min(140,iw-90)':'min(168,ih-80)':90:80");
+    }
+}
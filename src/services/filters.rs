@@ -0,0 +1,191 @@
+use serde::{Deserialize, Serialize};
+use crate::utils::validation::Violations;
+
+/// Filters one request may chain
+pub const MAX_FILTERS: usize = 16;
+
+/// Characters FFmpeg's filtergraph parser would read as syntax inside a file path option,
+/// such as a LUT or a subtitle file
+pub const FILTER_PATH_RESERVED: &[char] = &['\'', '\\', ':', ',', ';', '[', ']'];
+
+/// One step of a `VideoTranscodeRequest::filters` chain, e.g. `{"type": "scale", "width": 1280}`.
+/// Unset parameters keep FFmpeg's defaults
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum VideoFilter {
+    /// Resize; a missing side follows the aspect ratio, rounded to an even size
+    Scale { width: Option<u32>, height: Option<u32> },
+    /// Cut out a `width`x`height` window, centered unless `x`/`y` are given
+    Crop { width: u32, height: u32, x: Option<u32>, y: Option<u32> },
+    Eq {
+        /// -1 to 1
+        brightness: Option<f64>,
+        /// -2 to 2
+        contrast: Option<f64>,
+        /// 0 to 3
+        saturation: Option<f64>,
+        /// 0.1 to 10
+        gamma: Option<f64>,
+    },
+    Hue {
+        /// Rotation in degrees, -360 to 360
+        degrees: Option<f64>,
+        /// -10 to 10
+        saturation: Option<f64>,
+    },
+    Unsharp {
+        /// Odd matrix size from 3 to 23 (default: 5)
+        size: Option<u32>,
+        /// -2 to 5; negative values blur
+        amount: Option<f64>,
+    },
+    Fps { fps: u32 },
+    /// Color grade through a `.cube`/`.3dl` lookup table
+    Lut3d { path: String },
+}
+
+impl VideoFilter {
+    /// Report invalid parameters as `filters[index].field`
+    pub fn validate(&self, index: usize, violations: &mut Violations) {
+        let field = |name: &str| format!("filters[{}].{}", index, name);
+        let within = |violations: &mut Violations, name: &str, value: Option<f64>, min: f64, max: f64| {
+            if value.is_some_and(|value| !(min..=max).contains(&value)) {
+                violations.add(&field(name), format!("must be between {} and {}", min, max));
+            }
+        };
+        match self {
+            VideoFilter::Scale { width, height } => {
+                if width.is_none() && height.is_none() {
+                    violations.add(&field("width"), "width or height is required");
+                }
+                violations.range(&field("width"), *width, 2, 16384);
+                violations.range(&field("height"), *height, 2, 16384);
+            }
+            VideoFilter::Crop { width, height, x, y } => {
+                violations.range(&field("width"), Some(*width), 1, 16384);
+                violations.range(&field("height"), Some(*height), 1, 16384);
+                violations.range(&field("x"), *x, 0, 16384);
+                violations.range(&field("y"), *y, 0, 16384);
+            }
+            VideoFilter::Eq { brightness, contrast, saturation, gamma } => {
+                within(violations, "brightness", *brightness, -1.0, 1.0);
+                within(violations, "contrast", *contrast, -2.0, 2.0);
+                within(violations, "saturation", *saturation, 0.0, 3.0);
+                within(violations, "gamma", *gamma, 0.1, 10.0);
+            }
+            VideoFilter::Hue { degrees, saturation } => {
+                within(violations, "degrees", *degrees, -360.0, 360.0);
+                within(violations, "saturation", *saturation, -10.0, 10.0);
+            }
+            VideoFilter::Unsharp { size, amount } => {
+                within(violations, "amount", *amount, -2.0, 5.0);
+                if size.is_some_and(|size| size % 2 == 0 || !(3..=23).contains(&size)) {
+                    violations.add(&field("size"), "must be an odd number from 3 to 23");
+                }
+            }
+            VideoFilter::Fps { fps } => violations.range(&field("fps"), Some(*fps), 1, 240),
+            VideoFilter::Lut3d { path } => {
+                violations.path(&field("path"), path);
+                if path.contains(FILTER_PATH_RESERVED) {
+                    violations.add(&field("path"), "must not contain any of ' \\ : , ; [ ]");
+                }
+            }
+        }
+    }
+
+    /// This step as an FFmpeg filter, e.g. `scale=w=1280:h=-2`
+    pub fn to_filter(&self) -> String {
+        fn options(name: &str, options: &[(&str, Option<String>)]) -> String {
+            let set: Vec<String> = options
+                .iter()
+                .filter_map(|(key, value)| value.as_ref().map(|value| format!("{}={}", key, value)))
+                .collect();
+            if set.is_empty() {
+                name.to_string()
+            } else {
+                format!("{}={}", name, set.join(":"))
+            }
+        }
+        let text = |value: &Option<f64>| value.map(|value| value.to_string());
+        let number = |value: &Option<u32>| value.map(|value| value.to_string());
+        match self {
+            VideoFilter::Scale { width, height } => {
+                let side = |value: &Option<u32>| value.map_or("-2".to_string(), |value| value.to_string());
+                format!("scale=w={}:h={}", side(width), side(height))
+            }
+            VideoFilter::Crop { width, height, x, y } => options(
+                "crop",
+                &[("w", Some(width.to_string())), ("h", Some(height.to_string())), ("x", number(x)), ("y", number(y))],
+            ),
+            VideoFilter::Eq { brightness, contrast, saturation, gamma } => options(
+                "eq",
+                &[
+                    ("brightness", text(brightness)),
+                    ("contrast", text(contrast)),
+                    ("saturation", text(saturation)),
+                    ("gamma", text(gamma)),
+                ],
+            ),
+            VideoFilter::Hue { degrees, saturation } => options("hue", &[("h", text(degrees)), ("s", text(saturation))]),
+            VideoFilter::Unsharp { size, amount } => {
+                options("unsharp", &[("lx", number(size)), ("ly", number(size)), ("la", text(amount))])
+            }
+            VideoFilter::Fps { fps } => format!("fps={}", fps),
+            VideoFilter::Lut3d { path } => format!("lut3d=file={}", path),
+        }
+    }
+}
+
+/// `-filter_complex` graph running the chain over the first video stream, ending in `[v]`
+pub fn filter_graph(filters: &[VideoFilter]) -> String {
+    format!("[0:v:0]{}[v]", chain(filters))
+}
+
+/// The filters joined into one `-vf` style chain
+pub fn chain(filters: &[VideoFilter]) -> String {
+    filters.iter().map(VideoFilter::to_filter).collect::<Vec<_>>().join(",")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filter_chain_compiles_and_validates() {
+        let filters: Vec<VideoFilter> = serde_json::from_str(
+            r#"[
+                {"type": "crop", "width": 1920, "height": 800},
+                {"type": "scale", "width": 1280},
+                {"type": "eq", "contrast": 1.1, "saturation": 1.25},
+                {"type": "unsharp", "size": 5, "amount": 0.8},
+                {"type": "fps", "fps": 30},
+                {"type": "lut3d", "path": "/luts/film.cube"}
+            ]"#,
+        )
+        .unwrap();
+        assert_eq!(
+            filter_graph(&filters),
+            "[0:v:0]crop=w=1920:h=800,scale=w=1280:h=-2,eq=contrast=1.1:saturation=1.25,\
+             unsharp=lx=5:ly=5:la=0.8,fps=30,lut3d=file=/luts/film.cube[v]"
+        );
+        assert_eq!(VideoFilter::Hue { degrees: None, saturation: None }.to_filter(), "hue");
+
+        let mut violations = Violations::new();
+        for (index, filter) in filters.iter().enumerate() {
+            filter.validate(index, &mut violations);
+        }
+        assert!(violations.into_result().is_ok());
+
+        let invalid = [
+            VideoFilter::Scale { width: None, height: None },
+            VideoFilter::Eq { brightness: Some(2.0), contrast: None, saturation: None, gamma: None },
+            VideoFilter::Unsharp { size: Some(4), amount: None },
+            VideoFilter::Lut3d { path: "/luts/a.cube,drawtext=text=x".to_string() },
+        ];
+        for (index, filter) in invalid.iter().enumerate() {
+            let mut violations = Violations::new();
+            filter.validate(index, &mut violations);
+            assert!(violations.into_result().is_err(), "{:?} should be rejected", filter);
+        }
+    }
+}
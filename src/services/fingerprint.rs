@@ -0,0 +1,184 @@
+use anyhow::Result;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::path::Path;
+use crate::utils::error::{FfmpegErrorKind, FfmpegFailure};
+use crate::utils::{audit, sandbox};
+
+/// Seconds between sampled frames when the request doesn't set it
+pub const DEFAULT_INTERVAL: f64 = 1.0;
+
+/// Similarity at which two clips count as the same when the request doesn't set it
+pub const DEFAULT_THRESHOLD: f64 = 0.9;
+
+/// Frames one fingerprint may hold; a four-hour video at the default interval fits
+pub const MAX_HASHES: usize = 20_000;
+
+/// Side of the grayscale thumbnail each frame is hashed from
+pub const THUMB_SIZE: usize = 32;
+
+/// Side of the low-frequency DCT corner kept, one bit per coefficient
+const HASH_SIZE: usize = 8;
+
+/// 64-bit perceptual hash of one frame, serialized as 16 hex digits since JSON numbers
+/// can't hold it exactly
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameHash(pub u64);
+
+impl FrameHash {
+    pub fn distance(self, other: FrameHash) -> u32 {
+        (self.0 ^ other.0).count_ones()
+    }
+}
+
+impl Serialize for FrameHash {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&format!("{:016x}", self.0))
+    }
+}
+
+impl<'de> Deserialize<'de> for FrameHash {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let hex = String::deserialize(deserializer)?;
+        u64::from_str_radix(&hex, 16).map(FrameHash).map_err(serde::de::Error::custom)
+    }
+}
+
+/// Temporal pHash: one frame hash every `interval` seconds, from the start of the video
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Fingerprint {
+    pub interval: f64,
+    pub hashes: Vec<FrameHash>,
+}
+
+/// Best alignment found between two fingerprints
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Comparison {
+    /// 1 for identical frames, around 0.5 for unrelated ones
+    pub similarity: f64,
+    /// Whether `similarity` reached the threshold
+    pub duplicate: bool,
+    /// Seconds into `a` at which `b` starts; negative when `b` starts before `a`
+    pub offset_secs: f64,
+    /// Seconds both clips cover at that alignment
+    pub overlap_secs: f64,
+}
+
+/// pHash of a `THUMB_SIZE`² grayscale thumbnail: the sign of each low-frequency DCT
+/// coefficient against their median
+pub fn hash_thumbnail(pixels: &[u8]) -> FrameHash {
+    debug_assert_eq!(pixels.len(), THUMB_SIZE * THUMB_SIZE);
+    let n = THUMB_SIZE as f64;
+    let cosines: Vec<[f64; THUMB_SIZE]> = (0..HASH_SIZE)
+        .map(|u| std::array::from_fn(|x| ((2 * x + 1) as f64 * u as f64 * std::f64::consts::PI / (2.0 * n)).cos()))
+        .collect();
+
+    // Rows first, then columns, keeping only the coefficients the hash uses
+    let rows: Vec<[f64; HASH_SIZE]> = pixels
+        .chunks(THUMB_SIZE)
+        .map(|row| std::array::from_fn(|u| row.iter().zip(&cosines[u]).map(|(&p, c)| f64::from(p) * c).sum()))
+        .collect();
+    let coefficients: Vec<f64> = (0..HASH_SIZE)
+        .flat_map(|v| {
+            let (rows, cosines) = (&rows, &cosines);
+            (0..HASH_SIZE).map(move |u| rows.iter().zip(&cosines[v]).map(|(row, c)| row[u] * c).sum())
+        })
+        .collect();
+
+    let mut sorted = coefficients.clone();
+    sorted.sort_by(f64::total_cmp);
+    let median = (sorted[sorted.len() / 2 - 1] + sorted[sorted.len() / 2]) / 2.0;
+    let bits = coefficients.iter().fold(0u64, |bits, &c| (bits << 1) | u64::from(c > median));
+    FrameHash(bits)
+}
+
+/// Align `b` against `a` at every offset that overlaps at least half of the shorter clip and
+/// keep the one with the lowest mean Hamming distance. A trimmed copy matches at its offset
+/// into the original
+pub fn compare(a: &Fingerprint, b: &Fingerprint, threshold: f64) -> Comparison {
+    let (n, m) = (a.hashes.len() as isize, b.hashes.len() as isize);
+    let min_overlap = (n.min(m) + 1) / 2;
+    let mut best: Option<(f64, isize, isize)> = None;
+    for offset in (1 - m)..n {
+        let (start, end) = (offset.max(0), (offset + m).min(n));
+        let overlap = end - start;
+        if overlap < min_overlap.max(1) {
+            continue;
+        }
+        let distance: u32 = (start..end)
+            .map(|i| a.hashes[i as usize].distance(b.hashes[(i - offset) as usize]))
+            .sum();
+        let mean = f64::from(distance) / overlap as f64;
+        if best.is_none_or(|(best_mean, _, _)| mean < best_mean) {
+            best = Some((mean, offset, overlap));
+        }
+    }
+
+    let (mean, offset, overlap) = best.unwrap_or((64.0, 0, 0));
+    let similarity = 1.0 - mean / 64.0;
+    Comparison {
+        similarity,
+        duplicate: overlap > 0 && similarity >= threshold,
+        offset_secs: offset as f64 * a.interval,
+        overlap_secs: overlap as f64 * a.interval,
+    }
+}
+
+/// Sample `input` every `interval` seconds and hash each sample
+pub async fn fingerprint(job_id: &str, input: &Path, interval: f64) -> Result<Fingerprint> {
+    let mut command = sandbox::command("ffmpeg");
+    command
+        .arg("-hide_banner")
+        .arg("-nostdin")
+        .arg("-i").arg(input)
+        .arg("-map").arg("0:v:0")
+        // Area averaging before hashing, so noise and recompression don't flip bits
+        .arg("-vf").arg(format!("fps={},scale={1}:{1}:flags=area,format=gray", 1.0 / interval, THUMB_SIZE))
+        .arg("-frames:v").arg(MAX_HASHES.to_string())
+        .arg("-an")
+        .arg("-f").arg("rawvideo")
+        .arg("-");
+    let output = audit::output_async(Some(job_id), command).await?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(FfmpegFailure {
+            kind: FfmpegErrorKind::classify(&stderr),
+            message: format!("Fingerprinting failed: {}", stderr.trim()),
+        }
+        .into());
+    }
+    let hashes = output.stdout.chunks_exact(THUMB_SIZE * THUMB_SIZE).map(hash_thumbnail).collect();
+    Ok(Fingerprint { interval, hashes })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn thumbnail(seed: u32) -> Vec<u8> {
+        (0..THUMB_SIZE * THUMB_SIZE)
+            .map(|i| {
+                let (x, y) = ((i % THUMB_SIZE) as u32, (i / THUMB_SIZE) as u32);
+                ((x * seed + y * (seed ^ 7)).wrapping_mul(2654435761) >> 24) as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_hash_is_robust_and_finds_trimmed_copy() {
+        let frame = thumbnail(3);
+        let brighter: Vec<u8> = frame.iter().map(|&p| p.saturating_add(12)).collect();
+        assert!(hash_thumbnail(&frame).distance(hash_thumbnail(&brighter)) <= 2);
+
+        let original = Fingerprint { interval: 1.0, hashes: (1..=20).map(|seed| hash_thumbnail(&thumbnail(seed))).collect() };
+        let trimmed = Fingerprint { interval: 1.0, hashes: original.hashes[5..15].to_vec() };
+        let comparison = compare(&original, &trimmed, DEFAULT_THRESHOLD);
+        assert_eq!(comparison, Comparison { similarity: 1.0, duplicate: true, offset_secs: 5.0, overlap_secs: 10.0 });
+
+        let other = Fingerprint { interval: 1.0, hashes: (40..50).map(|seed| hash_thumbnail(&thumbnail(seed))).collect() };
+        assert!(!compare(&original, &other, DEFAULT_THRESHOLD).duplicate);
+
+        let json = serde_json::to_string(&FrameHash(0xff)).unwrap();
+        assert_eq!(json, "\"00000000000000ff\"");
+        assert_eq!(serde_json::from_str::<FrameHash>(&json).unwrap(), FrameHash(0xff));
+    }
+}
@@ -0,0 +1,179 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::process::Command;
+use crate::utils::error::{FfmpegErrorKind, FfmpegFailure, ServiceError};
+use crate::utils::validation::{Validate, Violations};
+use crate::utils::{audit, sandbox};
+
+/// Picture of a synthetic test image
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Pattern {
+    /// One flat `color`
+    #[default]
+    Solid,
+    /// Red across, green down, so orientation and crops can be checked
+    Gradient,
+    /// Uniform noise over `color`; defeats compression, for worst-case sizes
+    Noise,
+}
+
+/// A still generated by FFmpeg's lavfi sources, in the format of `output_path`'s extension
+#[derive(Debug, Clone, Deserialize)]
+pub struct ImageFixture {
+    pub output_path: String,
+    pub pattern: Option<Pattern>,
+    /// 1-16384 (default: 640)
+    pub width: Option<u32>,
+    /// 1-16384 (default: 480)
+    pub height: Option<u32>,
+    /// FFmpeg color name or `#RRGGBB` (default: gray)
+    pub color: Option<String>,
+}
+
+/// SMPTE color bars over a sine test tone, encoded with the defaults of `output_path`'s container
+#[derive(Debug, Clone, Deserialize)]
+pub struct VideoFixture {
+    pub output_path: String,
+    /// Seconds, up to 600 (default: 5)
+    pub duration: Option<f64>,
+    /// 16-7680 (default: 1280)
+    pub width: Option<u32>,
+    /// 16-4320 (default: 720)
+    pub height: Option<u32>,
+    /// 1-120 (default: 30)
+    pub fps: Option<u32>,
+    /// Tone in Hz, 20-20000 (default: 1000); 0 leaves out the audio track
+    pub frequency: Option<u32>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FixtureResponse {
+    pub output_path: String,
+    pub size_bytes: u64,
+}
+
+impl Validate for ImageFixture {
+    fn validate(&self) -> Result<(), ServiceError> {
+        let mut violations = Violations::new();
+        violations.path("output_path", &self.output_path);
+        violations.range("width", self.width, 1, 16384);
+        violations.range("height", self.height, 1, 16384);
+        // Goes into the filtergraph, so nothing that could end the option or the filter
+        let color_ok = |color: &String| {
+            !color.is_empty() && color.len() <= 32 && color.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '#' | '@' | '.'))
+        };
+        if self.color.as_ref().is_some_and(|color| !color_ok(color)) {
+            violations.add("color", "must be a color name or #RRGGBB");
+        }
+        violations.into_result()
+    }
+}
+
+impl ImageFixture {
+    /// lavfi source drawing the picture
+    pub fn source(&self) -> String {
+        let size = format!("{}x{}", self.width.unwrap_or(640), self.height.unwrap_or(480));
+        let color = self.color.as_deref().unwrap_or("gray");
+        match self.pattern.unwrap_or_default() {
+            Pattern::Solid => format!("color=c={}:s={}", color, size),
+            Pattern::Gradient => format!("color=c=black:s={},format=rgb24,geq=r='255*X/W':g='255*Y/H':b='128'", size),
+            Pattern::Noise => format!("color=c={}:s={},noise=alls=100:allf=u", color, size),
+        }
+    }
+
+    pub fn command(&self) -> Command {
+        let mut command = sandbox::command("ffmpeg");
+        command
+            .arg("-y").arg("-v").arg("error").arg("-nostdin")
+            .arg("-f").arg("lavfi")
+            .arg("-i").arg(self.source())
+            .arg("-frames:v").arg("1")
+            .arg(&self.output_path);
+        command
+    }
+}
+
+impl Validate for VideoFixture {
+    fn validate(&self) -> Result<(), ServiceError> {
+        let mut violations = Violations::new();
+        violations.path("output_path", &self.output_path);
+        if self.duration.is_some_and(|duration| duration.is_nan() || duration <= 0.0 || duration > 600.0) {
+            violations.add("duration", "must be between 0 and 600");
+        }
+        violations.range("width", self.width, 16, 7680);
+        violations.range("height", self.height, 16, 4320);
+        violations.range("fps", self.fps, 1, 120);
+        if self.frequency.is_some_and(|frequency| frequency != 0 && !(20..=20_000).contains(&frequency)) {
+            violations.add("frequency", "must be 0 or between 20 and 20000");
+        }
+        violations.into_result()
+    }
+}
+
+impl VideoFixture {
+    pub fn command(&self) -> Command {
+        let duration = self.duration.unwrap_or(5.0);
+        let mut command = sandbox::command("ffmpeg");
+        command
+            .arg("-y").arg("-v").arg("error").arg("-nostdin")
+            .arg("-f").arg("lavfi")
+            .arg("-i").arg(format!(
+                "smptehdbars=s={}x{}:r={}:d={}",
+                self.width.unwrap_or(1280),
+                self.height.unwrap_or(720),
+                self.fps.unwrap_or(30),
+                duration
+            ));
+        match self.frequency.unwrap_or(1000) {
+            0 => {}
+            frequency => {
+                command
+                    .arg("-f").arg("lavfi")
+                    .arg("-i").arg(format!("sine=f={}:r=48000:d={}", frequency, duration));
+            }
+        }
+        // Players and most encoders expect 4:2:0; the bars come out as 4:4:4
+        command.arg("-pix_fmt").arg("yuv420p").arg(&self.output_path);
+        command
+    }
+}
+
+/// Run a fixture's command and report what it wrote
+pub async fn generate(output_path: &str, command: Command) -> Result<FixtureResponse> {
+    let output = audit::output_async(None, command).await?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(FfmpegFailure {
+            kind: FfmpegErrorKind::classify(&stderr),
+            message: format!("Fixture generation failed: {}", stderr.trim()),
+        }
+        .into());
+    }
+    Ok(FixtureResponse { output_path: output_path.to_string(), size_bytes: std::fs::metadata(output_path)?.len() })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fixture_sources() {
+        let image = |pattern, color: Option<&str>| ImageFixture {
+            output_path: "out.png".to_string(),
+            pattern: Some(pattern),
+            width: Some(64),
+            height: None,
+            color: color.map(str::to_string),
+        };
+        assert_eq!(image(Pattern::Solid, Some("#ff8800")).source(), "color=c=#ff8800:s=64x480");
+        assert!(image(Pattern::Gradient, None).source().contains("geq=r='255*X/W'"));
+        assert_eq!(image(Pattern::Noise, None).source(), "color=c=gray:s=64x480,noise=alls=100:allf=u");
+        assert!(image(Pattern::Solid, Some("red,drawtext")).validate().is_err());
+
+        let video = VideoFixture { output_path: "bars.mp4".to_string(), duration: Some(2.0), width: None, height: None, fps: None, frequency: Some(0) };
+        let args: Vec<_> = video.command().get_args().map(|arg| arg.to_string_lossy().into_owned()).collect();
+        assert!(args.contains(&"smptehdbars=s=1280x720:r=30:d=2".to_string()));
+        assert!(!args.iter().any(|arg| arg.starts_with("sine=")));
+    }
+}
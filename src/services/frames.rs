@@ -0,0 +1,132 @@
+use anyhow::Result;
+use serde::Serialize;
+use std::path::Path;
+use crate::utils::error::{FfmpegErrorKind, FfmpegFailure};
+use crate::utils::{audit, sandbox};
+
+/// Frames one export writes when the request doesn't cap it
+pub const DEFAULT_MAX_FRAMES: u32 = 10_000;
+
+/// Image formats frames can be exported as
+pub static FRAME_FORMATS: &[&str] = &["png", "jpg"];
+
+/// Manifest written next to the exported frames as `index.json`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FrameIndex {
+    pub source: String,
+    pub format: String,
+    pub frames: Vec<ExportedFrame>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ExportedFrame {
+    /// File name within the output directory
+    pub file: String,
+    /// Position in the export, from 0
+    pub index: usize,
+    /// Seconds from the start of the source
+    pub time: f64,
+}
+
+/// File name of the `index`th exported frame; `image2` pattern is `frame_%06d.<format>`
+pub fn file_name(index: usize, format: &str) -> String {
+    format!("frame_{:06}.{}", index, format)
+}
+
+/// `-vf` chain keeping every `every`th frame and logging each kept one through `showinfo`
+pub fn filter(every: u32) -> String {
+    if every > 1 {
+        format!("select='not(mod(n,{}))',showinfo", every)
+    } else {
+        "showinfo".to_string()
+    }
+}
+
+/// Presentation times `showinfo` logged, one per written frame, shifted by the `-ss` offset the
+/// input was opened at (input seeking restarts timestamps at 0)
+pub fn parse_showinfo(stderr: &str, offset: f64) -> Vec<f64> {
+    stderr
+        .lines()
+        .filter(|line| line.contains("Parsed_showinfo"))
+        .filter_map(|line| line.split_whitespace().find_map(|token| token.strip_prefix("pts_time:")))
+        .filter_map(|time| time.parse::<f64>().ok())
+        .map(|time| offset + time)
+        .collect()
+}
+
+impl FrameIndex {
+    pub fn new(source: &str, format: &str, times: &[f64]) -> Self {
+        let frames = times
+            .iter()
+            .enumerate()
+            .map(|(index, &time)| ExportedFrame { file: file_name(index, format), index, time })
+            .collect();
+        Self { source: source.to_string(), format: format.to_string(), frames }
+    }
+}
+
+/// What part of the source to export, and how sparsely
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Selection {
+    pub start: f64,
+    pub end: Option<f64>,
+    pub every: u32,
+    pub max_frames: u32,
+}
+
+/// Write the selected frames of `input` into `output_dir` and return their source times
+pub async fn export(job_id: &str, input: &Path, output_dir: &Path, format: &str, selection: Selection) -> Result<Vec<f64>> {
+    let mut command = sandbox::command("ffmpeg");
+    command.arg("-y").arg("-hide_banner").arg("-nostdin");
+    if selection.start > 0.0 {
+        command.arg("-ss").arg(selection.start.to_string());
+    }
+    command.arg("-i").arg(input);
+    if let Some(end) = selection.end {
+        command.arg("-t").arg((end - selection.start).to_string());
+    }
+    command
+        .arg("-map").arg("0:v:0")
+        .arg("-vf").arg(filter(selection.every))
+        // One file per selected frame, without duplicating or dropping to a constant rate
+        .arg("-fps_mode").arg("passthrough")
+        .arg("-frames:v").arg(selection.max_frames.to_string())
+        .arg("-start_number").arg("0");
+    if format == "jpg" {
+        command.arg("-q:v").arg("2");
+    }
+    command.arg(output_dir.join(format!("frame_%06d.{}", format)));
+
+    let output = audit::output_async(Some(job_id), command).await?;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if !output.status.success() {
+        return Err(FfmpegFailure {
+            kind: FfmpegErrorKind::classify(&stderr),
+            message: format!("Frame export failed: {}", stderr.trim()),
+        }
+        .into());
+    }
+    Ok(parse_showinfo(&stderr, selection.start))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_index_from_showinfo() {
+        let stderr = "\
+[Parsed_showinfo_1 @ 0x5581] config in time_base: 1/25, frame_rate: 25/1
+[Parsed_showinfo_1 @ 0x5581] n:   0 pts:      0 pts_time:0       duration:      1 fmt:yuv420p
+[Parsed_showinfo_1 @ 0x5581] n:   1 pts:     10 pts_time:0.4     duration:      1 fmt:yuv420p
+frame=    2 fps=0.0 q=-0.0 Lsize=N/A time=00:00:00.44 bitrate=N/A speed=4.1x
+";
+        let times = parse_showinfo(stderr, 60.0);
+        assert_eq!(times, vec![60.0, 60.4]);
+
+        let index = FrameIndex::new("clip.mp4", "png", &times);
+        assert_eq!(index.frames[1], ExportedFrame { file: "frame_000001.png".to_string(), index: 1, time: 60.4 });
+        assert_eq!(filter(10), "select='not(mod(n,10))',showinfo");
+        assert_eq!(filter(1), "showinfo");
+    }
+}
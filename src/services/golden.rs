@@ -0,0 +1,200 @@
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::process::{Command, Stdio};
+use crate::services::animation::{self, AnimationFormat};
+use crate::services::autotrim::CropRect;
+use crate::services::blur::{self, Coverage};
+use crate::services::filters::{self, VideoFilter};
+use crate::services::fingerprint::{self, FrameHash, THUMB_SIZE};
+use crate::services::sticker::{self, StickerFormat};
+use crate::services::watermark::{self, Rgb};
+
+/// Perceptual hash of every case's render of the reference input. Run with `GOLDEN_UPDATE=1`
+/// after a deliberate change in appearance; cases without an entry are recorded on their first run
+const HASHES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/testdata/golden-hashes.txt");
+
+/// Bits of the 64-bit hash a render may differ by; encoder and scaler versions shift a few
+const MAX_DISTANCE: u32 = 4;
+
+/// How a case turns the reference input into its output
+enum Render {
+    /// Pixel code of this crate
+    Native(fn(&Rgb) -> Rgb),
+    /// A `-filter_complex` graph from one of the filter builders, and the label it ends in
+    Ffmpeg(String, Option<&'static str>),
+}
+
+fn cases() -> Vec<(&'static str, Render)> {
+    let chain = [
+        VideoFilter::Eq { brightness: None, contrast: Some(1.2), saturation: Some(1.4), gamma: None },
+        VideoFilter::Hue { degrees: Some(90.0), saturation: None },
+        VideoFilter::Unsharp { size: None, amount: Some(1.5) },
+        VideoFilter::Scale { width: Some(128), height: None },
+    ];
+    let crop = CropRect { width: 192, height: 128, x: 32, y: 32 };
+    vec![
+        ("watermark.embed", Render::Native(|input| {
+            let mut output = input.clone();
+            watermark::embed(&mut output, 0xC0FFEE, f64::from(watermark::DEFAULT_STRENGTH));
+            output
+        })),
+        ("blur.full", Render::Ffmpeg(blur::filter_graph(blur::DEFAULT_SIGMA, None, Coverage::Full), Some("[out]"))),
+        ("blur.red", Render::Ffmpeg(blur::filter_graph(blur::DEFAULT_SIGMA, Some(4), Coverage::Full), Some("[out]"))),
+        ("filters.chain", Render::Ffmpeg(filters::filter_graph(&chain), Some("[v]"))),
+        ("autotrim.crop", Render::Ffmpeg(crop.filter(), None)),
+        ("sticker.key", Render::Ffmpeg(sticker::filter(None, Some(("#00ff00", 10)), 128, StickerFormat::Apng), None)),
+        ("animation.gif", Render::Ffmpeg(animation::filter(10, 128, AnimationFormat::Gif), None)),
+    ]
+}
+
+/// 256x192 with a gradient, a checkerboard, a pure green key area and a bright disc, so color,
+/// geometry and detail changes all move the hash
+fn reference_input() -> Rgb {
+    let (width, height) = (256, 192);
+    let mut pixels = Vec::with_capacity(width * height * 3);
+    for y in 0..height {
+        for x in 0..width {
+            let (dx, dy) = (x as i64 - 176, y as i64 - 112);
+            let pixel = if x < 64 && y < 48 {
+                [0, 255, 0]
+            } else if dx * dx + dy * dy < 40 * 40 {
+                [250, 220, 40]
+            } else {
+                let checker = if (x / 16 + y / 16) % 2 == 0 { 60 } else { 0 };
+                [(x * 255 / width) as u8, (y * 255 / height) as u8, 120 + checker]
+            };
+            pixels.extend_from_slice(&pixel);
+        }
+    }
+    Rgb { width, height, pixels }
+}
+
+/// pHash of `image` reduced to a `THUMB_SIZE`² luma thumbnail by area averaging
+fn perceptual_hash(image: &Rgb) -> FrameHash {
+    let mut thumbnail = vec![0u8; THUMB_SIZE * THUMB_SIZE];
+    for (index, value) in thumbnail.iter_mut().enumerate() {
+        let (tx, ty) = (index % THUMB_SIZE, index / THUMB_SIZE);
+        let (x0, x1) = (tx * image.width / THUMB_SIZE, ((tx + 1) * image.width / THUMB_SIZE).max(tx * image.width / THUMB_SIZE + 1));
+        let (y0, y1) = (ty * image.height / THUMB_SIZE, ((ty + 1) * image.height / THUMB_SIZE).max(ty * image.height / THUMB_SIZE + 1));
+        let mut sum = 0.0;
+        for y in y0..y1 {
+            for x in x0..x1 {
+                let offset = (y * image.width + x) * 3;
+                let [r, g, b] = [0, 1, 2].map(|channel| f64::from(image.pixels[offset + channel]));
+                sum += 0.299 * r + 0.587 * g + 0.114 * b;
+            }
+        }
+        *value = (sum / ((x1 - x0) * (y1 - y0)) as f64).round() as u8;
+    }
+    fingerprint::hash_thumbnail(&thumbnail)
+}
+
+fn has_ffmpeg() -> bool {
+    Command::new("ffmpeg").arg("-version").output().is_ok_and(|output| output.status.success())
+}
+
+/// First frame FFmpeg makes of `input` through `graph`, read back as PPM
+fn render_ffmpeg(input: &Rgb, graph: &str, label: Option<&str>) -> Rgb {
+    let mut command = Command::new("ffmpeg");
+    command
+        .arg("-v").arg("error").arg("-nostdin")
+        .arg("-f").arg("rawvideo").arg("-pix_fmt").arg("rgb24")
+        .arg("-s").arg(format!("{}x{}", input.width, input.height))
+        .arg("-i").arg("pipe:0")
+        .arg("-filter_complex").arg(graph);
+    if let Some(label) = label {
+        command.arg("-map").arg(label);
+    }
+    command
+        .arg("-frames:v").arg("1")
+        .arg("-f").arg("image2pipe").arg("-c:v").arg("ppm").arg("-pix_fmt").arg("rgb24")
+        .arg("pipe:1");
+    let mut child = command.stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::piped()).spawn().unwrap();
+    let mut stdin = child.stdin.take().unwrap();
+    let frame = input.pixels.clone();
+    let writer = std::thread::spawn(move || stdin.write_all(&frame));
+    let output = child.wait_with_output().unwrap();
+    writer.join().unwrap().unwrap();
+    assert!(output.status.success(), "FFmpeg failed on {}: {}", graph, String::from_utf8_lossy(&output.stderr));
+    Rgb::from_ppm(&output.stdout).unwrap()
+}
+
+fn load() -> BTreeMap<String, FrameHash> {
+    std::fs::read_to_string(HASHES)
+        .unwrap_or_default()
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(|line| {
+            let (name, hex) = line.split_once(' ')?;
+            Some((name.to_string(), FrameHash(u64::from_str_radix(hex.trim(), 16).ok()?)))
+        })
+        .collect()
+}
+
+fn save(hashes: &BTreeMap<String, FrameHash>) {
+    let mut text = String::from("# Perceptual hashes of each golden-image case; see src/services/golden.rs\n");
+    for (name, hash) in hashes {
+        text.push_str(&format!("{} {:016x}\n", name, hash.0));
+    }
+    let path = std::path::Path::new(HASHES);
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(path, text).unwrap();
+}
+
+/// Refactoring pixel code or filter builders must not change what effects look like. Cases
+/// rendered by FFmpeg are skipped where it isn't installed
+#[test]
+fn test_effects_match_golden_hashes() {
+    let update = std::env::var("GOLDEN_UPDATE").is_ok_and(|value| value == "1");
+    let ffmpeg = has_ffmpeg();
+    let input = reference_input();
+    let mut hashes = load();
+    let mut recorded = Vec::new();
+    let mut changed = Vec::new();
+
+    for (name, render) in cases() {
+        let output = match render {
+            Render::Native(render) => render(&input),
+            Render::Ffmpeg(..) if !ffmpeg => {
+                eprintln!("golden: skipping {}, ffmpeg is not installed", name);
+                continue;
+            }
+            Render::Ffmpeg(graph, label) => render_ffmpeg(&input, &graph, label),
+        };
+        let hash = perceptual_hash(&output);
+        match hashes.get(name) {
+            Some(golden) if !update => {
+                let distance = golden.distance(hash);
+                if distance > MAX_DISTANCE {
+                    changed.push(format!("{}: {:016x} is {} bits from {:016x}", name, hash.0, distance, golden.0));
+                }
+            }
+            _ => {
+                hashes.insert(name.to_string(), hash);
+                recorded.push(name);
+            }
+        }
+    }
+
+    if !recorded.is_empty() {
+        eprintln!("golden: recorded {} in {}", recorded.join(", "), HASHES);
+        save(&hashes);
+    }
+    assert!(changed.is_empty(), "rendering changed; rerun with GOLDEN_UPDATE=1 if intended:\n{}", changed.join("\n"));
+}
+
+#[test]
+fn test_hash_catches_visible_changes() {
+    // The tolerance must absorb an imperceptible mark but not a visible edit
+    let input = reference_input();
+    let mut marked = input.clone();
+    watermark::embed(&mut marked, 1, f64::from(watermark::DEFAULT_STRENGTH));
+    assert!(perceptual_hash(&input).distance(perceptual_hash(&marked)) <= MAX_DISTANCE);
+
+    let mut flipped = input.clone();
+    for row in flipped.pixels.chunks_mut(input.width * 3) {
+        let pixels: Vec<[u8; 3]> = row.chunks(3).rev().map(|pixel| [pixel[0], pixel[1], pixel[2]]).collect();
+        row.copy_from_slice(&pixels.concat());
+    }
+    assert!(perceptual_hash(&input).distance(perceptual_hash(&flipped)) > MAX_DISTANCE);
+}
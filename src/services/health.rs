@@ -0,0 +1,140 @@
+use serde::Serialize;
+use std::process::{Command, Stdio};
+use crate::services::temp_files::TempFileManager;
+use crate::utils::fs;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CheckStatus {
+    Ok,
+    Skipped,
+    Failed,
+}
+
+/// Outcome of one readiness dependency, with the HTTP code an orchestrator would act on
+#[derive(Debug, Serialize)]
+pub struct HealthCheck {
+    pub name: &'static str,
+    pub status: CheckStatus,
+    pub status_code: u16,
+    pub detail: Option<String>,
+}
+
+impl HealthCheck {
+    fn new(name: &'static str, status: CheckStatus, detail: Option<String>) -> Self {
+        let status_code = if status == CheckStatus::Failed { 503 } else { 200 };
+        Self { name, status, status_code, detail }
+    }
+
+    fn from_result(name: &'static str, result: Result<String, String>) -> Self {
+        match result {
+            Ok(detail) => Self::new(name, CheckStatus::Ok, Some(detail)),
+            Err(detail) => Self::new(name, CheckStatus::Failed, Some(detail)),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReadinessReport {
+    pub ready: bool,
+    pub checks: Vec<HealthCheck>,
+}
+
+impl ReadinessReport {
+    fn from_checks(checks: Vec<HealthCheck>) -> Self {
+        let ready = checks.iter().all(|check| check.status != CheckStatus::Failed);
+        Self { ready, checks }
+    }
+}
+
+/// Run every readiness check; blocking, so callers should keep it off the async workers.
+/// `queue` is the job queue's `(depth, capacity)`
+pub fn readiness(queue: (usize, usize)) -> ReadinessReport {
+    ReadinessReport::from_checks(vec![
+        HealthCheck::from_result("ffmpeg", check_binary("ffmpeg")),
+        HealthCheck::from_result("ffprobe", check_binary("ffprobe")),
+        check_models(),
+        HealthCheck::from_result("workspace", check_workspace()),
+        HealthCheck::from_result("disk", check_disk(&TempFileManager::from_env())),
+        HealthCheck::from_result("queue", check_queue(queue)),
+    ])
+}
+
+/// A full queue rejects every new job, so stop routing traffic here until it drains
+fn check_queue((depth, capacity): (usize, usize)) -> Result<String, String> {
+    let detail = format!("{}/{} jobs waiting", depth, capacity);
+    if depth >= capacity {
+        Err(format!("queue full, {}", detail))
+    } else {
+        Ok(detail)
+    }
+}
+
+/// Once the job volume is down to its reserve, every transcode would be refused
+fn check_disk(temp: &TempFileManager) -> Result<String, String> {
+    let usage = temp.usage();
+    let Some(volume) = usage.volume else {
+        return Err(format!("free space of {} could not be queried", usage.root.display()));
+    };
+    let detail = format!(
+        "{} of {} bytes free, job files hold {} bytes",
+        volume.available_bytes, volume.total_bytes, usage.root_bytes
+    );
+    if volume.available_bytes < usage.reserve_bytes {
+        Err(format!("below the {} byte reserve, {}", usage.reserve_bytes, detail))
+    } else {
+        Ok(detail)
+    }
+}
+
+fn check_binary(binary: &str) -> Result<String, String> {
+    let output = Command::new(binary)
+        .arg("-version")
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| format!("{} not runnable: {}", binary, e))?;
+    if !output.status.success() {
+        return Err(format!("{} -version exited with {}", binary, output.status));
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    Ok(stdout.lines().next().unwrap_or(binary).to_string())
+}
+
+/// `MODEL_DIR` is optional; when set it must be a readable directory
+fn check_models() -> HealthCheck {
+    let Ok(model_dir) = std::env::var("MODEL_DIR") else {
+        return HealthCheck::new("models", CheckStatus::Skipped, Some("MODEL_DIR not set".to_string()));
+    };
+    let result = std::fs::read_dir(&model_dir)
+        .map(|entries| format!("{} ({} entries)", model_dir, entries.count()))
+        .map_err(|e| format!("{}: {}", model_dir, e));
+    HealthCheck::from_result("models", result)
+}
+
+fn check_workspace() -> Result<String, String> {
+    let workspace = fs::workspace_dir();
+    tempfile::tempfile_in(&workspace)
+        .map(|_| workspace.display().to_string())
+        .map_err(|e| format!("{} is not writable: {}", workspace.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_skipped_checks_do_not_block_readiness() {
+        let report = ReadinessReport::from_checks(vec![
+            HealthCheck::new("ffmpeg", CheckStatus::Ok, None),
+            HealthCheck::new("queue", CheckStatus::Skipped, None),
+        ]);
+        assert!(report.ready);
+
+        let report = ReadinessReport::from_checks(vec![
+            HealthCheck::new("ffmpeg", CheckStatus::Ok, None),
+            HealthCheck::new("workspace", CheckStatus::Failed, None),
+        ]);
+        assert!(!report.ready);
+        assert_eq!(report.checks[1].status_code, 503);
+    }
+}
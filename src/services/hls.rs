@@ -0,0 +1,568 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+use std::path::Path;
+use crate::utils::validation::Violations;
+
+/// Target segment length when the request doesn't set one
+pub const DEFAULT_SEGMENT_SECS: u32 = 4;
+
+/// Bit rate of the one audio group when `audio_bitrates` isn't set
+pub const DEFAULT_AUDIO_BITRATE: &str = "128k";
+
+/// Audio groups one request may ask for
+pub const MAX_AUDIO_BITRATES: usize = 4;
+
+/// Audio streams of the input packaged as renditions; later ones are left out
+pub const MAX_AUDIO_TRACKS: usize = 16;
+
+/// Container each media segment is written in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SegmentType {
+    /// `.ts` segments, playable everywhere
+    #[default]
+    Mpegts,
+    /// Fragmented MP4 (`.m4s` with an init segment), needed for HEVC on Apple devices
+    Fmp4,
+}
+
+/// `EXT-X-PLAYLIST-TYPE` of the media playlists
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PlaylistType {
+    #[default]
+    Vod,
+    /// Segments are only ever appended
+    Event,
+    /// No playlist type, so players treat it as a sliding window until `EXT-X-ENDLIST`
+    Live,
+}
+
+/// How `VideoTranscodeRequest::hls` renditions are segmented; unset fields keep 4-second
+/// MPEG-TS segments in VOD playlists
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct HlsOptions {
+    /// Target segment length in seconds. Streams are copied, so segments are cut at the
+    /// first keyframe after it
+    pub segment_duration: Option<u32>,
+    pub segment_type: Option<SegmentType>,
+    pub playlist_type: Option<PlaylistType>,
+    /// Tag the playlists with `EXT-X-INDEPENDENT-SEGMENTS`: every segment starts with a keyframe
+    pub independent_segments: Option<bool>,
+    /// Package every audio stream of the input as its own rendition (`EXT-X-MEDIA`),
+    /// grouped by bit rate, instead of muxing the first one into each video variant
+    pub separate_audio: Option<bool>,
+    /// One audio group per bit rate, e.g. `["64k", "160k"]` (default: 128k)
+    pub audio_bitrates: Option<Vec<String>>,
+    /// Also write scrub preview sprite sheets and their `sprite.vtt` track next to the playlists
+    pub sprites: Option<bool>,
+}
+
+impl HlsOptions {
+    /// Report invalid options as `hls.field`
+    pub fn validate(&self, violations: &mut Violations) {
+        violations.range("hls.segment_duration", self.segment_duration, 1, 60);
+        if let Some(bitrates) = &self.audio_bitrates {
+            if bitrates.is_empty() || bitrates.len() > MAX_AUDIO_BITRATES {
+                violations.add("hls.audio_bitrates", format!("must list 1 to {} bit rates", MAX_AUDIO_BITRATES));
+            }
+            for (index, bitrate) in bitrates.iter().enumerate() {
+                violations.bitrate(&format!("hls.audio_bitrates[{}]", index), Some(bitrate));
+            }
+            if !self.separate_audio() {
+                violations.add("hls.audio_bitrates", "needs separate_audio");
+            }
+        }
+    }
+
+    pub fn independent(&self) -> bool {
+        self.independent_segments.unwrap_or(false)
+    }
+
+    pub fn separate_audio(&self) -> bool {
+        self.separate_audio.unwrap_or(false)
+    }
+
+    pub fn sprites(&self) -> bool {
+        self.sprites.unwrap_or(false)
+    }
+
+    pub fn audio_bitrates(&self) -> Vec<String> {
+        self.audio_bitrates.clone().unwrap_or_else(|| vec![DEFAULT_AUDIO_BITRATE.to_string()])
+    }
+
+    /// FFmpeg options packaging one rendition as `{label}.m3u8` with its segments in `output_dir`
+    pub fn args(&self, output_dir: &str, label: &str) -> Vec<String> {
+        let mut args = vec![
+            "-f".to_string(),
+            "hls".to_string(),
+            "-hls_time".to_string(),
+            self.segment_duration.unwrap_or(DEFAULT_SEGMENT_SECS).to_string(),
+        ];
+        match self.playlist_type.unwrap_or_default() {
+            PlaylistType::Vod => args.extend(["-hls_playlist_type".to_string(), "vod".to_string()]),
+            PlaylistType::Event => args.extend(["-hls_playlist_type".to_string(), "event".to_string()]),
+            // Keep every segment listed; FFmpeg's default window of 5 would drop the rest
+            PlaylistType::Live => args.extend(["-hls_list_size".to_string(), "0".to_string()]),
+        }
+        let extension = match self.segment_type.unwrap_or_default() {
+            SegmentType::Mpegts => "ts",
+            SegmentType::Fmp4 => {
+                args.extend([
+                    "-hls_segment_type".to_string(),
+                    "fmp4".to_string(),
+                    // Resolved relative to the playlist
+                    "-hls_fmp4_init_filename".to_string(),
+                    format!("{}_init.mp4", label),
+                ]);
+                "m4s"
+            }
+        };
+        if self.independent() {
+            args.extend(["-hls_flags".to_string(), "independent_segments".to_string()]);
+        }
+        args.extend([
+            "-hls_segment_filename".to_string(),
+            format!("{}/{}_segment_%03d.{}", output_dir, label, extension),
+        ]);
+        args
+    }
+}
+
+/// One variant stream of a master playlist, described from its probed source file
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rendition {
+    /// Playlist URI relative to the master playlist
+    pub uri: String,
+    /// Peak bit rate: of the busiest segment once measured, the container's average until then
+    pub bandwidth: u64,
+    pub average_bandwidth: Option<u64>,
+    pub resolution: Option<(u64, u64)>,
+    pub frame_rate: Option<f64>,
+    /// RFC 6381 codecs string; left out when any stream's codec can't be described
+    pub codecs: Option<String>,
+    /// `GROUP-ID` of the audio renditions played with this variant
+    pub audio: Option<String>,
+}
+
+/// One audio stream of the input, as packaged into an audio rendition
+#[derive(Debug, Clone, PartialEq)]
+pub struct AudioTrack {
+    /// Stream index in the input
+    pub index: u64,
+    /// From the stream's `language` tag; `und` counts as none
+    pub language: Option<String>,
+    /// `NAME` shown in players: the stream's title, else its language, made unique
+    pub name: String,
+}
+
+/// An `EXT-X-MEDIA` audio rendition: one track at one group's bit rate
+#[derive(Debug, Clone, PartialEq)]
+pub struct AudioRendition {
+    pub group_id: String,
+    pub name: String,
+    pub language: Option<String>,
+    pub uri: String,
+    /// Played when the viewer hasn't picked a track; the first track of each group
+    pub default: bool,
+    /// Peak bit rate, added to the video's in each variant referencing the group
+    pub bandwidth: u64,
+    pub average_bandwidth: Option<u64>,
+    /// Renditions are encoded to AAC-LC
+    pub codecs: String,
+}
+
+impl AudioRendition {
+    pub fn group_id(bitrate: &str) -> String {
+        format!("audio-{}", bitrate.to_lowercase())
+    }
+
+    /// Measured from the segments of `playlist`, `nominal_bitrate` when there are none
+    pub fn new(group_id: String, track: &AudioTrack, default: bool, playlist: &Path, nominal_bitrate: &str) -> Self {
+        let measured = segment_bitrates(playlist);
+        Self {
+            group_id,
+            name: track.name.clone(),
+            language: track.language.clone(),
+            uri: playlist.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default(),
+            default,
+            bandwidth: measured.map_or_else(|| parse_bitrate(nominal_bitrate).unwrap_or_default(), |(peak, _)| peak),
+            average_bandwidth: measured.map(|(_, average)| average),
+            codecs: "mp4a.40.2".to_string(),
+        }
+    }
+}
+
+/// Audio streams of a probed input, at most `MAX_AUDIO_TRACKS`
+pub fn audio_tracks(probe: &serde_json::Value) -> Vec<AudioTrack> {
+    let streams = probe["streams"].as_array().map(Vec::as_slice).unwrap_or_default();
+    let mut tracks: Vec<AudioTrack> = Vec::new();
+    for stream in streams.iter().filter(|stream| stream["codec_type"] == "audio").take(MAX_AUDIO_TRACKS) {
+        let Some(index) = stream["index"].as_u64() else {
+            continue;
+        };
+        let tag = |name: &str| stream["tags"][name].as_str().map(str::trim).filter(|value| !value.is_empty());
+        let language = tag("language").filter(|language| *language != "und").map(str::to_string);
+        let base = tag("title")
+            .map(str::to_string)
+            .or_else(|| language.clone())
+            .unwrap_or_else(|| format!("Audio {}", tracks.len() + 1));
+        let mut name = base.clone();
+        for n in 2.. {
+            if !tracks.iter().any(|track| track.name == name) {
+                break;
+            }
+            name = format!("{} {}", base, n);
+        }
+        tracks.push(AudioTrack { index, language, name });
+    }
+    tracks
+}
+
+/// `128k`, `2.5M` or plain bits per second
+pub fn parse_bitrate(bitrate: &str) -> Option<u64> {
+    let (number, scale) = match bitrate.chars().last()? {
+        'k' | 'K' => (&bitrate[..bitrate.len() - 1], 1e3),
+        'm' | 'M' => (&bitrate[..bitrate.len() - 1], 1e6),
+        _ => (bitrate, 1.0),
+    };
+    number.parse::<f64>().ok().map(|number| (number * scale) as u64)
+}
+
+/// A probe without its audio streams, for variants packaged without them
+pub fn without_audio(probe: &serde_json::Value) -> serde_json::Value {
+    let mut probe = probe.clone();
+    if let Some(streams) = probe["streams"].as_array_mut() {
+        streams.retain(|stream| stream["codec_type"] != "audio");
+    }
+    probe
+}
+
+/// One variant per video rendition and audio group, each counting the group's busiest
+/// rendition in its bandwidth and the audio codec in its codecs
+pub fn audio_variants(video: &[Rendition], audio: &[AudioRendition]) -> Vec<Rendition> {
+    let mut groups: Vec<&str> = Vec::new();
+    for rendition in audio {
+        if !groups.contains(&rendition.group_id.as_str()) {
+            groups.push(&rendition.group_id);
+        }
+    }
+    let mut variants = Vec::new();
+    for group in groups {
+        let members: Vec<&AudioRendition> = audio.iter().filter(|rendition| rendition.group_id == group).collect();
+        let peak = members.iter().map(|rendition| rendition.bandwidth).max().unwrap_or_default();
+        let average = members.iter().map(|rendition| rendition.average_bandwidth).max().flatten();
+        for rendition in video {
+            let mut variant = rendition.clone();
+            variant.bandwidth += peak;
+            variant.average_bandwidth = rendition.average_bandwidth.zip(average).map(|(video, audio)| video + audio);
+            variant.codecs = rendition.codecs.as_ref().map(|codecs| format!("{},{}", codecs, members[0].codecs));
+            variant.audio = Some(group.to_string());
+            variants.push(variant);
+        }
+    }
+    variants
+}
+
+impl Rendition {
+    /// `file_bytes` is the fallback for containers that report no overall bit rate
+    pub fn from_probe(uri: String, probe: &serde_json::Value, file_bytes: Option<u64>) -> Result<Self> {
+        let format = &probe["format"];
+        let duration: Option<f64> = format["duration"].as_str().and_then(|d| d.parse().ok());
+        let bandwidth = format["bit_rate"]
+            .as_str()
+            .and_then(|rate| rate.parse().ok())
+            .or_else(|| match (file_bytes, duration) {
+                (Some(bytes), Some(duration)) if duration > 0.0 => Some((bytes as f64 * 8.0 / duration) as u64),
+                _ => None,
+            })
+            .ok_or_else(|| anyhow::anyhow!("could not determine the bit rate of {}", uri))?;
+
+        let streams = probe["streams"].as_array().map(Vec::as_slice).unwrap_or_default();
+        let video = streams.iter().find(|stream| stream["codec_type"] == "video");
+        let resolution = video.and_then(|stream| Some((stream["width"].as_u64()?, stream["height"].as_u64()?)));
+        let frame_rate = video.and_then(|stream| {
+            ["avg_frame_rate", "r_frame_rate"].iter().find_map(|field| parse_rate(stream[*field].as_str()?))
+        });
+        let codecs = streams
+            .iter()
+            .filter(|stream| matches!(stream["codec_type"].as_str(), Some("video" | "audio")))
+            .map(codec_string)
+            .collect::<Option<Vec<_>>>()
+            .filter(|codecs| !codecs.is_empty())
+            .map(|codecs| codecs.join(","));
+
+        Ok(Self { uri, bandwidth, average_bandwidth: None, resolution, frame_rate, codecs, audio: None })
+    }
+
+    /// Take `BANDWIDTH` and `AVERAGE-BANDWIDTH` from the segments listed in the media
+    /// `playlist`, as the spec defines them; kept as probed when there are none to measure
+    pub fn with_segments(mut self, playlist: &Path) -> Self {
+        if let Some((peak, average)) = segment_bitrates(playlist) {
+            self.bandwidth = peak;
+            self.average_bandwidth = Some(average);
+        }
+        self
+    }
+}
+
+/// `30000/1001` as frames per second; `0/0` for streams without a fixed rate
+fn parse_rate(rate: &str) -> Option<f64> {
+    let (numerator, denominator) = rate.split_once('/')?;
+    let (numerator, denominator): (f64, f64) = (numerator.parse().ok()?, denominator.parse().ok()?);
+    (numerator > 0.0 && denominator > 0.0).then(|| numerator / denominator)
+}
+
+/// Peak and average bits per second of the segments of a media playlist, from each
+/// `#EXTINF` duration and the size of the file it introduces
+pub fn segment_bitrates(playlist: &Path) -> Option<(u64, u64)> {
+    let dir = playlist.parent()?;
+    let text = std::fs::read_to_string(playlist).ok()?;
+    let (mut peak, mut bytes, mut secs) = (0.0f64, 0u64, 0.0f64);
+    let mut duration = None;
+    for line in text.lines().map(str::trim) {
+        if let Some(info) = line.strip_prefix("#EXTINF:") {
+            duration = info.split(',').next().and_then(|d| d.parse::<f64>().ok());
+        } else if !line.is_empty() && !line.starts_with('#') {
+            let (Some(duration), Ok(metadata)) = (duration.take(), std::fs::metadata(dir.join(line))) else {
+                continue;
+            };
+            if duration > 0.0 {
+                peak = peak.max(metadata.len() as f64 * 8.0 / duration);
+                bytes += metadata.len();
+                secs += duration;
+            }
+        }
+    }
+    (secs > 0.0).then(|| (peak.ceil() as u64, (bytes as f64 * 8.0 / secs).ceil() as u64))
+}
+
+/// `avc1.PPCCLL`, `hvc1.…` or `mp4a.40.n` for the codecs HLS players commonly check
+fn codec_string(stream: &serde_json::Value) -> Option<String> {
+    let profile = stream["profile"].as_str().unwrap_or_default();
+    let level = stream["level"].as_i64();
+    match stream["codec_name"].as_str()? {
+        "h264" => {
+            // profile_idc and constraint flags, as most encoders write them
+            let profile = match profile {
+                "Constrained Baseline" => "42E0",
+                "Baseline" => "4200",
+                "Main" => "4D40",
+                "High" => "6400",
+                "High 10" => "6E00",
+                "High 4:2:2" => "7A00",
+                _ => return None,
+            };
+            Some(format!("avc1.{}{:02X}", profile, level?))
+        }
+        "hevc" => {
+            let profile = match profile {
+                "Main" => "1.6",
+                "Main 10" => "2.4",
+                _ => return None,
+            };
+            Some(format!("hvc1.{}.L{}.B0", profile, level?))
+        }
+        "av1" => {
+            // Seq profile, level and tier, bit depth
+            let profile = match profile {
+                "Main" => 0,
+                "High" => 1,
+                "Professional" => 2,
+                _ => return None,
+            };
+            let depth = if stream["pix_fmt"].as_str().unwrap_or_default().contains("10") { 10 } else { 8 };
+            Some(format!("av01.{}.{:02}M.{:02}", profile, level?, depth))
+        }
+        "aac" => match profile {
+            "HE-AAC" => Some("mp4a.40.5".to_string()),
+            "HE-AACv2" => Some("mp4a.40.29".to_string()),
+            _ => Some("mp4a.40.2".to_string()),
+        },
+        "mp3" => Some("mp4a.40.34".to_string()),
+        "ac3" => Some("ac-3".to_string()),
+        _ => None,
+    }
+}
+
+/// Master playlist listing `audio` renditions, then every variant in `renditions`
+pub fn master_playlist(renditions: &[Rendition], audio: &[AudioRendition], independent_segments: bool) -> String {
+    let mut playlist = String::from("#EXTM3U\n");
+    if independent_segments {
+        playlist.push_str("#EXT-X-INDEPENDENT-SEGMENTS\n");
+    }
+    for rendition in audio {
+        let _ = write!(
+            playlist,
+            "#EXT-X-MEDIA:TYPE=AUDIO,GROUP-ID=\"{}\",NAME=\"{}\"",
+            rendition.group_id,
+            rendition.name.replace('"', "'")
+        );
+        if let Some(language) = &rendition.language {
+            let _ = write!(playlist, ",LANGUAGE=\"{}\"", language.replace('"', ""));
+        }
+        let default = if rendition.default { "YES" } else { "NO" };
+        let _ = writeln!(playlist, ",DEFAULT={},AUTOSELECT=YES,URI=\"{}\"", default, rendition.uri);
+    }
+    for rendition in renditions {
+        let _ = write!(playlist, "#EXT-X-STREAM-INF:BANDWIDTH={}", rendition.bandwidth);
+        if let Some(average) = rendition.average_bandwidth {
+            let _ = write!(playlist, ",AVERAGE-BANDWIDTH={}", average);
+        }
+        if let Some((width, height)) = rendition.resolution {
+            let _ = write!(playlist, ",RESOLUTION={}x{}", width, height);
+        }
+        if let Some(frame_rate) = rendition.frame_rate {
+            let _ = write!(playlist, ",FRAME-RATE={:.3}", frame_rate);
+        }
+        if let Some(codecs) = &rendition.codecs {
+            let _ = write!(playlist, ",CODECS=\"{}\"", codecs);
+        }
+        if let Some(group) = &rendition.audio {
+            let _ = write!(playlist, ",AUDIO=\"{}\"", group);
+        }
+        let _ = writeln!(playlist, "\n{}", rendition.uri);
+    }
+    playlist
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rendition_from_probe() {
+        let probe = serde_json::json!({
+            "streams": [
+                { "codec_type": "video", "codec_name": "h264", "profile": "High", "level": 31, "width": 1280, "height": 720 },
+                { "codec_type": "audio", "codec_name": "aac", "profile": "LC" }
+            ],
+            "format": { "duration": "10.000000", "bit_rate": "2612345" }
+        });
+        let rendition = Rendition::from_probe("720p.m3u8".to_string(), &probe, None).unwrap();
+        assert_eq!(rendition.bandwidth, 2612345);
+        assert_eq!(rendition.resolution, Some((1280, 720)));
+        assert_eq!(rendition.codecs.as_deref(), Some("avc1.64001F,mp4a.40.2"));
+        assert_eq!(
+            master_playlist(&[rendition], &[], false),
+            "#EXTM3U\n#EXT-X-STREAM-INF:BANDWIDTH=2612345,RESOLUTION=1280x720,CODECS=\"avc1.64001F,mp4a.40.2\"\n720p.m3u8\n"
+        );
+    }
+
+    #[test]
+    fn test_unknown_codec_omits_codecs_and_size_fallback() {
+        let probe = serde_json::json!({
+            "streams": [{ "codec_type": "video", "codec_name": "vp9", "width": 854, "height": 480 }],
+            "format": { "duration": "8.000000" }
+        });
+        let rendition = Rendition::from_probe("480p.m3u8".to_string(), &probe, Some(1_000_000)).unwrap();
+        assert_eq!(rendition.bandwidth, 1_000_000);
+        assert_eq!(rendition.codecs, None);
+        assert!(Rendition::from_probe("x.m3u8".to_string(), &probe, None).is_err());
+    }
+
+    #[test]
+    fn test_hls_options_args() {
+        let args = HlsOptions::default().args("out", "720p").join(" ");
+        assert_eq!(args, "-f hls -hls_time 4 -hls_playlist_type vod -hls_segment_filename out/720p_segment_%03d.ts");
+
+        let options = HlsOptions {
+            segment_duration: Some(6),
+            segment_type: Some(SegmentType::Fmp4),
+            playlist_type: Some(PlaylistType::Event),
+            independent_segments: Some(true),
+            ..HlsOptions::default()
+        };
+        assert_eq!(
+            options.args("out", "1080p").join(" "),
+            "-f hls -hls_time 6 -hls_playlist_type event -hls_segment_type fmp4 -hls_fmp4_init_filename 1080p_init.mp4 \
+             -hls_flags independent_segments -hls_segment_filename out/1080p_segment_%03d.m4s"
+        );
+        assert!(master_playlist(&[], &[], true).starts_with("#EXTM3U\n#EXT-X-INDEPENDENT-SEGMENTS\n"));
+
+        let options: HlsOptions = serde_json::from_str(r#"{"playlist_type": "live", "segment_duration": 0}"#).unwrap();
+        assert!(options.args("out", "480p").join(" ").contains("-hls_list_size 0"));
+        let mut violations = Violations::new();
+        options.validate(&mut violations);
+        assert!(violations.into_result().is_err());
+    }
+
+    #[test]
+    fn test_frame_rate_and_measured_bandwidth() {
+        let probe = serde_json::json!({
+            "streams": [{ "codec_type": "video", "codec_name": "av1", "profile": "Main", "level": 8,
+                          "pix_fmt": "yuv420p", "width": 1920, "height": 1080, "avg_frame_rate": "30000/1001" }],
+            "format": { "duration": "8.000000", "bit_rate": "3000000" }
+        });
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("1080p_segment_000.ts"), vec![0u8; 2_000_000]).unwrap();
+        std::fs::write(dir.path().join("1080p_segment_001.ts"), vec![0u8; 1_000_000]).unwrap();
+        let playlist = dir.path().join("1080p.m3u8");
+        std::fs::write(&playlist, "#EXTM3U\n#EXTINF:4.000000,\n1080p_segment_000.ts\n#EXTINF:4.000000,\n1080p_segment_001.ts\n#EXT-X-ENDLIST\n").unwrap();
+
+        let rendition = Rendition::from_probe("1080p.m3u8".to_string(), &probe, None).unwrap().with_segments(&playlist);
+        assert_eq!((rendition.bandwidth, rendition.average_bandwidth), (4_000_000, Some(3_000_000)));
+        assert_eq!(
+            master_playlist(&[rendition], &[], false),
+            "#EXTM3U\n#EXT-X-STREAM-INF:BANDWIDTH=4000000,AVERAGE-BANDWIDTH=3000000,RESOLUTION=1920x1080,\
+             FRAME-RATE=29.970,CODECS=\"av01.0.08M.08\"\n1080p.m3u8\n"
+        );
+    }
+
+    #[test]
+    fn test_audio_groups_per_language_and_bitrate() {
+        let probe = serde_json::json!({
+            "streams": [
+                { "index": 0, "codec_type": "video" },
+                { "index": 1, "codec_type": "audio", "tags": { "language": "eng" } },
+                { "index": 2, "codec_type": "audio", "tags": { "language": "eng", "title": "Commentary" } },
+                { "index": 3, "codec_type": "audio", "tags": { "language": "und" } }
+            ]
+        });
+        let tracks = audio_tracks(&probe);
+        let names: Vec<(u64, &str, Option<&str>)> =
+            tracks.iter().map(|track| (track.index, track.name.as_str(), track.language.as_deref())).collect();
+        assert_eq!(names, vec![(1, "eng", Some("eng")), (2, "Commentary", Some("eng")), (3, "Audio 3", None)]);
+
+        let audio: Vec<AudioRendition> = ["64k", "160k"]
+            .iter()
+            .flat_map(|bitrate| {
+                let tracks = &tracks;
+                tracks.iter().enumerate().map(move |(n, track)| {
+                    let playlist = Path::new("/none").join(format!("audio_{}_{}.m3u8", n, bitrate));
+                    AudioRendition::new(AudioRendition::group_id(bitrate), track, n == 0, &playlist, bitrate)
+                })
+            })
+            .collect();
+        let video = Rendition {
+            uri: "720p.m3u8".to_string(),
+            bandwidth: 2_000_000,
+            average_bandwidth: Some(1_500_000),
+            resolution: Some((1280, 720)),
+            frame_rate: None,
+            codecs: Some("avc1.64001F".to_string()),
+            audio: None,
+        };
+        let variants = audio_variants(&[video], &audio);
+        assert_eq!(variants.len(), 2);
+        assert_eq!((variants[1].bandwidth, variants[1].average_bandwidth), (2_160_000, None));
+
+        let playlist = master_playlist(&variants, &audio[..1], false);
+        assert_eq!(
+            playlist.lines().take(3).collect::<Vec<_>>(),
+            vec![
+                "#EXTM3U",
+                "#EXT-X-MEDIA:TYPE=AUDIO,GROUP-ID=\"audio-64k\",NAME=\"eng\",LANGUAGE=\"eng\",DEFAULT=YES,AUTOSELECT=YES,URI=\"audio_0_64k.m3u8\"",
+                "#EXT-X-STREAM-INF:BANDWIDTH=2064000,RESOLUTION=1280x720,CODECS=\"avc1.64001F,mp4a.40.2\",AUDIO=\"audio-64k\"",
+            ]
+        );
+
+        let mut violations = Violations::new();
+        HlsOptions { audio_bitrates: Some(vec!["loud".to_string()]), ..HlsOptions::default() }.validate(&mut violations);
+        match violations.into_result() {
+            Err(crate::utils::error::ServiceError::ValidationError(list)) => assert_eq!(list.len(), 2),
+            other => panic!("expected validation error, got {:?}", other),
+        }
+    }
+}
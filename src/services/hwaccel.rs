@@ -0,0 +1,145 @@
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::process::Command;
+use std::sync::OnceLock;
+
+/// `hardware_acceleration` of a transcode: a specific API, or `auto` for the first one this
+/// host can run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Accelerator {
+    Auto,
+    Nvenc,
+    Vaapi,
+    Videotoolbox,
+}
+
+/// H.264 and HEVC encoder of each accelerator, in the order `auto` tries them
+static HARDWARE_ENCODERS: &[(Accelerator, [&str; 2])] = &[
+    (Accelerator::Videotoolbox, ["h264_videotoolbox", "hevc_videotoolbox"]),
+    (Accelerator::Nvenc, ["h264_nvenc", "hevc_nvenc"]),
+    (Accelerator::Vaapi, ["h264_vaapi", "hevc_vaapi"]),
+];
+
+/// Software encoders a hardware one can stand in for, by the index of its family above
+static SOFTWARE_ENCODERS: &[[&str; 2]] = &[["libx264", "h264"], ["libx265", "hevc"]];
+
+/// Render node VAAPI encodes on, `VAAPI_DEVICE`
+fn vaapi_device() -> String {
+    std::env::var("VAAPI_DEVICE").unwrap_or_else(|_| "/dev/dri/renderD128".to_string())
+}
+
+/// A hardware encoder standing in for the requested software one
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HardwareEncoder {
+    pub accelerator: Accelerator,
+    pub name: &'static str,
+}
+
+impl HardwareEncoder {
+    /// Options before the first input
+    pub fn device_args(&self) -> Vec<String> {
+        match self.accelerator {
+            Accelerator::Vaapi => vec!["-vaapi_device".to_string(), vaapi_device()],
+            _ => Vec::new(),
+        }
+    }
+
+    /// Filter ending the video chain, when the encoder needs one. VAAPI only encodes frames
+    /// already on the device, so they are scaled to `resolution` on the CPU and uploaded last;
+    /// `-s` would scale after the upload. The other encoders take system memory frames and `-s`
+    pub fn upload_filter(&self, resolution: Option<&str>) -> Option<String> {
+        if self.accelerator != Accelerator::Vaapi {
+            return None;
+        }
+        Some(match resolution {
+            Some(resolution) => format!("scale={},format=nv12,hwupload", resolution.replace('x', ":")),
+            None => "format=nv12,hwupload".to_string(),
+        })
+    }
+}
+
+/// Family of `codec`, libx264 when unset, if hardware can encode it
+fn family(codec: Option<&str>) -> Option<usize> {
+    let codec = codec.unwrap_or("libx264");
+    SOFTWARE_ENCODERS.iter().position(|names| names.contains(&codec))
+}
+
+/// Whether a hardware encoder can stand in for `codec`
+pub fn supports(codec: Option<&str>) -> bool {
+    family(codec).is_some()
+}
+
+/// Hardware encoders `requested` allows for `codec`, in order of preference
+fn candidates(requested: Accelerator, codec: Option<&str>) -> Vec<HardwareEncoder> {
+    let Some(family) = family(codec) else {
+        return Vec::new();
+    };
+    HARDWARE_ENCODERS
+        .iter()
+        .filter(|(accelerator, _)| requested == Accelerator::Auto || requested == *accelerator)
+        .map(|(accelerator, names)| HardwareEncoder { accelerator: *accelerator, name: names[family] })
+        .collect()
+}
+
+/// Hardware encoder for `codec` that this host can run, or `None` to stay on the CPU
+pub fn select(requested: Accelerator, codec: Option<&str>) -> Option<HardwareEncoder> {
+    candidates(requested, codec).into_iter().find(|encoder| usable().contains(&encoder.name))
+}
+
+/// Hardware encoders that completed a test encode, probed once. An encoder compiled into
+/// FFmpeg still needs the device and a working driver, which only running it shows
+pub fn usable() -> &'static [&'static str] {
+    static USABLE: OnceLock<Vec<&'static str>> = OnceLock::new();
+    USABLE.get_or_init(|| {
+        let usable: Vec<&'static str> = candidates(Accelerator::Auto, Some("h264"))
+            .into_iter()
+            .chain(candidates(Accelerator::Auto, Some("hevc")))
+            .filter(test_encode)
+            .map(|encoder| encoder.name)
+            .collect();
+        info!("Usable hardware encoders: [{}]", usable.join(", "));
+        usable
+    })
+}
+
+/// Encode a few frames of a generated black clip to nowhere
+fn test_encode(encoder: &HardwareEncoder) -> bool {
+    let mut command = Command::new("ffmpeg");
+    command
+        .arg("-hide_banner")
+        .arg("-nostdin")
+        .arg("-v").arg("error")
+        .args(encoder.device_args())
+        .arg("-f").arg("lavfi")
+        .arg("-i").arg("color=black:s=256x256:r=25:d=0.2");
+    if let Some(filter) = encoder.upload_filter(None) {
+        command.arg("-vf").arg(filter);
+    }
+    command
+        .arg("-c:v").arg(encoder.name)
+        .arg("-f").arg("null")
+        .arg("-");
+    command.output().is_ok_and(|output| output.status.success())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_candidates_and_upload_filter() {
+        let names = |requested, codec| candidates(requested, codec).into_iter().map(|encoder| encoder.name).collect::<Vec<_>>();
+        assert_eq!(names(Accelerator::Auto, None), vec!["h264_videotoolbox", "h264_nvenc", "h264_vaapi"]);
+        assert_eq!(names(Accelerator::Nvenc, Some("libx265")), vec!["hevc_nvenc"]);
+        assert!(names(Accelerator::Auto, Some("libvpx-vp9")).is_empty());
+        assert!(supports(Some("hevc")) && !supports(Some("copy")));
+
+        let vaapi = HardwareEncoder { accelerator: Accelerator::Vaapi, name: "h264_vaapi" };
+        assert_eq!(vaapi.upload_filter(Some("1280x720")).as_deref(), Some("scale=1280:720,format=nv12,hwupload"));
+        assert_eq!(vaapi.device_args()[0], "-vaapi_device");
+        let nvenc = HardwareEncoder { accelerator: Accelerator::Nvenc, name: "h264_nvenc" };
+        assert_eq!(nvenc.upload_filter(Some("1280x720")), None);
+        assert!(nvenc.device_args().is_empty());
+    }
+}
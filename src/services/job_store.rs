@@ -0,0 +1,868 @@
+use chrono::Utc;
+use log::{info, warn};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Mutex, RwLock};
+use std::thread::JoinHandle;
+use tokio::sync::broadcast;
+use crate::middleware::{auth, request_id};
+use crate::models::admin::{DailyUsage, JobStats, OperationStats, SlowJob, UsageQuery};
+use crate::models::job::{JobAttempt, JobListResponse, JobProgress, JobQuery, JobRecord, JobStatus, Priority, DEFAULT_PAGE_SIZE};
+use crate::utils::audit::CommandAudit;
+use crate::utils::fs;
+
+/// Oldest records are dropped once the history grows past this
+const MAX_JOB_HISTORY: usize = 10_000;
+
+/// `/admin/stats` picks the slowest jobs among this many of the latest finished ones
+const RECENT_JOBS: usize = 100;
+const SLOWEST_JOBS: usize = 5;
+
+/// Updates a slow progress subscriber may fall behind by before it has to re-read the record
+const UPDATE_BUFFER: usize = 256;
+
+/// Where job records outlive the process. The store writes a record when a job starts and
+/// when it finishes; progress updates stay in memory
+pub trait JobBackend: Send + Sync {
+    fn save(&self, job: &JobRecord) -> io::Result<()>;
+    /// Every saved record, oldest first; a job saved twice appears twice
+    fn load(&self) -> io::Result<Vec<JobRecord>>;
+    /// Replace everything saved with `jobs`, to drop superseded and evicted records
+    fn compact(&self, jobs: &[JobRecord]) -> io::Result<()>;
+}
+
+/// Records as JSON in one Redis hash keyed by job id, for deployments where several replicas
+/// or short-lived containers share the history
+#[cfg(feature = "redis")]
+pub struct RedisBackend {
+    client: redis::Client,
+    /// Dropped when the server goes away and opened again by the next query
+    connection: Mutex<Option<redis::Connection>>,
+    key: String,
+}
+
+#[cfg(feature = "redis")]
+impl RedisBackend {
+    /// `url` as `redis://[:password@]host[:port][/db]`; records go under the `key` hash
+    pub fn open(url: &str, key: impl Into<String>) -> io::Result<Self> {
+        let client = redis::Client::open(url).map_err(io::Error::other)?;
+        // Connect now, so a wrong URL fails startup instead of the first save
+        let connection = client.get_connection().map_err(io::Error::other)?;
+        Ok(Self { client, connection: Mutex::new(Some(connection)), key: key.into() })
+    }
+
+    /// Run `query`, reconnecting and trying once more when the connection was lost, e.g. to a
+    /// Redis restart or failover
+    fn query<T>(&self, query: impl Fn(&mut redis::Connection) -> redis::RedisResult<T>) -> io::Result<T> {
+        let mut connection = self.connection.lock().unwrap();
+        let mut reconnected = false;
+        loop {
+            if connection.is_none() {
+                *connection = Some(self.client.get_connection().map_err(io::Error::other)?);
+            }
+            match query(connection.as_mut().expect("connected above")) {
+                Err(e) if !reconnected && (e.is_io_error() || e.is_connection_dropped()) => {
+                    warn!("Lost the Redis connection for {}, reconnecting: {}", self.key, e);
+                    *connection = None;
+                    reconnected = true;
+                }
+                result => return result.map_err(io::Error::other),
+            }
+        }
+    }
+}
+
+#[cfg(feature = "redis")]
+impl JobBackend for RedisBackend {
+    fn save(&self, job: &JobRecord) -> io::Result<()> {
+        let value = serde_json::to_string(job)?;
+        self.query(|connection| redis::cmd("HSET").arg(&self.key).arg(&job.job_id).arg(&value).query::<()>(connection))
+    }
+
+    fn load(&self) -> io::Result<Vec<JobRecord>> {
+        let values: Vec<String> = self.query(|connection| redis::cmd("HVALS").arg(&self.key).query(connection))?;
+        let mut jobs: Vec<JobRecord> = values
+            .iter()
+            .filter_map(|value| match serde_json::from_str(value) {
+                Ok(job) => Some(job),
+                Err(e) => {
+                    warn!("Skipping unreadable job record in {}: {}", self.key, e);
+                    None
+                }
+            })
+            .collect();
+        // A hash has no order; the history is kept oldest first
+        jobs.sort_by_key(|job| job.created_at);
+        Ok(jobs)
+    }
+
+    fn compact(&self, jobs: &[JobRecord]) -> io::Result<()> {
+        let mut pipe = redis::pipe();
+        pipe.atomic().del(&self.key).ignore();
+        for job in jobs {
+            pipe.hset(&self.key, &job.job_id, serde_json::to_string(job)?).ignore();
+        }
+        self.query(|connection| pipe.query::<()>(connection))
+    }
+}
+
+/// Append-only JSON lines file, compacted on startup
+pub struct FileBackend {
+    path: PathBuf,
+    file: Mutex<File>,
+}
+
+impl FileBackend {
+    pub fn open(path: impl Into<PathBuf>) -> io::Result<Self> {
+        let path = path.into();
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        Ok(Self { path, file: Mutex::new(file) })
+    }
+}
+
+impl JobBackend for FileBackend {
+    fn save(&self, job: &JobRecord) -> io::Result<()> {
+        let mut line = serde_json::to_vec(job)?;
+        line.push(b'\n');
+        self.file.lock().unwrap().write_all(&line)
+    }
+
+    fn load(&self) -> io::Result<Vec<JobRecord>> {
+        let mut jobs = Vec::new();
+        for line in BufReader::new(File::open(&self.path)?).lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            // A line cut short by a crash mid-write shouldn't lose the rest of the history
+            match serde_json::from_str(&line) {
+                Ok(job) => jobs.push(job),
+                Err(e) => warn!("Skipping unreadable job record in {}: {}", self.path.display(), e),
+            }
+        }
+        Ok(jobs)
+    }
+
+    fn compact(&self, jobs: &[JobRecord]) -> io::Result<()> {
+        let mut file = self.file.lock().unwrap();
+        let temp = self.path.with_extension("tmp");
+        let mut out = io::BufWriter::new(File::create(&temp)?);
+        for job in jobs {
+            serde_json::to_writer(&mut out, job)?;
+            out.write_all(b"\n")?;
+        }
+        out.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+        std::fs::rename(&temp, &self.path)?;
+        *file = OpenOptions::new().append(true).open(&self.path)?;
+        Ok(())
+    }
+}
+
+/// History of processing jobs, newest last. Kept in memory and, with a backend, persisted so
+/// clients can still poll a job after the service restarts
+pub struct JobStore {
+    jobs: RwLock<VecDeque<JobRecord>>,
+    /// Records for the backend; one writer thread saves them in order, off the `jobs` lock
+    /// and the async runtime
+    saves: Option<mpsc::Sender<JobRecord>>,
+    writer: Option<JoinHandle<()>>,
+    /// Every state change and progress update, for `/jobs/{job_id}/progress` streams
+    updates: broadcast::Sender<JobRecord>,
+    /// Jobs the previous process left queued or running that can be queued again
+    interrupted: Mutex<Vec<String>>,
+    /// Running jobs whose outcome is kept as their `result`; they complete with `set_result`
+    awaiting_result: Mutex<HashSet<String>>,
+}
+
+impl Default for JobStore {
+    fn default() -> Self {
+        Self {
+            jobs: RwLock::default(),
+            saves: None,
+            writer: None,
+            updates: broadcast::channel(UPDATE_BUFFER).0,
+            interrupted: Mutex::default(),
+            awaiting_result: Mutex::default(),
+        }
+    }
+}
+
+impl Drop for JobStore {
+    /// Let the writer save what is still queued, so a clean shutdown loses no record
+    fn drop(&mut self) {
+        self.saves.take();
+        if let Some(writer) = self.writer.take() {
+            let _ = writer.join();
+        }
+    }
+}
+
+impl JobStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Restore the history saved in `backend`. Jobs that were still queued or running when the
+    /// previous process stopped are marked failed, since nothing will finish them unless they
+    /// are re-queued through `take_interrupted`
+    pub fn with_backend(backend: Box<dyn JobBackend>) -> io::Result<Self> {
+        let mut latest: HashMap<String, usize> = HashMap::new();
+        let mut restored: Vec<JobRecord> = Vec::new();
+        for job in backend.load()? {
+            match latest.get(&job.job_id) {
+                Some(&index) => restored[index] = job,
+                None => {
+                    latest.insert(job.job_id.clone(), restored.len());
+                    restored.push(job);
+                }
+            }
+        }
+        let mut interrupted = Vec::new();
+        for job in restored.iter_mut().filter(|job| matches!(job.status, JobStatus::Queued | JobStatus::Running)) {
+            job.status = JobStatus::Failed;
+            job.error = Some("interrupted by a service restart".to_string());
+            if job.request.is_some() {
+                interrupted.push(job.job_id.clone());
+            }
+        }
+        let skip = restored.len().saturating_sub(MAX_JOB_HISTORY);
+        backend.compact(&restored[skip..])?;
+        let jobs = restored.into_iter().skip(skip).collect();
+        let (saves, pending) = mpsc::channel::<JobRecord>();
+        let writer = std::thread::Builder::new().name("job-store".to_string()).spawn(move || {
+            for job in pending {
+                if let Err(e) = backend.save(&job) {
+                    warn!("[{}] Failed to persist job record: {}", job.job_id, e);
+                }
+            }
+        })?;
+        Ok(Self {
+            jobs: RwLock::new(jobs),
+            saves: Some(saves),
+            writer: Some(writer),
+            updates: broadcast::channel(UPDATE_BUFFER).0,
+            interrupted: Mutex::new(interrupted),
+            awaiting_result: Mutex::default(),
+        })
+    }
+
+    /// Persist to Redis at `JOB_STORE_URL` (needs the `redis` feature) or to the JSON lines file
+    /// at `JOB_STORE_PATH`; memory only when neither is set
+    pub fn from_env() -> io::Result<Self> {
+        let var = |name: &str| std::env::var(name).ok().map(|v| v.trim().to_string()).filter(|v| !v.is_empty());
+        let (store, location) = if let Some(url) = var("JOB_STORE_URL") {
+            (Self::with_backend(Self::redis_backend(&url)?)?, url)
+        } else if let Some(path) = var("JOB_STORE_PATH") {
+            (Self::with_backend(Box::new(FileBackend::open(&path)?))?, path)
+        } else {
+            return Ok(Self::new());
+        };
+        info!("Restored {} jobs from {}", store.jobs.read().unwrap().len(), location);
+        Ok(store)
+    }
+
+    #[cfg(feature = "redis")]
+    fn redis_backend(url: &str) -> io::Result<Box<dyn JobBackend>> {
+        let key = std::env::var("JOB_STORE_KEY").unwrap_or_else(|_| "media:jobs".to_string());
+        Ok(Box::new(RedisBackend::open(url, key)?))
+    }
+
+    #[cfg(not(feature = "redis"))]
+    fn redis_backend(_url: &str) -> io::Result<Box<dyn JobBackend>> {
+        Err(io::Error::other("JOB_STORE_URL needs a build with the `redis` feature"))
+    }
+
+    /// Interrupted jobs that were queued with their request, once; the caller queues them again
+    pub fn take_interrupted(&self) -> Vec<JobRecord> {
+        let ids = std::mem::take(&mut *self.interrupted.lock().unwrap());
+        ids.iter().filter_map(|job_id| self.get(job_id)).collect()
+    }
+
+    /// Put a finished record back in the queued state, keeping its id so clients polling it
+    /// see the retry through
+    pub fn requeue(&self, job_id: &str) -> bool {
+        let mut jobs = self.jobs.write().unwrap();
+        let Some(job) = jobs.iter_mut().rev().find(|job| job.job_id == job_id) else {
+            return false;
+        };
+        job.status = JobStatus::Queued;
+        job.started_at = None;
+        job.finished_at = None;
+        job.processing_time_ms = None;
+        job.error = None;
+        job.progress = None;
+        job.result = None;
+        self.persist(job);
+        true
+    }
+
+    /// Publish `job` and queue it for the writer thread. Only a channel send, so callers do
+    /// it under the `jobs` lock, which keeps the saves in the order of the changes
+    fn persist(&self, job: &JobRecord) {
+        self.publish(job);
+        if let Some(saves) = &self.saves {
+            // The writer only stops once the store is dropped
+            let _ = saves.send(job.clone());
+        }
+    }
+
+    fn publish(&self, job: &JobRecord) {
+        // No receivers is the normal case
+        let _ = self.updates.send(job.clone());
+    }
+
+    /// Updates to every job from now on; filter by `job_id`
+    pub fn subscribe(&self) -> broadcast::Receiver<JobRecord> {
+        self.updates.subscribe()
+    }
+
+    /// A single job, for status polling
+    pub fn get(&self, job_id: &str) -> Option<JobRecord> {
+        let jobs = self.jobs.read().unwrap();
+        jobs.iter().rev().find(|job| job.job_id == job_id).cloned()
+    }
+
+    /// Record a job waiting for a queue worker; `start` then picks the record up
+    pub fn enqueue(
+        &self,
+        job_id: &str,
+        job_type: &str,
+        priority: Priority,
+        input_path: &str,
+        output_path: &str,
+        request: Option<serde_json::Value>,
+    ) {
+        let mut record = Self::record(job_id, job_type, JobStatus::Queued, input_path, output_path);
+        record.priority = priority;
+        record.request = request;
+        self.push(record);
+    }
+
+    pub fn start(&self, job_id: &str, job_type: &str, input_path: &str, output_path: &str) {
+        let _ = self.start_within(job_id, job_type, input_path, output_path, None);
+    }
+
+    /// `start`, unless the caller's tenant already runs `max_running` jobs; then their number
+    /// comes back. Counting and opening the record share one lock, so two workers can't both
+    /// take the last slot
+    pub fn start_within(
+        &self,
+        job_id: &str,
+        job_type: &str,
+        input_path: &str,
+        output_path: &str,
+        max_running: Option<usize>,
+    ) -> Result<(), usize> {
+        let input_bytes = path_bytes(input_path);
+        let mut jobs = self.jobs.write().unwrap();
+        if let (Some(max), Some(tenant)) = (max_running, auth::current_tenant()) {
+            let running = jobs
+                .iter()
+                .filter(|job| job.status == JobStatus::Running && job.tenant.as_deref() == Some(tenant.as_str()))
+                .count();
+            if running >= max {
+                return Err(running);
+            }
+        }
+        let queued = jobs
+            .iter_mut()
+            .rev()
+            .find(|job| job.job_id == job_id && job.status == JobStatus::Queued);
+        if let Some(job) = queued {
+            // The processor knows the real paths, e.g. the master playlist of an HLS job
+            job.input_path = input_path.to_string();
+            job.output_path = output_path.to_string();
+            job.input_bytes = input_bytes;
+            job.status = JobStatus::Running;
+            job.started_at = Some(Utc::now());
+            self.persist(job);
+            return Ok(());
+        }
+        let mut record = Self::record(job_id, job_type, JobStatus::Running, input_path, output_path);
+        record.started_at = Some(record.created_at);
+        record.input_bytes = input_bytes;
+        self.append(&mut jobs, record);
+        Ok(())
+    }
+
+    fn record(job_id: &str, job_type: &str, status: JobStatus, input_path: &str, output_path: &str) -> JobRecord {
+        JobRecord {
+            job_id: job_id.to_string(),
+            job_type: job_type.to_string(),
+            status,
+            priority: Priority::default(),
+            input_path: input_path.to_string(),
+            output_path: output_path.to_string(),
+            request_id: request_id::current(),
+            tenant: auth::current_tenant(),
+            created_at: Utc::now(),
+            request: None,
+            started_at: None,
+            finished_at: None,
+            processing_time_ms: None,
+            error: None,
+            progress: None,
+            input_bytes: None,
+            output_bytes: None,
+            result: None,
+            commands: Vec::new(),
+            attempts: Vec::new(),
+        }
+    }
+
+    fn push(&self, record: JobRecord) {
+        let mut jobs = self.jobs.write().unwrap();
+        self.append(&mut jobs, record);
+    }
+
+    fn append(&self, jobs: &mut VecDeque<JobRecord>, record: JobRecord) {
+        if jobs.len() >= MAX_JOB_HISTORY {
+            jobs.pop_front();
+        }
+        self.persist(&record);
+        jobs.push_back(record);
+    }
+
+    /// Drop the record of a job that was never accepted
+    pub fn discard(&self, job_id: &str) {
+        let mut jobs = self.jobs.write().unwrap();
+        if let Some(index) = jobs.iter().rposition(|job| job.job_id == job_id) {
+            jobs.remove(index);
+        }
+    }
+
+    pub fn set_progress(&self, job_id: &str, progress: JobProgress) {
+        let mut jobs = self.jobs.write().unwrap();
+        if let Some(job) = jobs.iter_mut().rev().find(|job| job.job_id == job_id) {
+            job.progress = Some(progress);
+            self.publish(job);
+        }
+    }
+
+    pub fn record_command(&self, job_id: &str, command: CommandAudit) {
+        let mut jobs = self.jobs.write().unwrap();
+        if let Some(job) = jobs.iter_mut().rev().find(|job| job.job_id == job_id) {
+            job.commands.push(command);
+        }
+    }
+
+    pub fn record_attempt(&self, job_id: &str, attempt: JobAttempt) {
+        let mut jobs = self.jobs.write().unwrap();
+        if let Some(job) = jobs.iter_mut().rev().find(|job| job.job_id == job_id) {
+            job.attempts.push(attempt);
+            self.persist(job);
+        }
+    }
+
+    /// Record the outcome of a job started with `start`, or of a queued job that failed before
+    /// it could start; returns its processing time
+    pub fn finish<T>(&self, job_id: &str, result: &anyhow::Result<T>) -> Option<u64> {
+        let mut jobs = self.jobs.write().unwrap();
+        let job = jobs.iter_mut().rev().find(|job| job.job_id == job_id)?;
+        if job.status == JobStatus::Cancelled && job.finished_at.is_some() {
+            // Cancelled while queued; nothing ran
+            return None;
+        }
+        let finished_at = Utc::now();
+        // Time spent waiting in the queue isn't processing time
+        let started_at = job.started_at.unwrap_or(job.created_at);
+        let processing_time_ms = (finished_at - started_at).num_milliseconds().max(0) as u64;
+        job.processing_time_ms = Some(processing_time_ms);
+        job.finished_at = Some(finished_at);
+        match result {
+            // Whatever the processor made of its killed FFmpeg, the job stays cancelled
+            _ if job.status == JobStatus::Cancelled => {}
+            // Left running until its result is in, so pollers never see it completed without one
+            Ok(_) if self.awaiting_result.lock().unwrap().contains(job_id) => {}
+            Ok(_) => {
+                job.status = JobStatus::Completed;
+                job.output_bytes = path_bytes(&job.output_path);
+            }
+            Err(e) => {
+                job.status = JobStatus::Failed;
+                job.error = Some(e.to_string());
+            }
+        }
+        self.persist(job);
+        Some(processing_time_ms)
+    }
+
+    /// Have the running job complete only once `set_result` brings its outcome
+    pub fn await_result(&self, job_id: &str) {
+        self.awaiting_result.lock().unwrap().insert(job_id.to_string());
+    }
+
+    /// Keep what a job awaiting its result produced and settle the status `finish` left open.
+    /// A job that failed before `finish` is settled by it instead
+    pub fn set_result(&self, job_id: &str, result: &anyhow::Result<serde_json::Value>) {
+        self.awaiting_result.lock().unwrap().remove(job_id);
+        let mut jobs = self.jobs.write().unwrap();
+        let Some(job) = jobs.iter_mut().rev().find(|job| job.job_id == job_id) else {
+            return;
+        };
+        if job.status == JobStatus::Cancelled {
+            return;
+        }
+        if let Ok(value) = result {
+            job.result = Some(value.clone());
+        }
+        if job.status == JobStatus::Running && job.finished_at.is_some() {
+            match result {
+                Ok(_) => {
+                    job.status = JobStatus::Completed;
+                    job.output_bytes = path_bytes(&job.output_path);
+                }
+                Err(e) => {
+                    job.status = JobStatus::Failed;
+                    job.error = Some(e.to_string());
+                }
+            }
+        }
+        self.persist(job);
+    }
+
+    /// Mark a queued or running job cancelled; returns the status it had, or `None` if there is
+    /// no such job. Finished jobs are left alone
+    pub fn cancel(&self, job_id: &str) -> Option<JobStatus> {
+        let mut jobs = self.jobs.write().unwrap();
+        let job = jobs.iter_mut().rev().find(|job| job.job_id == job_id)?;
+        let previous = job.status;
+        match previous {
+            JobStatus::Queued => {
+                job.status = JobStatus::Cancelled;
+                job.finished_at = Some(Utc::now());
+            }
+            // Finished for good once the processor has reaped its FFmpeg children
+            JobStatus::Running => job.status = JobStatus::Cancelled,
+            _ => return Some(previous),
+        }
+        self.persist(job);
+        Some(previous)
+    }
+
+    pub fn is_cancelled(&self, job_id: &str) -> bool {
+        let jobs = self.jobs.read().unwrap();
+        jobs.iter().rev().any(|job| job.job_id == job_id && job.status == JobStatus::Cancelled)
+    }
+
+    pub fn running(&self) -> Vec<JobRecord> {
+        self.with_status(JobStatus::Running)
+    }
+
+    /// Jobs waiting for a queue worker, oldest first
+    pub fn queued(&self) -> Vec<JobRecord> {
+        self.with_status(JobStatus::Queued)
+    }
+
+    fn with_status(&self, status: JobStatus) -> Vec<JobRecord> {
+        let jobs = self.jobs.read().unwrap();
+        jobs.iter().filter(|job| job.status == status).cloned().collect()
+    }
+
+    /// Counts, per-operation averages, last-hour throughput and the slowest of the recent jobs
+    pub fn stats(&self) -> JobStats {
+        let jobs = self.jobs.read().unwrap();
+        let hour_ago = Utc::now() - chrono::Duration::hours(1);
+        let count = |status: JobStatus| jobs.iter().filter(|job| job.status == status).count();
+
+        let operations = Self::group_stats(jobs.iter().map(|job| (job.job_type.as_str(), job)));
+        let tenants = Self::group_stats(
+            jobs.iter().filter_map(|job| Some((job.tenant.as_deref()?, job))),
+        );
+
+        let finished_last_hour = jobs
+            .iter()
+            .filter(|job| job.finished_at.is_some_and(|at| at >= hour_ago))
+            .count();
+
+        let mut slowest_recent: Vec<SlowJob> = jobs
+            .iter()
+            .rev()
+            .filter(|job| job.finished_at.is_some())
+            .take(RECENT_JOBS)
+            .filter_map(|job| {
+                Some(SlowJob {
+                    job_id: job.job_id.clone(),
+                    job_type: job.job_type.clone(),
+                    input_path: job.input_path.clone(),
+                    processing_time_ms: job.processing_time_ms?,
+                    created_at: job.created_at,
+                })
+            })
+            .collect();
+        slowest_recent.sort_by_key(|job| Reverse(job.processing_time_ms));
+        slowest_recent.truncate(SLOWEST_JOBS);
+
+        JobStats {
+            total: jobs.len(),
+            queued: count(JobStatus::Queued),
+            running: count(JobStatus::Running),
+            completed: count(JobStatus::Completed),
+            failed: count(JobStatus::Failed),
+            cancelled: count(JobStatus::Cancelled),
+            throughput_per_minute: finished_last_hour as f64 / 60.0,
+            operations,
+            tenants,
+            slowest_recent,
+        }
+    }
+
+    /// Counts and average completed processing time per group key
+    fn group_stats<'a>(jobs: impl Iterator<Item = (&'a str, &'a JobRecord)>) -> BTreeMap<String, OperationStats> {
+        let mut groups: BTreeMap<String, OperationStats> = BTreeMap::new();
+        let mut total_time: BTreeMap<&str, (u64, u64)> = BTreeMap::new();
+        for (key, job) in jobs {
+            let stats = groups.entry(key.to_string()).or_default();
+            stats.count += 1;
+            match job.status {
+                JobStatus::Completed => stats.completed += 1,
+                JobStatus::Failed => stats.failed += 1,
+                JobStatus::Queued | JobStatus::Running | JobStatus::Cancelled => {}
+            }
+            if let (JobStatus::Completed, Some(ms)) = (job.status, job.processing_time_ms) {
+                let (sum, n) = total_time.entry(key).or_default();
+                *sum += ms;
+                *n += 1;
+            }
+        }
+        for (key, (sum, n)) in total_time {
+            if let Some(stats) = groups.get_mut(key) {
+                stats.avg_processing_time_ms = Some(sum / n);
+            }
+        }
+        groups
+    }
+
+    /// Finished jobs and their input and output bytes per UTC day of finishing and tenant,
+    /// over the kept history; the query is expected to be validated
+    pub fn usage(&self, query: &UsageQuery) -> Vec<DailyUsage> {
+        let jobs = self.jobs.read().unwrap();
+        let mut days: BTreeMap<(chrono::NaiveDate, Option<&str>), DailyUsage> = BTreeMap::new();
+        for job in jobs.iter() {
+            let Some(date) = job.finished_at.map(|at| at.date_naive()) else {
+                continue;
+            };
+            if query.from.is_some_and(|from| date < from)
+                || query.to.is_some_and(|to| date > to)
+                || query.tenant.as_ref().is_some_and(|tenant| job.tenant.as_ref() != Some(tenant))
+            {
+                continue;
+            }
+            let usage = days.entry((date, job.tenant.as_deref())).or_insert_with(|| DailyUsage {
+                date,
+                tenant: job.tenant.clone(),
+                jobs: 0,
+                completed: 0,
+                input_bytes: 0,
+                output_bytes: 0,
+            });
+            usage.jobs += 1;
+            if job.status == JobStatus::Completed {
+                usage.completed += 1;
+            }
+            usage.input_bytes += job.input_bytes.unwrap_or(0);
+            usage.output_bytes += job.output_bytes.unwrap_or(0);
+        }
+        days.into_values().collect()
+    }
+
+    /// Filter, sort and paginate the history; the query is expected to be validated
+    pub fn query(&self, query: &JobQuery) -> JobListResponse {
+        let jobs = self.jobs.read().unwrap();
+        let mut matching: Vec<&JobRecord> = jobs
+            .iter()
+            .filter(|job| query.status.iter().all(|status| job.status == *status))
+            .filter(|job| query.job_type.iter().all(|job_type| job.job_type == *job_type))
+            .filter(|job| query.tenant.iter().all(|tenant| job.tenant.as_ref() == Some(tenant)))
+            .filter(|job| query.from.iter().all(|from| job.created_at >= *from))
+            .filter(|job| query.to.iter().all(|to| job.created_at <= *to))
+            .collect();
+
+        match query.sort.as_deref().unwrap_or("-created_at") {
+            "created_at" => matching.sort_by_key(|job| job.created_at),
+            "finished_at" => matching.sort_by_key(|job| job.finished_at),
+            "-finished_at" => matching.sort_by_key(|job| Reverse(job.finished_at)),
+            _ => matching.sort_by_key(|job| Reverse(job.created_at)),
+        }
+
+        let page = query.page.unwrap_or(1).max(1) as usize;
+        let per_page = query.per_page.map(|n| n as usize).unwrap_or(DEFAULT_PAGE_SIZE);
+        let total = matching.len();
+        let jobs = matching
+            .into_iter()
+            .skip((page - 1) * per_page)
+            .take(per_page)
+            .cloned()
+            .collect();
+
+        JobListResponse { jobs, page, per_page, total }
+    }
+}
+
+/// Size of the file at `path`, or of everything below it for a directory; `None` for
+/// anything not on local disk
+fn path_bytes(path: &str) -> Option<u64> {
+    let path = Path::new(path);
+    let metadata = std::fs::metadata(path).ok()?;
+    Some(if metadata.is_dir() { fs::dir_usage(path).0 } else { metadata.len() })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_query_filters_and_paginates() {
+        let store = JobStore::new();
+        for i in 0..5 {
+            let job_id = format!("job-{}", i);
+            store.start(&job_id, "video.transcode", "in.mp4", "out.mp4");
+            let result: anyhow::Result<()> = if i == 2 { Err(anyhow::anyhow!("boom")) } else { Ok(()) };
+            store.finish(&job_id, &result);
+        }
+        store.start("job-audio", "audio.extract", "in.mp4", "out.mp3");
+
+        let failed = store.query(&JobQuery { status: Some(JobStatus::Failed), ..Default::default() });
+        assert_eq!(failed.total, 1);
+        assert_eq!(failed.jobs[0].error.as_deref(), Some("boom"));
+
+        let page = store.query(&JobQuery {
+            job_type: Some("video.transcode".to_string()),
+            sort: Some("created_at".to_string()),
+            page: Some(2),
+            per_page: Some(2),
+            ..Default::default()
+        });
+        assert_eq!(page.total, 5);
+        let ids: Vec<&str> = page.jobs.iter().map(|job| job.job_id.as_str()).collect();
+        assert_eq!(ids, vec!["job-2", "job-3"]);
+    }
+
+    #[test]
+    fn test_stats_per_operation() {
+        let store = JobStore::new();
+        store.start("ok", "video.transcode", "a.mp4", "b.mp4");
+        store.finish("ok", &anyhow::Ok(()));
+        store.start("bad", "video.transcode", "a.mp4", "b.mp4");
+        store.finish::<()>("bad", &Err(anyhow::anyhow!("boom")));
+        store.start("busy", "audio.extract", "a.mp4", "b.mp3");
+
+        let stats = store.stats();
+        assert_eq!((stats.total, stats.running, stats.completed, stats.failed), (3, 1, 1, 1));
+        let transcode = &stats.operations["video.transcode"];
+        assert_eq!((transcode.count, transcode.completed, transcode.failed), (2, 1, 1));
+        assert!(transcode.avg_processing_time_ms.is_some());
+        assert_eq!(stats.operations["audio.extract"].avg_processing_time_ms, None);
+        assert_eq!(stats.slowest_recent.len(), 2);
+        assert_eq!(store.running()[0].job_id, "busy");
+
+        store.set_progress("busy", JobProgress::batch(1, 4, Some("a/b.jpg".to_string())));
+        let progress = store.running()[0].progress.clone().unwrap();
+        assert_eq!((progress.percent, progress.completed, progress.total), (25.0, Some(1), Some(4)));
+    }
+
+    #[tokio::test]
+    async fn test_usage_per_tenant_and_day() {
+        let dir = tempfile::tempdir().unwrap();
+        let (input, output) = (dir.path().join("in.mp4"), dir.path().join("out.mp4"));
+        std::fs::write(&input, vec![0u8; 100]).unwrap();
+        let (input, output) = (input.to_str().unwrap(), output.to_str().unwrap());
+        let store = JobStore::new();
+        auth::with_tenant(Some("team-a".to_string()), async {
+            store.start("a-1", "video.transcode", input, output);
+            std::fs::write(output, vec![0u8; 40]).unwrap();
+            store.finish("a-1", &anyhow::Ok(()));
+            store.start("a-2", "video.transcode", input, output);
+            store.finish::<()>("a-2", &Err(anyhow::anyhow!("boom")));
+        })
+        .await;
+        store.start("untenanted", "video.transcode", input, "missing.mp4");
+        store.finish("untenanted", &anyhow::Ok(()));
+        store.start("running", "video.transcode", input, output);
+
+        let usage = store.usage(&UsageQuery::default());
+        assert_eq!(usage.len(), 2);
+        let team = usage.iter().find(|day| day.tenant.as_deref() == Some("team-a")).unwrap();
+        assert_eq!((team.jobs, team.completed, team.input_bytes, team.output_bytes), (2, 1, 200, 40));
+        assert_eq!(team.date, Utc::now().date_naive());
+
+        let tomorrow = Utc::now().date_naive().succ_opt();
+        assert!(store.usage(&UsageQuery { from: tomorrow, ..Default::default() }).is_empty());
+        let only = store.usage(&UsageQuery { tenant: Some("team-b".to_string()), ..Default::default() });
+        assert!(only.is_empty());
+    }
+
+    #[test]
+    fn test_cancel_queued_and_running_jobs() {
+        let store = JobStore::new();
+        store.enqueue("waiting", "video.transcode", Priority::Normal, "a.mp4", "b.mp4", None);
+        store.start("busy", "video.transcode", "a.mp4", "b.mp4");
+        store.start("done", "video.transcode", "a.mp4", "b.mp4");
+        store.finish("done", &anyhow::Ok(()));
+
+        assert_eq!(store.cancel("waiting"), Some(JobStatus::Queued));
+        assert_eq!(store.cancel("busy"), Some(JobStatus::Running));
+        assert_eq!(store.cancel("done"), Some(JobStatus::Completed));
+        assert_eq!(store.cancel("missing"), None);
+
+        // Nothing ran for the queued job; the running one is closed once its FFmpeg exits
+        assert_eq!(store.finish::<()>("waiting", &Err(anyhow::anyhow!("killed"))), None);
+        assert!(store.get("busy").unwrap().finished_at.is_none());
+        assert!(store.finish::<()>("busy", &Err(anyhow::anyhow!("killed"))).is_some());
+        let busy = store.get("busy").unwrap();
+        assert_eq!((busy.status, busy.error), (JobStatus::Cancelled, None));
+        assert_eq!(store.get("done").unwrap().status, JobStatus::Completed);
+        assert_eq!(store.stats().cancelled, 2);
+    }
+
+    #[tokio::test]
+    async fn test_subscribers_see_progress_and_finish() {
+        let store = JobStore::new();
+        store.start("other", "audio.extract", "a.mp4", "b.mp3");
+        let mut updates = store.subscribe();
+        store.start("watched", "video.transcode", "a.mp4", "b.mp4");
+        store.set_progress("watched", JobProgress { percent: 40.0, eta_secs: Some(12.5), ..JobProgress::default() });
+        store.finish("watched", &anyhow::Ok(()));
+
+        let started = updates.recv().await.unwrap();
+        assert_eq!((started.job_id.as_str(), started.status), ("watched", JobStatus::Running));
+        let progress = updates.recv().await.unwrap().progress.unwrap();
+        assert_eq!((progress.percent, progress.eta_secs), (40.0, Some(12.5)));
+        assert_eq!(updates.recv().await.unwrap().status, JobStatus::Completed);
+    }
+
+    #[test]
+    fn test_file_backend_restores_latest_records() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("jobs.jsonl");
+        {
+            let store = JobStore::with_backend(Box::new(FileBackend::open(&path).unwrap())).unwrap();
+            store.start("done", "video.transcode", "a.mp4", "b.mp4");
+            store.finish("done", &anyhow::Ok(()));
+            store.start("cut-off", "audio.extract", "a.mp4", "b.mp3");
+            let request = serde_json::json!({ "input_path": "a.mp4", "output_path": "c.mp4" });
+            store.enqueue("resumable", "video.transcode", Priority::High, "a.mp4", "c.mp4", Some(request));
+        }
+        std::fs::OpenOptions::new().append(true).open(&path).unwrap().write_all(b"{\"job_id\":").unwrap();
+
+        let store = JobStore::with_backend(Box::new(FileBackend::open(&path).unwrap())).unwrap();
+        assert_eq!(store.get("done").unwrap().status, JobStatus::Completed);
+        let interrupted = store.get("cut-off").unwrap();
+        assert_eq!(interrupted.status, JobStatus::Failed);
+        assert!(store.get("missing").is_none());
+        // Compaction leaves one line per job
+        assert_eq!(std::fs::read_to_string(&path).unwrap().lines().count(), 3);
+
+        // Only the job that kept its request can be queued again, and only once
+        let resumable = store.take_interrupted();
+        assert_eq!(resumable.len(), 1);
+        assert_eq!(resumable[0].request.as_ref().unwrap()["output_path"], "c.mp4");
+        assert!(store.take_interrupted().is_empty());
+        assert!(store.requeue("resumable"));
+        let requeued = store.get("resumable").unwrap();
+        assert_eq!((requeued.status, requeued.error), (JobStatus::Queued, None));
+    }
+}
\ No newline at end of file
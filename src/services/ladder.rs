@@ -0,0 +1,142 @@
+use serde::{Deserialize, Serialize};
+use crate::services::hls;
+use crate::services::video_processor::{QualityProfile, QUALITY_PROFILES};
+use crate::utils::validation::{parse_resolution, Violations, VIDEO_CODECS};
+
+/// Renditions one ladder may have; they all encode at once
+pub const MAX_RUNGS: usize = 8;
+
+/// Longest label, which names the rendition's files
+const MAX_LABEL_LEN: usize = 32;
+
+/// One rendition of an adaptive bitrate ladder, e.g.
+/// `{"label": "720p", "resolution": "1280x720", "bitrate": "2.5M"}`; it is written as
+/// `<output>_<label>.mp4` and packaged as `<label>.m3u8`
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Rung {
+    pub label: String,
+    pub resolution: String,
+    pub bitrate: String,
+    /// Encoder of this rendition, in place of the request's `codec`
+    pub codec: Option<String>,
+}
+
+impl From<&QualityProfile> for Rung {
+    fn from(profile: &QualityProfile) -> Self {
+        Self {
+            label: profile.label.to_string(),
+            resolution: profile.resolution.to_string(),
+            bitrate: profile.bitrate.to_string(),
+            codec: None,
+        }
+    }
+}
+
+/// The built-in ladder, `QUALITY_PROFILES`
+pub fn default_ladder() -> Vec<Rung> {
+    QUALITY_PROFILES.iter().map(Rung::from).collect()
+}
+
+pub fn validate(ladder: &[Rung], violations: &mut Violations) {
+    if ladder.is_empty() || ladder.len() > MAX_RUNGS {
+        violations.add("ladder", format!("must have 1 to {} renditions", MAX_RUNGS));
+    }
+    for (index, rung) in ladder.iter().enumerate() {
+        let field = |name: &str| format!("ladder[{}].{}", index, name);
+        let label = &rung.label;
+        if label.is_empty()
+            || label.len() > MAX_LABEL_LEN
+            || !label.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        {
+            violations.add(&field("label"), format!("must be 1-{} letters, digits, '_' or '-'", MAX_LABEL_LEN));
+        } else if label == "master" || label.starts_with("audio_") {
+            // Names the packager gives the master playlist and the audio renditions
+            violations.add(&field("label"), "is reserved for the master and audio playlists");
+        } else if ladder[..index].iter().any(|other| other.label == *label) {
+            violations.add(&field("label"), "must be unique");
+        }
+        violations.resolution(&field("resolution"), Some(&rung.resolution));
+        violations.bitrate(&field("bitrate"), Some(&rung.bitrate));
+        violations.encoder(&field("codec"), rung.codec.as_deref(), VIDEO_CODECS);
+        if rung.codec.as_deref() == Some("copy") {
+            violations.add(&field("codec"), "renditions are re-encoded, so codec can't be copy");
+        }
+    }
+}
+
+/// Longer side first, so portrait and landscape frames compare alike
+fn landscape((width, height): (u32, u32)) -> (u32, u32) {
+    (width.max(height), width.min(height))
+}
+
+/// Per-title fit of `ladder` to a source of `source` dimensions: renditions larger than the
+/// source are dropped, since upscaling only spends bits on blur, and the rest are turned to
+/// the source's orientation. When every rendition is larger, the smallest is kept at the
+/// source's own size with its bit rate scaled down by pixel count
+pub fn fit(ladder: &[Rung], source: (u32, u32)) -> Vec<Rung> {
+    let portrait = source.1 > source.0;
+    let (long, short) = landscape(source);
+    let orient = |(width, height): (u32, u32)| if portrait { (height, width) } else { (width, height) };
+    let sized = |rung: &Rung| parse_resolution(&rung.resolution).map(landscape);
+    let fitting: Vec<Rung> = ladder
+        .iter()
+        .filter_map(|rung| {
+            let dimensions = sized(rung).filter(|&(width, height)| width <= long && height <= short)?;
+            let (width, height) = orient(dimensions);
+            Some(Rung { resolution: format!("{}x{}", width, height), ..rung.clone() })
+        })
+        .collect();
+    if !fitting.is_empty() {
+        return fitting;
+    }
+
+    let Some((smallest, (width, height))) = ladder
+        .iter()
+        .filter_map(|rung| Some((rung, sized(rung)?)))
+        .min_by_key(|(_, (width, height))| u64::from(*width) * u64::from(*height))
+    else {
+        return Vec::new();
+    };
+    // Encoders want even dimensions
+    let (long, short) = ((long & !1).max(2), (short & !1).max(2));
+    let share = (f64::from(long) * f64::from(short)) / (f64::from(width) * f64::from(height));
+    let bitrate = hls::parse_bitrate(&smallest.bitrate)
+        .map_or_else(|| smallest.bitrate.clone(), |bits| ((bits as f64 * share) as u64).max(1).to_string());
+    let (width, height) = orient((long, short));
+    vec![Rung { resolution: format!("{}x{}", width, height), bitrate, ..smallest.clone() }]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fit_ladder_to_source() {
+        let ladder = default_ladder();
+        let labels = |rungs: &[Rung]| rungs.iter().map(|rung| rung.label.clone()).collect::<Vec<_>>();
+        assert_eq!(labels(&fit(&ladder, (1920, 1080))), vec!["1080p", "720p", "480p"]);
+        assert_eq!(labels(&fit(&ladder, (854, 480))), vec!["480p"]);
+
+        // A phone's portrait clip keeps its 720p rendition, turned upright
+        let portrait = fit(&ladder, (720, 1280));
+        assert_eq!(labels(&portrait), vec!["720p", "480p"]);
+        assert_eq!(portrait[0].resolution, "720x1280");
+
+        // Smaller than every rendition: the smallest at the source's size, on a quarter of its bits
+        let tiny = fit(&ladder, (427, 241));
+        assert_eq!(tiny.len(), 1);
+        assert_eq!(tiny[0].resolution, "426x240");
+        assert_eq!(hls::parse_bitrate(&tiny[0].bitrate).map(|bits| bits / 1000), Some(249));
+
+        let mut violations = Violations::new();
+        validate(&ladder, &mut violations);
+        assert!(violations.into_result().is_ok());
+        let mut bad = ladder.clone();
+        bad[1].label = "1080p".to_string();
+        bad[2].label = "audio_0".to_string();
+        bad[0].resolution = "big".to_string();
+        let mut violations = Violations::new();
+        validate(&bad, &mut violations);
+        assert!(violations.into_result().is_err());
+    }
+}
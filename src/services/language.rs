@@ -0,0 +1,136 @@
+use anyhow::Result;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use crate::utils::error::{FfmpegErrorKind, FfmpegFailure};
+use crate::utils::{audit, sandbox};
+
+/// Seconds of speech Whisper looks at; its encoder window is 30 seconds
+pub const DEFAULT_SAMPLE_SECS: u32 = 30;
+
+/// Model file `WHISPER_MODEL` defaults to inside `MODEL_DIR`
+const DEFAULT_MODEL_FILE: &str = "ggml-base.bin";
+
+/// Share of a track skipped before sampling, past intros, music and silence
+const SKIP_SHARE: f64 = 0.1;
+
+/// whisper.cpp CLI and model used to identify spoken languages
+#[derive(Debug, Clone, PartialEq)]
+pub struct WhisperConfig {
+    pub binary: String,
+    pub model: PathBuf,
+    pub sample_secs: u32,
+}
+
+impl WhisperConfig {
+    /// `WHISPER_MODEL` (default: `ggml-base.bin` in `MODEL_DIR`), `WHISPER_BIN` (default:
+    /// `whisper-cli`) and `LANGUAGE_SAMPLE_SECS` (default: 30). `None` when there is no model
+    pub fn from_env() -> Option<Self> {
+        let model = match std::env::var("WHISPER_MODEL") {
+            Ok(model) => PathBuf::from(model),
+            Err(_) => Path::new(&std::env::var("MODEL_DIR").ok()?).join(DEFAULT_MODEL_FILE),
+        };
+        if !model.is_file() {
+            return None;
+        }
+        Some(Self {
+            binary: std::env::var("WHISPER_BIN").unwrap_or_else(|_| "whisper-cli".to_string()),
+            model,
+            sample_secs: std::env::var("LANGUAGE_SAMPLE_SECS")
+                .ok()
+                .and_then(|secs| secs.trim().parse().ok())
+                .map_or(DEFAULT_SAMPLE_SECS, |secs: u32| secs.clamp(5, 300)),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DetectedLanguage {
+    /// ISO 639-1 code, e.g. `en`
+    pub language: String,
+    pub probability: f64,
+}
+
+/// whisper.cpp's verdict: `whisper_full_with_state: auto-detected language: de (p = 0.973642)`
+pub fn parse_detected(stderr: &str) -> Option<DetectedLanguage> {
+    let line = stderr.lines().find_map(|line| line.split_once("auto-detected language: "))?.1;
+    let (language, rest) = line.split_once(' ')?;
+    let probability = rest.trim().strip_prefix("(p = ")?.strip_suffix(')')?.parse().ok()?;
+    Some(DetectedLanguage { language: language.to_string(), probability })
+}
+
+/// Where sampling starts on a track of `duration` seconds
+pub fn sample_start(duration: Option<f64>, sample_secs: u32) -> f64 {
+    match duration {
+        Some(duration) if duration > 2.0 * f64::from(sample_secs) => duration * SKIP_SHARE,
+        _ => 0.0,
+    }
+}
+
+/// Identify the language spoken on audio stream `stream_index` of `input`, sampling it into
+/// `work_dir`; `None` when Whisper found nothing to go on
+pub async fn detect(
+    job_id: &str,
+    input: &Path,
+    stream_index: u64,
+    duration: Option<f64>,
+    config: &WhisperConfig,
+    work_dir: &Path,
+) -> Result<Option<DetectedLanguage>> {
+    // Whisper reads 16 kHz mono PCM
+    let sample = work_dir.join(format!("language-{}.wav", stream_index));
+    let mut command = sandbox::command("ffmpeg");
+    command
+        .arg("-y")
+        .arg("-hide_banner")
+        .arg("-nostdin")
+        .arg("-ss").arg(sample_start(duration, config.sample_secs).to_string())
+        .arg("-t").arg(config.sample_secs.to_string())
+        .arg("-i").arg(input)
+        .arg("-map").arg(format!("0:{}", stream_index))
+        .arg("-ac").arg("1")
+        .arg("-ar").arg("16000")
+        .arg("-c:a").arg("pcm_s16le")
+        .arg(&sample);
+    let output = audit::output_async(Some(job_id), command).await?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(FfmpegFailure {
+            kind: FfmpegErrorKind::classify(&stderr),
+            message: format!("Audio sampling failed: {}", stderr.trim()),
+        }
+        .into());
+    }
+
+    let mut command = sandbox::command(&config.binary);
+    command
+        .arg("-m").arg(&config.model)
+        .arg("-l").arg("auto")
+        .arg("--detect-language")
+        .arg("-f").arg(&sample);
+    let output = audit::output_async(Some(job_id), command).await?;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if !output.status.success() {
+        anyhow::bail!("Language detection failed: {}", stderr.trim());
+    }
+    Ok(parse_detected(&stderr))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_detected_language() {
+        let stderr = "\
+whisper_init_from_file_with_params_no_state: loading model from 'ggml-base.bin'
+main: processing '.language-x.wav' (480000 samples, 30.0 sec), 4 threads, 1 processors, lang = auto, task = transcribe ...
+whisper_full_with_state: auto-detected language: de (p = 0.973642)
+";
+        assert_eq!(parse_detected(stderr), Some(DetectedLanguage { language: "de".to_string(), probability: 0.973642 }));
+        assert_eq!(parse_detected("main: processing ...\n"), None);
+
+        assert_eq!(sample_start(Some(600.0), 30), 60.0);
+        assert_eq!(sample_start(Some(45.0), 30), 0.0);
+        assert_eq!(sample_start(None, 30), 0.0);
+    }
+}
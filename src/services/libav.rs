@@ -0,0 +1,152 @@
+use anyhow::Result;
+use ffmpeg_next as ffmpeg;
+use ffmpeg::{codec, encoder, format, media, Rational};
+use std::path::Path;
+use crate::utils::error::ServiceError;
+
+fn seconds(timestamp: i64) -> f64 {
+    timestamp as f64 / f64::from(ffmpeg::ffi::AV_TIME_BASE)
+}
+
+fn rational(value: Rational) -> String {
+    format!("{}/{}", value.numerator(), value.denominator())
+}
+
+fn medium_name(medium: media::Type) -> &'static str {
+    match medium {
+        media::Type::Video => "video",
+        media::Type::Audio => "audio",
+        media::Type::Subtitle => "subtitle",
+        media::Type::Data => "data",
+        media::Type::Attachment => "attachment",
+        media::Type::Unknown => "unknown",
+    }
+}
+
+/// Probe through the linked libavformat, producing the subset of ffprobe's
+/// `-show_format -show_streams` JSON the service reads
+pub fn probe(path: &Path, count_packets: bool) -> Result<serde_json::Value> {
+    let mut input = format::input(&path)
+        .map_err(|e| ServiceError::CorruptInput(format!("Could not read headers of {}: {}", path.display(), e)))?;
+
+    let mut streams = Vec::new();
+    for stream in input.streams() {
+        let parameters = stream.parameters();
+        let medium = parameters.medium();
+        let mut info = serde_json::json!({
+            "index": stream.index(),
+            "codec_name": parameters.id().name(),
+            "codec_type": medium_name(medium),
+            "time_base": rational(stream.time_base()),
+            "avg_frame_rate": rational(stream.avg_frame_rate()),
+            "disposition": {
+                "attached_pic": i32::from(stream.disposition().contains(format::stream::Disposition::ATTACHED_PIC)),
+            },
+        });
+        let decoder = codec::context::Context::from_parameters(parameters)?.decoder();
+        match medium {
+            media::Type::Video => {
+                if let Ok(video) = decoder.video() {
+                    info["width"] = video.width().into();
+                    info["height"] = video.height().into();
+                }
+            }
+            media::Type::Audio => {
+                if let Ok(audio) = decoder.audio() {
+                    info["sample_rate"] = audio.rate().to_string().into();
+                    info["channels"] = audio.channels().into();
+                }
+            }
+            _ => {}
+        }
+        streams.push(info);
+    }
+
+    // Same as ffprobe -count_packets: demux everything, decode nothing
+    if count_packets {
+        let mut counts = vec![0u64; streams.len()];
+        for (stream, _) in input.packets() {
+            if let Some(count) = counts.get_mut(stream.index()) {
+                *count += 1;
+            }
+        }
+        for (info, count) in streams.iter_mut().zip(counts) {
+            info["nb_read_packets"] = count.to_string().into();
+        }
+    }
+
+    let container = input.format();
+    let mut format = serde_json::json!({
+        "filename": path.display().to_string(),
+        "nb_streams": streams.len(),
+        "format_name": container.name(),
+        "format_long_name": container.description(),
+    });
+    if input.duration() > 0 {
+        format["duration"] = format!("{:.6}", seconds(input.duration())).into();
+    }
+    if input.bit_rate() > 0 {
+        format["bit_rate"] = input.bit_rate().to_string().into();
+    }
+
+    Ok(serde_json::json!({ "streams": streams, "format": format }))
+}
+
+/// Copy the audio, video and subtitle streams of `input` into the container implied by
+/// `output`'s extension without re-encoding. `on_progress` gets the input timestamp in seconds
+/// and returns `false` to abandon the remux, leaving a partial output behind
+pub fn remux(input: &Path, output: &Path, mut on_progress: impl FnMut(f64) -> bool) -> Result<()> {
+    let mut ictx = format::input(&input)
+        .map_err(|e| ServiceError::CorruptInput(format!("Could not open {}: {}", input.display(), e)))?;
+    let mut octx = format::output(&output)
+        .map_err(|e| ServiceError::InvalidFormat(format!("Could not create {}: {}", output.display(), e)))?;
+
+    let stream_count = ictx.nb_streams() as usize;
+    let mut mapping: Vec<Option<usize>> = vec![None; stream_count];
+    let mut input_time_bases = vec![Rational(0, 1); stream_count];
+    let mut next_index = 0;
+    for (index, stream) in ictx.streams().enumerate() {
+        let medium = stream.parameters().medium();
+        if !matches!(medium, media::Type::Audio | media::Type::Video | media::Type::Subtitle) {
+            continue;
+        }
+        mapping[index] = Some(next_index);
+        input_time_bases[index] = stream.time_base();
+        next_index += 1;
+
+        let mut output_stream = octx.add_stream(encoder::find(codec::Id::None))?;
+        output_stream.set_parameters(stream.parameters());
+        // Let the muxer choose a codec tag that is valid for the output container
+        unsafe {
+            (*output_stream.parameters().as_mut_ptr()).codec_tag = 0;
+        }
+    }
+
+    octx.set_metadata(ictx.metadata().to_owned());
+    octx.write_header()?;
+
+    for (stream, mut packet) in ictx.packets() {
+        let index = stream.index();
+        let Some(output_index) = mapping.get(index).copied().flatten() else {
+            continue;
+        };
+        // The muxer may adjust time bases in write_header, so read them afterwards
+        let output_time_base = octx
+            .stream(output_index)
+            .map(|stream| stream.time_base())
+            .ok_or_else(|| anyhow::anyhow!("output stream {} missing", output_index))?;
+
+        if let Some(pts) = packet.pts() {
+            if !on_progress(pts as f64 * f64::from(input_time_bases[index])) {
+                return Err(anyhow::anyhow!("remux of {} stopped", input.display()));
+            }
+        }
+        packet.rescale_ts(input_time_bases[index], output_time_base);
+        packet.set_position(-1);
+        packet.set_stream(output_index);
+        packet.write_interleaved(&mut octx)?;
+    }
+
+    octx.write_trailer()?;
+    Ok(())
+}
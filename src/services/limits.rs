@@ -0,0 +1,177 @@
+use anyhow::Result;
+use log::warn;
+use serde::Deserialize;
+use std::path::Path;
+use crate::services::probe;
+use crate::utils::error::ServiceError;
+
+/// Resource limits applied to every input before it is decoded
+#[derive(Debug, Clone)]
+pub struct InputLimits {
+    pub max_file_bytes: u64,
+    /// Width × height of the first video/image stream
+    pub max_pixels: u64,
+    pub max_gif_frames: u64,
+    pub max_duration_secs: f64,
+}
+
+impl Default for InputLimits {
+    fn default() -> Self {
+        Self {
+            max_file_bytes: 4 * 1024 * 1024 * 1024, // 4GB
+            max_pixels: 100_000_000,                // 100MP
+            max_gif_frames: 1000,
+            max_duration_secs: 4.0 * 60.0 * 60.0, // 4h
+        }
+    }
+}
+
+impl InputLimits {
+    /// Read `MAX_INPUT_BYTES`, `MAX_INPUT_PIXELS`, `MAX_GIF_FRAMES` and `MAX_DURATION_SECS`
+    pub fn from_env() -> Self {
+        fn var<T: std::str::FromStr>(name: &str) -> Option<T> {
+            std::env::var(name).ok()?.trim().parse().ok()
+        }
+        let defaults = Self::default();
+        Self {
+            max_file_bytes: var("MAX_INPUT_BYTES").unwrap_or(defaults.max_file_bytes),
+            max_pixels: var("MAX_INPUT_PIXELS").unwrap_or(defaults.max_pixels),
+            max_gif_frames: var("MAX_GIF_FRAMES").unwrap_or(defaults.max_gif_frames),
+            max_duration_secs: var("MAX_DURATION_SECS").unwrap_or(defaults.max_duration_secs),
+        }
+    }
+
+    /// Fail fast on oversized inputs; only container/stream headers are read, nothing is decoded
+    pub async fn check(&self, job_id: Option<&str>, path: &Path) -> Result<()> {
+        let bytes = std::fs::metadata(path)?.len();
+        if bytes > self.max_file_bytes {
+            return Err(too_large(path, format!("{} bytes exceeds the {} byte limit", bytes, self.max_file_bytes)));
+        }
+
+        let summary = probe(job_id, path).await?;
+        self.check_summary(path, &summary)
+    }
+
+    fn check_summary(&self, path: &Path, summary: &ProbeSummary) -> Result<()> {
+        if let (Some(width), Some(height)) = (summary.width, summary.height) {
+            let pixels = width * height;
+            if pixels > self.max_pixels {
+                return Err(too_large(
+                    path,
+                    format!("{}x{} ({} pixels) exceeds the {} pixel limit", width, height, pixels, self.max_pixels),
+                ));
+            }
+        }
+        if let Some(frames) = summary.gif_frames {
+            if frames > self.max_gif_frames {
+                return Err(too_large(path, format!("GIF has {} frames, limit is {}", frames, self.max_gif_frames)));
+            }
+        }
+        if let Some(duration) = summary.duration {
+            if duration > self.max_duration_secs {
+                return Err(too_large(
+                    path,
+                    format!("duration {:.0}s exceeds the {:.0}s limit", duration, self.max_duration_secs),
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn too_large(path: &Path, reason: String) -> anyhow::Error {
+    warn!("Rejected input {}: {}", path.display(), reason);
+    ServiceError::InputTooLarge(format!("{}: {}", path.display(), reason)).into()
+}
+
+#[derive(Debug, Default, PartialEq)]
+struct ProbeSummary {
+    width: Option<u64>,
+    height: Option<u64>,
+    duration: Option<f64>,
+    gif_frames: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct ProbeOutput {
+    #[serde(default)]
+    streams: Vec<ProbeStream>,
+    format: Option<ProbeFormat>,
+}
+
+#[derive(Deserialize)]
+struct ProbeStream {
+    codec_type: Option<String>,
+    width: Option<u64>,
+    height: Option<u64>,
+    nb_read_packets: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ProbeFormat {
+    format_name: Option<String>,
+    duration: Option<String>,
+}
+
+async fn probe(job_id: Option<&str>, path: &Path) -> Result<ProbeSummary> {
+    let info = probe::probe(job_id, path).await?;
+    parse_probe(&info)
+}
+
+fn parse_probe(probe: &serde_json::Value) -> Result<ProbeSummary> {
+    let output = ProbeOutput::deserialize(probe)?;
+    let stream = output
+        .streams
+        .iter()
+        .find(|stream| stream.codec_type.as_deref() == Some("video"));
+    let format = output.format.as_ref();
+    let is_gif = format
+        .and_then(|format| format.format_name.as_deref())
+        .map(|name| name.split(',').any(|name| name == "gif"))
+        .unwrap_or(false);
+
+    Ok(ProbeSummary {
+        width: stream.and_then(|stream| stream.width),
+        height: stream.and_then(|stream| stream.height),
+        duration: format
+            .and_then(|format| format.duration.as_deref())
+            .and_then(|duration| duration.parse().ok()),
+        gif_frames: stream
+            .filter(|_| is_gif)
+            .and_then(|stream| stream.nb_read_packets.as_deref())
+            .and_then(|frames| frames.parse().ok()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_probe_gif() {
+        let json = r#"{
+            "streams": [
+                {"codec_type": "audio"},
+                {"codec_type": "video", "width": 480, "height": 270, "nb_read_packets": "1500"}
+            ],
+            "format": {"format_name": "gif", "duration": "60.000000"}
+        }"#;
+        let summary = parse_probe(&serde_json::from_str(json).unwrap()).unwrap();
+        assert_eq!(
+            summary,
+            ProbeSummary { width: Some(480), height: Some(270), duration: Some(60.0), gif_frames: Some(1500) }
+        );
+
+        let error = InputLimits::default().check_summary(Path::new("anim.gif"), &summary).unwrap_err();
+        assert_eq!(ServiceError::from(error).code(), "input_too_large");
+    }
+
+    #[test]
+    fn test_pixel_limit() {
+        let limits = InputLimits { max_pixels: 1920 * 1080, ..InputLimits::default() };
+        let hd = ProbeSummary { width: Some(1920), height: Some(1080), ..ProbeSummary::default() };
+        let bomb = ProbeSummary { width: Some(30_000), height: Some(30_000), ..ProbeSummary::default() };
+        assert!(limits.check_summary(Path::new("hd.png"), &hd).is_ok());
+        assert!(limits.check_summary(Path::new("bomb.png"), &bomb).is_err());
+    }
+}
@@ -0,0 +1,235 @@
+use anyhow::Result;
+use log::{info, warn};
+use std::path::Path;
+use std::sync::{Arc, OnceLock};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use crate::services::probe;
+use crate::utils::error::ServiceError;
+
+/// Budget accounting unit; permits are counted in MiB so large budgets fit a `u32`
+const MIB: u64 = 1024 * 1024;
+
+/// Decoded frames alive at once for a still image: decoder output, scaler output, encoder input
+const IMAGE_FRAMES: u64 = 3;
+/// Reference frames plus encoder lookahead for a video transcode
+const VIDEO_FRAMES: u64 = 16;
+
+/// Largest libavcodec `lowres` factor (1/8 per side), supported by the JPEG decoder only
+const MAX_LOWRES: u8 = 3;
+
+static BUDGET: OnceLock<MemoryBudget> = OnceLock::new();
+
+/// Decoded size of one frame from the probed dimensions and bit depth
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Footprint {
+    pub width: u64,
+    pub height: u64,
+    pub bit_depth: u64,
+    /// Baseline JPEG, which libavcodec can decode at 1/2, 1/4 or 1/8 size
+    pub jpeg: bool,
+}
+
+impl Footprint {
+    /// First video/image stream of an ffprobe result; `None` for audio-only inputs
+    pub fn from_probe(probe: &serde_json::Value) -> Option<Self> {
+        let stream = probe["streams"]
+            .as_array()?
+            .iter()
+            .find(|stream| stream["codec_type"] == "video")?;
+        let bit_depth = stream["bits_per_raw_sample"]
+            .as_str()
+            .and_then(|bits| bits.parse().ok())
+            .unwrap_or(8);
+        Some(Self {
+            width: stream["width"].as_u64()?,
+            height: stream["height"].as_u64()?,
+            bit_depth,
+            jpeg: stream["codec_name"] == "mjpeg",
+        })
+    }
+
+    /// Assumes four channels: RGBA is the widest pixel format the pipelines convert through.
+    /// Saturates, so a crafted header claiming absurd dimensions is rejected by the budget
+    /// instead of wrapping around to a small estimate
+    pub fn frame_bytes(&self) -> u64 {
+        self.width
+            .saturating_mul(self.height)
+            .saturating_mul(4)
+            .saturating_mul(self.bit_depth.div_ceil(8))
+    }
+}
+
+/// What an image job will be run with
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ImagePlan {
+    pub bytes: u64,
+    /// Value for FFmpeg's `-lowres` input option; 0 decodes at full size
+    pub lowres: u8,
+}
+
+/// Held for the lifetime of a job; dropping it returns the memory to the budget
+pub struct Reservation {
+    _permit: Option<OwnedSemaphorePermit>,
+}
+
+/// Process-wide memory budget shared by all FFmpeg jobs. Jobs whose estimate does not fit
+/// the remaining budget wait for running jobs to finish instead of risking an OOM kill
+pub struct MemoryBudget {
+    limit_bytes: Option<u64>,
+    /// Decode oversized JPEGs at reduced resolution instead of rejecting them
+    reduce_oversized: bool,
+    available: Arc<Semaphore>,
+}
+
+impl MemoryBudget {
+    pub fn new(limit_bytes: Option<u64>, reduce_oversized: bool) -> Self {
+        let permits = limit_bytes.map(|bytes| (bytes / MIB).min(Semaphore::MAX_PERMITS as u64)).unwrap_or(0);
+        Self {
+            limit_bytes,
+            reduce_oversized,
+            available: Arc::new(Semaphore::new(permits as usize)),
+        }
+    }
+
+    /// Read `MEMORY_BUDGET_MB` and `MEMORY_REDUCE_OVERSIZED`; without a budget nothing is accounted
+    pub fn from_env() -> Self {
+        let limit_mb = std::env::var("MEMORY_BUDGET_MB").ok().and_then(|v| v.trim().parse::<u64>().ok());
+        let reduce_oversized = std::env::var("MEMORY_REDUCE_OVERSIZED")
+            .map(|v| matches!(v.trim().to_lowercase().as_str(), "1" | "true" | "yes" | "on"))
+            .unwrap_or(false);
+        Self::new(limit_mb.map(|mb| mb * MIB), reduce_oversized)
+    }
+
+    /// Estimate for a still image, shrinking the decode when it alone would exceed the budget
+    pub fn plan_image(&self, footprint: &Footprint) -> ImagePlan {
+        let full = footprint.frame_bytes().saturating_mul(IMAGE_FRAMES);
+        let mut plan = ImagePlan { bytes: full, lowres: 0 };
+        let Some(limit) = self.limit_bytes else {
+            return plan;
+        };
+        if !self.reduce_oversized || !footprint.jpeg {
+            return plan;
+        }
+        // Each lowres step halves both sides, quartering the decoded frame
+        while plan.bytes > limit && plan.lowres < MAX_LOWRES {
+            plan.lowres += 1;
+            plan.bytes = full >> (2 * plan.lowres);
+        }
+        plan
+    }
+
+    /// Estimate for a video transcode
+    pub fn plan_video(&self, footprint: &Footprint) -> u64 {
+        footprint.frame_bytes().saturating_mul(VIDEO_FRAMES)
+    }
+
+    /// Wait until `bytes` fit the budget. Fails right away for jobs the budget can never fit,
+    /// since queueing them would block forever
+    pub async fn reserve(&self, job_id: Option<&str>, bytes: u64) -> Result<Reservation> {
+        let Some(limit) = self.limit_bytes else {
+            return Ok(Reservation { _permit: None });
+        };
+        let label = job_id.unwrap_or("-");
+        if bytes > limit {
+            warn!("[{}] Estimated {} MiB exceeds the {} MiB memory budget", label, bytes / MIB, limit / MIB);
+            return Err(ServiceError::InputTooLarge(format!(
+                "job needs an estimated {} MiB, the memory budget is {} MiB",
+                bytes / MIB,
+                limit / MIB
+            ))
+            .into());
+        }
+
+        let permits = bytes.div_ceil(MIB).max(1) as u32;
+        if self.available.available_permits() < permits as usize {
+            info!("[{}] Waiting for {} MiB of memory budget", label, permits);
+        }
+        let permit = self
+            .available
+            .clone()
+            .acquire_many_owned(permits)
+            .await
+            .map_err(|e| anyhow::anyhow!("memory budget closed: {}", e))?;
+        Ok(Reservation { _permit: Some(permit) })
+    }
+}
+
+/// The budget every processor in this process draws from
+pub fn budget() -> &'static MemoryBudget {
+    BUDGET.get_or_init(MemoryBudget::from_env)
+}
+
+/// Probe `path` and reserve memory for decoding it as a still image; returns the `-lowres` to use
+pub async fn reserve_image(job_id: Option<&str>, path: &Path) -> Result<(Reservation, u8)> {
+    let budget = budget();
+    let Some(footprint) = footprint(job_id, path).await? else {
+        return Ok((budget.reserve(job_id, 0).await?, 0));
+    };
+    let plan = budget.plan_image(&footprint);
+    if plan.lowres > 0 {
+        info!(
+            "[{}] Decoding {}x{} {} at 1/{} size to fit the memory budget",
+            job_id.unwrap_or("-"),
+            footprint.width,
+            footprint.height,
+            path.display(),
+            1 << plan.lowres
+        );
+    }
+    Ok((budget.reserve(job_id, plan.bytes).await?, plan.lowres))
+}
+
+/// Probe `path` and reserve memory for `encodes` simultaneous video encodes of it
+pub async fn reserve_video(job_id: Option<&str>, path: &Path, encodes: u64) -> Result<Reservation> {
+    let budget = budget();
+    let bytes = footprint(job_id, path)
+        .await?
+        .map(|footprint| budget.plan_video(&footprint) * encodes)
+        .unwrap_or(0);
+    budget.reserve(job_id, bytes).await
+}
+
+async fn footprint(job_id: Option<&str>, path: &Path) -> Result<Option<Footprint>> {
+    if budget().limit_bytes.is_none() {
+        return Ok(None);
+    }
+    let info = probe::probe(job_id, path).await?;
+    Ok(Footprint::from_probe(&info))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_footprint_and_lowres_plan() {
+        let probe = serde_json::json!({
+            "streams": [{ "codec_type": "video", "codec_name": "mjpeg", "width": 8000, "height": 6000, "bits_per_raw_sample": "8" }]
+        });
+        let footprint = Footprint::from_probe(&probe).unwrap();
+        assert_eq!(footprint.frame_bytes(), 8000 * 6000 * 4);
+
+        // 576 MB at full size, 144 MB at half size
+        let budget = MemoryBudget::new(Some(200 * MIB), true);
+        assert_eq!(budget.plan_image(&footprint).lowres, 1);
+        let strict = MemoryBudget::new(Some(200 * MIB), false);
+        assert_eq!(strict.plan_image(&footprint).lowres, 0);
+        let png = Footprint { jpeg: false, bit_depth: 16, ..footprint };
+        assert_eq!(budget.plan_image(&png), ImagePlan { bytes: 8000 * 6000 * 8 * IMAGE_FRAMES, lowres: 0 });
+
+        let absurd = Footprint { width: u64::MAX / 2, height: 3, ..footprint };
+        assert_eq!(budget.plan_video(&absurd), u64::MAX);
+    }
+
+    #[tokio::test]
+    async fn test_reservations_queue_and_reject() {
+        let budget = MemoryBudget::new(Some(100 * MIB), false);
+        let first = budget.reserve(None, 60 * MIB).await.unwrap();
+        assert!(budget.reserve(None, 101 * MIB).await.is_err());
+
+        let queued = tokio::time::timeout(std::time::Duration::from_millis(50), budget.reserve(None, 60 * MIB)).await;
+        assert!(queued.is_err());
+        drop(first);
+        assert!(budget.reserve(None, 60 * MIB).await.is_ok());
+    }
+}
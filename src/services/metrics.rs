@@ -0,0 +1,124 @@
+use serde::Serialize;
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::Mutex;
+
+/// Samples kept per bucket; percentiles describe recent behaviour, not all-time
+const MAX_SAMPLES: usize = 1000;
+
+/// Dimensions processing times are grouped by
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct MetricKey {
+    pub operation: String,
+    pub resolution: String,
+    pub codec: String,
+    pub input_size: &'static str,
+}
+
+impl MetricKey {
+    /// `resolution`/`codec` of `None` mean the source's own / FFmpeg's default
+    pub fn new(operation: &str, resolution: Option<&str>, codec: Option<&str>, input_bytes: Option<u64>) -> Self {
+        Self {
+            operation: operation.to_string(),
+            resolution: resolution.unwrap_or("source").to_string(),
+            codec: codec.unwrap_or("default").to_string(),
+            input_size: size_class(input_bytes),
+        }
+    }
+}
+
+fn size_class(bytes: Option<u64>) -> &'static str {
+    const MB: u64 = 1024 * 1024;
+    match bytes {
+        None => "unknown",
+        Some(b) if b < 10 * MB => "<10MB",
+        Some(b) if b < 100 * MB => "10-100MB",
+        Some(b) if b < 1024 * MB => "100MB-1GB",
+        Some(_) => ">1GB",
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct BucketStats {
+    pub operation: String,
+    pub resolution: String,
+    pub codec: String,
+    pub input_size: &'static str,
+    pub count: usize,
+    pub p50_ms: u64,
+    pub p90_ms: u64,
+    pub p99_ms: u64,
+    pub max_ms: u64,
+}
+
+/// Processing-time samples bucketed by operation, resolution, codec and input size
+#[derive(Default)]
+pub struct MetricsCollector {
+    buckets: Mutex<BTreeMap<MetricKey, VecDeque<u64>>>,
+}
+
+impl MetricsCollector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&self, key: MetricKey, processing_time_ms: u64) {
+        let mut buckets = self.buckets.lock().unwrap();
+        let samples = buckets.entry(key).or_default();
+        if samples.len() == MAX_SAMPLES {
+            samples.pop_front();
+        }
+        samples.push_back(processing_time_ms);
+    }
+
+    pub fn snapshot(&self) -> Vec<BucketStats> {
+        let buckets = self.buckets.lock().unwrap();
+        buckets
+            .iter()
+            .filter(|(_, samples)| !samples.is_empty())
+            .map(|(key, samples)| {
+                let mut sorted: Vec<u64> = samples.iter().copied().collect();
+                sorted.sort_unstable();
+                BucketStats {
+                    operation: key.operation.clone(),
+                    resolution: key.resolution.clone(),
+                    codec: key.codec.clone(),
+                    input_size: key.input_size,
+                    count: sorted.len(),
+                    p50_ms: percentile(&sorted, 50.0),
+                    p90_ms: percentile(&sorted, 90.0),
+                    p99_ms: percentile(&sorted, 99.0),
+                    max_ms: sorted[sorted.len() - 1],
+                }
+            })
+            .collect()
+    }
+}
+
+/// Nearest-rank percentile of an ascending, non-empty slice
+fn percentile(sorted: &[u64], p: f64) -> u64 {
+    let rank = ((p / 100.0) * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_percentiles_per_bucket() {
+        let collector = MetricsCollector::new();
+        let hd = MetricKey::new("video.transcode", Some("1280x720"), Some("libx264"), Some(5 * 1024 * 1024));
+        for ms in 1..=100 {
+            collector.record(hd.clone(), ms);
+        }
+        collector.record(MetricKey::new("audio.extract", None, None, None), 42);
+
+        let snapshot = collector.snapshot();
+        assert_eq!(snapshot.len(), 2);
+        let audio = &snapshot[0];
+        assert_eq!((audio.codec.as_str(), audio.input_size, audio.p99_ms), ("default", "unknown", 42));
+        let video = &snapshot[1];
+        assert_eq!(video.input_size, "<10MB");
+        assert_eq!((video.count, video.p50_ms, video.p90_ms, video.p99_ms, video.max_ms), (100, 50, 90, 99, 100));
+    }
+}
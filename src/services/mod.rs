@@ -1 +1,54 @@
-pub mod video_processor; 
\ No newline at end of file
+pub mod video_processor;
+pub mod sync_processor;
+pub mod capabilities;
+pub mod job_store;
+pub mod health;
+pub mod metrics;
+pub mod limits;
+pub mod url_signer;
+pub mod tenants;
+pub mod scanner;
+pub mod probe;
+pub mod libav;
+pub mod memory;
+pub mod benchmark;
+pub mod hls;
+pub mod queue;
+pub mod autotrim;
+pub mod watermark;
+pub mod blur;
+pub mod retry;
+pub mod filters;
+pub mod cover;
+pub mod sequence;
+pub mod sticker;
+pub mod slideshow;
+pub mod frames;
+pub mod remote;
+pub mod fingerprint;
+pub mod faces;
+pub mod language;
+pub mod temp_files;
+pub mod storage;
+pub mod result_cache;
+pub mod output_check;
+pub mod quality;
+pub mod sprites;
+pub mod scenes;
+pub mod fixtures;
+pub mod animation;
+pub mod overlay;
+pub mod subtitles;
+pub mod exif;
+pub mod privacy;
+pub mod panorama;
+pub mod hwaccel;
+pub mod orientation;
+pub mod ratecontrol;
+pub mod ladder;
+pub mod upscale;
+pub mod tonemap;
+#[cfg(test)]
+mod golden;
+#[cfg(test)]
+mod test_images;
\ No newline at end of file
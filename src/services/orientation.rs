@@ -0,0 +1,242 @@
+use serde::Serialize;
+use crate::services::watermark::Rgb;
+
+/// Height scans are analyzed at: body text of a 300 dpi page keeps lines over ten pixels high
+pub const ANALYSIS_HEIGHT: u32 = 1024;
+
+/// How much more striped one projection must be than the other to tell which way lines run
+const LINE_RATIO: f64 = 1.5;
+
+/// Ink a row needs, relative to the darkest row, to belong to a text line
+const LINE_THRESHOLD: f64 = 0.05;
+
+/// Ink a row needs, relative to its line's darkest, to belong to the x-height band
+const CORE_THRESHOLD: f64 = 0.5;
+
+/// Rows a text line must span; thinner runs are rules and specks
+const MIN_LINE_ROWS: usize = 4;
+
+/// Ascender/descender imbalance below which the direction is left undecided
+pub const MIN_CONFIDENCE: f64 = 0.2;
+
+/// Share of ink pixels outside which an image is not a text page: blank or a photograph
+const INK_RANGE: std::ops::RangeInclusive<f64> = 0.005..=0.4;
+
+/// Clockwise rotation, in degrees, that turns the content upright
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Orientation {
+    pub rotation: u32,
+    /// Ascender/descender imbalance of the lines, 0-1
+    pub confidence: f64,
+}
+
+impl Orientation {
+    /// `-vf` applying the rotation; `None` when the content already is upright
+    pub fn filter(&self) -> Option<&'static str> {
+        match self.rotation {
+            90 => Some("transpose=clock"),
+            180 => Some("hflip,vflip"),
+            270 => Some("transpose=cclock"),
+            _ => None,
+        }
+    }
+}
+
+/// Dark-on-light pixels of a page, thresholded by Otsu's method
+struct Ink {
+    width: usize,
+    height: usize,
+    pixels: Vec<bool>,
+}
+
+impl Ink {
+    fn new(image: &Rgb) -> Option<Self> {
+        let luma: Vec<u8> = image
+            .pixels
+            .chunks_exact(3)
+            .map(|pixel| ((299 * u32::from(pixel[0]) + 587 * u32::from(pixel[1]) + 114 * u32::from(pixel[2])) / 1000) as u8)
+            .collect();
+        let threshold = otsu(&luma);
+        let mut pixels: Vec<bool> = luma.iter().map(|&value| value <= threshold).collect();
+        // Light text on a dark ground
+        if pixels.iter().filter(|&&ink| ink).count() * 2 > pixels.len() {
+            pixels.iter_mut().for_each(|ink| *ink = !*ink);
+        }
+        let share = pixels.iter().filter(|&&ink| ink).count() as f64 / pixels.len().max(1) as f64;
+        INK_RANGE.contains(&share).then_some(Self { width: image.width, height: image.height, pixels })
+    }
+
+    fn rows(&self) -> Vec<f64> {
+        self.pixels.chunks(self.width).map(|row| row.iter().filter(|&&ink| ink).count() as f64).collect()
+    }
+
+    fn columns(&self) -> Vec<f64> {
+        let mut columns = vec![0.0; self.width];
+        for row in self.pixels.chunks(self.width) {
+            for (column, &ink) in columns.iter_mut().zip(row) {
+                *column += f64::from(u8::from(ink));
+            }
+        }
+        columns
+    }
+
+    /// Turned a quarter clockwise
+    fn rotated(&self) -> Self {
+        let (width, height) = (self.height, self.width);
+        let pixels = (0..width * height).map(|at| self.pixels[(self.height - 1 - at % width) * self.width + at / width]).collect();
+        Self { width, height, pixels }
+    }
+}
+
+/// Threshold maximizing the between-class variance of the luma histogram
+fn otsu(luma: &[u8]) -> u8 {
+    let mut histogram = [0u64; 256];
+    for &value in luma {
+        histogram[usize::from(value)] += 1;
+    }
+    let total = luma.len() as f64;
+    let sum: f64 = histogram.iter().enumerate().map(|(value, &count)| value as f64 * count as f64).sum();
+    let (mut below, mut below_sum, mut best, mut threshold) = (0.0, 0.0, 0.0, 0);
+    for (value, &count) in histogram.iter().enumerate() {
+        below += count as f64;
+        below_sum += value as f64 * count as f64;
+        let above = total - below;
+        if below == 0.0 || above == 0.0 {
+            continue;
+        }
+        let between = below * above * (below_sum / below - (sum - below_sum) / above).powi(2);
+        if between > best {
+            best = between;
+            threshold = value as u8;
+        }
+    }
+    threshold
+}
+
+/// Coefficient of variation of a projection: text lines alternate with blank gaps across
+/// them and blur together along them
+fn striping(profile: &[f64]) -> f64 {
+    let mean = profile.iter().sum::<f64>() / profile.len().max(1) as f64;
+    if mean == 0.0 {
+        return 0.0;
+    }
+    let variance = profile.iter().map(|value| (value - mean).powi(2)).sum::<f64>() / profile.len() as f64;
+    variance.sqrt() / mean
+}
+
+/// Ink above each line's x-height band against ink below it, -1 to 1. Latin script has far
+/// more ascenders and capitals than descenders, so upright lines come out positive
+fn asymmetry(rows: &[f64]) -> Option<f64> {
+    let peak = rows.iter().copied().fold(0.0, f64::max);
+    let (mut above, mut below) = (0.0, 0.0);
+    let mut start = 0;
+    while start < rows.len() {
+        if rows[start] <= peak * LINE_THRESHOLD {
+            start += 1;
+            continue;
+        }
+        let end = (start..rows.len()).find(|&row| rows[row] <= peak * LINE_THRESHOLD).unwrap_or(rows.len());
+        let line = &rows[start..end];
+        start = end;
+        if line.len() < MIN_LINE_ROWS {
+            continue;
+        }
+        let line_peak = line.iter().copied().fold(0.0, f64::max);
+        let core_top = line.iter().position(|&value| value >= line_peak * CORE_THRESHOLD).unwrap_or_default();
+        let core_bottom = line.iter().rposition(|&value| value >= line_peak * CORE_THRESHOLD).unwrap_or_default();
+        above += line[..core_top].iter().sum::<f64>();
+        below += line[core_bottom + 1..].iter().sum::<f64>();
+    }
+    (above + below > 0.0).then(|| (above - below) / (above + below))
+}
+
+/// Which way the text of a scanned page runs. `None` for pages without enough text lines to
+/// tell, such as photographs, and for scripts without an ascender/descender imbalance
+pub fn detect(image: &Rgb) -> Option<Orientation> {
+    let ink = Ink::new(image)?;
+    let (rows, columns) = (striping(&ink.rows()), striping(&ink.columns()));
+    let (base, lines) = if rows >= columns * LINE_RATIO {
+        (0, ink)
+    } else if columns >= rows * LINE_RATIO {
+        (90, ink.rotated())
+    } else {
+        return None;
+    };
+    let asymmetry = asymmetry(&lines.rows())?;
+    if asymmetry.abs() < MIN_CONFIDENCE {
+        return None;
+    }
+    let rotation = if asymmetry > 0.0 { base } else { base + 180 };
+    Some(Orientation { rotation, confidence: asymmetry.abs() })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::test_images::Lcg;
+
+    /// A page of text-like lines: x-height glyph boxes, three in ten with an ascender and one
+    /// in ten with a descender
+    fn page() -> Rgb {
+        let (width, height) = (360, 260);
+        let mut pixels = vec![250u8; width * height * 3];
+        let mut rng = Lcg::new(2024);
+        let mut ink = |x: usize, y: usize| pixels[(y * width + x) * 3..][..3].fill(20);
+        for line in 0..10 {
+            let top = 15 + line * 22;
+            let mut x = 20;
+            while x + 6 < width - 20 {
+                let roll = rng.below(20);
+                if roll == 0 {
+                    x += 5;
+                    continue;
+                }
+                for column in x..x + 5 {
+                    (top + 7..top + 14).for_each(|row| ink(column, row));
+                }
+                let stem = match roll {
+                    1..=6 => top + 1..top + 7,
+                    7..=8 => top + 14..top + 19,
+                    _ => 0..0,
+                };
+                for row in stem {
+                    ink(x, row);
+                    ink(x + 1, row);
+                }
+                x += 6;
+            }
+        }
+        Rgb { width, height, pixels }
+    }
+
+    /// `image` turned a quarter clockwise
+    fn rotate(image: &Rgb) -> Rgb {
+        let (width, height) = (image.height, image.width);
+        let mut pixels = Vec::with_capacity(image.pixels.len());
+        for at in 0..width * height {
+            let source = ((image.height - 1 - at % width) * image.width + at / width) * 3;
+            pixels.extend_from_slice(&image.pixels[source..source + 3]);
+        }
+        Rgb { width, height, pixels }
+    }
+
+    #[test]
+    fn test_detect_page_orientation() {
+        let upright = page();
+        let found = detect(&upright).unwrap();
+        assert_eq!(found.rotation, 0);
+        assert!(found.confidence > 0.5);
+        assert_eq!(found.filter(), None);
+
+        // Turned clockwise by a quarter N times, the page needs 4 - N quarters back
+        let mut turned = upright;
+        for expected in [270, 180, 90] {
+            turned = rotate(&turned);
+            assert_eq!(detect(&turned).map(|found| found.rotation), Some(expected));
+        }
+        assert_eq!(Orientation { rotation: 90, confidence: 1.0 }.filter(), Some("transpose=clock"));
+
+        let blank = Rgb { width: 64, height: 64, pixels: vec![255; 64 * 64 * 3] };
+        assert_eq!(detect(&blank), None);
+    }
+}
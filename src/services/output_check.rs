@@ -0,0 +1,155 @@
+use anyhow::Result;
+use log::info;
+use std::path::Path;
+use crate::services::probe;
+use crate::utils::error::{FfmpegErrorKind, FfmpegFailure};
+use crate::utils::{audit, sandbox};
+
+/// Share of the input's duration the output may differ by, when that is more than the
+/// absolute allowance
+const DURATION_DELTA_SHARE: f64 = 0.01;
+
+/// Checks run on a finished encode before it counts as done. FFmpeg exits 0 after warnings
+/// that leave truncated or undecodable files, so its exit status alone says too little
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutputCheck {
+    pub enabled: bool,
+    /// Seconds the output's duration may differ from the input's
+    pub max_duration_delta: f64,
+    /// Seconds decoded at the start and at the end of the output
+    pub decode_secs: f64,
+}
+
+impl Default for OutputCheck {
+    fn default() -> Self {
+        Self { enabled: true, max_duration_delta: 1.0, decode_secs: 2.0 }
+    }
+}
+
+impl OutputCheck {
+    /// `OUTPUT_VALIDATION` (`off` disables), `OUTPUT_MAX_DURATION_DELTA_SECS` (default: 1) and
+    /// `OUTPUT_DECODE_SECS` (default: 2)
+    pub fn from_env() -> Self {
+        fn var<T: std::str::FromStr>(name: &str) -> Option<T> {
+            std::env::var(name).ok()?.trim().parse().ok()
+        }
+        let defaults = Self::default();
+        let enabled = std::env::var("OUTPUT_VALIDATION")
+            .map_or(true, |value| !matches!(value.trim().to_lowercase().as_str(), "off" | "false" | "0"));
+        Self {
+            enabled,
+            max_duration_delta: var("OUTPUT_MAX_DURATION_DELTA_SECS").unwrap_or(defaults.max_duration_delta),
+            decode_secs: var("OUTPUT_DECODE_SECS").filter(|secs: &f64| *secs > 0.0).unwrap_or(defaults.decode_secs),
+        }
+    }
+
+    /// Fail unless `output` has every stream kind of `expected` that `input` has, about the
+    /// input's duration, and decodes cleanly at both ends
+    pub async fn verify(&self, job_id: &str, input: &str, output: &str, expected: &[&str]) -> Result<()> {
+        if !self.enabled {
+            return Ok(());
+        }
+        let duration = probe::duration(&*probe::probe(Some(job_id), Path::new(input)).await?);
+        self.verify_clip(job_id, input, output, expected, duration).await
+    }
+
+    /// `verify` for outputs covering part of the input: they should run about `duration`
+    /// seconds, or any length for `None`
+    pub async fn verify_clip(&self, job_id: &str, input: &str, output: &str, expected: &[&str], duration: Option<f64>) -> Result<()> {
+        if !self.enabled {
+            return Ok(());
+        }
+        let input_probe = probe::probe(Some(job_id), Path::new(input)).await?;
+        let output_probe = probe::probe(Some(job_id), Path::new(output)).await.map_err(|e| invalid(output, &e.to_string()))?;
+        compare(&input_probe, &output_probe, expected, duration, self.max_duration_delta)
+            .map_err(|reason| invalid(output, &reason))?;
+
+        for (end, seek) in [("start", None), ("end", Some(format!("-{}", self.decode_secs)))] {
+            let mut command = sandbox::command("ffmpeg");
+            command.arg("-v").arg("error").arg("-nostdin");
+            if let Some(seek) = &seek {
+                command.arg("-sseof").arg(seek);
+            }
+            command
+                .arg("-i").arg(output)
+                .arg("-t").arg(self.decode_secs.to_string())
+                .arg("-f").arg("null")
+                .arg("-");
+            let decoded = audit::output_async(Some(job_id), command).await?;
+            let stderr = String::from_utf8_lossy(&decoded.stderr);
+            if !decoded.status.success() || !stderr.trim().is_empty() {
+                let detail = stderr.lines().next().unwrap_or("decoding failed");
+                return Err(invalid(output, &format!("does not decode at its {}: {}", end, detail)));
+            }
+        }
+        info!("[{}] Output {} passed validation", job_id, output);
+        Ok(())
+    }
+}
+
+/// Compare the probes of a job's input and output: each kind in `expected` present in the
+/// input must be in the output, whose duration may differ from `duration` by `max_delta`
+/// seconds or 1%
+pub fn compare(
+    input: &serde_json::Value,
+    output: &serde_json::Value,
+    expected: &[&str],
+    duration: Option<f64>,
+    max_delta: f64,
+) -> Result<(), String> {
+    let has = |probe: &serde_json::Value, kind: &str| {
+        probe["streams"]
+            .as_array()
+            .is_some_and(|streams| streams.iter().any(|stream| stream["codec_type"] == kind))
+    };
+    if let Some(kind) = expected.iter().find(|kind| has(input, kind) && !has(output, kind)) {
+        return Err(format!("has no {} stream", kind));
+    }
+
+    let Some(output_duration) = probe::duration(output).filter(|duration| *duration > 0.0) else {
+        return Err("has no duration".to_string());
+    };
+    if let Some(duration) = duration {
+        let allowed = max_delta.max(duration * DURATION_DELTA_SHARE);
+        if (duration - output_duration).abs() > allowed {
+            return Err(format!(
+                "runs {:.2}s against the expected {:.2}s, more than {:.2}s apart",
+                output_duration, duration, allowed
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn invalid(output: &str, reason: &str) -> anyhow::Error {
+    FfmpegFailure {
+        kind: FfmpegErrorKind::Other,
+        message: format!("Output validation failed: {} {}", output, reason),
+    }
+    .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compare_streams_and_duration() {
+        let probe = |duration: &str, kinds: &[&str]| {
+            let streams: Vec<_> = kinds.iter().map(|kind| serde_json::json!({ "codec_type": kind })).collect();
+            serde_json::json!({ "format": { "duration": duration }, "streams": streams })
+        };
+        let input = probe("120.0", &["video", "audio"]);
+        let full = Some(120.0);
+        assert_eq!(compare(&input, &probe("120.4", &["video", "audio"]), &["video", "audio"], full, 1.0), Ok(()));
+        assert_eq!(compare(&input, &probe("120.0", &["video"]), &["video", "audio"], full, 1.0), Err("has no audio stream".to_string()));
+        assert_eq!(compare(&input, &probe("120.0", &["audio"]), &["audio"], full, 1.0), Ok(()));
+        assert!(compare(&input, &probe("61.0", &["video", "audio"]), &["video", "audio"], full, 1.0).unwrap_err().starts_with("runs 61.00s"));
+        // Long inputs get 1%, not the absolute allowance
+        assert_eq!(compare(&probe("3600", &["video"]), &probe("3630", &["video"]), &["video"], Some(3600.0), 1.0), Ok(()));
+        assert_eq!(compare(&input, &probe("0", &["video"]), &["video"], full, 1.0), Err("has no duration".to_string()));
+        // Clips are held to their own length, or to none
+        assert_eq!(compare(&input, &probe("10.2", &["video", "audio"]), &["video"], Some(10.0), 1.0), Ok(()));
+        assert_eq!(compare(&input, &probe("14.7", &["video", "audio"]), &["video"], None, 1.0), Ok(()));
+    }
+}
@@ -0,0 +1,124 @@
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Opacity of a visible watermark when the request doesn't set one
+pub const DEFAULT_OPACITY: f64 = 0.8;
+
+/// Width of an image watermark, relative to the video's, when the request doesn't set it
+pub const DEFAULT_IMAGE_SCALE: f64 = 0.15;
+
+/// Height of a text watermark's line, relative to the video's, when the request doesn't set it
+pub const DEFAULT_TEXT_SCALE: f64 = 0.05;
+
+/// Pixels between a watermark and the edges it is anchored to
+pub const DEFAULT_MARGIN: u32 = 16;
+
+/// Longest text a watermark may carry
+pub const MAX_TEXT_LENGTH: usize = 200;
+
+/// Where a visible watermark sits on the frame
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WatermarkPosition {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    #[default]
+    BottomRight,
+    Center,
+}
+
+impl WatermarkPosition {
+    /// `x` and `y` expressions placing an `inner`-sized mark on an `outer`-sized frame, in the
+    /// variable names of the filter evaluating them
+    fn offsets(self, margin: u32, (outer_w, outer_h): (&str, &str), (inner_w, inner_h): (&str, &str)) -> (String, String) {
+        let near = margin.to_string();
+        let far = |outer: &str, inner: &str| format!("{}-{}-{}", outer, inner, margin);
+        let middle = |outer: &str, inner: &str| format!("({}-{})/2", outer, inner);
+        match self {
+            WatermarkPosition::TopLeft => (near.clone(), near),
+            WatermarkPosition::TopRight => (far(outer_w, inner_w), near),
+            WatermarkPosition::BottomLeft => (near, far(outer_h, inner_h)),
+            WatermarkPosition::BottomRight => (far(outer_w, inner_w), far(outer_h, inner_h)),
+            WatermarkPosition::Center => (middle(outer_w, inner_w), middle(outer_h, inner_h)),
+        }
+    }
+}
+
+/// How a watermark looks and when it shows
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Placement {
+    pub position: WatermarkPosition,
+    pub opacity: f64,
+    pub margin: u32,
+    /// Seconds the mark is shown from, and until; the whole video without them
+    pub start: Option<f64>,
+    pub end: Option<f64>,
+}
+
+impl Placement {
+    /// Timeline option limiting the filter to the window; both filters take `enable`
+    fn enable(&self) -> String {
+        match (self.start, self.end) {
+            (None, None) => String::new(),
+            (start, Some(end)) => format!(":enable='between(t,{},{})'", start.unwrap_or(0.0), end),
+            (Some(start), None) => format!(":enable='gte(t,{})'", start),
+        }
+    }
+}
+
+/// `-filter_complex` graph ending in `[v]` that scales input 1 to `width` pixels, fades it to
+/// the placement's opacity and overlays it. A still image is a single frame, which `overlay`
+/// keeps showing for the rest of the video
+pub fn image_filter(width: u32, placement: &Placement) -> String {
+    let (x, y) = placement.position.offsets(placement.margin, ("W", "H"), ("w", "h"));
+    format!(
+        "[1:v]scale={}:-1,format=rgba,colorchannelmixer=aa={}[mark];[0:v][mark]overlay=x={}:y={}{}[v]",
+        width,
+        placement.opacity,
+        x,
+        y,
+        placement.enable()
+    )
+}
+
+/// `-filter_complex` graph ending in `[v]` that draws the text of `text_file` with a thin dark
+/// outline, so it stays legible on light and dark footage. The text is read from a file and
+/// drawn with `expansion=none`, so no character of it can end the option or expand as a
+/// `%{...}` sequence
+pub fn text_filter(text_file: &Path, font_size: u32, color: &str, placement: &Placement) -> String {
+    let (x, y) = placement.position.offsets(placement.margin, ("w", "h"), ("text_w", "text_h"));
+    format!(
+        "[0:v]drawtext=textfile='{}':expansion=none:fontsize={}:fontcolor={}@{opacity}:borderw=2:bordercolor=black@{opacity}:x={}:y={}{}[v]",
+        text_file.display(),
+        font_size,
+        color,
+        x,
+        y,
+        placement.enable(),
+        opacity = placement.opacity
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_overlay_filters() {
+        let placement = Placement { position: WatermarkPosition::BottomRight, opacity: 0.5, margin: 16, start: None, end: None };
+        assert_eq!(
+            image_filter(192, &placement),
+            "[1:v]scale=192:-1,format=rgba,colorchannelmixer=aa=0.5[mark];[0:v][mark]overlay=x=W-w-16:y=H-h-16[v]"
+        );
+
+        let windowed = Placement { position: WatermarkPosition::Center, start: Some(2.5), end: Some(10.0), ..placement };
+        assert!(image_filter(64, &windowed).ends_with("overlay=x=(W-w)/2:y=(H-h)/2:enable='between(t,2.5,10)'[v]"));
+        let until = Placement { position: WatermarkPosition::TopLeft, start: None, end: Some(3.0), ..placement };
+        assert!(image_filter(64, &until).ends_with("overlay=x=16:y=16:enable='between(t,0,3)'[v]"));
+
+        let text = text_filter(Path::new("/tmp/job/mark.txt"), 36, "white", &Placement { position: WatermarkPosition::TopRight, start: Some(5.0), ..placement });
+        assert!(text.starts_with("[0:v]drawtext=textfile='/tmp/job/mark.txt':expansion=none:fontsize=36:fontcolor=white@0.5:"));
+        assert!(text.ends_with(":x=w-text_w-16:y=16:enable='gte(t,5)'[v]"));
+    }
+}
@@ -0,0 +1,256 @@
+use serde::Serialize;
+use crate::services::watermark::Rgb;
+
+/// Height images are compared at; panning shots overlap in their structure, which survives
+/// the downscale, and the search stays cheap enough to try every pair
+pub const ANALYSIS_HEIGHT: u32 = 96;
+
+/// Images one analysis may order; the ordering tries every chain through them
+pub const MAX_IMAGES: usize = 12;
+
+/// Correlation every neighbouring pair needs for the set to count as stitchable
+pub const DEFAULT_MIN_SCORE: f64 = 0.5;
+
+/// Overlaps searched, as fractions of the narrower image's width. Below the minimum there is
+/// too little shared to align on; above the maximum the shots are near duplicates
+const MIN_OVERLAP: f64 = 0.1;
+const MAX_OVERLAP: f64 = 0.7;
+
+/// Vertical drift searched, as a fraction of the height, for handheld pans
+const MAX_DRIFT: f64 = 0.1;
+
+/// Overlapping areas flatter than this, such as clear sky, can't be aligned
+const MIN_VARIANCE: f64 = 1e-3;
+
+/// Luma gradient magnitude of an image. Edges line up between shots of a pan while exposure
+/// and white balance, which cameras adjust from shot to shot, cancel out
+pub struct EdgeMap {
+    width: usize,
+    height: usize,
+    values: Vec<f32>,
+}
+
+impl EdgeMap {
+    pub fn new(image: &Rgb) -> Self {
+        let (width, height) = (image.width, image.height);
+        let luma: Vec<f32> = image
+            .pixels
+            .chunks_exact(3)
+            .map(|pixel| 0.299 * f32::from(pixel[0]) + 0.587 * f32::from(pixel[1]) + 0.114 * f32::from(pixel[2]))
+            .collect();
+        let mut values = vec![0.0; width * height];
+        // Central differences; the one-pixel border stays 0 and is left out of comparisons
+        for y in 1..height.saturating_sub(1) {
+            for x in 1..width.saturating_sub(1) {
+                let at = y * width + x;
+                let dx = luma[at + 1] - luma[at - 1];
+                let dy = luma[at + width] - luma[at - width];
+                values[at] = (dx * dx + dy * dy).sqrt() / 255.0;
+            }
+        }
+        Self { width, height, values }
+    }
+}
+
+/// Where one image continues another to the right
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Overlap {
+    /// Share of the left image's width the right one covers
+    pub fraction: f64,
+    /// How much lower the right image's framing sits, as a fraction of the height
+    pub drift: f64,
+    /// Normalized cross-correlation of the edges in the shared area, -1 to 1
+    pub score: f64,
+}
+
+/// One neighbouring pair of the ordered set, as indices of the analyzed images
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Link {
+    pub from: usize,
+    pub to: usize,
+    /// `None` when the two share no area that could be aligned
+    pub overlap: Option<Overlap>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Analysis {
+    /// Indices of the analyzed images, left to right
+    pub order: Vec<usize>,
+    pub links: Vec<Link>,
+    pub stitchable: bool,
+}
+
+/// Correlation of the right `width` columns of `left` with the left `width` columns of
+/// `right` shifted down by `drift` rows, over the interior both share
+fn correlate(left: &EdgeMap, right: &EdgeMap, width: usize, drift: isize) -> Option<f64> {
+    let height = left.height.min(right.height) as isize;
+    let (top, bottom) = (1.max(1 + drift), (height - 1).min(height - 1 + drift));
+    if bottom - top < height / 2 || width < 4 {
+        return None;
+    }
+    let (mut sum_l, mut sum_r, mut sum_ll, mut sum_rr, mut sum_lr) = (0.0, 0.0, 0.0, 0.0, 0.0);
+    for y in top..bottom {
+        let left_row = &left.values[y as usize * left.width + left.width - width..][..width];
+        let right_row = &right.values[(y - drift) as usize * right.width..][..width];
+        for (&l, &r) in left_row[1..width - 1].iter().zip(&right_row[1..width - 1]) {
+            let (l, r) = (f64::from(l), f64::from(r));
+            sum_l += l;
+            sum_r += r;
+            sum_ll += l * l;
+            sum_rr += r * r;
+            sum_lr += l * r;
+        }
+    }
+    let count = ((bottom - top) as usize * (width - 2)) as f64;
+    let variance_l = sum_ll / count - (sum_l / count).powi(2);
+    let variance_r = sum_rr / count - (sum_r / count).powi(2);
+    if variance_l < MIN_VARIANCE || variance_r < MIN_VARIANCE {
+        return None;
+    }
+    let covariance = sum_lr / count - (sum_l / count) * (sum_r / count);
+    Some(covariance / (variance_l * variance_r).sqrt())
+}
+
+/// Best placement of `right` continuing `left` to the right, searched over every overlap
+/// width and drift in range
+pub fn overlap(left: &EdgeMap, right: &EdgeMap) -> Option<Overlap> {
+    let narrow = left.width.min(right.width);
+    let height = left.height.min(right.height);
+    let max_drift = (height as f64 * MAX_DRIFT) as isize;
+    let widths = (narrow as f64 * MIN_OVERLAP).ceil() as usize..=(narrow as f64 * MAX_OVERLAP) as usize;
+    let mut best: Option<Overlap> = None;
+    for width in widths {
+        for drift in -max_drift..=max_drift {
+            let Some(score) = correlate(left, right, width, drift) else {
+                continue;
+            };
+            if best.is_none_or(|best| score > best.score) {
+                let (fraction, drift) = (width as f64 / left.width as f64, drift as f64 / height as f64);
+                best = Some(Overlap { fraction, drift, score });
+            }
+        }
+    }
+    best
+}
+
+/// Chain through all images with the highest summed score, where `scores[a][b]` rates `b`
+/// continuing `a` and pairs without a score count as -1. Held-Karp over subsets, which
+/// `MAX_IMAGES` keeps small
+pub fn order(scores: &[Vec<Option<f64>>]) -> Vec<usize> {
+    let count = scores.len();
+    if count < 2 {
+        return (0..count).collect();
+    }
+    let full = (1usize << count) - 1;
+    // Per subset and last image: the best chain's total, and the image before the last
+    let mut best = vec![vec![None::<(f64, usize)>; count]; full + 1];
+    for start in 0..count {
+        best[1 << start][start] = Some((0.0, start));
+    }
+    for mask in 1..=full {
+        for last in 0..count {
+            let Some((total, _)) = best[mask][last] else {
+                continue;
+            };
+            for next in (0..count).filter(|next| mask & (1 << next) == 0) {
+                let candidate = total + scores[last][next].unwrap_or(-1.0);
+                let entry = &mut best[mask | 1 << next][next];
+                if entry.is_none_or(|(total, _)| candidate > total) {
+                    *entry = Some((candidate, last));
+                }
+            }
+        }
+    }
+
+    let total = |last: usize| best[full][last].map_or(f64::MIN, |(total, _)| total);
+    let mut last = (0..count).max_by(|&a, &b| total(a).total_cmp(&total(b))).unwrap_or_default();
+    let (mut mask, mut order) = (full, vec![last]);
+    while mask.count_ones() > 1 {
+        let Some((_, previous)) = best[mask][last] else {
+            break;
+        };
+        mask &= !(1 << last);
+        last = previous;
+        order.push(last);
+    }
+    order.reverse();
+    order
+}
+
+/// Order `maps` left to right and rate each neighbouring overlap; the set is stitchable when
+/// every neighbour scores at least `min_score`
+pub fn analyze(maps: &[EdgeMap], min_score: f64) -> Analysis {
+    let overlaps: Vec<Vec<Option<Overlap>>> = maps
+        .iter()
+        .enumerate()
+        .map(|(a, left)| maps.iter().enumerate().map(|(b, right)| if a == b { None } else { overlap(left, right) }).collect())
+        .collect();
+    let scores: Vec<Vec<Option<f64>>> =
+        overlaps.iter().map(|row| row.iter().map(|overlap| overlap.map(|overlap| overlap.score)).collect()).collect();
+    let order = order(&scores);
+    let links: Vec<Link> =
+        order.windows(2).map(|pair| Link { from: pair[0], to: pair[1], overlap: overlaps[pair[0]][pair[1]] }).collect();
+    let stitchable = !links.is_empty() && links.iter().all(|link| link.overlap.is_some_and(|overlap| overlap.score >= min_score));
+    Analysis { order, links, stitchable }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::test_images::Lcg;
+
+    /// Textured landscape from a fixed linear congruential sequence, smoothed so it has edges
+    /// at several scales rather than pure noise
+    fn scene(width: usize, height: usize, seed: u32) -> Vec<u8> {
+        let mut rng = Lcg::new(seed);
+        let noise: Vec<u32> = (0..width * height).map(|_| rng.below(256)).collect();
+        (0..width * height)
+            .map(|at| {
+                let (x, y) = (at % width, at / width);
+                let block = noise[(y / 6 * 6) * width + x / 6 * 6];
+                ((block + noise[at] / 4 + (x * 40 / width) as u32) / 2) as u8
+            })
+            .collect()
+    }
+
+    /// The `ANALYSIS_HEIGHT` rows of `scene` from `top`, columns from `left`, brightened by `gain`
+    fn view(scene: &[u8], scene_width: usize, left: usize, top: usize, width: usize, gain: u8) -> Rgb {
+        let height = ANALYSIS_HEIGHT as usize;
+        let pixels = (0..width * height)
+            .flat_map(|at| {
+                let value = scene[(top + at / width) * scene_width + left + at % width].saturating_add(gain);
+                [value, value, value]
+            })
+            .collect();
+        Rgb { width, height, pixels }
+    }
+
+    #[test]
+    fn test_orders_overlapping_views() {
+        let (width, height) = (300, ANALYSIS_HEIGHT as usize + 12);
+        let landscape = scene(width, height, 7);
+        // Three pans sharing 48 of 128 columns, drifting, with the middle one brighter
+        let views = [view(&landscape, width, 160, 2, 128, 0), view(&landscape, width, 0, 4, 128, 0), view(&landscape, width, 80, 8, 128, 30)];
+        let maps: Vec<EdgeMap> = views.iter().map(EdgeMap::new).collect();
+
+        let analysis = analyze(&maps, DEFAULT_MIN_SCORE);
+        assert_eq!(analysis.order, vec![1, 2, 0]);
+        assert!(analysis.stitchable);
+        let first = analysis.links[0].overlap.unwrap();
+        assert!((first.fraction - 48.0 / 128.0).abs() < 1e-9);
+        assert!((first.drift - 4.0 / ANALYSIS_HEIGHT as f64).abs() < 1e-9);
+        assert!(first.score > 0.95);
+        let second = analysis.links[1].overlap.unwrap();
+        assert!((second.drift + 6.0 / ANALYSIS_HEIGHT as f64).abs() < 1e-9);
+
+        // A shot of somewhere else breaks the chain
+        let elsewhere = scene(width, height, 99);
+        let mut maps = maps;
+        maps.push(EdgeMap::new(&view(&elsewhere, width, 40, 0, 128, 0)));
+        let analysis = analyze(&maps, DEFAULT_MIN_SCORE);
+        assert_eq!(analysis.order.len(), 4);
+        assert!(!analysis.stitchable);
+
+        assert_eq!(order(&[vec![None]]), vec![0]);
+    }
+}
@@ -0,0 +1,183 @@
+use serde::Serialize;
+use crate::services::exif;
+
+/// Mean Earth radius, in meters
+const EARTH_RADIUS_M: f64 = 6_371_008.8;
+
+/// Container tags carrying a recording location as ISO 6709, e.g. `+52.5200+013.4050/`;
+/// phones write the QuickTime key, FFmpeg's MP4 muxer the first two
+pub static LOCATION_TAGS: &[&str] = &["location", "location-eng", "com.apple.quicktime.location.ISO6709"];
+
+/// A circle around a sensitive place, such as someone's home
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct GeoFence {
+    pub latitude: f64,
+    pub longitude: f64,
+    pub radius_m: f64,
+}
+
+impl GeoFence {
+    /// Haversine distance from the center is within the radius
+    pub fn contains(&self, latitude: f64, longitude: f64) -> bool {
+        let (lat1, lat2) = (self.latitude.to_radians(), latitude.to_radians());
+        let (dlat, dlon) = (lat2 - lat1, (longitude - self.longitude).to_radians());
+        let a = (dlat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (dlon / 2.0).sin().powi(2);
+        2.0 * EARTH_RADIUS_M * a.sqrt().min(1.0).asin() <= self.radius_m
+    }
+}
+
+/// `PRIVACY_ZONES="52.5200:13.4050:500,48.8566:2.3522:250"`: latitude, longitude and radius in
+/// meters of each zone. Coordinates inside a zone are removed from extracted metadata and
+/// from the files jobs write; those elsewhere are left alone
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct PrivacyZones(Vec<GeoFence>);
+
+impl PrivacyZones {
+    pub fn from_env() -> Result<Self, String> {
+        Self::parse(&std::env::var("PRIVACY_ZONES").unwrap_or_default())
+    }
+
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let mut zones = Vec::new();
+        for entry in spec.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let fields: Vec<f64> = entry
+                .split(':')
+                .map(|field| field.trim().parse().map_err(|_| format!("invalid number in '{}'", entry)))
+                .collect::<Result<_, _>>()?;
+            let [latitude, longitude, radius_m] = fields[..] else {
+                return Err(format!("expected LATITUDE:LONGITUDE:RADIUS_M in '{}'", entry));
+            };
+            if !(-90.0..=90.0).contains(&latitude) || !(-180.0..=180.0).contains(&longitude) {
+                return Err(format!("coordinates out of range in '{}'", entry));
+            }
+            if radius_m.is_nan() || radius_m <= 0.0 {
+                return Err(format!("radius must be positive in '{}'", entry));
+            }
+            zones.push(GeoFence { latitude, longitude, radius_m });
+        }
+        Ok(Self(zones))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn contains(&self, latitude: f64, longitude: f64) -> bool {
+        self.0.iter().any(|zone| zone.contains(latitude, longitude))
+    }
+
+    /// Location tags of `tags`, an ffprobe `tags` object, whose coordinates fall inside a zone
+    fn zoned_tags(&self, tags: &serde_json::Value) -> Vec<String> {
+        let Some(tags) = tags.as_object() else {
+            return Vec::new();
+        };
+        tags.iter()
+            .filter(|(name, _)| LOCATION_TAGS.iter().any(|tag| tag.eq_ignore_ascii_case(name)))
+            .filter(|(_, value)| value.as_str().and_then(parse_iso6709).is_some_and(|(lat, lon)| self.contains(lat, lon)))
+            .map(|(name, _)| name.clone())
+            .collect()
+    }
+
+    /// Drop zoned location tags from an ffprobe result, at the container and stream level;
+    /// returns whether any were dropped
+    pub fn redact_probe(&self, probe: &mut serde_json::Value) -> bool {
+        let mut redacted = false;
+        let mut redact = |tags: &mut serde_json::Value| {
+            for name in self.zoned_tags(tags) {
+                if let Some(tags) = tags.as_object_mut() {
+                    redacted |= tags.remove(&name).is_some();
+                }
+            }
+        };
+        // Through `get_mut`, since indexing would add the keys a probe lacks
+        if let Some(streams) = probe.get_mut("streams").and_then(serde_json::Value::as_array_mut) {
+            for tags in streams.iter_mut().filter_map(|stream| stream.get_mut("tags")) {
+                redact(tags);
+            }
+        }
+        if let Some(tags) = probe.get_mut("format").and_then(|format| format.get_mut("tags")) {
+            redact(tags);
+        }
+        redacted
+    }
+
+    /// FFmpeg output options clearing the container-level location tags of the probed input
+    /// that fall inside a zone; FFmpeg otherwise copies them into every output
+    pub fn metadata_args(&self, probe: &serde_json::Value) -> Vec<String> {
+        self.zoned_tags(&probe["format"]["tags"])
+            .into_iter()
+            .flat_map(|name| ["-metadata".to_string(), format!("{}=", name)])
+            .collect()
+    }
+
+    /// Blank the EXIF GPS block of the JPEG at `path` if its coordinates fall inside a zone;
+    /// returns whether it was blanked
+    pub fn redact_jpeg(&self, path: &std::path::Path) -> std::io::Result<bool> {
+        if self.is_empty() {
+            return Ok(false);
+        }
+        let mut jpeg = std::fs::read(path)?;
+        let zoned = exif::gps(&jpeg).is_some_and(|(lat, lon)| self.contains(lat, lon));
+        if zoned && exif::strip_gps(&mut jpeg) {
+            std::fs::write(path, jpeg)?;
+            return Ok(true);
+        }
+        Ok(false)
+    }
+}
+
+/// Latitude and longitude of an ISO 6709 point in decimal degrees, e.g. `+52.5200+013.4050+034.000/`
+pub fn parse_iso6709(value: &str) -> Option<(f64, f64)> {
+    let value = value.trim().trim_end_matches('/');
+    let mut fields = Vec::with_capacity(3);
+    let mut start = 0;
+    for (index, c) in value.char_indices().skip(1) {
+        if matches!(c, '+' | '-') {
+            fields.push(&value[start..index]);
+            start = index;
+        }
+    }
+    fields.push(&value[start..]);
+    let [latitude, longitude, ..] = fields[..] else {
+        return None;
+    };
+    if !latitude.starts_with(['+', '-']) || !longitude.starts_with(['+', '-']) {
+        return None;
+    }
+    let (latitude, longitude): (f64, f64) = (latitude.parse().ok()?, longitude.parse().ok()?);
+    ((-90.0..=90.0).contains(&latitude) && (-180.0..=180.0).contains(&longitude)).then_some((latitude, longitude))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_zones_and_location_tags() {
+        let zones = PrivacyZones::parse("52.5200:13.4050:500, 48.8566:2.3522:250").unwrap();
+        // About 330 m east of the first center, and about 1.1 km north of it
+        assert!(zones.contains(52.52, 13.4099));
+        assert!(!zones.contains(52.53, 13.405));
+        assert!(PrivacyZones::parse("52.52:13.40").is_err());
+        assert!(PrivacyZones::parse("95:13.40:100").is_err());
+        assert!(PrivacyZones::parse("52.52:13.40:-1").is_err());
+        assert!(PrivacyZones::parse("").unwrap().is_empty());
+
+        assert_eq!(parse_iso6709("+52.5200+013.4050+034.000/"), Some((52.52, 13.405)));
+        assert_eq!(parse_iso6709("-33.8688+151.2093/"), Some((-33.8688, 151.2093)));
+        assert_eq!(parse_iso6709("52.52,13.40"), None);
+
+        let mut probe = serde_json::json!({
+            "format": { "tags": { "location": "+52.5201+013.4051/", "title": "Garden" } },
+            "streams": [{ "tags": { "com.apple.quicktime.location.ISO6709": "+48.8566+002.3522+035.000/" } }]
+        });
+        assert_eq!(zones.metadata_args(&probe), vec!["-metadata", "location="]);
+        assert!(zones.redact_probe(&mut probe));
+        assert_eq!(probe["format"]["tags"], serde_json::json!({ "title": "Garden" }));
+        assert_eq!(probe["streams"][0]["tags"], serde_json::json!({}));
+        assert!(!zones.redact_probe(&mut probe));
+
+        let elsewhere = serde_json::json!({ "format": { "tags": { "location": "+40.7128-074.0060/" } } });
+        assert!(zones.metadata_args(&elsewhere).is_empty());
+    }
+}
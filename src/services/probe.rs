@@ -0,0 +1,184 @@
+use anyhow::Result;
+use log::debug;
+use std::collections::{HashMap, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::SystemTime;
+use crate::services::libav;
+use crate::utils::error::ServiceError;
+use crate::utils::{audit, sandbox};
+
+/// Probes kept before the oldest is evicted
+const MAX_CACHED_PROBES: usize = 256;
+
+static CACHE: OnceLock<ProbeCache> = OnceLock::new();
+
+/// A cached probe is only reused while the file's size and mtime are unchanged
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct ProbeKey {
+    path: PathBuf,
+    len: u64,
+    modified: Option<SystemTime>,
+}
+
+impl ProbeKey {
+    fn of(path: &Path) -> std::io::Result<Self> {
+        let metadata = std::fs::metadata(path)?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            len: metadata.len(),
+            modified: metadata.modified().ok(),
+        })
+    }
+}
+
+/// ffprobe `-show_format -show_streams` output (or the libav equivalent), shared by the limit checks, duration lookups
+/// and `/video/info`, so one input costs one ffprobe spawn instead of three
+#[derive(Default)]
+struct ProbeCache {
+    entries: Mutex<CacheEntries>,
+}
+
+#[derive(Default)]
+struct CacheEntries {
+    probes: HashMap<ProbeKey, Arc<serde_json::Value>>,
+    /// Insertion order, oldest first
+    order: VecDeque<ProbeKey>,
+}
+
+impl ProbeCache {
+    fn get(&self, key: &ProbeKey) -> Option<Arc<serde_json::Value>> {
+        self.entries.lock().unwrap().probes.get(key).cloned()
+    }
+
+    fn insert(&self, key: ProbeKey, probe: Arc<serde_json::Value>) {
+        let mut entries = self.entries.lock().unwrap();
+        if entries.probes.insert(key.clone(), probe).is_none() {
+            entries.order.push_back(key);
+        }
+        while entries.order.len() > MAX_CACHED_PROBES {
+            if let Some(oldest) = entries.order.pop_front() {
+                entries.probes.remove(&oldest);
+            }
+        }
+    }
+}
+
+/// Format and stream metadata of `path`; GIFs also get `nb_read_packets` (their frame count).
+/// A cache miss reads the file, in-process on the blocking thread pool or through ffprobe
+pub async fn probe(job_id: Option<&str>, path: &Path) -> Result<Arc<serde_json::Value>> {
+    let cache = CACHE.get_or_init(ProbeCache::default);
+    let key = ProbeKey::of(path)
+        .map_err(|e| ServiceError::FileNotFound(format!("{}: {}", path.display(), e)))?;
+    if let Some(probe) = cache.get(&key) {
+        return Ok(probe);
+    }
+
+    let is_gif = path
+        .extension()
+        .map(|ext| ext.eq_ignore_ascii_case("gif"))
+        .unwrap_or(false);
+
+    // Without a sandbox there is no reason to pay for an ffprobe spawn; the subprocess stays
+    // as the fallback for anything the library reader rejects
+    let probe = if sandbox::is_enabled() {
+        run_ffprobe(job_id, path, is_gif).await?
+    } else {
+        let file = path.to_path_buf();
+        let probed = tokio::task::spawn_blocking(move || libav::probe(&file, is_gif))
+            .await
+            .unwrap_or_else(|e| Err(e.into()));
+        match probed {
+            Ok(probe) => probe,
+            Err(e) => {
+                debug!("[{}] In-process probe of {} failed, using ffprobe: {}", job_id.unwrap_or("-"), path.display(), e);
+                run_ffprobe(job_id, path, is_gif).await?
+            }
+        }
+    };
+    let probe = Arc::new(probe);
+    cache.insert(key, probe.clone());
+    Ok(probe)
+}
+
+async fn run_ffprobe(job_id: Option<&str>, path: &Path, is_gif: bool) -> Result<serde_json::Value> {
+    let mut command = sandbox::command("ffprobe");
+    command
+        .arg("-v").arg("error")
+        .arg("-show_format")
+        .arg("-show_streams")
+        .arg("-of").arg("json");
+    // Counting packets demuxes the whole file, which is still cheap next to decoding it
+    if is_gif {
+        command.arg("-count_packets");
+    }
+    command.arg(path);
+
+    let output = audit::output_async(job_id, command).await?;
+    if !output.status.success() {
+        return Err(ServiceError::CorruptInput(format!(
+            "Could not read headers of {}: {}",
+            path.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))
+        .into());
+    }
+    Ok(serde_json::from_slice(&output.stdout)?)
+}
+
+/// Container duration in seconds
+pub fn duration(probe: &serde_json::Value) -> Option<f64> {
+    probe["format"]["duration"].as_str()?.parse().ok()
+}
+
+/// Width and height of the first video stream that isn't a cover image
+pub fn dimensions(probe: &serde_json::Value) -> Option<(u32, u32)> {
+    let video = probe["streams"]
+        .as_array()?
+        .iter()
+        .find(|stream| stream["codec_type"] == "video" && stream["disposition"]["attached_pic"] != 1)?;
+    Some((video["width"].as_u64()? as u32, video["height"].as_u64()? as u32))
+}
+
+/// Whether the first video stream is stored turned, by a display matrix or a `rotate` tag;
+/// FFmpeg turns the EXIF orientation of photos into the former
+pub fn has_rotation(probe: &serde_json::Value) -> bool {
+    let Some(video) = probe["streams"].as_array().and_then(|streams| streams.iter().find(|stream| stream["codec_type"] == "video")) else {
+        return false;
+    };
+    let tagged = video["tags"]["rotate"].as_str().and_then(|rotate| rotate.parse::<f64>().ok()).is_some_and(|rotate| rotate != 0.0);
+    let matrix = video["side_data_list"]
+        .as_array()
+        .is_some_and(|side_data| side_data.iter().any(|entry| entry["rotation"].as_f64().is_some_and(|rotation| rotation != 0.0)));
+    tagged || matrix
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache_evicts_oldest() {
+        let cache = ProbeCache::default();
+        let key = |i: u64| ProbeKey { path: PathBuf::from("clip.mp4"), len: i, modified: None };
+        for i in 0..=MAX_CACHED_PROBES as u64 {
+            cache.insert(key(i), Arc::new(serde_json::json!({ "format": { "duration": "1.5" } })));
+        }
+        assert!(cache.get(&key(0)).is_none());
+        let latest = cache.get(&key(MAX_CACHED_PROBES as u64)).unwrap();
+        assert_eq!(duration(&latest), Some(1.5));
+    }
+
+    #[test]
+    fn test_has_rotation() {
+        let stream = |extra: serde_json::Value| {
+            let mut stream = serde_json::json!({ "codec_type": "video" });
+            stream.as_object_mut().unwrap().extend(extra.as_object().unwrap().clone());
+            serde_json::json!({ "streams": [stream] })
+        };
+        assert!(!has_rotation(&stream(serde_json::json!({}))));
+        assert!(has_rotation(&stream(serde_json::json!({ "side_data_list": [{ "side_data_type": "Display Matrix", "rotation": -90 }] }))));
+        assert!(!has_rotation(&stream(serde_json::json!({ "side_data_list": [{ "rotation": 0 }] }))));
+        assert!(has_rotation(&stream(serde_json::json!({ "tags": { "rotate": "180" } }))));
+    }
+}
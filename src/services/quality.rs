@@ -0,0 +1,190 @@
+use anyhow::Result;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::process::Command;
+use crate::utils::error::{FfmpegErrorKind, FfmpegFailure};
+use crate::utils::validation::{Violations, VIDEO_CODECS};
+use crate::utils::{audit, sandbox};
+
+/// Encoder an A/B side uses when it doesn't name one
+pub const DEFAULT_CODEC: &str = "libx264";
+
+/// Speed presets of the x264-style encoders
+pub static PRESETS: &[&str] = &[
+    "ultrafast", "superfast", "veryfast", "faster", "fast", "medium", "slow", "slower", "veryslow",
+];
+
+/// What `/video/compare` writes next to the encodes: one frame side by side, the whole clip, or nothing
+pub static COMPOSITES: &[&str] = &["image", "video", "none"];
+
+/// Encoder settings of one side of an A/B comparison
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct EncodeParams {
+    /// Video encoder, default `libx264`
+    pub codec: Option<String>,
+    pub bitrate: Option<String>,
+    /// Constant quality, lower is better; 0-63
+    pub crf: Option<u32>,
+    /// e.g. `medium` or `slow`
+    pub preset: Option<String>,
+    pub resolution: Option<String>,
+}
+
+impl EncodeParams {
+    pub fn codec(&self) -> &str {
+        self.codec.as_deref().unwrap_or(DEFAULT_CODEC)
+    }
+
+    pub fn validate(&self, field: &str, violations: &mut Violations) {
+        let field = |name: &str| format!("{}.{}", field, name);
+        violations.encoder(&field("codec"), self.codec.as_deref(), VIDEO_CODECS);
+        if self.codec.as_deref() == Some("copy") {
+            violations.add(&field("codec"), "comparing encodes needs re-encoding, so codec can't be copy");
+        }
+        violations.bitrate(&field("bitrate"), self.bitrate.as_deref());
+        violations.range(&field("crf"), self.crf, 0, 63);
+        violations.ffmpeg_token(&field("preset"), self.preset.as_deref(), PRESETS);
+        violations.resolution(&field("resolution"), self.resolution.as_deref());
+    }
+
+    /// Output options for these settings; audio is left out, only the picture is compared
+    pub fn apply(&self, command: &mut Command) {
+        command.arg("-c:v").arg(self.codec());
+        if let Some(bitrate) = &self.bitrate {
+            command.arg("-b:v").arg(bitrate);
+        }
+        if let Some(crf) = self.crf {
+            command.arg("-crf").arg(crf.to_string());
+        }
+        if let Some(preset) = &self.preset {
+            command.arg("-preset").arg(preset.to_lowercase());
+        }
+        if let Some(resolution) = &self.resolution {
+            command.arg("-s").arg(resolution);
+        }
+        command.arg("-an");
+    }
+}
+
+/// How close an encode is to its source
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct Scores {
+    /// Mean SSIM over all planes, 1 for identical pictures
+    pub ssim: Option<f64>,
+    /// Mean VMAF, 0-100; `None` when not asked for or FFmpeg was built without libvmaf
+    pub vmaf: Option<f64>,
+}
+
+/// The ssim filter's summary: `[Parsed_ssim_4 @ 0x...] SSIM Y:0.991 (20.6) U:0.995 (23.1) V:0.994 (22.7) All:0.992 (21.3)`
+pub fn parse_ssim(stderr: &str) -> Option<f64> {
+    let line = stderr.lines().rev().find(|line| line.contains("SSIM Y:"))?;
+    line.split_once(" All:")?.1.split_whitespace().next()?.parse().ok()
+}
+
+/// The libvmaf filter's summary: `[Parsed_libvmaf_5 @ 0x...] VMAF score: 93.412764`
+pub fn parse_vmaf(stderr: &str) -> Option<f64> {
+    let line = stderr.lines().rev().find_map(|line| line.split_once("VMAF score: "))?.1;
+    line.trim().parse().ok()
+}
+
+/// Score `encoded` against the first `secs` seconds of `source`, scaled to the source's
+/// `width`x`height` first since both metrics compare pictures of one size
+pub async fn score(job_id: &str, source: &Path, encoded: &Path, (width, height): (u32, u32), secs: f64, vmaf: bool) -> Result<Scores> {
+    if vmaf {
+        match run(job_id, source, encoded, width, height, secs, true).await {
+            Ok(stderr) => return Ok(Scores { ssim: parse_ssim(&stderr), vmaf: parse_vmaf(&stderr) }),
+            Err(e) if e.to_string().contains("No such filter: 'libvmaf'") => {
+                warn!("[{}] FFmpeg has no libvmaf, scoring SSIM only", job_id);
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    let stderr = run(job_id, source, encoded, width, height, secs, false).await?;
+    Ok(Scores { ssim: parse_ssim(&stderr), vmaf: None })
+}
+
+async fn run(job_id: &str, source: &Path, encoded: &Path, width: u32, height: u32, secs: f64, vmaf: bool) -> Result<String> {
+    // Both filters take the distorted picture first; timestamps are reset so frames pair up
+    let prepare = format!(
+        "[0:v]scale={}:{}:flags=bicubic,setpts=PTS-STARTPTS[encoded];[1:v]setpts=PTS-STARTPTS[source]",
+        width, height
+    );
+    let graph = if vmaf {
+        format!(
+            "{};[encoded]split[e1][e2];[source]split[s1][s2];[e1][s1]ssim;[e2][s2]libvmaf=n_threads=4",
+            prepare
+        )
+    } else {
+        format!("{};[encoded][source]ssim", prepare)
+    };
+    let mut command = sandbox::command("ffmpeg");
+    command
+        .arg("-hide_banner").arg("-nostdin")
+        .arg("-i").arg(encoded)
+        .arg("-t").arg(secs.to_string())
+        .arg("-i").arg(source)
+        .arg("-lavfi").arg(graph)
+        .arg("-f").arg("null")
+        .arg("-");
+    let output = audit::output_async(Some(job_id), command).await?;
+    let stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+    if !output.status.success() {
+        let detail = stderr.lines().rev().find(|line| !line.trim().is_empty()).unwrap_or_default();
+        return Err(FfmpegFailure {
+            kind: FfmpegErrorKind::classify(&stderr),
+            message: format!("Quality scoring failed: {}", detail.trim()),
+        }
+        .into());
+    }
+    Ok(stderr)
+}
+
+/// Write `a` and `b` side by side into `output`, scaled to `height`: the frame at `at` seconds
+/// for an image, or the whole clip for a video
+pub async fn composite(job_id: &str, a: &Path, b: &Path, height: u32, at: Option<f64>, output: &Path) -> Result<()> {
+    let mut command = sandbox::command("ffmpeg");
+    command.arg("-y").arg("-v").arg("error").arg("-nostdin");
+    for input in [a, b] {
+        if let Some(at) = at {
+            command.arg("-ss").arg(format!("{:.3}", at));
+        }
+        command.arg("-i").arg(input);
+    }
+    command.arg("-filter_complex").arg(format!(
+        "[0:v]scale=-2:{height}[a];[1:v]scale=-2:{height}[b];[a][b]hstack=inputs=2",
+        height = height
+    ));
+    match at {
+        Some(_) => command.arg("-frames:v").arg("1"),
+        None => command.arg("-c:v").arg(DEFAULT_CODEC).arg("-crf").arg("18").arg("-an"),
+    };
+    command.arg(output);
+    let result = audit::output_async(Some(job_id), command).await?;
+    if !result.status.success() {
+        let stderr = String::from_utf8_lossy(&result.stderr);
+        return Err(FfmpegFailure {
+            kind: FfmpegErrorKind::classify(&stderr),
+            message: format!("Composite failed: {}", stderr.trim()),
+        }
+        .into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_scores() {
+        let stderr = "\
+[Parsed_ssim_4 @ 0x5581c0] SSIM Y:0.991245 (20.578) U:0.995012 (23.021) V:0.994330 (22.466) All:0.992514 (21.257)
+[Parsed_libvmaf_5 @ 0x5581c8] VMAF score: 93.412764
+";
+        assert_eq!(parse_ssim(stderr), Some(0.992514));
+        assert_eq!(parse_vmaf(stderr), Some(93.412764));
+        assert_eq!(parse_ssim("frame=  240 fps=120\n"), None);
+        assert_eq!(parse_vmaf("frame=  240 fps=120\n"), None);
+    }
+}
@@ -0,0 +1,458 @@
+use anyhow::Result;
+use log::{info, warn};
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::{oneshot, Notify};
+use uuid::Uuid;
+use crate::middleware::{auth, request_id};
+use crate::models::job::{JobRecord, Priority};
+use crate::services::job_store::JobStore;
+use crate::utils::error::ServiceError;
+
+const DEFAULT_CAPACITY: usize = 1000;
+const DEFAULT_AGING_SECS: u64 = 60;
+
+type Task = Pin<Box<dyn Future<Output = Result<()>> + Send>>;
+
+tokio::task_local! {
+    static JOB_ID: String;
+}
+
+/// Id reserved for the job when it was queued, or a fresh one for work run inline (gRPC, CLI)
+pub fn job_id() -> String {
+    JOB_ID.try_with(|id| id.clone()).unwrap_or_else(|_| Uuid::new_v4().to_string())
+}
+
+/// A submitted job with the request context it has to run under
+struct QueuedJob {
+    job_id: String,
+    request_id: Option<String>,
+    tenant: Option<String>,
+    priority: Priority,
+    queued_at: Instant,
+    task: Task,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct QueueConfig {
+    /// Jobs run at once
+    pub workers: usize,
+    /// Jobs allowed to wait; submissions beyond this fail with `503 queue_full`
+    pub capacity: usize,
+    /// A waiting job is treated as one priority class higher for every `aging` it has waited
+    pub aging: Duration,
+}
+
+impl QueueConfig {
+    /// Read `QUEUE_WORKERS` (default: one per core), `QUEUE_CAPACITY` (default: 1000) and
+    /// `QUEUE_AGING_SECS` (default: 60)
+    pub fn from_env() -> Self {
+        let var = |name: &str| {
+            std::env::var(name)
+                .ok()
+                .and_then(|v| v.trim().parse::<usize>().ok())
+                .filter(|&n| n > 0)
+        };
+        Self {
+            workers: var("QUEUE_WORKERS")
+                .unwrap_or_else(|| std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1)),
+            capacity: var("QUEUE_CAPACITY").unwrap_or(DEFAULT_CAPACITY),
+            aging: Duration::from_secs(var("QUEUE_AGING_SECS").map_or(DEFAULT_AGING_SECS, |secs| secs as u64)),
+        }
+    }
+}
+
+/// Waiting jobs, one FIFO per priority class
+#[derive(Default)]
+struct Pending {
+    classes: [VecDeque<QueuedJob>; Priority::ALL.len()],
+}
+
+impl Pending {
+    fn len(&self) -> usize {
+        self.classes.iter().map(VecDeque::len).sum()
+    }
+
+    fn push(&mut self, job: QueuedJob) {
+        self.classes[job.priority as usize].push_back(job);
+    }
+
+    /// The oldest job of the class whose head ranks highest once aged, so a steady stream of
+    /// high-priority thumbnails can't hold back a low-priority transcode forever. Ties go to
+    /// the higher class
+    fn pop(&mut self, now: Instant, aging: Duration) -> Option<QueuedJob> {
+        let rank = |job: &QueuedJob| {
+            let waited = now.saturating_duration_since(job.queued_at);
+            let promotions = if aging.is_zero() { 0 } else { (waited.as_millis() / aging.as_millis()) as usize };
+            job.priority as usize + promotions
+        };
+        let (_, class) = (0..self.classes.len())
+            .filter_map(|class| self.classes[class].front().map(|job| (rank(job), class)))
+            .max()?;
+        self.classes[class].pop_front()
+    }
+}
+
+/// Bounded priority queue of processing jobs drained by a fixed pool of workers, so a burst
+/// of requests waits its turn instead of starting an FFmpeg process per request
+pub struct JobQueue {
+    pending: Arc<Mutex<Pending>>,
+    ready: Arc<Notify>,
+    config: QueueConfig,
+    busy: Arc<AtomicUsize>,
+    jobs: Arc<JobStore>,
+    /// Dropped with the queue, which stops its runtime
+    _shutdown: oneshot::Sender<()>,
+}
+
+impl JobQueue {
+    /// Spawn the workers on a multi-threaded runtime of their own. actix runs each HTTP worker
+    /// and the main system on a single-threaded runtime, where one job's blocking step would
+    /// stall every other job and the server with it
+    pub fn start(config: QueueConfig, jobs: Arc<JobStore>) -> Self {
+        let pending = Arc::new(Mutex::new(Pending::default()));
+        let ready = Arc::new(Notify::new());
+        let busy = Arc::new(AtomicUsize::new(0));
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .thread_name("job-queue")
+            .enable_all()
+            .build()
+            .expect("Failed to start job queue runtime");
+        for _ in 0..config.workers {
+            runtime.spawn(Self::work(pending.clone(), ready.clone(), config.aging, busy.clone(), jobs.clone()));
+        }
+        // Owned by a plain thread, since a runtime can't be dropped from async code
+        let (shutdown, stopped) = oneshot::channel::<()>();
+        std::thread::Builder::new()
+            .name("job-queue-runtime".to_string())
+            .spawn(move || {
+                runtime.block_on(async {
+                    stopped.await.ok();
+                });
+            })
+            .expect("Failed to start job queue thread");
+        info!("Job queue started with {} workers, {} slots", config.workers, config.capacity);
+        Self { pending, ready, config, busy, jobs, _shutdown: shutdown }
+    }
+
+    async fn work(
+        pending: Arc<Mutex<Pending>>,
+        ready: Arc<Notify>,
+        aging: Duration,
+        busy: Arc<AtomicUsize>,
+        jobs: Arc<JobStore>,
+    ) {
+        loop {
+            let next = pending.lock().unwrap().pop(Instant::now(), aging);
+            let Some(job) = next else {
+                // A push between the pop and this wait leaves a permit, so nothing is missed
+                ready.notified().await;
+                continue;
+            };
+            let QueuedJob { job_id, request_id, tenant, task, .. } = job;
+            if jobs.is_cancelled(&job_id) {
+                info!("[{}] Skipping job cancelled while queued", job_id);
+                continue;
+            }
+            busy.fetch_add(1, Ordering::SeqCst);
+            let task = JOB_ID.scope(job_id.clone(), request_id::scope(request_id, auth::with_tenant(tenant, task)));
+
+            // Run on its own task so a panicking job doesn't take the worker down with it
+            let result = match tokio::spawn(task).await {
+                Ok(result) => result,
+                Err(e) => Err(anyhow::anyhow!("job panicked: {}", e)),
+            };
+            if let Err(e) = &result {
+                warn!("[{}] Queued job failed: {}", job_id, e);
+            }
+            // Jobs that failed before the processor opened their record (e.g. a missing input)
+            // or that panicked would otherwise stay queued or running forever
+            if jobs.get(&job_id).is_some_and(|job| job.finished_at.is_none()) {
+                jobs.finish(&job_id, &result);
+            }
+            busy.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+
+    /// Queue `task` and return its job id right away; callers poll `/jobs/{job_id}`
+    pub fn submit<F>(
+        &self,
+        job_type: &str,
+        priority: Priority,
+        input_path: &str,
+        output_path: &str,
+        task: F,
+    ) -> Result<String>
+    where
+        F: Future<Output = Result<()>> + Send + 'static,
+    {
+        self.enqueue(job_type, priority, input_path, output_path, None, task)
+    }
+
+    /// `submit`, keeping `request` on the job record so `resume` can queue the job again if the
+    /// service restarts before it finishes
+    pub fn submit_request<R, F>(
+        &self,
+        job_type: &str,
+        priority: Priority,
+        request: &R,
+        input_path: &str,
+        output_path: &str,
+        task: F,
+    ) -> Result<String>
+    where
+        R: Serialize,
+        F: Future<Output = Result<()>> + Send + 'static,
+    {
+        let request = serde_json::to_value(request)?;
+        self.enqueue(job_type, priority, input_path, output_path, Some(request), task)
+    }
+
+    fn enqueue<F>(
+        &self,
+        job_type: &str,
+        priority: Priority,
+        input_path: &str,
+        output_path: &str,
+        request: Option<serde_json::Value>,
+        task: F,
+    ) -> Result<String>
+    where
+        F: Future<Output = Result<()>> + Send + 'static,
+    {
+        let job_id = Uuid::new_v4().to_string();
+        // Recorded first, so a worker that picks the job up right away finds it queued
+        self.jobs.enqueue(&job_id, job_type, priority, input_path, output_path, request);
+        let job = QueuedJob {
+            job_id: job_id.clone(),
+            request_id: request_id::current(),
+            tenant: auth::current_tenant(),
+            priority,
+            queued_at: Instant::now(),
+            task: Box::pin(task),
+        };
+        self.send(job).inspect_err(|_| self.jobs.discard(&job_id))
+    }
+
+    /// `task` as a job whose output is kept as its record's `result`, for jobs queued with
+    /// `submit` and friends whose callers poll for the outcome instead of waiting for it
+    pub fn keeping_result<T, F>(&self, task: F) -> impl Future<Output = Result<()>> + Send + 'static
+    where
+        T: Serialize,
+        F: Future<Output = Result<T>> + Send + 'static,
+    {
+        let jobs = self.jobs.clone();
+        async move {
+            let job_id = job_id();
+            jobs.await_result(&job_id);
+            let result = task.await.and_then(|value| Ok(serde_json::to_value(value)?));
+            jobs.set_result(&job_id, &result);
+            result.map(|_| ())
+        }
+    }
+
+    /// Queue a job the previous process left unfinished, under its original id, request id and
+    /// tenant so clients polling it see the retry through
+    pub fn resume<F>(&self, job: &JobRecord, task: F) -> Result<()>
+    where
+        F: Future<Output = Result<()>> + Send + 'static,
+    {
+        self.jobs.requeue(&job.job_id);
+        let queued = QueuedJob {
+            job_id: job.job_id.clone(),
+            request_id: job.request_id.clone(),
+            tenant: job.tenant.clone(),
+            priority: job.priority,
+            queued_at: Instant::now(),
+            task: Box::pin(task),
+        };
+        if let Err(e) = self.send(queued) {
+            self.jobs.finish::<()>(&job.job_id, &Err(anyhow::anyhow!("could not be re-queued: {}", e)));
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    fn send(&self, job: QueuedJob) -> Result<String> {
+        let job_id = job.job_id.clone();
+        {
+            let mut pending = self.pending.lock().unwrap();
+            if pending.len() >= self.config.capacity {
+                return Err(ServiceError::QueueFull(format!(
+                    "{} jobs are already waiting, retry later",
+                    self.config.capacity
+                ))
+                .into());
+            }
+            pending.push(job);
+        }
+        self.ready.notify_one();
+        Ok(job_id)
+    }
+
+    /// Run `task` on a worker and wait for its result, so endpoints that answer with the
+    /// outcome count against the same concurrency limit as queued jobs
+    pub async fn run<T, F>(
+        &self,
+        job_type: &str,
+        priority: Priority,
+        input_path: &str,
+        output_path: &str,
+        task: F,
+    ) -> Result<T>
+    where
+        T: Send + 'static,
+        F: Future<Output = Result<T>> + Send + 'static,
+    {
+        let (sender, receiver) = oneshot::channel();
+        let job_id = self.submit(job_type, priority, input_path, output_path, async move {
+            let result = task.await;
+            let outcome = result.as_ref().map(|_| ()).map_err(|e| anyhow::anyhow!("{}", e));
+            let _ = sender.send(result);
+            outcome
+        })?;
+        let result = receiver
+            .await
+            .map_err(|_| anyhow::anyhow!("queued job stopped before finishing"))
+            .and_then(|result| result);
+        // A killed FFmpeg surfaces as some processing error; report what actually happened
+        match result {
+            Err(_) if self.jobs.is_cancelled(&job_id) => {
+                Err(ServiceError::JobCancelled(format!("job {} was cancelled", job_id)).into())
+            }
+            result => result,
+        }
+    }
+
+    pub fn config(&self) -> QueueConfig {
+        self.config
+    }
+
+    /// Jobs waiting for a worker
+    pub fn depth(&self) -> usize {
+        self.pending.lock().unwrap().len()
+    }
+
+    /// Jobs waiting for a worker per priority class, lowest first
+    pub fn depth_by_priority(&self) -> [usize; Priority::ALL.len()] {
+        let pending = self.pending.lock().unwrap();
+        std::array::from_fn(|class| pending.classes[class].len())
+    }
+
+    /// Workers currently running a job
+    pub fn busy(&self) -> usize {
+        self.busy.load(Ordering::SeqCst)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::job::JobStatus;
+
+    fn config(workers: usize, capacity: usize) -> QueueConfig {
+        QueueConfig { workers, capacity, aging: Duration::from_secs(DEFAULT_AGING_SECS) }
+    }
+
+    #[tokio::test]
+    async fn test_workers_run_with_reserved_job_id() {
+        let jobs = Arc::new(JobStore::new());
+        let queue = JobQueue::start(config(1, 4), jobs.clone());
+
+        let failed_id = queue
+            .submit("test.fail", Priority::Normal, "in", "out", async { Err(anyhow::anyhow!("no input")) })
+            .unwrap();
+        let seen = queue
+            .run("test.echo", Priority::Normal, "in", "out", async { anyhow::Ok(job_id()) })
+            .await
+            .unwrap();
+        assert_ne!(seen, failed_id);
+
+        // The failing job never opened its record; the worker closed it
+        let failed = jobs.get(&failed_id).unwrap();
+        assert_eq!(failed.status, JobStatus::Failed);
+        assert_eq!(failed.error.as_deref(), Some("no input"));
+        // Processors pick up the id the job was queued under
+        assert_eq!(jobs.get(&seen).unwrap().job_type, "test.echo");
+    }
+
+    #[tokio::test]
+    async fn test_kept_result_completes_the_job() {
+        let jobs = Arc::new(JobStore::new());
+        let queue = JobQueue::start(config(1, 4), jobs.clone());
+        let (done, finished) = oneshot::channel();
+        let store = jobs.clone();
+        let task = queue.keeping_result(async move {
+            let job_id = job_id();
+            store.start(&job_id, "test.result", "in", "out");
+            store.finish(&job_id, &anyhow::Ok(()));
+            // What the processor finished stays running until its result is kept
+            let status = store.get(&job_id).unwrap().status;
+            anyhow::Ok(serde_json::json!({ "job_id": job_id, "status": status }))
+        });
+        let job_id = queue.submit("test.result", Priority::Normal, "in", "out", async move {
+            let result = task.await;
+            let _ = done.send(());
+            result
+        }).unwrap();
+        finished.await.unwrap();
+
+        let job = jobs.get(&job_id).unwrap();
+        assert_eq!(job.status, JobStatus::Completed);
+        let result = job.result.unwrap();
+        assert_eq!(result["job_id"], job_id.as_str());
+        assert_eq!(result["status"], "running");
+    }
+
+    #[tokio::test]
+    async fn test_full_queue_rejects_and_discards() {
+        let jobs = Arc::new(JobStore::new());
+        let queue = JobQueue::start(config(1, 1), jobs.clone());
+        let (release, wait) = oneshot::channel::<()>();
+        queue.submit("test.block", Priority::Normal, "in", "out", async move {
+            wait.await.ok();
+            anyhow::Ok(())
+        }).unwrap();
+        // One running and one waiting fill the worker and the single slot
+        while queue.busy() == 0 {
+            tokio::task::yield_now().await;
+        }
+        queue.submit("test.wait", Priority::Normal, "in", "out", async { anyhow::Ok(()) }).unwrap();
+
+        let error = queue
+            .submit("test.rejected", Priority::High, "in", "out", async { anyhow::Ok(()) })
+            .unwrap_err();
+        assert_eq!(ServiceError::from(error).code(), "queue_full");
+        assert_eq!(queue.depth(), 1);
+        assert!(jobs.queued().iter().all(|job| job.job_type != "test.rejected"));
+        release.send(()).unwrap();
+    }
+
+    #[test]
+    fn test_pop_prefers_priority_then_age() {
+        let now = Instant::now();
+        let job = |name: &str, priority: Priority, waited: u64| QueuedJob {
+            job_id: name.to_string(),
+            request_id: None,
+            tenant: None,
+            priority,
+            queued_at: now - Duration::from_secs(waited),
+            task: Box::pin(async { anyhow::Ok(()) }),
+        };
+        let aging = Duration::from_secs(60);
+        let mut pending = Pending::default();
+        pending.push(job("transcode", Priority::Low, 150));
+        pending.push(job("normal", Priority::Normal, 10));
+        pending.push(job("thumbnail", Priority::High, 0));
+        pending.push(job("thumbnail-2", Priority::High, 0));
+
+        // Two aging periods lift the transcode past the normal job, not past fresh high ones
+        let order: Vec<String> = std::iter::from_fn(|| pending.pop(now, aging)).map(|job| job.job_id).collect();
+        assert_eq!(order, vec!["thumbnail", "thumbnail-2", "transcode", "normal"]);
+    }
+}
@@ -0,0 +1,114 @@
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use crate::services::hls;
+use crate::utils::validation::Violations;
+
+/// Highest CRF any supported encoder takes; x264 and x265 stop at 51, VP9 and AV1 at 63
+pub const MAX_CRF: u32 = 63;
+
+/// `rate_control` of a transcode, e.g. `{"mode": "crf", "value": 23}` or
+/// `{"mode": "two_pass", "value": "3M"}`
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(tag = "mode", content = "value", rename_all = "snake_case")]
+pub enum RateControl {
+    /// Constant quality, lower is better; a `bitrate` alongside caps it for streaming
+    Crf(u32),
+    /// Average bit rate, spent where the first pass found the picture needs it
+    TwoPass(String),
+    /// Constant bit rate, for links that can't absorb peaks
+    Cbr(String),
+}
+
+impl RateControl {
+    pub fn validate(&self, field: &str, violations: &mut Violations) {
+        match self {
+            RateControl::Crf(crf) => violations.range(field, Some(*crf), 0, MAX_CRF),
+            RateControl::TwoPass(bitrate) | RateControl::Cbr(bitrate) => violations.bitrate(field, Some(bitrate)),
+        }
+    }
+
+    /// The target bit rate, which CRF has none of
+    pub fn bitrate(&self) -> Option<&str> {
+        match self {
+            RateControl::Crf(_) => None,
+            RateControl::TwoPass(bitrate) | RateControl::Cbr(bitrate) => Some(bitrate),
+        }
+    }
+
+    /// The same mode aiming at `bitrate` instead, as each rendition of a ladder does; CRF
+    /// takes it as its cap
+    pub fn at(&self, bitrate: &str) -> RateControl {
+        match self {
+            RateControl::Crf(crf) => RateControl::Crf(*crf),
+            RateControl::TwoPass(_) => RateControl::TwoPass(bitrate.to_string()),
+            RateControl::Cbr(_) => RateControl::Cbr(bitrate.to_string()),
+        }
+    }
+
+    pub fn is_two_pass(&self) -> bool {
+        matches!(self, RateControl::TwoPass(_))
+    }
+
+    /// Output options for `codec`. `cap` bounds CRF with a twice-as-large VBV buffer; without
+    /// one, VP9 and AV1 need `-b:v 0` to encode at constant quality rather than their default rate
+    pub fn args(&self, codec: Option<&str>, cap: Option<&str>) -> Vec<String> {
+        let mut args = Vec::new();
+        match self {
+            RateControl::Crf(crf) => {
+                args.extend(["-crf".to_string(), crf.to_string()]);
+                match cap {
+                    Some(cap) => args.extend(["-maxrate".to_string(), cap.to_string(), "-bufsize".to_string(), double(cap)]),
+                    None if codec.is_some_and(|codec| codec.starts_with("libvpx") || codec.starts_with("libaom")) => {
+                        args.extend(["-b:v".to_string(), "0".to_string()])
+                    }
+                    None => {}
+                }
+            }
+            RateControl::TwoPass(bitrate) => args.extend(["-b:v".to_string(), bitrate.clone()]),
+            RateControl::Cbr(bitrate) => {
+                for option in ["-b:v", "-minrate", "-maxrate", "-bufsize"] {
+                    args.extend([option.to_string(), bitrate.clone()]);
+                }
+            }
+        }
+        args
+    }
+}
+
+/// `bitrate` in bits per second times two
+fn double(bitrate: &str) -> String {
+    hls::parse_bitrate(bitrate).map_or_else(|| bitrate.to_string(), |bits| (bits * 2).to_string())
+}
+
+/// Options of pass `pass` (1 or 2) of a two-pass encode whose statistics live at `log_prefix`.
+/// x265 keeps its own statistics file and ignores `-pass`
+pub fn pass_args(codec: Option<&str>, pass: u8, log_prefix: &Path) -> Vec<String> {
+    if codec == Some("libx265") {
+        return vec!["-x265-params".to_string(), format!("pass={}:stats={}.log", pass, log_prefix.display())];
+    }
+    vec!["-pass".to_string(), pass.to_string(), "-passlogfile".to_string(), log_prefix.display().to_string()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rate_control_args() {
+        let crf: RateControl = serde_json::from_str(r#"{"mode": "crf", "value": 23}"#).unwrap();
+        assert_eq!(crf.args(Some("libx264"), None), vec!["-crf", "23"]);
+        assert_eq!(crf.args(Some("libx264"), Some("2.5M")), vec!["-crf", "23", "-maxrate", "2.5M", "-bufsize", "5000000"]);
+        assert_eq!(crf.args(Some("libvpx-vp9"), None), vec!["-crf", "23", "-b:v", "0"]);
+        assert_eq!(crf.at("1M"), crf);
+
+        let cbr: RateControl = serde_json::from_str(r#"{"mode": "cbr", "value": "2M"}"#).unwrap();
+        assert_eq!(cbr.args(None, None), vec!["-b:v", "2M", "-minrate", "2M", "-maxrate", "2M", "-bufsize", "2M"]);
+        let two_pass = RateControl::TwoPass("3M".to_string());
+        assert_eq!(serde_json::to_value(&two_pass).unwrap(), serde_json::json!({ "mode": "two_pass", "value": "3M" }));
+        assert_eq!(two_pass.at("1M").bitrate(), Some("1M"));
+
+        let prefix = Path::new("/tmp/job/ffmpeg2pass");
+        assert_eq!(pass_args(Some("libx264"), 1, prefix), vec!["-pass", "1", "-passlogfile", "/tmp/job/ffmpeg2pass"]);
+        assert_eq!(pass_args(Some("libx265"), 2, prefix), vec!["-x265-params", "pass=2:stats=/tmp/job/ffmpeg2pass.log"]);
+    }
+}
@@ -0,0 +1,283 @@
+use anyhow::Result;
+use log::info;
+use std::net::IpAddr;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tempfile::TempPath;
+use crate::services::storage::{Storage, StorageUri};
+use crate::services::temp_files::TempFileManager;
+use crate::services::tenants::{self, Managed};
+use crate::utils::error::ServiceError;
+
+/// Where and how much remote inputs may be downloaded from
+#[derive(Debug, Clone, PartialEq)]
+pub struct RemoteConfig {
+    /// Largest download accepted, checked against `Content-Length` and again while streaming
+    pub max_bytes: u64,
+    /// For the whole download, connecting included
+    pub timeout: Duration,
+    /// Hosts URLs may point at, redirects included; empty allows none and `*` any
+    pub allowed_hosts: Vec<String>,
+    /// Where downloads are kept until their job finishes
+    pub dir: PathBuf,
+}
+
+impl Default for RemoteConfig {
+    fn default() -> Self {
+        Self {
+            max_bytes: 2 * 1024 * 1024 * 1024,
+            timeout: Duration::from_secs(300),
+            allowed_hosts: Vec::new(),
+            dir: std::env::temp_dir(),
+        }
+    }
+}
+
+impl RemoteConfig {
+    /// Read `REMOTE_INPUT_MAX_BYTES` (default: 2 GiB), `REMOTE_INPUT_TIMEOUT_SECS` (default: 300),
+    /// `REMOTE_INPUT_HOSTS` (comma-separated) and `REMOTE_INPUT_DIR` (default: `downloads` in
+    /// the managed `TEMP_DIR`, so downloads of crashed jobs are collected with it)
+    pub fn from_env() -> Self {
+        fn var(name: &str) -> Option<String> {
+            std::env::var(name).ok().filter(|value| !value.trim().is_empty())
+        }
+        let defaults = Self::default();
+        Self {
+            max_bytes: var("REMOTE_INPUT_MAX_BYTES").and_then(|v| v.trim().parse().ok()).unwrap_or(defaults.max_bytes),
+            timeout: var("REMOTE_INPUT_TIMEOUT_SECS")
+                .and_then(|v| v.trim().parse().ok())
+                .map_or(defaults.timeout, Duration::from_secs),
+            allowed_hosts: var("REMOTE_INPUT_HOSTS")
+                .map(|hosts| hosts.split(',').map(|host| host.trim().to_lowercase()).filter(|host| !host.is_empty()).collect())
+                .unwrap_or_default(),
+            dir: var("REMOTE_INPUT_DIR").map_or_else(|| TempFileManager::from_env().root().join("downloads"), PathBuf::from),
+        }
+    }
+
+    /// Whether `url` is an HTTPS URL on one of the allowed hosts. Addresses given literally must
+    /// be public; names are checked once resolved, see `PublicResolver`
+    pub fn allows(&self, url: &str) -> bool {
+        host(url).is_some_and(|host| {
+            let listed = self.allowed_hosts.iter().any(|allowed| allowed == "*" || *allowed == host);
+            listed && host.parse::<IpAddr>().map_or(true, is_public)
+        })
+    }
+}
+
+/// Whether `ip` is reachable on the internet rather than the service's own host or network:
+/// not loopback, private, link-local, unique-local, unspecified, broadcast or multicast
+pub fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            !(ip.is_loopback() || ip.is_private() || ip.is_link_local() || ip.is_unspecified() || ip.is_broadcast() || ip.is_multicast())
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(mapped) => is_public(IpAddr::V4(mapped)),
+            None => !(ip.is_loopback() || ip.is_unique_local() || ip.is_unicast_link_local() || ip.is_unspecified() || ip.is_multicast()),
+        },
+    }
+}
+
+/// Whether an input names a remote file instead of a local path
+pub fn is_url(input: &str) -> bool {
+    let scheme = input.split_once("://").map(|(scheme, _)| scheme.to_lowercase());
+    matches!(scheme.as_deref(), Some("https" | "http"))
+}
+
+/// Lowercased host of an `https://` URL, without credentials or port; `None` for anything else
+pub fn host(url: &str) -> Option<String> {
+    let (scheme, rest) = url.split_once("://")?;
+    if !scheme.eq_ignore_ascii_case("https") {
+        return None;
+    }
+    let authority = rest.split(['/', '?', '#']).next()?;
+    let host_port = authority.rsplit_once('@').map_or(authority, |(_, host)| host);
+    let host = match host_port.strip_prefix('[') {
+        // IPv6 literal
+        Some(bracketed) => bracketed.split_once(']')?.0,
+        None => host_port.split(':').next()?,
+    };
+    (!host.is_empty()).then(|| host.to_lowercase())
+}
+
+/// Extension of the URL's file name, so FFmpeg and the extension checks see the same
+/// file type the local path would have had
+fn suffix(url: &str) -> String {
+    let path = url.split(['?', '#']).next().unwrap_or_default();
+    let name = path.rsplit('/').next().unwrap_or_default();
+    Path::new(name)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .filter(|ext| ext.len() <= 5 && ext.chars().all(|c| c.is_ascii_alphanumeric()))
+        .map(|ext| format!(".{}", ext))
+        .unwrap_or_default()
+}
+
+/// A downloaded input; the file is removed when this is dropped
+pub struct Download {
+    path: TempPath,
+    pub bytes: u64,
+    _managed: Option<Managed>,
+}
+
+impl Download {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+/// When `input` is a URL or a storage URI, download it and point `input` at the local copy.
+/// Keep the returned download alive until the job is done with the file
+pub async fn localize(input: &mut String) -> Result<Option<Download>> {
+    let config = RemoteConfig::from_env();
+    let download = match StorageUri::parse(input) {
+        Some(location) => fetch_object(&location, &config).await?,
+        None if is_url(input) => fetch(input, &config).await?,
+        None => return Ok(None),
+    };
+    info!("Downloaded {} ({} bytes) to {}", input, download.bytes, download.path().display());
+    *input = download.path().to_string_lossy().into_owned();
+    Ok(Some(download))
+}
+
+/// Stream `url` into a temp file in `config.dir`
+pub async fn fetch(url: &str, config: &RemoteConfig) -> Result<Download> {
+    if !config.allows(url) {
+        return Err(ServiceError::BadRequest(format!("{} is not an allowed HTTPS URL", url)).into());
+    }
+    let (file, path) = temp_file(config, url)?;
+    let bytes = match tokio::time::timeout(config.timeout, download(url, config, tokio::fs::File::from_std(file))).await {
+        Ok(result) => result?,
+        Err(_) => return Err(timed_out(url, config).into()),
+    };
+    Ok(Download { _managed: tenants::manage(&path), path, bytes })
+}
+
+/// Download a storage object into a temp file in `config.dir`, within the same limits as URLs
+pub async fn fetch_object(location: &StorageUri, config: &RemoteConfig) -> Result<Download> {
+    let backend = Storage::from_env().backend(location)?;
+    let (_, path) = temp_file(config, &location.key)?;
+    let bytes = match tokio::time::timeout(config.timeout, backend.get(location, &path, config.max_bytes)).await {
+        Ok(result) => result?,
+        Err(_) => return Err(timed_out(&location.to_string(), config).into()),
+    };
+    Ok(Download { _managed: tenants::manage(&path), path, bytes })
+}
+
+fn temp_file(config: &RemoteConfig, name: &str) -> Result<(std::fs::File, TempPath)> {
+    TempFileManager::from_env().output_dir(&config.dir)?;
+    let file = tempfile::Builder::new().prefix(".remote-").suffix(&suffix(name)).tempfile_in(&config.dir)?;
+    // Sandboxed FFmpeg may run as a different uid than the one that created the file
+    std::fs::set_permissions(file.path(), std::fs::Permissions::from_mode(0o644))?;
+    Ok(file.into_parts())
+}
+
+fn timed_out(source: &str, config: &RemoteConfig) -> ServiceError {
+    ServiceError::FileNotFound(format!("Download of {} timed out after {}s", source, config.timeout.as_secs()))
+}
+
+/// The system resolver with internal addresses left out, so neither a URL nor any of its
+/// redirect hops reaches the service's own network, whatever its name resolves to. Connecting
+/// only to what was checked also leaves no window to rebind the name in between
+#[cfg(feature = "remote")]
+struct PublicResolver;
+
+#[cfg(feature = "remote")]
+impl reqwest::dns::Resolve for PublicResolver {
+    fn resolve(&self, name: reqwest::dns::Name) -> reqwest::dns::Resolving {
+        let host = name.as_str().to_string();
+        Box::pin(async move {
+            let addresses: Vec<std::net::SocketAddr> = tokio::net::lookup_host((host.as_str(), 0))
+                .await?
+                .filter(|address| is_public(address.ip()))
+                .collect();
+            if addresses.is_empty() {
+                return Err(format!("{} only resolves to internal addresses", host).into());
+            }
+            let addresses: reqwest::dns::Addrs = Box::new(addresses.into_iter());
+            Ok(addresses)
+        })
+    }
+}
+
+#[cfg(feature = "remote")]
+async fn download(url: &str, config: &RemoteConfig, mut file: tokio::fs::File) -> Result<u64> {
+    use tokio::io::AsyncWriteExt;
+
+    let policy = {
+        let config = config.clone();
+        reqwest::redirect::Policy::custom(move |attempt| {
+            if attempt.previous().len() >= 5 {
+                attempt.error("too many redirects")
+            } else if config.allows(attempt.url().as_str()) {
+                attempt.follow()
+            } else {
+                // Stopping would hand back the 3xx response, and its body would become the input
+                let refused = format!("redirect to {} is not allowed", attempt.url());
+                attempt.error(refused)
+            }
+        })
+    };
+    let client = reqwest::Client::builder()
+        .https_only(true)
+        .redirect(policy)
+        .dns_resolver(std::sync::Arc::new(PublicResolver))
+        .build()?;
+    let unreachable = |e: reqwest::Error| ServiceError::FileNotFound(format!("Could not download {}: {}", url, e));
+    let too_large = || ServiceError::InputTooLarge(format!("{}: larger than the {} byte download limit", url, config.max_bytes));
+    let mut response = client.get(url).send().await.map_err(unreachable)?;
+    if !response.status().is_success() {
+        return Err(ServiceError::FileNotFound(format!("Could not download {}: HTTP {}", url, response.status())).into());
+    }
+    if response.content_length().is_some_and(|length| length > config.max_bytes) {
+        return Err(too_large().into());
+    }
+
+    let mut written = 0u64;
+    while let Some(chunk) = response.chunk().await.map_err(unreachable)? {
+        written += chunk.len() as u64;
+        if written > config.max_bytes {
+            return Err(too_large().into());
+        }
+        file.write_all(&chunk).await?;
+    }
+    file.flush().await?;
+    Ok(written)
+}
+
+#[cfg(not(feature = "remote"))]
+async fn download(url: &str, _config: &RemoteConfig, _file: tokio::fs::File) -> Result<u64> {
+    Err(ServiceError::BadRequest(format!("{}: URL inputs need a build with the `remote` feature", url)).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_url_host_and_suffix() {
+        assert!(is_url("https://cdn.example.com/clip.mp4"));
+        assert!(is_url("HTTP://cdn.example.com/clip.mp4"));
+        assert!(!is_url("/media/https://clip.mp4") && !is_url("clip.mp4"));
+
+        assert_eq!(host("https://user:pw@CDN.example.com:8443/a/b.mp4?x=1").as_deref(), Some("cdn.example.com"));
+        assert_eq!(host("https://[::1]:8443/b.mp4").as_deref(), Some("::1"));
+        assert_eq!(host("http://cdn.example.com/b.mp4"), None);
+
+        let mut config = RemoteConfig::default();
+        assert!(!config.allows("https://anything.example.org/x"));
+        config.allowed_hosts = vec!["cdn.example.com".to_string()];
+        assert!(config.allows("https://cdn.example.com/x"));
+        assert!(!config.allows("https://cdn.example.com.evil.org/x"));
+        config.allowed_hosts = vec!["*".to_string()];
+        assert!(config.allows("https://anything.example.org/x") && config.allows("https://93.184.216.34/x"));
+        for internal in ["127.0.0.1", "10.1.2.3", "172.16.0.1", "192.168.1.1", "169.254.169.254", "0.0.0.0", "[::1]", "[fe80::1]", "[fd00::1]", "[::ffff:10.0.0.1]"] {
+            assert!(!config.allows(&format!("https://{}/x", internal)), "{}", internal);
+        }
+        assert!(is_public("2606:4700::1111".parse().unwrap()));
+
+        assert_eq!(suffix("https://cdn.example.com/media/clip.MP4?token=a.b"), ".MP4");
+        assert_eq!(suffix("https://cdn.example.com/download?id=3"), "");
+    }
+}
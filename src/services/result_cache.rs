@@ -0,0 +1,235 @@
+use anyhow::Result;
+use log::{info, warn};
+use sha2::{Digest, Sha256};
+use std::future::Future;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// Which entries go first once the cache holds more than its limit
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Eviction {
+    /// Least recently stored or served
+    #[default]
+    Lru,
+    /// Oldest stored, however often it is served
+    Fifo,
+}
+
+/// Outputs of earlier jobs, keyed by the content of their inputs, the operation and its
+/// parameters, so an identical request is answered with a copy instead of being processed again
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ResultCache {
+    /// `None` disables the cache
+    dir: Option<PathBuf>,
+    max_bytes: u64,
+    /// Entries older than this are misses and get removed
+    ttl: Option<Duration>,
+    eviction: Eviction,
+}
+
+/// What one eviction pass removed
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Evicted {
+    pub entries: u64,
+    pub bytes: u64,
+}
+
+impl ResultCache {
+    pub fn new(dir: Option<PathBuf>, max_bytes: u64, ttl: Option<Duration>, eviction: Eviction) -> Self {
+        Self { dir, max_bytes, ttl, eviction }
+    }
+
+    /// `RESULT_CACHE_DIR` (unset disables the cache), `RESULT_CACHE_MAX_BYTES` (default: 1 GiB),
+    /// `RESULT_CACHE_TTL_SECS` (default: no expiry) and `RESULT_CACHE_EVICTION` (`lru` or `fifo`)
+    pub fn from_env() -> Self {
+        let var = |name: &str| std::env::var(name).ok().filter(|value| !value.trim().is_empty());
+        let eviction = match var("RESULT_CACHE_EVICTION").map(|policy| policy.trim().to_lowercase()).as_deref() {
+            Some("fifo") => Eviction::Fifo,
+            _ => Eviction::Lru,
+        };
+        Self::new(
+            var("RESULT_CACHE_DIR").map(PathBuf::from),
+            var("RESULT_CACHE_MAX_BYTES").and_then(|v| v.trim().parse().ok()).unwrap_or(1024 * 1024 * 1024),
+            var("RESULT_CACHE_TTL_SECS")
+                .and_then(|v| v.trim().parse().ok())
+                .filter(|secs| *secs > 0)
+                .map(Duration::from_secs),
+            eviction,
+        )
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.dir.is_some()
+    }
+
+    /// SHA-256 over `operation`, `params`, the output's extension (it picks the encoder) and
+    /// the content of every input. Reads the inputs, so this is blocking
+    pub fn key(inputs: &[PathBuf], operation: &str, params: &serde_json::Value, output: &Path) -> Result<String> {
+        let extension = output.extension().map(|ext| ext.to_string_lossy().to_lowercase()).unwrap_or_default();
+        let mut hasher = Sha256::new();
+        for part in [operation, &params.to_string(), &extension] {
+            hasher.update((part.len() as u64).to_le_bytes());
+            hasher.update(part.as_bytes());
+        }
+        let mut buffer = vec![0u8; 64 * 1024];
+        for input in inputs {
+            let mut file = std::fs::File::open(input)?;
+            hasher.update(file.metadata()?.len().to_le_bytes());
+            loop {
+                let read = file.read(&mut buffer)?;
+                if read == 0 {
+                    break;
+                }
+                hasher.update(&buffer[..read]);
+            }
+        }
+        Ok(hasher.finalize().iter().map(|byte| format!("{:02x}", byte)).collect())
+    }
+
+    /// Restore `output` from the cache when an identical request produced it before, otherwise
+    /// await `job` and keep a copy of what it wrote. Returns whether it was a hit
+    pub async fn run(
+        &self,
+        job_id: &str,
+        inputs: &[&str],
+        operation: &str,
+        params: serde_json::Value,
+        output: &str,
+        job: impl Future<Output = Result<()>>,
+    ) -> Result<bool> {
+        let Some(dir) = self.dir.clone() else {
+            job.await?;
+            return Ok(false);
+        };
+        let output = PathBuf::from(output);
+        let key = {
+            let inputs: Vec<PathBuf> = inputs.iter().map(PathBuf::from).collect();
+            let (operation, output) = (operation.to_string(), output.clone());
+            tokio::task::spawn_blocking(move || Self::key(&inputs, &operation, &params, &output)).await??
+        };
+        let entry = dir.join(&key);
+        if self.restore(&entry, &output) {
+            info!("[{}] {} served from the result cache ({})", job_id, operation, key);
+            return Ok(true);
+        }
+
+        job.await?;
+        if let Err(e) = self.store(&dir, &entry, &output) {
+            warn!("[{}] Could not cache {}: {}", job_id, output.display(), e);
+        }
+        Ok(false)
+    }
+
+    fn restore(&self, entry: &Path, output: &Path) -> bool {
+        let Ok(metadata) = std::fs::metadata(entry) else {
+            return false;
+        };
+        if self.expired(&metadata) {
+            let _ = std::fs::remove_file(entry);
+            return false;
+        }
+        if let Err(e) = std::fs::copy(entry, output) {
+            warn!("Could not restore {} from the result cache: {}", output.display(), e);
+            return false;
+        }
+        if self.eviction == Eviction::Lru {
+            let touched = std::fs::File::options().write(true).open(entry).and_then(|file| file.set_modified(SystemTime::now()));
+            if let Err(e) = touched {
+                warn!("Could not mark {} as used: {}", entry.display(), e);
+            }
+        }
+        true
+    }
+
+    fn store(&self, dir: &Path, entry: &Path, output: &Path) -> Result<()> {
+        std::fs::create_dir_all(dir)?;
+        // Concurrent identical jobs each write their own copy; the last rename wins
+        let staging = tempfile::Builder::new().prefix(".staging-").tempfile_in(dir)?.into_temp_path();
+        std::fs::copy(output, &staging)?;
+        staging.persist(entry)?;
+        self.evict();
+        Ok(())
+    }
+
+    fn expired(&self, metadata: &std::fs::Metadata) -> bool {
+        let age = metadata.modified().ok().and_then(|modified| SystemTime::now().duration_since(modified).ok());
+        self.ttl.is_some_and(|ttl| age.is_some_and(|age| age > ttl))
+    }
+
+    /// Remove expired entries, then the first to go under the eviction policy until the cache
+    /// fits in its limit again
+    pub fn evict(&self) -> Evicted {
+        let mut evicted = Evicted::default();
+        let Some(entries) = self.dir.as_ref().and_then(|dir| std::fs::read_dir(dir).ok()) else {
+            return evicted;
+        };
+        let mut kept = Vec::new();
+        for entry in entries.flatten() {
+            if entry.file_name().to_string_lossy().starts_with('.') {
+                continue;
+            }
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+            if !metadata.is_file() {
+                continue;
+            }
+            if self.expired(&metadata) {
+                if std::fs::remove_file(entry.path()).is_ok() {
+                    evicted.entries += 1;
+                    evicted.bytes += metadata.len();
+                }
+            } else {
+                kept.push((metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH), metadata.len(), entry.path()));
+            }
+        }
+
+        // Oldest modification first: stored, or for LRU also last served
+        kept.sort();
+        let mut total: u64 = kept.iter().map(|(_, bytes, _)| bytes).sum();
+        for (_, bytes, path) in kept {
+            if total <= self.max_bytes {
+                break;
+            }
+            if std::fs::remove_file(&path).is_ok() {
+                total -= bytes;
+                evicted.entries += 1;
+                evicted.bytes += bytes;
+            }
+        }
+        evicted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_hit_skips_the_job_and_eviction_keeps_the_limit() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("in.jpg");
+        std::fs::write(&input, b"pixels").unwrap();
+        let (input, first, second) = (input.to_str().unwrap(), dir.path().join("a.jpg"), dir.path().join("b.jpg"));
+        let cache = ResultCache::new(Some(dir.path().join("cache")), 10, None, Eviction::Lru);
+        let params = serde_json::json!({ "sigma": 8 });
+
+        let write = |path: PathBuf| async move { Ok(std::fs::write(path, b"blurred")?) };
+        let hit = cache.run("job-1", &[input], "image.blur", params.clone(), first.to_str().unwrap(), write(first.clone())).await;
+        assert!(!hit.unwrap());
+        let fail = async { anyhow::bail!("should have been served from the cache") };
+        let hit = cache.run("job-2", &[input], "image.blur", params.clone(), second.to_str().unwrap(), fail).await;
+        assert!(hit.unwrap());
+        assert_eq!(std::fs::read(&second).unwrap(), b"blurred");
+
+        // Other parameters or another output format make another entry; 14 bytes don't fit in 10
+        let key = |params: &serde_json::Value, output: &str| ResultCache::key(&[PathBuf::from(input)], "image.blur", params, Path::new(output)).unwrap();
+        assert_ne!(key(&params, "a.jpg"), key(&serde_json::json!({ "sigma": 9 }), "a.jpg"));
+        assert_ne!(key(&params, "a.jpg"), key(&params, "a.png"));
+        let third = dir.path().join("c.png");
+        let hit = cache.run("job-3", &[input], "image.blur", params, third.to_str().unwrap(), write(third.clone())).await;
+        assert!(!hit.unwrap());
+        assert_eq!(std::fs::read_dir(dir.path().join("cache")).unwrap().count(), 1);
+    }
+}
@@ -0,0 +1,89 @@
+use std::io;
+use std::time::Duration;
+use crate::utils::error::{FfmpegErrorKind, FfmpegFailure};
+
+/// How often, and how patiently, FFmpeg runs that failed for a transient reason are repeated
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetryPolicy {
+    /// Runs per FFmpeg invocation, the first one included; 1 disables retries
+    pub max_attempts: u32,
+    /// Wait before the first retry, doubled for every later one
+    pub backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(30),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Read `JOB_RETRY_MAX_ATTEMPTS` (default: 3), `JOB_RETRY_BACKOFF_MS` (default: 1000) and
+    /// `JOB_RETRY_MAX_BACKOFF_MS` (default: 30000)
+    pub fn from_env() -> Self {
+        fn var(name: &str) -> Option<u64> {
+            std::env::var(name).ok()?.trim().parse().ok()
+        }
+        let defaults = Self::default();
+        Self {
+            max_attempts: var("JOB_RETRY_MAX_ATTEMPTS").map_or(defaults.max_attempts, |n| n.clamp(1, 10) as u32),
+            backoff: var("JOB_RETRY_BACKOFF_MS").map_or(defaults.backoff, Duration::from_millis),
+            max_backoff: var("JOB_RETRY_MAX_BACKOFF_MS").map_or(defaults.max_backoff, Duration::from_millis),
+        }
+    }
+
+    /// Whether a run that failed with `error` on its `attempt`th try gets another one
+    pub fn should_retry(&self, attempt: u32, error: &anyhow::Error) -> bool {
+        attempt < self.max_attempts && is_transient(error)
+    }
+
+    /// Wait after the `attempt`th try failed
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let doublings = attempt.saturating_sub(1).min(16);
+        self.backoff.saturating_mul(1 << doublings).min(self.max_backoff)
+    }
+}
+
+/// Failures a second run can plausibly get past: FFmpeg reporting a busy or briefly unreachable
+/// resource or being killed by a stray signal, or a spawn that hit a busy executable or the
+/// process limit
+pub fn is_transient(error: &anyhow::Error) -> bool {
+    if let Some(failure) = error.downcast_ref::<FfmpegFailure>() {
+        return failure.kind == FfmpegErrorKind::Transient;
+    }
+    error.downcast_ref::<io::Error>().is_some_and(|e| {
+        matches!(
+            e.kind(),
+            io::ErrorKind::WouldBlock
+                | io::ErrorKind::Interrupted
+                | io::ErrorKind::ResourceBusy
+                | io::ErrorKind::ExecutableFileBusy
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retries_transient_failures_with_backoff() {
+        let policy = RetryPolicy::default();
+        let failure = |kind| anyhow::Error::from(FfmpegFailure { kind, message: "Transcoding failed".to_string() });
+
+        assert!(policy.should_retry(1, &failure(FfmpegErrorKind::Transient)));
+        assert!(!policy.should_retry(3, &failure(FfmpegErrorKind::Transient)));
+        assert!(!policy.should_retry(1, &failure(FfmpegErrorKind::CorruptInput)));
+        assert!(policy.should_retry(1, &io::Error::from(io::ErrorKind::ExecutableFileBusy).into()));
+        assert!(!policy.should_retry(1, &io::Error::from(io::ErrorKind::NotFound).into()));
+
+        assert_eq!(policy.delay(1), Duration::from_secs(1));
+        assert_eq!(policy.delay(3), Duration::from_secs(4));
+        assert_eq!(policy.delay(20), Duration::from_secs(30));
+    }
+}
@@ -0,0 +1,226 @@
+use anyhow::Result;
+use log::{error, info, warn};
+use std::fs::File;
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use crate::utils::error::ServiceError;
+use crate::utils::fs;
+
+/// INSTREAM chunk size; clamd's default StreamMaxLength still bounds the total
+const CHUNK_SIZE: usize = 64 * 1024;
+const TIMEOUT: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ClamdAddress {
+    Tcp(String),
+    Unix(PathBuf),
+}
+
+impl ClamdAddress {
+    /// `host:port`, or `unix:/run/clamav/clamd.ctl`
+    pub fn parse(value: &str) -> Self {
+        match value.strip_prefix("unix:") {
+            Some(path) => ClamdAddress::Unix(PathBuf::from(path)),
+            None => ClamdAddress::Tcp(value.to_string()),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Verdict {
+    Clean,
+    Infected(String),
+}
+
+/// Optional clamd scan of every input before it is decoded; infected files are moved to quarantine
+pub struct Scanner {
+    address: Option<ClamdAddress>,
+    quarantine_dir: PathBuf,
+    /// Let inputs through when clamd is unreachable instead of failing the job
+    fail_open: bool,
+}
+
+impl Default for Scanner {
+    fn default() -> Self {
+        Self {
+            address: None,
+            quarantine_dir: fs::workspace_dir().join("quarantine"),
+            fail_open: false,
+        }
+    }
+}
+
+impl Scanner {
+    /// Read `CLAMD_ADDRESS`, `QUARANTINE_DIR` and `CLAMD_FAIL_OPEN`; scanning is off without an address
+    pub fn from_env() -> Self {
+        let var = |name: &str| std::env::var(name).ok().map(|v| v.trim().to_string()).filter(|v| !v.is_empty());
+        let defaults = Self::default();
+        Self {
+            address: var("CLAMD_ADDRESS").map(|address| ClamdAddress::parse(&address)),
+            quarantine_dir: var("QUARANTINE_DIR").map(PathBuf::from).unwrap_or(defaults.quarantine_dir),
+            fail_open: var("CLAMD_FAIL_OPEN")
+                .map(|v| matches!(v.to_lowercase().as_str(), "1" | "true" | "yes" | "on"))
+                .unwrap_or(false),
+        }
+    }
+
+    /// Streams the whole file to clamd, over blocking sockets on the blocking thread pool
+    pub async fn scan(&self, job_id: Option<&str>, path: &Path) -> Result<()> {
+        let Some(address) = self.address.clone() else {
+            return Ok(());
+        };
+        let label = job_id.unwrap_or("-");
+
+        let file = path.to_path_buf();
+        let scanned = tokio::task::spawn_blocking(move || scan_file(&address, &file))
+            .await
+            .unwrap_or_else(|e| Err(std::io::Error::other(e)));
+        let verdict = match scanned {
+            Ok(verdict) => verdict,
+            Err(e) if self.fail_open => {
+                warn!("[{}] Virus scan skipped for {}: {}", label, path.display(), e);
+                return Ok(());
+            }
+            Err(e) => {
+                error!("[{}] Virus scan failed for {}: {}", label, path.display(), e);
+                return Err(ServiceError::ScanUnavailable(format!("could not scan {}: {}", path.display(), e)).into());
+            }
+        };
+
+        match verdict {
+            Verdict::Clean => Ok(()),
+            Verdict::Infected(signature) => {
+                let (dir, job_id, file) = (self.quarantine_dir.clone(), job_id.map(String::from), path.to_path_buf());
+                let quarantined = tokio::task::spawn_blocking(move || quarantine(&dir, job_id.as_deref(), &file)).await??;
+                warn!(
+                    "[{}] Infected input {} ({}), quarantined as {}",
+                    label,
+                    path.display(),
+                    signature,
+                    quarantined.display()
+                );
+                Err(ServiceError::InfectedInput(format!("{}: {}", path.display(), signature)).into())
+            }
+        }
+    }
+
+}
+
+fn quarantine(quarantine_dir: &Path, job_id: Option<&str>, path: &Path) -> Result<PathBuf> {
+    std::fs::create_dir_all(quarantine_dir)?;
+    let file_name = path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+    let target = quarantine_dir.join(format!("{}-{}", job_id.unwrap_or("unknown"), file_name));
+    // rename fails across filesystems; fall back to copy + remove
+    if std::fs::rename(path, &target).is_err() {
+        std::fs::copy(path, &target)?;
+        std::fs::remove_file(path)?;
+    }
+    info!("Quarantined {} -> {}", path.display(), target.display());
+    Ok(target)
+}
+
+fn scan_file(address: &ClamdAddress, path: &Path) -> std::io::Result<Verdict> {
+    let file = File::open(path)?;
+    let reply = match address {
+        ClamdAddress::Tcp(address) => {
+            let stream = TcpStream::connect(address)?;
+            stream.set_read_timeout(Some(TIMEOUT))?;
+            stream.set_write_timeout(Some(TIMEOUT))?;
+            instream(stream, file)?
+        }
+        ClamdAddress::Unix(socket) => {
+            let stream = UnixStream::connect(socket)?;
+            stream.set_read_timeout(Some(TIMEOUT))?;
+            stream.set_write_timeout(Some(TIMEOUT))?;
+            instream(stream, file)?
+        }
+    };
+    parse_reply(&reply)
+}
+
+/// clamd INSTREAM: length-prefixed chunks terminated by a zero-length chunk
+fn instream(mut stream: impl Read + Write, mut file: File) -> std::io::Result<String> {
+    stream.write_all(b"zINSTREAM\0")?;
+    let mut buffer = vec![0u8; CHUNK_SIZE];
+    loop {
+        let n = file.read(&mut buffer)?;
+        stream.write_all(&(n as u32).to_be_bytes())?;
+        if n == 0 {
+            break;
+        }
+        stream.write_all(&buffer[..n])?;
+    }
+    stream.flush()?;
+
+    let mut reply = Vec::new();
+    stream.read_to_end(&mut reply)?;
+    Ok(String::from_utf8_lossy(&reply).trim_end_matches('\0').trim().to_string())
+}
+
+/// `stream: OK`, `stream: Eicar-Signature FOUND` or `INSTREAM size limit exceeded. ERROR`
+fn parse_reply(reply: &str) -> std::io::Result<Verdict> {
+    let result = reply.strip_prefix("stream:").unwrap_or(reply).trim();
+    if result == "OK" {
+        Ok(Verdict::Clean)
+    } else if let Some(signature) = result.strip_suffix("FOUND") {
+        Ok(Verdict::Infected(signature.trim().to_string()))
+    } else {
+        Err(std::io::Error::other(format!("clamd: {}", reply)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    #[test]
+    fn test_parse_reply() {
+        assert_eq!(parse_reply("stream: OK").unwrap(), Verdict::Clean);
+        assert_eq!(
+            parse_reply("stream: Eicar-Signature FOUND").unwrap(),
+            Verdict::Infected("Eicar-Signature".to_string())
+        );
+        assert!(parse_reply("INSTREAM size limit exceeded. ERROR").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_infected_input_is_quarantined() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let clamd = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut command = [0u8; 10];
+            stream.read_exact(&mut command).unwrap();
+            loop {
+                let mut length = [0u8; 4];
+                stream.read_exact(&mut length).unwrap();
+                let length = u32::from_be_bytes(length) as usize;
+                if length == 0 {
+                    break;
+                }
+                let mut chunk = vec![0u8; length];
+                stream.read_exact(&mut chunk).unwrap();
+            }
+            stream.write_all(b"stream: Eicar-Signature FOUND\0").unwrap();
+        });
+
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("upload.jpg");
+        std::fs::write(&input, b"X5O!P%@AP[4\\PZX54(P^)7CC)7}$EICAR").unwrap();
+        let scanner = Scanner {
+            address: Some(ClamdAddress::parse(&address)),
+            quarantine_dir: dir.path().join("quarantine"),
+            fail_open: false,
+        };
+
+        let error = scanner.scan(Some("job-1"), &input).await.unwrap_err();
+        clamd.join().unwrap();
+        assert_eq!(ServiceError::from(error).code(), "infected_input");
+        assert!(!input.exists());
+        assert!(dir.path().join("quarantine/job-1-upload.jpg").exists());
+    }
+}
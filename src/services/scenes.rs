@@ -0,0 +1,112 @@
+use anyhow::Result;
+use serde::Serialize;
+use std::path::Path;
+use crate::utils::error::{FfmpegErrorKind, FfmpegFailure};
+use crate::utils::{audit, sandbox};
+
+/// `scene` score above which a frame counts as a cut when the request doesn't set it; FFmpeg
+/// scores from 0 (same picture) to 1 (nothing in common)
+pub const DEFAULT_THRESHOLD: f64 = 0.4;
+
+/// Cuts one detection returns at most, keeping the strongest
+pub const MAX_SCENES: usize = 1000;
+
+/// A detected cut: the first frame of a new scene
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct SceneCut {
+    /// Seconds into the video
+    pub timestamp: f64,
+    pub score: f64,
+}
+
+/// Frames `metadata=print` wrote, each a `frame:12 pts:61440 pts_time:5.12` line followed by
+/// its `lavfi.scene_score=0.634512`
+pub fn parse_cuts(stdout: &str) -> Vec<SceneCut> {
+    let mut cuts = Vec::new();
+    let mut timestamp = None;
+    for line in stdout.lines() {
+        if let Some(time) = line.split_whitespace().find_map(|field| field.strip_prefix("pts_time:")) {
+            timestamp = time.parse().ok();
+        } else if let Some(score) = line.trim().strip_prefix("lavfi.scene_score=") {
+            if let (Some(timestamp), Ok(score)) = (timestamp.take(), score.parse()) {
+                cuts.push(SceneCut { timestamp, score });
+            }
+        }
+    }
+    cuts
+}
+
+/// Drop cuts closer than `min_interval` seconds to the previous kept one, then keep the
+/// `max` strongest, in time order
+pub fn thin(cuts: Vec<SceneCut>, min_interval: f64, max: usize) -> Vec<SceneCut> {
+    let mut kept: Vec<SceneCut> = Vec::with_capacity(cuts.len());
+    for cut in cuts {
+        match kept.last_mut() {
+            Some(last) if cut.timestamp - last.timestamp < min_interval => {
+                // A fade scores several frames in a row; the sharpest one marks the cut
+                if cut.score > last.score {
+                    *last = cut;
+                }
+            }
+            _ => kept.push(cut),
+        }
+    }
+    if kept.len() > max {
+        kept.sort_by(|a, b| b.score.total_cmp(&a.score));
+        kept.truncate(max);
+        kept.sort_by(|a, b| a.timestamp.total_cmp(&b.timestamp));
+    }
+    kept
+}
+
+/// Frames of `input` whose scene score exceeds `threshold`. Decodes the whole video, so it
+/// takes about as long as playing it at decode speed
+pub async fn detect(job_id: &str, input: &Path, threshold: f64) -> Result<Vec<SceneCut>> {
+    let mut command = sandbox::command("ffmpeg");
+    command
+        .arg("-hide_banner").arg("-v").arg("error").arg("-nostdin")
+        .arg("-i").arg(input)
+        .arg("-map").arg("0:v:0")
+        .arg("-vf").arg(format!("select='gt(scene,{})',metadata=print:file=-", threshold))
+        .arg("-f").arg("null")
+        .arg("-");
+    let output = audit::output_async(Some(job_id), command).await?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(FfmpegFailure {
+            kind: FfmpegErrorKind::classify(&stderr),
+            message: format!("Scene detection failed: {}", stderr.trim()),
+        }
+        .into());
+    }
+    Ok(parse_cuts(&String::from_utf8_lossy(&output.stdout)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_thin_cuts() {
+        let stdout = "\
+frame:0    pts:61440   pts_time:5.12
+lavfi.scene_score=0.634512
+frame:1    pts:62464   pts_time:5.2
+lavfi.scene_score=0.801200
+frame:2    pts:368640  pts_time:30.72
+lavfi.scene_score=0.451000
+frame:3    pts:737280  pts_time:61.44
+lavfi.scene_score=0.999000
+";
+        let cuts = parse_cuts(stdout);
+        assert_eq!(cuts.len(), 4);
+        assert_eq!(cuts[0], SceneCut { timestamp: 5.12, score: 0.634512 });
+
+        // The two frames of one fade collapse into the sharper one
+        let thinned = thin(cuts.clone(), 1.0, MAX_SCENES);
+        assert_eq!(thinned.iter().map(|cut| cut.timestamp).collect::<Vec<_>>(), vec![5.2, 30.72, 61.44]);
+        let strongest = thin(cuts, 1.0, 2);
+        assert_eq!(strongest.iter().map(|cut| cut.timestamp).collect::<Vec<_>>(), vec![5.2, 61.44]);
+        assert!(parse_cuts("").is_empty());
+    }
+}
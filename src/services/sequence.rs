@@ -0,0 +1,60 @@
+use std::io;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+use tempfile::NamedTempFile;
+
+/// Images one sequence may list
+pub const MAX_FRAMES: usize = 2000;
+
+fn quote(path: &Path) -> String {
+    format!("'{}'", path.to_string_lossy().replace('\'', r"'\''"))
+}
+
+/// FFmpeg concat demuxer script showing each image for its duration in seconds. The demuxer
+/// drops the last duration unless the last file is listed once more
+pub fn concat_script(frames: &[(&Path, f64)]) -> String {
+    let mut script = String::from("ffconcat version 1.0\n");
+    for (path, duration) in frames {
+        script.push_str(&format!("file {}\nduration {}\n", quote(path), duration));
+    }
+    if let Some((last, _)) = frames.last() {
+        script.push_str(&format!("file {}\n", quote(last)));
+    }
+    script
+}
+
+/// Write the concat script for `frames` next to `output`, where FFmpeg may already read and
+/// write. Paths are made absolute so they don't resolve against the script's directory;
+/// the input needs `-f concat -safe 0`
+pub fn write_script(output: &Path, frames: &[(String, f64)]) -> io::Result<NamedTempFile> {
+    let absolute = frames
+        .iter()
+        .map(|(path, duration)| Ok((std::path::absolute(path)?, *duration)))
+        .collect::<io::Result<Vec<_>>>()?;
+    let entries: Vec<(&Path, f64)> = absolute.iter().map(|(path, duration)| (path.as_path(), *duration)).collect();
+
+    let dir = output.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let script = tempfile::Builder::new().prefix(".sequence-").suffix(".ffconcat").tempfile_in(dir)?;
+    std::fs::write(script.path(), concat_script(&entries))?;
+    // Sandboxed FFmpeg may run as a different uid than the one that created the file
+    std::fs::set_permissions(script.path(), std::fs::Permissions::from_mode(0o644))?;
+    Ok(script)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_concat_script_repeats_last_frame() {
+        let script = concat_script(&[(Path::new("/frames/a.png"), 0.5), (Path::new("/frames/it's.png"), 2.0)]);
+        assert_eq!(
+            script,
+            "ffconcat version 1.0\n\
+             file '/frames/a.png'\nduration 0.5\n\
+             file '/frames/it'\\''s.png'\nduration 2\n\
+             file '/frames/it'\\''s.png'\n"
+        );
+        assert_eq!(concat_script(&[]), "ffconcat version 1.0\n");
+    }
+}
@@ -0,0 +1,170 @@
+use std::io;
+use std::path::Path;
+
+/// Images one slideshow may have; FFmpeg opens each as its own input
+pub const MAX_SLIDES: usize = 500;
+
+pub const DEFAULT_IMAGE_SECS: f64 = 3.0;
+pub const DEFAULT_TRANSITION_SECS: f64 = 1.0;
+pub const DEFAULT_FPS: u32 = 30;
+
+/// `xfade` transitions a request may pick, plus `none` for hard cuts
+pub static TRANSITIONS: &[&str] = &[
+    "none", "fade", "dissolve", "wipeleft", "wiperight", "slideleft", "slideright", "circleopen", "circleclose",
+];
+
+/// How far a Ken Burns slide zooms in over its duration
+const KEN_BURNS_ZOOM: f64 = 0.2;
+
+/// Layout and timing of a slideshow; every image is fitted (letterboxed) to `width`x`height`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Slideshow {
+    pub width: u32,
+    pub height: u32,
+    pub fps: u32,
+    pub image_secs: f64,
+    /// `xfade` transition and its length; `None` cuts straight from one image to the next
+    pub transition: Option<(String, f64)>,
+    /// Slowly zoom into each image instead of holding it still
+    pub ken_burns: bool,
+}
+
+impl Slideshow {
+    /// Length of the video for `count` images; transitions overlap neighbouring slides
+    pub fn duration(&self, count: usize) -> f64 {
+        let overlap = self.transition.as_ref().map_or(0.0, |(_, secs)| *secs);
+        count as f64 * self.image_secs - count.saturating_sub(1) as f64 * overlap
+    }
+
+    /// `-filter_complex` graph over inputs `0..count`, one still image each, ending in `[v]`
+    pub fn filter_graph(&self, count: usize) -> String {
+        let (width, height, fps) = (self.width, self.height, self.fps);
+        let frames = (self.image_secs * f64::from(fps)).round().max(1.0) as u64;
+        let zoom = if self.ken_burns {
+            let step = KEN_BURNS_ZOOM / frames as f64;
+            format!("z='min(zoom+{:.6},{})':x='iw/2-(iw/zoom/2)':y='ih/2-(ih/zoom/2)'", step, 1.0 + KEN_BURNS_ZOOM)
+        } else {
+            "z=1".to_string()
+        };
+        let mut graph: Vec<String> = (0..count)
+            .map(|index| {
+                format!(
+                    "[{index}:v]scale={width}:{height}:force_original_aspect_ratio=decrease,\
+                     pad={width}:{height}:(ow-iw)/2:(oh-ih)/2,setsar=1,\
+                     zoompan={zoom}:d={frames}:s={width}x{height}:fps={fps}[s{index}]"
+                )
+            })
+            .collect();
+
+        let slides: String = (0..count).map(|index| format!("[s{}]", index)).collect();
+        match &self.transition {
+            Some((transition, secs)) if count > 1 => {
+                let mut previous = "s0".to_string();
+                for index in 1..count {
+                    let offset = index as f64 * (self.image_secs - secs);
+                    let joined = format!("x{}", index);
+                    graph.push(format!(
+                        "[{previous}][s{index}]xfade=transition={transition}:duration={secs}:offset={offset}[{joined}]"
+                    ));
+                    previous = joined;
+                }
+                graph.push(format!("[{}]format=yuv420p[v]", previous));
+            }
+            _ => graph.push(format!("{}concat=n={}:v=1:a=0,format=yuv420p[v]", slides, count)),
+        }
+        graph.join(";")
+    }
+}
+
+/// Whether `name` matches `pattern`, where `*` stands for any run of characters and `?` for one
+pub fn wildcard(pattern: &str, name: &str) -> bool {
+    let (pattern, name): (Vec<char>, Vec<char>) = (pattern.chars().collect(), name.chars().collect());
+    // Position of the last `*` and the name position it was tried at, to backtrack to
+    let (mut p, mut n, mut star) = (0, 0, None);
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((star_p, star_n)) => {
+                    p = star_p + 1;
+                    n = star_n + 1;
+                    star = Some((star_p, star_n + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Files matching a `dir/*.jpg`-style pattern, sorted by name. Wildcards are only
+/// supported in the file name, not in directories
+pub fn expand(pattern: &str) -> io::Result<Vec<String>> {
+    let path = Path::new(pattern);
+    let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let file_pattern = path.file_name().and_then(|name| name.to_str()).unwrap_or_default();
+    let mut matches: Vec<String> = std::fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_ok_and(|kind| kind.is_file()))
+        .filter(|entry| entry.file_name().to_str().is_some_and(|name| wildcard(file_pattern, name)))
+        .map(|entry| entry.path().to_string_lossy().into_owned())
+        .collect();
+    matches.sort();
+    Ok(matches)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wildcard_and_expand() {
+        assert!(wildcard("*.jpg", "IMG_0001.jpg"));
+        assert!(wildcard("IMG_????.jpg", "IMG_0001.jpg"));
+        assert!(wildcard("a*b*c", "axxbyyc"));
+        assert!(!wildcard("*.jpg", "IMG_0001.jpeg"));
+        assert!(!wildcard("a*b", "acbc"));
+
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["b.jpg", "a.jpg", "c.png"] {
+            std::fs::write(dir.path().join(name), b"").unwrap();
+        }
+        let found = expand(&format!("{}/*.jpg", dir.path().display())).unwrap();
+        let names: Vec<&str> = found.iter().map(|path| path.rsplit('/').next().unwrap()).collect();
+        assert_eq!(names, ["a.jpg", "b.jpg"]);
+    }
+
+    #[test]
+    fn test_slideshow_graph() {
+        let mut show = Slideshow {
+            width: 1280,
+            height: 720,
+            fps: 25,
+            image_secs: 4.0,
+            transition: Some(("fade".to_string(), 1.0)),
+            ken_burns: false,
+        };
+        assert_eq!(show.duration(3), 10.0);
+        let graph = show.filter_graph(3);
+        assert!(graph.starts_with(
+            "[0:v]scale=1280:720:force_original_aspect_ratio=decrease,pad=1280:720:(ow-iw)/2:(oh-ih)/2,setsar=1,\
+             zoompan=z=1:d=100:s=1280x720:fps=25[s0];"
+        ));
+        assert!(graph.contains("[s0][s1]xfade=transition=fade:duration=1:offset=3[x1]"));
+        assert!(graph.ends_with("[x1][s2]xfade=transition=fade:duration=1:offset=6[x2];[x2]format=yuv420p[v]"));
+
+        show.transition = None;
+        show.ken_burns = true;
+        assert_eq!(show.duration(3), 12.0);
+        let graph = show.filter_graph(2);
+        assert!(graph.contains("zoompan=z='min(zoom+0.002000,1.2)':x='iw/2-(iw/zoom/2)':y='ih/2-(ih/zoom/2)':d=100"));
+        assert!(graph.ends_with("[s0][s1]concat=n=2:v=1:a=0,format=yuv420p[v]"));
+    }
+}
@@ -0,0 +1,114 @@
+/// Seconds between sampled frames when the request doesn't set it
+pub const DEFAULT_INTERVAL: f64 = 10.0;
+
+/// Tile width when the request doesn't set it; the height follows the aspect ratio
+pub const DEFAULT_TILE_WIDTH: u32 = 160;
+
+/// Tiles per row when the request doesn't set it
+pub const DEFAULT_COLUMNS: u32 = 10;
+
+/// Rows per sheet; further frames continue on the next sheet, so no image grows unbounded
+pub const MAX_ROWS: u32 = 10;
+
+/// Base name of the sheets (`sprite_000.jpg` and up) and of the WebVTT track
+pub const SPRITE_NAME: &str = "sprite";
+
+/// Where each sampled frame lands: a grid of `columns` x `rows` tiles per sheet
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpriteLayout {
+    pub interval: f64,
+    pub tile_width: u32,
+    pub tile_height: u32,
+    pub columns: u32,
+    pub rows: u32,
+    /// Frames sampled over the whole video
+    pub frames: u32,
+}
+
+impl SpriteLayout {
+    /// Layout for a `duration`-second video of `width`x`height`, one tile every `interval` seconds
+    pub fn new(duration: f64, interval: f64, tile_width: u32, columns: u32, (width, height): (u32, u32)) -> Self {
+        let frames = (duration / interval).ceil().max(1.0) as u32;
+        // Even, like FFmpeg's `-2`, so tiles of subsampled sources line up with the grid
+        let tile_height = ((f64::from(tile_width) * f64::from(height) / f64::from(width.max(1))).round() as u32 / 2 * 2).max(2);
+        let columns = columns.min(frames);
+        Self {
+            interval,
+            tile_width,
+            tile_height,
+            columns,
+            rows: frames.div_ceil(columns).min(MAX_ROWS),
+            frames,
+        }
+    }
+
+    pub fn per_sheet(&self) -> u32 {
+        self.columns * self.rows
+    }
+
+    pub fn sheets(&self) -> u32 {
+        self.frames.div_ceil(self.per_sheet())
+    }
+
+    pub fn sheet_name(index: u32) -> String {
+        format!("{}_{:03}.jpg", SPRITE_NAME, index)
+    }
+
+    /// Sample, scale and tile in one pass; the image2 muxer writes each full grid as a sheet
+    pub fn filter(&self) -> String {
+        format!(
+            "fps=1/{},scale={}:{},tile={}x{}",
+            self.interval, self.tile_width, self.tile_height, self.columns, self.rows
+        )
+    }
+
+    /// WebVTT track with one cue per tile, pointing into its sheet with a `#xywh=` media
+    /// fragment; players resolve the sheet names relative to the track
+    pub fn webvtt(&self, duration: f64) -> String {
+        let mut vtt = String::from("WEBVTT\n");
+        for frame in 0..self.frames {
+            let start = f64::from(frame) * self.interval;
+            let end = (start + self.interval).min(duration.max(start));
+            let tile = frame % self.per_sheet();
+            vtt.push_str(&format!(
+                "\n{} --> {}\n{}#xywh={},{},{},{}\n",
+                timestamp(start),
+                timestamp(end),
+                Self::sheet_name(frame / self.per_sheet()),
+                tile % self.columns * self.tile_width,
+                tile / self.columns * self.tile_height,
+                self.tile_width,
+                self.tile_height
+            ));
+        }
+        vtt
+    }
+}
+
+/// `HH:MM:SS.mmm`, the only form WebVTT accepts for cue times over an hour
+fn timestamp(secs: f64) -> String {
+    let millis = (secs.max(0.0) * 1000.0).round() as u64;
+    format!("{:02}:{:02}:{:02}.{:03}", millis / 3_600_000, millis / 60_000 % 60, millis / 1000 % 60, millis % 1000)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_layout_and_webvtt() {
+        // 25 minutes every 10 seconds is 150 tiles: one full 10x10 sheet and half of a second
+        let layout = SpriteLayout::new(1500.0, 10.0, 160, 10, (1920, 1080));
+        assert_eq!((layout.frames, layout.tile_height, layout.rows, layout.sheets()), (150, 90, 10, 2));
+        assert_eq!(layout.filter(), "fps=1/10,scale=160:90,tile=10x10");
+        let vtt = layout.webvtt(1500.0);
+        assert!(vtt.starts_with("WEBVTT\n\n00:00:00.000 --> 00:00:10.000\nsprite_000.jpg#xywh=0,0,160,90\n"));
+        assert!(vtt.contains("\n00:01:50.000 --> 00:02:00.000\nsprite_000.jpg#xywh=160,90,160,90\n"));
+        assert!(vtt.ends_with("\n00:24:50.000 --> 00:25:00.000\nsprite_001.jpg#xywh=1440,360,160,90\n"));
+
+        // Short clips get one row no wider than they need, the last cue ending with the video
+        let short = SpriteLayout::new(25.0, 10.0, 120, 10, (720, 1280));
+        assert_eq!((short.columns, short.rows, short.tile_height), (3, 1, 212));
+        assert!(short.webvtt(25.0).ends_with("00:00:20.000 --> 00:00:25.000\nsprite_000.jpg#xywh=240,0,120,212\n"));
+    }
+}
@@ -0,0 +1,91 @@
+/// Longest side of a sticker when the request doesn't set one; what sticker packs usually expect
+pub const DEFAULT_MAX_DIMENSION: u32 = 512;
+
+/// Frame rate of frame sequences when the request doesn't set one
+pub const DEFAULT_FPS: u32 = 15;
+
+/// `colorkey` similarity in percent when a key color is given without a tolerance
+pub const DEFAULT_TOLERANCE: u32 = 10;
+
+/// Output extensions and the animated formats with alpha they produce
+pub static STICKER_EXTENSIONS: &[&str] = &["webm", "png", "apng"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StickerFormat {
+    /// VP9 in WebM with an alpha plane
+    Webm,
+    /// Animated PNG, looping forever
+    Apng,
+}
+
+impl StickerFormat {
+    pub fn from_path(path: &str) -> Option<Self> {
+        let extension = std::path::Path::new(path).extension()?.to_str()?.to_lowercase();
+        match extension.as_str() {
+            "webm" => Some(StickerFormat::Webm),
+            "png" | "apng" => Some(StickerFormat::Apng),
+            _ => None,
+        }
+    }
+
+    /// Output options; `.png` outputs need `-f apng` or FFmpeg writes the first frame only
+    pub fn encoder_args(self) -> &'static [&'static str] {
+        match self {
+            // Alt-ref frames can't carry alpha in older libvpx builds
+            StickerFormat::Webm => &["-c:v", "libvpx-vp9", "-b:v", "0", "-crf", "32", "-auto-alt-ref", "0", "-an", "-f", "webm"],
+            StickerFormat::Apng => &["-c:v", "apng", "-plays", "0", "-an", "-f", "apng"],
+        }
+    }
+
+    fn pixel_format(self) -> &'static str {
+        match self {
+            StickerFormat::Webm => "yuva420p",
+            StickerFormat::Apng => "rgba",
+        }
+    }
+}
+
+/// Whether `value` is a `#RRGGBB` (or bare `RRGGBB`) color
+pub fn is_hex_color(value: &str) -> bool {
+    let hex = value.strip_prefix('#').unwrap_or(value);
+    hex.len() == 6 && hex.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// `-vf` chain: resample, key out the background (before scaling, so edges aren't smeared
+/// into it), fit within a `max_dimension` square and convert to a format with alpha
+pub fn filter(fps: Option<u32>, key: Option<(&str, u32)>, max_dimension: u32, format: StickerFormat) -> String {
+    let mut steps = Vec::new();
+    if let Some(fps) = fps {
+        steps.push(format!("fps={}", fps));
+    }
+    if let Some((color, tolerance)) = key {
+        let similarity = f64::from(tolerance) / 100.0;
+        steps.push(format!("colorkey=0x{}:{}:0.05", color.trim_start_matches('#'), similarity));
+    }
+    steps.push(format!("scale={0}:{0}:force_original_aspect_ratio=decrease", max_dimension));
+    steps.push(format!("format={}", format.pixel_format()));
+    steps.join(",")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sticker_filter_and_format() {
+        assert_eq!(StickerFormat::from_path("out/wave.WEBM"), Some(StickerFormat::Webm));
+        assert_eq!(StickerFormat::from_path("out/wave.png"), Some(StickerFormat::Apng));
+        assert_eq!(StickerFormat::from_path("out/wave.gif"), None);
+        assert!(is_hex_color("#00FF00") && is_hex_color("00ff00"));
+        assert!(!is_hex_color("#0f0") && !is_hex_color("green"));
+
+        assert_eq!(
+            filter(None, Some(("#00ff00", 25)), 512, StickerFormat::Webm),
+            "colorkey=0x00ff00:0.25:0.05,scale=512:512:force_original_aspect_ratio=decrease,format=yuva420p"
+        );
+        assert_eq!(
+            filter(Some(15), None, 256, StickerFormat::Apng),
+            "fps=15,scale=256:256:force_original_aspect_ratio=decrease,format=rgba"
+        );
+    }
+}
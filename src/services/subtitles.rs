@@ -0,0 +1,109 @@
+use serde::Serialize;
+use std::path::Path;
+
+/// Subtitle files that can be burned in or muxed
+pub static SUBTITLE_EXTENSIONS: &[&str] = &["srt", "ass", "ssa", "vtt"];
+
+/// Output extensions of extracted tracks
+pub static EXTRACT_EXTENSIONS: &[&str] = &["srt", "ass", "vtt"];
+
+/// Subtitle files one mux request may add
+pub const MAX_TRACKS: usize = 16;
+
+/// Text subtitle codecs as ffprobe names them. The rest (PGS, DVD, DVB) are bitmaps, which
+/// can be burned in but not converted to a text format
+static TEXT_CODECS: &[&str] = &["subrip", "srt", "ass", "ssa", "webvtt", "mov_text", "text", "microdvd", "subviewer"];
+
+/// A subtitle stream of a probed container
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SubtitleStream {
+    /// Index among the container's streams, for `-map 0:N`
+    pub index: u64,
+    pub codec: String,
+    pub language: Option<String>,
+}
+
+impl SubtitleStream {
+    pub fn is_text(&self) -> bool {
+        TEXT_CODECS.contains(&self.codec.as_str())
+    }
+}
+
+/// Subtitle streams of a probed input, in container order
+pub fn streams(probe: &serde_json::Value) -> Vec<SubtitleStream> {
+    let streams = probe["streams"].as_array().map(Vec::as_slice).unwrap_or_default();
+    streams
+        .iter()
+        .filter(|stream| stream["codec_type"] == "subtitle")
+        .filter_map(|stream| {
+            Some(SubtitleStream {
+                index: stream["index"].as_u64()?,
+                codec: stream["codec_name"].as_str().unwrap_or_default().to_string(),
+                language: stream["tags"]["language"].as_str().filter(|language| *language != "und").map(str::to_string),
+            })
+        })
+        .collect()
+}
+
+/// Encoder for subtitles muxed into `output`'s container: MP4 and QuickTime only carry
+/// `mov_text`, WebM only WebVTT, and Matroska takes SRT and ASS as they are
+pub fn mux_codec(output: &Path) -> &'static str {
+    match extension(output).as_deref() {
+        Some("mp4" | "m4v" | "mov") => "mov_text",
+        Some("webm") => "webvtt",
+        _ => "copy",
+    }
+}
+
+/// Encoder writing an extracted track in the format of `output`'s extension
+pub fn extract_codec(output: &Path) -> Option<&'static str> {
+    match extension(output)?.as_str() {
+        "srt" => Some("srt"),
+        "ass" => Some("ass"),
+        "vtt" => Some("webvtt"),
+        _ => None,
+    }
+}
+
+/// `-vf` rendering a subtitle file, or with `stream` the `stream`th subtitle track of the
+/// container at `path`, onto the frames; ASS styling and positioning are kept. `path` must be
+/// free of [`FILTER_PATH_RESERVED`](crate::services::filters::FILTER_PATH_RESERVED)
+pub fn burn_filter(path: &str, stream: Option<u32>) -> String {
+    match stream {
+        Some(stream) => format!("subtitles=filename={}:si={}", path, stream),
+        None => format!("subtitles=filename={}", path),
+    }
+}
+
+fn extension(path: &Path) -> Option<String> {
+    Some(path.extension()?.to_str()?.to_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_subtitle_streams_and_codecs() {
+        let probe = serde_json::json!({
+            "streams": [
+                { "index": 0, "codec_type": "video", "codec_name": "h264" },
+                { "index": 2, "codec_type": "subtitle", "codec_name": "subrip", "tags": { "language": "eng" } },
+                { "index": 3, "codec_type": "subtitle", "codec_name": "hdmv_pgs_subtitle", "tags": { "language": "und" } }
+            ]
+        });
+        let streams = streams(&probe);
+        assert_eq!(streams[0], SubtitleStream { index: 2, codec: "subrip".to_string(), language: Some("eng".to_string()) });
+        assert!(streams[0].is_text());
+        assert!(!streams[1].is_text() && streams[1].language.is_none());
+
+        assert_eq!(mux_codec(Path::new("out/film.MP4")), "mov_text");
+        assert_eq!(mux_codec(Path::new("out/film.webm")), "webvtt");
+        assert_eq!(mux_codec(Path::new("out/film.mkv")), "copy");
+        assert_eq!(extract_codec(Path::new("subs.vtt")), Some("webvtt"));
+        assert_eq!(extract_codec(Path::new("subs.txt")), None);
+
+        assert_eq!(burn_filter("/media/subs/film.ass", None), "subtitles=filename=/media/subs/film.ass");
+        assert_eq!(burn_filter("/media/film.mkv", Some(1)), "subtitles=filename=/media/film.mkv:si=1");
+    }
+}
@@ -0,0 +1,490 @@
+use anyhow::Result;
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::UNIX_EPOCH;
+use crate::models::image::LosslessJpegRequest;
+use crate::models::job::JobProgress;
+use crate::models::sync::{DerivativeSpec, MirrorSyncRequest, MirrorSyncResponse, SyncFailure};
+use crate::services::job_store::JobStore;
+use crate::services::queue;
+use crate::services::limits::InputLimits;
+use crate::services::memory;
+use crate::services::privacy::PrivacyZones;
+use crate::services::probe;
+use crate::services::scanner::Scanner;
+use crate::services::result_cache::ResultCache;
+use crate::services::temp_files::TempFileManager;
+use crate::services::tenants::TenantQuotas;
+use crate::utils::{audit, sandbox};
+use crate::utils::error::ServiceError;
+use crate::utils::validation::Validate;
+
+/// Name of the state file kept at the root of every mirrored output tree
+const MANIFEST_FILE: &str = ".mirror-manifest.json";
+
+/// Source extensions picked up when walking the source tree
+pub static SOURCE_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png", "webp", "gif", "bmp", "tif", "tiff"];
+
+/// Derivative formats the mirror can produce
+pub static DERIVATIVE_FORMATS: &[&str] = &["webp", "jpg", "jpeg", "png", "gif"];
+
+/// `paletteuse` dithering modes accepted for indexed (GIF/PNG8) derivatives
+pub static DITHER_MODES: &[&str] = &["none", "bayer", "floyd_steinberg", "sierra2", "sierra2_4a"];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ManifestEntry {
+    mtime: u64,
+    size: u64,
+    output: String,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Manifest {
+    spec: Option<DerivativeSpec>,
+    entries: BTreeMap<String, ManifestEntry>,
+}
+
+#[derive(Default)]
+pub struct SyncProcessor {
+    jobs: Arc<JobStore>,
+    limits: InputLimits,
+    quotas: TenantQuotas,
+    scanner: Scanner,
+    temp: TempFileManager,
+    cache: ResultCache,
+    privacy: PrivacyZones,
+}
+
+impl SyncProcessor {
+    pub fn new() -> Self {
+        Self::with_jobs(Arc::new(JobStore::new()))
+    }
+
+    /// Record mirror runs in an existing job history, so they show up in `/jobs` next to video jobs
+    pub fn with_jobs(jobs: Arc<JobStore>) -> Self {
+        Self {
+            jobs,
+            limits: InputLimits::from_env(),
+            // A malformed TENANT_QUOTAS already fails VideoProcessor::new at startup
+            quotas: TenantQuotas::from_env().unwrap_or_default(),
+            scanner: Scanner::from_env(),
+            temp: TempFileManager::from_env(),
+            cache: ResultCache::from_env(),
+            // Likewise a malformed PRIVACY_ZONES
+            privacy: PrivacyZones::from_env().unwrap_or_default(),
+        }
+    }
+
+    /// Mirror `source_dir` into `output_dir`, only reprocessing files whose mtime/size changed
+    pub async fn mirror(&self, request: &MirrorSyncRequest) -> Result<MirrorSyncResponse> {
+        let job_id = queue::job_id();
+        let spec = &request.derivative;
+
+        info!("[{}] Starting mirror sync: {} -> {}", job_id, request.source_dir, request.output_dir);
+
+        let format = spec.format.to_lowercase();
+        if !DERIVATIVE_FORMATS.contains(&format.as_str()) {
+            return Err(ServiceError::InvalidFormat(format!("Unsupported derivative format: {}", spec.format)).into());
+        }
+
+        self.quotas.start_job(&self.jobs, &job_id, "sync.mirror", &request.source_dir, &request.output_dir)?;
+        let result = self.run_mirror(&job_id, request, &format).await;
+        self.jobs.finish(&job_id, &result);
+        result
+    }
+
+    async fn run_mirror(&self, job_id: &str, request: &MirrorSyncRequest, format: &str) -> Result<MirrorSyncResponse> {
+        let job_id = job_id.to_string();
+        let spec = &request.derivative;
+
+        let source_root = Path::new(&request.source_dir);
+        if !source_root.is_dir() {
+            return Err(ServiceError::FileNotFound(format!("Source directory not found: {}", request.source_dir)).into());
+        }
+
+        let output_root = Path::new(&request.output_dir);
+        self.temp.output_dir(output_root)?;
+
+        let manifest_path = output_root.join(MANIFEST_FILE);
+        let mut manifest = Self::load_manifest(&manifest_path);
+
+        // A changed spec invalidates every derivative in the tree
+        if manifest.spec.as_ref() != Some(spec) {
+            if manifest.spec.is_some() {
+                info!("[{}] Derivative spec changed, reprocessing whole tree", job_id);
+            }
+            manifest.entries.clear();
+            manifest.spec = Some(spec.clone());
+        }
+
+        let mut sources = Vec::new();
+        // An output tree inside the source tree would otherwise be mirrored into itself
+        let output_canonical = std::fs::canonicalize(output_root).ok();
+        Self::collect_sources(source_root, output_canonical.as_deref(), &mut sources)?;
+        sources.sort();
+        info!("[{}] Found {} source files", job_id, sources.len());
+        let relatives: Vec<String> = sources
+            .iter()
+            .map(|source| source.strip_prefix(source_root).unwrap_or(source).to_string_lossy().to_string())
+            .collect();
+        let outputs = Self::derivative_paths(&relatives, format);
+        let claimed: HashSet<&str> = outputs.iter().map(String::as_str).collect();
+
+        let mut processed = Vec::new();
+        let mut failed = Vec::new();
+        let mut skipped = 0;
+
+        let total = sources.len();
+        for (completed, source) in sources.iter().enumerate() {
+            // Checked between files; what finished so far is kept for the next run to skip
+            if self.jobs.is_cancelled(&job_id) {
+                if let Err(e) = Self::save_manifest(&manifest_path, &manifest) {
+                    error!("[{}] Failed to write mirror manifest: {}", job_id, e);
+                }
+                return Err(ServiceError::JobCancelled(format!(
+                    "mirror cancelled after {} of {} files",
+                    completed, total
+                ))
+                .into());
+            }
+            let relative = relatives[completed].clone();
+            self.jobs.set_progress(&job_id, JobProgress::batch(completed, total, Some(relative.clone())));
+            let metadata = std::fs::metadata(source)?;
+            let mtime = metadata
+                .modified()?
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0);
+            let size = metadata.len();
+
+            let output_relative = outputs[completed].clone();
+            let output_path = output_root.join(&output_relative);
+
+            // Skip files that are unchanged since the last run and still have their derivative
+            if let Some(entry) = manifest.entries.get(&relative) {
+                if entry.mtime == mtime && entry.size == size && output_path.exists() {
+                    skipped += 1;
+                    continue;
+                }
+            }
+
+            self.temp.output_parent(&output_path)?;
+
+            let checked = match self.limits.check(Some(&job_id), source).await {
+                Ok(()) => self.scanner.scan(Some(&job_id), source).await,
+                Err(e) => Err(e),
+            };
+            let rendered = match checked {
+                Ok(()) => Self::render_within_budget(&job_id, source, &output_path, spec, format).await,
+                Err(e) => Err(e),
+            };
+            match rendered {
+                Ok(()) => {
+                    info!("[{}] Rendered derivative: {}", job_id, output_relative);
+                    let previous = manifest.entries.insert(
+                        relative.clone(),
+                        ManifestEntry { mtime, size, output: output_relative.clone() },
+                    );
+                    // Renamed since the last run, e.g. once a source sharing its stem appeared
+                    if let Some(previous) = previous.filter(|entry| entry.output != output_relative && !claimed.contains(entry.output.as_str())) {
+                        let stale = output_root.join(&previous.output);
+                        if let Err(e) = std::fs::remove_file(&stale) {
+                            warn!("[{}] Could not remove renamed derivative {}: {}", job_id, stale.display(), e);
+                        }
+                    }
+                    processed.push(relative);
+                }
+                Err(e) => {
+                    warn!("[{}] Failed to render {}: {}", job_id, relative, e);
+                    manifest.entries.remove(&relative);
+                    failed.push(SyncFailure { path: relative, error: e.to_string() });
+                }
+            }
+        }
+
+        self.jobs.set_progress(&job_id, JobProgress::batch(total, total, None));
+
+        // Drop derivatives whose source no longer exists
+        let mut removed = Vec::new();
+        if request.prune.unwrap_or(false) {
+            let stale: Vec<String> = manifest
+                .entries
+                .keys()
+                .filter(|relative| !source_root.join(relative).exists())
+                .cloned()
+                .collect();
+            for relative in stale {
+                if let Some(entry) = manifest.entries.remove(&relative) {
+                    let output_path = output_root.join(&entry.output);
+                    if let Err(e) = std::fs::remove_file(&output_path) {
+                        warn!("[{}] Could not remove stale derivative {}: {}", job_id, output_path.display(), e);
+                    }
+                    removed.push(entry.output);
+                }
+            }
+        }
+
+        if let Err(e) = Self::save_manifest(&manifest_path, &manifest) {
+            error!("[{}] Failed to write mirror manifest: {}", job_id, e);
+            return Err(e);
+        }
+
+        info!(
+            "[{}] Mirror sync completed: {} processed, {} skipped, {} removed, {} failed",
+            job_id,
+            processed.len(),
+            skipped,
+            removed.len(),
+            failed.len()
+        );
+
+        Ok(MirrorSyncResponse { job_id, processed, skipped, removed, failed })
+    }
+
+    /// Recursively collect image files below `dir`, leaving out the directory `skip`
+    fn collect_sources(dir: &Path, skip: Option<&Path>, sources: &mut Vec<PathBuf>) -> Result<()> {
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                if skip.is_some_and(|skip| std::fs::canonicalize(&path).is_ok_and(|path| path == skip)) {
+                    continue;
+                }
+                Self::collect_sources(&path, skip, sources)?;
+            } else if Self::is_source_file(&path) {
+                sources.push(path);
+            }
+        }
+        Ok(())
+    }
+
+    /// Derivative of each source, relative to the output tree: the source's path with the
+    /// derivative's extension, or with it appended when sources that differ only by extension
+    /// would share one, so `a.jpg` and `a.png` become `a.jpg.webp` and `a.png.webp`
+    fn derivative_paths(relatives: &[String], format: &str) -> Vec<String> {
+        let replaced: Vec<String> = relatives
+            .iter()
+            .map(|relative| Path::new(relative).with_extension(format).to_string_lossy().to_string())
+            .collect();
+        let mut claims: HashMap<&str, usize> = HashMap::new();
+        for output in &replaced {
+            *claims.entry(output).or_default() += 1;
+        }
+        relatives
+            .iter()
+            .zip(&replaced)
+            .map(|(relative, output)| if claims[output.as_str()] > 1 { format!("{}.{}", relative, format) } else { output.clone() })
+            .collect()
+    }
+
+    fn is_source_file(path: &Path) -> bool {
+        path.extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| SOURCE_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+            .unwrap_or(false)
+    }
+
+    /// Wait for the image's share of the memory budget, then render it
+    async fn render_within_budget(
+        job_id: &str,
+        input: &Path,
+        output: &Path,
+        spec: &DerivativeSpec,
+        format: &str,
+    ) -> Result<()> {
+        let (_reservation, lowres) = memory::reserve_image(Some(job_id), input).await?;
+        Self::render(input, output, spec, format, lowres).await
+    }
+
+    /// Render a single derivative with FFmpeg
+    pub async fn render_derivative(input: &Path, output: &Path, spec: &DerivativeSpec, format: &str) -> Result<()> {
+        Self::render(input, output, spec, format, 0).await
+    }
+
+    async fn render(input: &Path, output: &Path, spec: &DerivativeSpec, format: &str, lowres: u8) -> Result<()> {
+        let mut command = sandbox::command("ffmpeg");
+        command.arg("-y").arg("-v").arg("error");
+        if lowres > 0 {
+            command.arg("-lowres").arg(lowres.to_string());
+        }
+        command.arg("-i").arg(input);
+
+        if let Some(filters) = Self::filter_graph(spec, format) {
+            command.arg("-vf").arg(filters);
+        }
+
+        if let Some(quality) = spec.quality {
+            let quality = quality.clamp(1, 100);
+            match format {
+                "webp" => {
+                    command.arg("-quality").arg(quality.to_string());
+                }
+                "jpg" | "jpeg" => {
+                    // Map 1-100 onto FFmpeg's inverted 31-2 qscale range
+                    let qscale = 2 + (100 - quality) * 29 / 100;
+                    command.arg("-q:v").arg(qscale.to_string());
+                }
+                _ => {}
+            }
+        }
+
+        command.arg("-frames:v").arg("1").arg(output);
+
+        let output = audit::output_async(None, command).await?;
+        if output.status.success() {
+            Ok(())
+        } else {
+            Err(anyhow::anyhow!("{}", String::from_utf8_lossy(&output.stderr).trim()))
+        }
+    }
+
+    /// Rotate, flip or crop a JPEG with jpegtran. The DCT blocks are rearranged without decoding
+    /// them, so repeated orientation fixes and crops never lose quality
+    pub async fn lossless_jpeg(&self, request: &LosslessJpegRequest) -> Result<String> {
+        request.validate()?;
+        let job_id = queue::job_id();
+        let input = Path::new(&request.input_path);
+        if !input.exists() {
+            return Err(ServiceError::FileNotFound(format!("Input file not found: {}", request.input_path)).into());
+        }
+        self.limits.check(Some(&job_id), input).await?;
+        self.scanner.scan(Some(&job_id), input).await?;
+
+        let info = probe::probe(Some(&job_id), input).await?;
+        let is_jpeg = info["streams"]
+            .as_array()
+            .map(|streams| streams.iter().any(|stream| stream["codec_name"] == "mjpeg"))
+            .unwrap_or(false);
+        if !is_jpeg {
+            return Err(ServiceError::InvalidFormat(format!("{} is not a JPEG", request.input_path)).into());
+        }
+
+        info!("[{}] Lossless JPEG transform: {} -> {}", job_id, request.input_path, request.output_path);
+        self.quotas.start_job(&self.jobs, &job_id, "image.lossless_jpeg", &request.input_path, &request.output_path)?;
+        // Cached copies must not outlive a zone added later
+        let params = serde_json::json!({
+            "transform": request.transform,
+            "crop": request.crop,
+            "trim": request.trim,
+            "privacy_zones": self.privacy,
+        });
+        let job = Self::jpegtran(&job_id, request, &self.privacy);
+        let result = self
+            .cache
+            .run(&job_id, &[&request.input_path], "image.lossless_jpeg", params, &request.output_path, job)
+            .await;
+        self.jobs.finish(&job_id, &result);
+        result.map(|_| job_id)
+    }
+
+    async fn jpegtran(job_id: &str, request: &LosslessJpegRequest, privacy: &PrivacyZones) -> Result<()> {
+        let mut command = sandbox::command("jpegtran");
+        // A rotated or flipped image would still carry its old EXIF Orientation and be turned
+        // again by viewers, so transforms keep only the ICC profile; plain crops keep everything
+        let copy = if request.transform.is_some() { "icc" } else { "all" };
+        command.arg("-copy").arg(copy);
+        command.arg(if request.trim.unwrap_or(false) { "-trim" } else { "-perfect" });
+        match request.transform.as_deref() {
+            Some("rotate90") => { command.arg("-rotate").arg("90"); }
+            Some("rotate180") => { command.arg("-rotate").arg("180"); }
+            Some("rotate270") => { command.arg("-rotate").arg("270"); }
+            Some("flip_horizontal") => { command.arg("-flip").arg("horizontal"); }
+            Some("flip_vertical") => { command.arg("-flip").arg("vertical"); }
+            Some("transpose") => { command.arg("-transpose"); }
+            Some("transverse") => { command.arg("-transverse"); }
+            _ => {}
+        }
+        if let Some(crop) = &request.crop {
+            command.arg("-crop").arg(crop);
+        }
+        command.arg("-outfile").arg(&request.output_path).arg(&request.input_path);
+
+        let output = audit::output_async(Some(job_id), command).await?;
+        if output.status.success() {
+            // Copying everything carries the EXIF GPS block over as well
+            if copy == "all" && privacy.redact_jpeg(Path::new(&request.output_path))? {
+                info!("[{}] Blanked EXIF GPS coordinates inside a privacy zone", job_id);
+            }
+            Ok(())
+        } else {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            error!("[{}] jpegtran failed: {}", job_id, stderr.trim());
+            // -perfect refuses images whose size isn't a multiple of the block size
+            Err(anyhow::anyhow!("Lossless JPEG transform failed: {}", stderr.trim()))
+        }
+    }
+
+    fn filter_graph(spec: &DerivativeSpec, format: &str) -> Option<String> {
+        let mut filters = Vec::new();
+        // Fit into a max_dimension box without ever upscaling
+        if let Some(max) = spec.max_dimension {
+            filters.push(format!("scale='min({max},iw)':'min({max},ih)':force_original_aspect_ratio=decrease"));
+        }
+        // GIF, and PNG with a palette size, get an optimal palette for this image instead of
+        // FFmpeg's fixed default one; paletteuse then emits pal8, which PNG stores as PNG8
+        if format == "gif" || (format == "png" && spec.colors.is_some()) {
+            let colors = spec.colors.unwrap_or(256).clamp(2, 256);
+            let dither = spec.dither.as_deref().unwrap_or("sierra2_4a");
+            filters.push(format!(
+                "split[frame][source];[source]palettegen=max_colors={colors}:reserve_transparent=1:stats_mode=single[palette];[frame][palette]paletteuse=dither={dither}"
+            ));
+        }
+        (!filters.is_empty()).then(|| filters.join(","))
+    }
+
+    fn load_manifest(path: &Path) -> Manifest {
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    fn save_manifest(path: &Path, manifest: &Manifest) -> Result<()> {
+        std::fs::write(path, serde_json::to_string_pretty(manifest)?)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filter_graph() {
+        let spec = |colors: Option<u32>| DerivativeSpec {
+            format: "png".to_string(),
+            max_dimension: Some(512),
+            quality: None,
+            colors,
+            dither: Some("bayer".to_string()),
+        };
+        assert_eq!(
+            SyncProcessor::filter_graph(&spec(None), "png").as_deref(),
+            Some("scale='min(512,iw)':'min(512,ih)':force_original_aspect_ratio=decrease")
+        );
+        let png8 = SyncProcessor::filter_graph(&spec(Some(16)), "png").unwrap();
+        assert!(png8.contains("palettegen=max_colors=16") && png8.ends_with("paletteuse=dither=bayer"));
+        assert!(SyncProcessor::filter_graph(&spec(None), "gif").unwrap().contains("max_colors=256"));
+    }
+
+    #[test]
+    fn test_derivatives_of_the_same_stem_keep_apart() {
+        let relatives = ["a.jpg", "a.png", "b/c.tif", "d.webp"].map(String::from);
+        assert_eq!(
+            SyncProcessor::derivative_paths(&relatives, "webp"),
+            vec!["a.jpg.webp", "a.png.webp", "b/c.webp", "d.webp"]
+        );
+
+        // An output tree below the sources isn't walked into
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("derived/nested")).unwrap();
+        std::fs::write(dir.path().join("photo.jpg"), b"").unwrap();
+        std::fs::write(dir.path().join("derived/nested/photo.jpg"), b"").unwrap();
+        let skip = std::fs::canonicalize(dir.path().join("derived")).unwrap();
+        let mut sources = Vec::new();
+        SyncProcessor::collect_sources(dir.path(), Some(&skip), &mut sources).unwrap();
+        assert_eq!(sources, vec![dir.path().join("photo.jpg")]);
+    }
+}
@@ -0,0 +1,287 @@
+use log::{info, warn};
+use serde::Serialize;
+use std::io;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tempfile::TempDir;
+use crate::utils::error::ServiceError;
+use crate::utils::fs::{self, DiskSpace};
+
+/// Free space jobs must leave on a volume when `DISK_RESERVE_BYTES` isn't set
+pub const DEFAULT_RESERVE_BYTES: u64 = 256 * 1024 * 1024;
+
+/// Allowance for the container, index and audio on top of an estimate's video bit rate
+const ESTIMATE_HEADROOM: f64 = 1.1;
+
+/// Owns the scratch space jobs work in and the directories they save into: per-job working
+/// directories under one root, a cap on how much that root may hold, and removal of whatever
+/// is left there past a TTL (working directories of crashed runs, outputs nobody fetched)
+#[derive(Debug, Clone, PartialEq)]
+pub struct TempFileManager {
+    root: PathBuf,
+    /// Bytes the root may hold before new job directories are refused
+    max_bytes: Option<u64>,
+    /// Age after which entries of the root are garbage-collected; `None` keeps them
+    ttl: Option<Duration>,
+    /// Free bytes a volume must keep beyond a job's estimated output
+    reserve_bytes: u64,
+}
+
+/// A job's working directory, removed with everything in it when dropped
+#[derive(Debug)]
+pub struct JobDir(TempDir);
+
+impl JobDir {
+    pub fn path(&self) -> &Path {
+        self.0.path()
+    }
+}
+
+/// How full the root and the volume it lives on are
+#[derive(Debug, Clone, Serialize)]
+pub struct StorageUsage {
+    pub root: PathBuf,
+    pub root_bytes: u64,
+    pub max_bytes: Option<u64>,
+    pub reserve_bytes: u64,
+    /// `None` when the volume could not be queried
+    pub volume: Option<DiskSpace>,
+}
+
+/// What one garbage collection pass removed
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Collected {
+    pub entries: u64,
+    pub bytes: u64,
+}
+
+impl Default for TempFileManager {
+    fn default() -> Self {
+        Self::new(fs::workspace_dir().join("jobs"), None, Some(Duration::from_secs(24 * 60 * 60)))
+    }
+}
+
+impl TempFileManager {
+    pub fn new(root: impl Into<PathBuf>, max_bytes: Option<u64>, ttl: Option<Duration>) -> Self {
+        Self { root: root.into(), max_bytes, ttl, reserve_bytes: DEFAULT_RESERVE_BYTES }
+    }
+
+    /// `TEMP_DIR` (default: `<WORKSPACE_DIR>/jobs`), `TEMP_MAX_BYTES` (default: unlimited),
+    /// `TEMP_TTL_SECS` (default: 86400; 0 disables collection) and `DISK_RESERVE_BYTES`
+    /// (default: 256 MiB)
+    pub fn from_env() -> Self {
+        let var = |name: &str| std::env::var(name).ok().and_then(|v| v.trim().parse::<u64>().ok());
+        let defaults = Self::default();
+        let root = std::env::var("TEMP_DIR").map_or(defaults.root, PathBuf::from);
+        let ttl = match var("TEMP_TTL_SECS") {
+            Some(0) => None,
+            Some(secs) => Some(Duration::from_secs(secs)),
+            None => defaults.ttl,
+        };
+        Self {
+            reserve_bytes: var("DISK_RESERVE_BYTES").unwrap_or(DEFAULT_RESERVE_BYTES),
+            ..Self::new(root, var("TEMP_MAX_BYTES"), ttl)
+        }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Refuse to grow the root past `TEMP_MAX_BYTES`. Measuring walks the root, so this is blocking
+    pub fn check_quota(&self) -> Result<(), ServiceError> {
+        let Some(max) = self.max_bytes else {
+            return Ok(());
+        };
+        let (bytes, _) = fs::dir_usage(&self.root);
+        if bytes >= max {
+            return Err(ServiceError::InsufficientStorage(format!(
+                "{} holds {} bytes of job files, limit is {}",
+                self.root.display(),
+                bytes,
+                max
+            )));
+        }
+        Ok(())
+    }
+
+    /// Refuse a job whose output, estimated at `needed` bytes, would leave less than the
+    /// reserve free on the volume of `dir`. Volumes that can't be queried pass
+    pub fn check_space(&self, dir: &Path, needed: u64) -> Result<(), ServiceError> {
+        let space = match fs::disk_space(dir) {
+            Ok(space) => space,
+            Err(e) => {
+                warn!("Could not query free space of {}: {}", dir.display(), e);
+                return Ok(());
+            }
+        };
+        if space.available_bytes < needed.saturating_add(self.reserve_bytes) {
+            return Err(ServiceError::InsufficientStorage(format!(
+                "{} has {} bytes free; the output needs about {} and {} stay reserved",
+                dir.display(),
+                space.available_bytes,
+                needed,
+                self.reserve_bytes
+            )));
+        }
+        Ok(())
+    }
+
+    /// Bytes written in `duration_secs` at `bits_per_sec`, with headroom for the container
+    pub fn estimate_bytes(duration_secs: f64, bits_per_sec: u64) -> u64 {
+        (duration_secs.max(0.0) * bits_per_sec as f64 / 8.0 * ESTIMATE_HEADROOM) as u64
+    }
+
+    /// Current usage of the root and its volume. Measuring walks the root, so this is blocking
+    pub fn usage(&self) -> StorageUsage {
+        StorageUsage {
+            root: self.root.clone(),
+            root_bytes: fs::dir_usage(&self.root).0,
+            max_bytes: self.max_bytes,
+            reserve_bytes: self.reserve_bytes,
+            volume: fs::disk_space(&self.root).ok(),
+        }
+    }
+
+    /// A fresh working directory for `job_id` under the root
+    pub fn job_dir(&self, job_id: &str) -> anyhow::Result<JobDir> {
+        self.check_quota()?;
+        self.create_dir(&self.root)?;
+        let dir = tempfile::Builder::new().prefix(&format!("{}-", job_id)).tempdir_in(&self.root)?;
+        // Sandboxed FFmpeg may run as a different uid and writes its files in here
+        std::fs::set_permissions(dir.path(), std::fs::Permissions::from_mode(0o777))?;
+        Ok(JobDir(dir))
+    }
+
+    /// Create the directory a job saves into, with its parents. Directories under the root
+    /// count against its quota
+    pub fn output_dir(&self, dir: &Path) -> anyhow::Result<()> {
+        if dir.starts_with(&self.root) {
+            self.check_quota()?;
+        }
+        self.create_dir(dir)
+    }
+
+    /// `output_dir` for the directory `file` is saved in
+    pub fn output_parent(&self, file: &Path) -> anyhow::Result<()> {
+        match file.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            Some(parent) => self.output_dir(parent),
+            None => Ok(()),
+        }
+    }
+
+    fn create_dir(&self, dir: &Path) -> anyhow::Result<()> {
+        std::fs::create_dir_all(dir).map_err(|e| {
+            if e.raw_os_error() == Some(libc::ENOSPC) {
+                ServiceError::InsufficientStorage(format!("{}: {}", dir.display(), e)).into()
+            } else {
+                anyhow::anyhow!("Could not create {}: {}", dir.display(), e)
+            }
+        })
+    }
+
+    /// Remove entries of the root not modified for longer than the TTL
+    pub fn collect_garbage(&self) -> io::Result<Collected> {
+        let mut collected = Collected::default();
+        let Some(ttl) = self.ttl else {
+            return Ok(collected);
+        };
+        let entries = match std::fs::read_dir(&self.root) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(collected),
+            Err(e) => return Err(e),
+        };
+        let now = SystemTime::now();
+        for entry in entries.flatten() {
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+            let age = metadata.modified().ok().and_then(|modified| now.duration_since(modified).ok());
+            if age.is_none_or(|age| age < ttl) {
+                continue;
+            }
+            let path = entry.path();
+            let (bytes, removed) = if metadata.is_dir() {
+                (fs::dir_usage(&path).0, std::fs::remove_dir_all(&path))
+            } else {
+                (metadata.len(), std::fs::remove_file(&path))
+            };
+            match removed {
+                Ok(()) => {
+                    collected.entries += 1;
+                    collected.bytes += bytes;
+                }
+                Err(e) => warn!("Could not remove expired {}: {}", path.display(), e),
+            }
+        }
+        Ok(collected)
+    }
+
+    /// Collect garbage on a background thread every quarter TTL (at least every minute,
+    /// at most every hour)
+    pub fn spawn_collector(&self) {
+        let Some(ttl) = self.ttl else {
+            return;
+        };
+        let manager = self.clone();
+        let period = (ttl / 4).clamp(Duration::from_secs(60), Duration::from_secs(60 * 60));
+        std::thread::spawn(move || loop {
+            std::thread::sleep(period);
+            match manager.collect_garbage() {
+                Ok(collected) if collected.entries > 0 => info!(
+                    "Removed {} expired entries ({} bytes) from {}",
+                    collected.entries,
+                    collected.bytes,
+                    manager.root.display()
+                ),
+                Ok(_) => {}
+                Err(e) => warn!("Garbage collection of {} failed: {}", manager.root.display(), e),
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_job_dirs_quota_and_collection() {
+        let workspace = tempfile::tempdir().unwrap();
+        let root = workspace.path().join("jobs");
+        let manager = TempFileManager::new(&root, Some(1024), Some(Duration::ZERO));
+
+        let dir = manager.job_dir("job-1").unwrap();
+        assert!(dir.path().starts_with(&root) && dir.path().is_dir());
+        std::fs::write(dir.path().join("sample.wav"), vec![0u8; 2048]).unwrap();
+        let error = manager.job_dir("job-2").unwrap_err();
+        assert!(matches!(error.downcast_ref(), Some(ServiceError::InsufficientStorage(_))));
+        let path = dir.path().to_path_buf();
+        drop(dir);
+        assert!(!path.exists());
+
+        manager.output_dir(&root.join("out/nested")).unwrap();
+        std::fs::write(root.join("out/nested/left-over.mp4"), b"x").unwrap();
+        let collected = manager.collect_garbage().unwrap();
+        assert_eq!(collected, Collected { entries: 1, bytes: 1 });
+        assert!(!root.join("out").exists());
+
+        let keeping = TempFileManager::new(&root, None, None);
+        keeping.output_dir(&root.join("kept")).unwrap();
+        assert_eq!(keeping.collect_garbage().unwrap(), Collected::default());
+    }
+
+    #[test]
+    fn test_space_preflight() {
+        // 10 minutes at 8 Mbit/s is 600 MB, plus headroom
+        assert_eq!(TempFileManager::estimate_bytes(600.0, 8_000_000), 660_000_000);
+
+        let workspace = tempfile::tempdir().unwrap();
+        let manager = TempFileManager::new(workspace.path(), None, None);
+        manager.check_space(&workspace.path().join("not/created/yet"), 0).unwrap();
+        let error = manager.check_space(workspace.path(), u64::MAX).unwrap_err();
+        assert!(matches!(error, ServiceError::InsufficientStorage(_)));
+        assert!(manager.usage().volume.is_some_and(|volume| volume.total_bytes > 0));
+    }
+}
@@ -0,0 +1,211 @@
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Mutex;
+use crate::middleware::auth;
+use crate::services::job_store::JobStore;
+use crate::utils::error::ServiceError;
+use crate::utils::fs;
+
+/// Workspace a tenant's inputs and outputs must live in: `<workspace>/tenants/<tenant>`
+pub fn workspace(tenant: &str) -> PathBuf {
+    fs::workspace_dir().join("tenants").join(tenant)
+}
+
+/// Key prefix a tenant's objects must have in any bucket: `tenants/<tenant>/`
+pub fn object_prefix(tenant: &str) -> String {
+    format!("tenants/{}/", tenant)
+}
+
+/// Whether `path` stays inside `root` once `.` and `..` are resolved lexically
+pub fn is_within(root: &Path, path: &Path) -> bool {
+    let mut resolved = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::ParentDir => {
+                if !resolved.pop() {
+                    return false;
+                }
+            }
+            Component::CurDir => {}
+            other => resolved.push(other),
+        }
+    }
+    path.is_absolute() && resolved.starts_with(root)
+}
+
+/// Files the service made for a tenant's job outside its workspace (downloads, staged uploads)
+static MANAGED: Mutex<Vec<(String, PathBuf)>> = Mutex::new(Vec::new());
+
+/// Lets the current tenant's paths point at `path` and below until dropped
+#[derive(Debug)]
+pub struct Managed {
+    tenant: String,
+    path: PathBuf,
+}
+
+/// Register `path` for the current tenant; `None` outside a tenant, where any path is allowed
+pub fn manage(path: &Path) -> Option<Managed> {
+    let tenant = auth::current_tenant()?;
+    let mut managed = MANAGED.lock().unwrap_or_else(|e| e.into_inner());
+    managed.push((tenant.clone(), path.to_path_buf()));
+    Some(Managed { tenant, path: path.to_path_buf() })
+}
+
+/// Whether `path` lies in something the service created for `tenant` and still keeps
+pub fn is_managed(tenant: &str, path: &Path) -> bool {
+    let managed = MANAGED.lock().unwrap_or_else(|e| e.into_inner());
+    managed.iter().any(|(owner, root)| owner == tenant && is_within(root, path))
+}
+
+impl Drop for Managed {
+    fn drop(&mut self) {
+        let mut managed = MANAGED.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(index) = managed.iter().position(|(owner, root)| *owner == self.tenant && *root == self.path) {
+            managed.swap_remove(index);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Quota {
+    /// Jobs a tenant may have running at once
+    pub max_running_jobs: Option<usize>,
+    /// Bytes stored under the tenant's workspace
+    pub max_storage_bytes: Option<u64>,
+}
+
+/// Per-tenant quotas: defaults from `TENANT_MAX_JOBS` / `TENANT_MAX_STORAGE_BYTES`, overridden per
+/// tenant by `TENANT_QUOTAS="team-a:jobs=4:storage_bytes=10737418240,team-b:jobs=2"`
+#[derive(Debug, Default)]
+pub struct TenantQuotas {
+    default: Quota,
+    overrides: HashMap<String, Quota>,
+}
+
+impl TenantQuotas {
+    pub fn from_env() -> Result<Self, String> {
+        let var = |name: &str| std::env::var(name).ok().and_then(|v| v.trim().parse().ok());
+        let default = Quota {
+            max_running_jobs: var("TENANT_MAX_JOBS").map(|jobs: u64| jobs as usize),
+            max_storage_bytes: var("TENANT_MAX_STORAGE_BYTES"),
+        };
+        Self::parse(default, &std::env::var("TENANT_QUOTAS").unwrap_or_default())
+    }
+
+    pub fn parse(default: Quota, spec: &str) -> Result<Self, String> {
+        let mut overrides = HashMap::new();
+        for entry in spec.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let mut fields = entry.split(':');
+            let tenant = fields.next().unwrap_or_default().trim().to_string();
+            let mut quota = default;
+            for field in fields {
+                let (name, value) = field
+                    .split_once('=')
+                    .ok_or_else(|| format!("expected NAME=VALUE in '{}'", entry))?;
+                let value: u64 = value.trim().parse().map_err(|_| format!("invalid number in '{}'", entry))?;
+                match name.trim() {
+                    "jobs" => quota.max_running_jobs = Some(value as usize),
+                    "storage_bytes" => quota.max_storage_bytes = Some(value),
+                    other => return Err(format!("unknown quota '{}' (jobs, storage_bytes)", other)),
+                }
+            }
+            overrides.insert(tenant, quota);
+        }
+        Ok(Self { default, overrides })
+    }
+
+    pub fn quota(&self, tenant: &str) -> Quota {
+        self.overrides.get(tenant).copied().unwrap_or(self.default)
+    }
+
+    /// Open the job's record unless the caller's tenant is at its running-job or storage quota.
+    /// Measuring storage walks the tenant workspace, so this is blocking
+    pub fn start_job(
+        &self,
+        jobs: &JobStore,
+        job_id: &str,
+        job_type: &str,
+        input_path: &str,
+        output_path: &str,
+    ) -> Result<(), ServiceError> {
+        let Some(tenant) = auth::current_tenant() else {
+            jobs.start(job_id, job_type, input_path, output_path);
+            return Ok(());
+        };
+        let quota = self.quota(&tenant);
+        if let Some(max) = quota.max_storage_bytes {
+            let (bytes, _) = fs::dir_usage(&workspace(&tenant));
+            if bytes >= max {
+                return Err(ServiceError::QuotaExceeded(format!(
+                    "tenant '{}' stores {} bytes, limit is {}",
+                    tenant, bytes, max
+                )));
+            }
+        }
+        jobs.start_within(job_id, job_type, input_path, output_path, quota.max_running_jobs)
+            .map_err(|running| {
+                ServiceError::QuotaExceeded(format!(
+                    "tenant '{}' already has {} running job(s), limit is {}",
+                    tenant,
+                    running,
+                    quota.max_running_jobs.unwrap_or_default()
+                ))
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_is_within() {
+        let root = Path::new("/work/tenants/team-a");
+        assert!(is_within(root, Path::new("/work/tenants/team-a/in/clip.mp4")));
+        assert!(!is_within(root, Path::new("/work/tenants/team-a/../team-b/clip.mp4")));
+        assert!(!is_within(root, Path::new("/work/tenants/team-ab/clip.mp4")));
+        assert!(!is_within(root, Path::new("team-a/clip.mp4")));
+    }
+
+    #[test]
+    fn test_quota_overrides_and_admission() {
+        let default = Quota { max_running_jobs: Some(2), max_storage_bytes: None };
+        let quotas = TenantQuotas::parse(default, "team-a:jobs=4, team-b:jobs=1:storage_bytes=100").unwrap();
+        assert_eq!(quotas.quota("team-a").max_running_jobs, Some(4));
+        assert_eq!(quotas.quota("team-b"), Quota { max_running_jobs: Some(1), max_storage_bytes: Some(100) });
+        assert_eq!(quotas.quota("team-c"), default);
+        assert!(TenantQuotas::parse(default, "team-a:cpu=4").is_err());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_concurrent_starts_stay_within_the_job_quota() {
+        let default = Quota { max_running_jobs: Some(2), max_storage_bytes: None };
+        let quotas = Arc::new(TenantQuotas::parse(default, "").unwrap());
+        let jobs = Arc::new(JobStore::new());
+
+        let starts: Vec<_> = (0..16)
+            .map(|i| {
+                let (quotas, jobs) = (quotas.clone(), jobs.clone());
+                tokio::spawn(auth::with_tenant(Some("team-a".to_string()), async move {
+                    quotas.start_job(&jobs, &format!("job-{}", i), "video.transcode", "in.mp4", "out.mp4")
+                }))
+            })
+            .collect();
+        let mut refused = 0;
+        for start in starts {
+            if let Err(e) = start.await.unwrap() {
+                assert_eq!(e.code(), "quota_exceeded");
+                refused += 1;
+            }
+        }
+        assert_eq!((refused, jobs.running().len()), (14, 2));
+
+        // Other tenants and untenanted work have slots of their own
+        let other = auth::with_tenant(Some("team-b".to_string()), async {
+            quotas.start_job(&jobs, "other", "video.transcode", "in.mp4", "out.mp4")
+        });
+        assert!(other.await.is_ok());
+        assert!(quotas.start_job(&jobs, "untenanted", "video.transcode", "in.mp4", "out.mp4").is_ok());
+    }
+}
@@ -0,0 +1,15 @@
+/// Linear congruential sequence with the C library's constants, so the synthetic images of the
+/// pixel analysis tests come out the same on every run
+pub struct Lcg(u32);
+
+impl Lcg {
+    pub fn new(seed: u32) -> Self {
+        Self(seed)
+    }
+
+    /// Next value below `bound`, from the high bits since the low ones repeat quickly
+    pub fn below(&mut self, bound: u32) -> u32 {
+        self.0 = self.0.wrapping_mul(1103515245).wrapping_add(12345);
+        (self.0 >> 16) % bound
+    }
+}
@@ -0,0 +1,102 @@
+use serde::{Deserialize, Serialize};
+
+/// Peak luminance, in nits, that SDR white maps to
+const NOMINAL_PEAK: u32 = 100;
+
+/// High dynamic range signal of a video, by its transfer function
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Hdr {
+    /// Perceptual quantizer (SMPTE ST 2084), also the base layer of most Dolby Vision footage
+    Hdr10,
+    /// Hybrid log-gamma (ARIB STD-B67), which phones and broadcast cameras record
+    Hlg,
+}
+
+impl Hdr {
+    /// From the `color_transfer` ffprobe reports for the first video stream that isn't a
+    /// cover image; `None` for SDR and untagged inputs
+    pub fn detect(probe: &serde_json::Value) -> Option<Self> {
+        let video = probe["streams"]
+            .as_array()?
+            .iter()
+            .find(|stream| stream["codec_type"] == "video" && stream["disposition"]["attached_pic"] != 1)?;
+        match video["color_transfer"].as_str()? {
+            "smpte2084" => Some(Hdr::Hdr10),
+            "arib-std-b67" => Some(Hdr::Hlg),
+            _ => None,
+        }
+    }
+
+    /// zimg's name for the transfer function
+    fn transfer(self) -> &'static str {
+        match self {
+            Hdr::Hdr10 => "smpte2084",
+            Hdr::Hlg => "arib-std-b67",
+        }
+    }
+}
+
+/// `tone_map` of a transcode: the curve compressing HDR highlights into SDR range
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ToneMap {
+    /// Filmic; keeps detail in highlights and skies
+    #[default]
+    Hable,
+    /// Leaves midtones closest to the source
+    Mobius,
+    Reinhard,
+    /// Cuts off everything above SDR white
+    Clip,
+}
+
+impl ToneMap {
+    fn name(self) -> &'static str {
+        match self {
+            ToneMap::Hable => "hable",
+            ToneMap::Mobius => "mobius",
+            ToneMap::Reinhard => "reinhard",
+            ToneMap::Clip => "clip",
+        }
+    }
+}
+
+/// Filter chain bringing `hdr` BT.2020 frames to BT.709 SDR: linearize, convert the
+/// primaries in float, apply the curve, then encode with the BT.709 transfer. The input's
+/// properties are given explicitly since many phones tag only the container
+pub fn filter(hdr: Hdr, curve: ToneMap) -> String {
+    format!(
+        "zscale=tin={}:pin=bt2020:min=bt2020nc:t=linear:npl={},format=gbrpf32le,zscale=p=bt709,\
+         tonemap=tonemap={}:desat=0,zscale=t=bt709:m=bt709:r=tv,format=yuv420p",
+        hdr.transfer(),
+        NOMINAL_PEAK,
+        curve.name()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_hdr_and_filter() {
+        let probe = |transfer: &str| {
+            serde_json::json!({ "streams": [
+                { "codec_type": "audio" },
+                { "codec_type": "video", "color_transfer": transfer, "color_primaries": "bt2020" }
+            ] })
+        };
+        assert_eq!(Hdr::detect(&probe("smpte2084")), Some(Hdr::Hdr10));
+        assert_eq!(Hdr::detect(&probe("arib-std-b67")), Some(Hdr::Hlg));
+        assert_eq!(Hdr::detect(&probe("bt709")), None);
+        assert_eq!(Hdr::detect(&serde_json::json!({ "streams": [{ "codec_type": "video" }] })), None);
+
+        let chain = filter(Hdr::Hlg, ToneMap::default());
+        assert!(chain.starts_with("zscale=tin=arib-std-b67:pin=bt2020:min=bt2020nc:t=linear:npl=100,"));
+        assert!(chain.contains("tonemap=tonemap=hable:desat=0"));
+        assert!(chain.ends_with("format=yuv420p"));
+        let curve: ToneMap = serde_json::from_str(r#""mobius""#).unwrap();
+        assert!(filter(Hdr::Hdr10, curve).contains("tin=smpte2084") && filter(Hdr::Hdr10, curve).contains("tonemap=mobius"));
+    }
+}
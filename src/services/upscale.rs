@@ -0,0 +1,128 @@
+use serde::{Deserialize, Serialize};
+use crate::services::watermark::Rgb;
+
+/// Factor a request doesn't set
+pub const DEFAULT_SCALE: u32 = 2;
+
+pub const MAX_SCALE: u32 = 4;
+
+/// JPEG's DCT block size; compression leaves its artifacts as steps along this grid
+const BLOCK: usize = 8;
+
+/// Blockiness above which `auto` deblocks; uncompressed and lightly compressed photos stay near 1
+pub const BLOCKY: f64 = 1.3;
+
+/// Blockiness above which the strong deblocking filter is used instead of the weak one
+const VERY_BLOCKY: f64 = 1.8;
+
+/// Whether compression artifacts are removed before upscaling, which would otherwise
+/// enlarge the 8x8 block edges along with the picture
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Deblock {
+    /// Only when the input measures as blocky
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+/// Mean luma step across the 8-pixel block grid against the mean step between the pixels
+/// inside blocks, over rows and columns. Coarse quantization flattens each block and leaves
+/// a step at its edge, raising the ratio; `None` for images smaller than two blocks
+pub fn blockiness(image: &Rgb) -> Option<f64> {
+    let (width, height) = (image.width, image.height);
+    if width < 2 * BLOCK || height < 2 * BLOCK {
+        return None;
+    }
+    let luma: Vec<f64> = image
+        .pixels
+        .chunks_exact(3)
+        .map(|pixel| 0.299 * f64::from(pixel[0]) + 0.587 * f64::from(pixel[1]) + 0.114 * f64::from(pixel[2]))
+        .collect();
+    let (mut edge, mut edges, mut inner, mut inners) = (0.0, 0u64, 0.0, 0u64);
+    let mut step = |offset: usize, a: usize, b: usize| {
+        let step = (luma[a] - luma[b]).abs();
+        if offset.is_multiple_of(BLOCK) {
+            edge += step;
+            edges += 1;
+        } else {
+            inner += step;
+            inners += 1;
+        }
+    };
+    for y in 0..height {
+        for x in 1..width {
+            step(x, y * width + x, y * width + x - 1);
+        }
+    }
+    for y in 1..height {
+        for x in 0..width {
+            step(y, y * width + x, (y - 1) * width + x);
+        }
+    }
+    // One gray level of slack keeps flat images, with no steps anywhere, at 1
+    Some((edge / edges as f64 + 1.0) / (inner / inners as f64 + 1.0))
+}
+
+/// `deblock` filter for an image of `blockiness`, or `None` when `mode` leaves it alone
+pub fn deblock_filter(mode: Deblock, blockiness: Option<f64>) -> Option<&'static str> {
+    let blockiness = blockiness.unwrap_or(0.0);
+    match mode {
+        Deblock::Never => None,
+        Deblock::Auto if blockiness < BLOCKY => None,
+        _ if blockiness >= VERY_BLOCKY => Some("deblock=filter=strong:block=8"),
+        _ => Some("deblock=filter=weak:block=8"),
+    }
+}
+
+/// `-vf` cleaning up with `deblock`, if any, then enlarging `scale` times with Lanczos
+pub fn filter(scale: u32, deblock: Option<&str>) -> String {
+    let upscale = format!("scale=iw*{0}:ih*{0}:flags=lanczos", scale);
+    match deblock {
+        Some(deblock) => format!("{},{}", deblock, upscale),
+        None => upscale,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::test_images::Lcg;
+
+    /// 64x64 gray image from a fixed linear congruential sequence; `flat` paints each 8x8
+    /// block one shade, as heavy JPEG compression does
+    fn image(flat: bool) -> Rgb {
+        let size = 64;
+        let mut rng = Lcg::new(11);
+        let mut noise = || rng.below(32) as u8;
+        let shades: Vec<u8> = (0..size * size / (BLOCK * BLOCK)).map(|_| 100 + noise()).collect();
+        let pixels = (0..size * size)
+            .flat_map(|at| {
+                let (x, y) = (at % size, at / size);
+                let value = if flat { shades[y / BLOCK * (size / BLOCK) + x / BLOCK] } else { 100 + noise() };
+                [value; 3]
+            })
+            .collect();
+        Rgb { width: size, height: size, pixels }
+    }
+
+    #[test]
+    fn test_blockiness_and_filter() {
+        let blocky = blockiness(&image(true)).unwrap();
+        let clean = blockiness(&image(false)).unwrap();
+        assert!(blocky > VERY_BLOCKY, "{}", blocky);
+        assert!((clean - 1.0).abs() < 0.1, "{}", clean);
+        assert_eq!(blockiness(&Rgb { width: 8, height: 8, pixels: vec![0; 8 * 8 * 3] }), None);
+
+        assert_eq!(deblock_filter(Deblock::Auto, Some(clean)), None);
+        assert_eq!(deblock_filter(Deblock::Auto, Some(blocky)), Some("deblock=filter=strong:block=8"));
+        assert_eq!(deblock_filter(Deblock::Always, None), Some("deblock=filter=weak:block=8"));
+        assert_eq!(deblock_filter(Deblock::Never, Some(blocky)), None);
+        assert_eq!(filter(2, None), "scale=iw*2:ih*2:flags=lanczos");
+        assert_eq!(
+            filter(3, deblock_filter(Deblock::Always, Some(blocky))),
+            "deblock=filter=strong:block=8,scale=iw*3:ih*3:flags=lanczos"
+        );
+    }
+}
@@ -0,0 +1,127 @@
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::path::PathBuf;
+use crate::services::storage::{Storage, StorageUri};
+use crate::utils::error::ServiceError;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Issues and checks `/files/{token}` links; disabled unless `SIGNED_URL_SECRET` is set.
+/// Outputs in object storage get the store's own presigned URLs instead
+#[derive(Default)]
+pub struct UrlSigner {
+    secret: Option<Vec<u8>>,
+    ttl_secs: i64,
+    /// Prefix for issued links, e.g. `https://media.example.com`; empty gives relative URLs
+    base_url: String,
+    storage: Storage,
+}
+
+impl UrlSigner {
+    /// Read `SIGNED_URL_SECRET`, `SIGNED_URL_TTL_SECS` (default 3600) and `PUBLIC_BASE_URL`
+    pub fn from_env() -> Self {
+        let var = |name: &str| std::env::var(name).ok().filter(|v| !v.trim().is_empty());
+        Self::new(
+            var("SIGNED_URL_SECRET").map(String::into_bytes),
+            var("SIGNED_URL_TTL_SECS").and_then(|v| v.trim().parse().ok()).unwrap_or(3600),
+            var("PUBLIC_BASE_URL").unwrap_or_default(),
+        )
+        .with_storage(Storage::from_env())
+    }
+
+    pub fn new(secret: Option<Vec<u8>>, ttl_secs: i64, base_url: String) -> Self {
+        Self {
+            secret,
+            ttl_secs,
+            base_url: base_url.trim_end_matches('/').to_string(),
+            storage: Storage::default(),
+        }
+    }
+
+    /// Presign links to outputs in `storage`'s backends
+    pub fn with_storage(mut self, storage: Storage) -> Self {
+        self.storage = storage;
+        self
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.secret.is_some()
+    }
+
+    /// Link to `path` valid for the configured TTL; `None` when signing is disabled. A storage
+    /// URI gets a presigned URL from its backend, with or without a secret
+    pub fn sign(&self, path: &str) -> Option<String> {
+        if let Some(location) = StorageUri::parse(path) {
+            return self.storage.presign(&location, self.ttl_secs.max(1) as u64);
+        }
+        self.sign_until(path, Utc::now() + chrono::Duration::seconds(self.ttl_secs))
+    }
+
+    fn sign_until(&self, path: &str, expires_at: DateTime<Utc>) -> Option<String> {
+        let secret = self.secret.as_ref()?;
+        let payload = URL_SAFE_NO_PAD.encode(format!("{}:{}", expires_at.timestamp(), path));
+        let signature = URL_SAFE_NO_PAD.encode(Self::mac(secret, &payload).finalize().into_bytes());
+        Some(format!("{}/files/{}.{}", self.base_url, payload, signature))
+    }
+
+    /// Path a token grants access to, if its signature is valid and it has not expired
+    pub fn verify(&self, token: &str) -> Result<PathBuf, ServiceError> {
+        let invalid = || ServiceError::Forbidden("invalid or tampered file link".to_string());
+        let secret = self.secret.as_ref().ok_or_else(invalid)?;
+
+        let (payload, signature) = token.rsplit_once('.').ok_or_else(invalid)?;
+        let signature = URL_SAFE_NO_PAD.decode(signature).map_err(|_| invalid())?;
+        Self::mac(secret, payload).verify_slice(&signature).map_err(|_| invalid())?;
+
+        let payload = URL_SAFE_NO_PAD.decode(payload).map_err(|_| invalid())?;
+        let payload = String::from_utf8(payload).map_err(|_| invalid())?;
+        let (expires_at, path) = payload.split_once(':').ok_or_else(invalid)?;
+        let expires_at: i64 = expires_at.parse().map_err(|_| invalid())?;
+        if Utc::now().timestamp() > expires_at {
+            return Err(ServiceError::Forbidden("file link has expired".to_string()));
+        }
+        Ok(PathBuf::from(path))
+    }
+
+    fn mac(secret: &[u8], payload: &str) -> HmacSha256 {
+        let mut mac = HmacSha256::new_from_slice(secret).expect("HMAC accepts keys of any length");
+        mac.update(payload.as_bytes());
+        mac
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn token(url: &str) -> &str {
+        url.strip_prefix("https://media.example.com/files/").unwrap()
+    }
+
+    #[test]
+    fn test_sign_and_verify() {
+        let signer = UrlSigner::new(Some(b"secret".to_vec()), 60, "https://media.example.com/".to_string());
+        let url = signer.sign("/data/out/video.mp4").unwrap();
+        assert_eq!(signer.verify(token(&url)).unwrap(), PathBuf::from("/data/out/video.mp4"));
+
+        let other = UrlSigner::new(Some(b"other".to_vec()), 60, String::new());
+        assert_eq!(other.verify(token(&url)).unwrap_err().code(), "forbidden");
+
+        let (payload, signature) = token(&url).rsplit_once('.').unwrap();
+        let forged = URL_SAFE_NO_PAD.encode(format!("{}:/etc/passwd", i64::MAX));
+        assert!(signer.verify(&format!("{}.{}", forged, signature)).is_err());
+        assert!(signer.verify(payload).is_err());
+
+        assert!(UrlSigner::default().sign("/data/out/video.mp4").is_none());
+    }
+
+    #[test]
+    fn test_expired_link() {
+        let signer = UrlSigner::new(Some(b"secret".to_vec()), 60, "https://media.example.com".to_string());
+        let url = signer.sign_until("/data/out/video.mp4", Utc::now() - chrono::Duration::seconds(1)).unwrap();
+        assert!(signer.verify(token(&url)).is_err());
+    }
+}
@@ -1,11 +1,70 @@
 use anyhow::Result;
 use ffmpeg_next as ffmpeg;
 use log::{error, info, warn};
-use std::process::{Command, Stdio};
-use std::io::{BufRead, BufReader};
+use std::process::{Command, ExitStatus, Stdio};
+use std::collections::VecDeque;
+use std::path::PathBuf;
 use std::os::unix::process::ExitStatusExt;
-use uuid::Uuid;
-use crate::models::video::{VideoTranscodeRequest, AudioExtractRequest};
+use std::sync::Arc;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, BufReader};
+use tokio::sync::mpsc::UnboundedSender;
+use crate::models::image::{
+    AutotrimRequest, AutotrimResponse, BlurRequest, BlurResponse, PanoramaAnalysisResponse, PanoramaAnalyzeRequest, PanoramaLink, StickerRequest, StickerResponse, UpscaleRequest, UpscaleResponse, WatermarkDetectRequest, WatermarkDetectResponse, WatermarkEmbedRequest,
+    WatermarkEmbedResponse,
+};
+use crate::models::job::{JobAttempt, JobProgress, JobStatus};
+use crate::models::video::{VideoTranscodeRequest, AnimationRequest, AnimationResponse, VideoWatermarkRequest, VideoWatermarkResponse, SubtitleBurnRequest, SubtitleExtractRequest, SubtitleExtractResponse, SubtitleMuxRequest, SubtitleResponse, AudioExtractRequest, CoverAttachRequest, CoverExtractRequest, CoverResponse,
+    EncodeCompareRequest, EncodeCompareResponse, EncodeOutcome, FaceIndexRequest, FaceIndexResponse, FingerprintRequest, FingerprintResponse, FrameExportRequest, FrameExportResponse, SlideshowRequest, SlideshowResponse, MultiQualityHlsResponse, ProgressEvent,
+    SceneDetectRequest, SceneDetectResponse, SpriteSheetRequest, SpriteSheetResponse,    Thumbnail, ThumbnailRequest, ThumbnailResponse, TrimMode, TrimRequest, TrimResponse};
+use crate::services::animation::{self, AnimationFormat};
+use crate::services::autotrim::{self, Border};
+use crate::services::cover;
+use crate::services::faces::{self, FaceIndex, FaceModel};
+use crate::services::filters;
+use crate::services::fingerprint;
+use crate::services::frames::{self, FrameIndex, Selection};
+use crate::services::blur::{self, Coverage};
+use crate::services::orientation::{self, Orientation};
+use crate::services::overlay::{self, Placement};
+use crate::services::panorama::{self, EdgeMap};
+use crate::services::privacy::PrivacyZones;
+use crate::services::subtitles::{self, SubtitleStream};
+use crate::services::hls;
+use crate::services::hwaccel::{self, HardwareEncoder};
+use crate::services::ladder::{self, Rung};
+use crate::services::job_store::JobStore;
+use crate::services::language::{self, WhisperConfig};
+use crate::services::queue;
+use crate::services::libav;
+use crate::services::limits::InputLimits;
+use crate::services::memory;
+use crate::services::metrics::{MetricKey, MetricsCollector};
+use crate::services::output_check::OutputCheck;
+use crate::services::probe;
+use crate::services::quality;
+use crate::services::ratecontrol::{self, RateControl};
+use crate::services::retry::RetryPolicy;
+use crate::services::scenes;
+use crate::services::result_cache::ResultCache;
+use crate::services::temp_files::TempFileManager;
+use crate::services::scanner::Scanner;
+use crate::services::sequence;
+use crate::services::slideshow::{self, Slideshow};
+use crate::services::sprites::{self, SpriteLayout};
+use crate::services::sticker::{self, StickerFormat};
+use crate::services::tenants::TenantQuotas;
+use crate::services::tonemap::{self, Hdr};
+use crate::services::upscale::{self, Deblock};
+use crate::services::watermark;
+use crate::utils::{audit, children, sandbox};
+use crate::utils::error::{FfmpegErrorKind, FfmpegFailure, ServiceError};
+use crate::utils::validation::{Validate, Violations, CONTAINER_FORMATS};
+
+/// Channel used by callers that want live progress for a running FFmpeg job
+pub type ProgressSender = UnboundedSender<ProgressEvent>;
+
+/// Number of trailing stderr lines kept to explain a failed FFmpeg run
+const STDERR_TAIL_LINES: usize = 20;
 
 pub struct QualityProfile {
     pub label: &'static str,
@@ -19,18 +78,137 @@ pub static QUALITY_PROFILES: &[QualityProfile] = &[
     QualityProfile { label: "480p",  resolution: "854x480",   bitrate: "1M" },
 ];
 
-pub struct VideoProcessor;
+/// Options a transcode derives from probing its input
+struct ProbedOptions {
+    /// Output options clearing location tags inside a privacy zone
+    privacy: Vec<String>,
+    /// Filters bringing an HDR input down to SDR, ahead of any requested ones
+    tone_map: Option<String>,
+}
+
+pub struct VideoProcessor {
+    jobs: Arc<JobStore>,
+    metrics: Arc<MetricsCollector>,
+    limits: InputLimits,
+    quotas: TenantQuotas,
+    scanner: Scanner,
+    retry: RetryPolicy,
+    temp: TempFileManager,
+    cache: ResultCache,
+    output_check: OutputCheck,
+    privacy: PrivacyZones,
+}
 
 impl VideoProcessor {
     pub fn new() -> Result<Self> {
         // Initialize FFmpeg
         ffmpeg::init()?;
         info!("FFmpeg initialized successfully");
-        Ok(Self)
+        Ok(Self {
+            jobs: Arc::new(JobStore::from_env().map_err(|e| anyhow::anyhow!("Invalid job store (JOB_STORE_URL/JOB_STORE_PATH): {}", e))?),
+            metrics: Arc::new(MetricsCollector::new()),
+            limits: InputLimits::from_env(),
+            quotas: TenantQuotas::from_env().map_err(|e| anyhow::anyhow!("Invalid TENANT_QUOTAS: {}", e))?,
+            scanner: Scanner::from_env(),
+            retry: RetryPolicy::from_env(),
+            temp: TempFileManager::from_env(),
+            cache: ResultCache::from_env(),
+            output_check: OutputCheck::from_env(),
+            privacy: PrivacyZones::from_env().map_err(|e| anyhow::anyhow!("Invalid PRIVACY_ZONES: {}", e))?,
+        })
+    }
+
+    /// History of the jobs run by this processor
+    pub fn jobs(&self) -> &JobStore {
+        &self.jobs
+    }
+
+    /// Handle on the job history, for processors that record their runs alongside these
+    pub fn shared_jobs(&self) -> Arc<JobStore> {
+        self.jobs.clone()
+    }
+
+    /// Working directories and save paths of jobs, with their quota and cleanup
+    pub fn temp_files(&self) -> &TempFileManager {
+        &self.temp
+    }
+
+    /// Processing times of successful jobs, bucketed for capacity planning
+    pub fn metrics(&self) -> &MetricsCollector {
+        &self.metrics
+    }
+
+    /// Size/pixel limits first, since they only read headers, then the virus scan
+    async fn check_input(&self, job_id: Option<&str>, input_path: &str) -> Result<()> {
+        let path = std::path::Path::new(input_path);
+        self.limits.check(job_id, path).await?;
+        self.scanner.scan(job_id, path).await
+    }
+
+    /// Admit the job against the caller's tenant quota, then open its record
+    fn start_job(&self, job_id: &str, job_type: &str, input_path: &str, output_path: &str) -> Result<()> {
+        self.quotas.start_job(&self.jobs, job_id, job_type, input_path, output_path)?;
+        Ok(())
+    }
+
+    /// Close the job record and feed successful runs into the processing-time metrics
+    fn finish_job<T>(&self, job_id: &str, result: &Result<T>, key: MetricKey) {
+        let cancelled = self.jobs.is_cancelled(job_id);
+        if let Some(processing_time_ms) = self.jobs.finish(job_id, result) {
+            if result.is_ok() && !cancelled {
+                self.metrics.record(key, processing_time_ms);
+            }
+        }
+        // FFmpeg was killed mid-write; a truncated file would pass for a finished output
+        if let Some(job) = self.jobs.get(job_id).filter(|_| cancelled) {
+            let output = std::path::Path::new(&job.output_path);
+            if output.is_file() {
+                match std::fs::remove_file(output) {
+                    Ok(()) => info!("[{}] Removed partial output {}", job_id, job.output_path),
+                    Err(e) => warn!("[{}] Could not remove partial output {}: {}", job_id, job.output_path, e),
+                }
+            }
+        }
+    }
+
+    /// `finish_job` for jobs run through the result cache; copies served from it would skew
+    /// the processing-time metrics, so they are left out
+    fn finish_cached_job(&self, job_id: &str, result: &Result<bool>, key: MetricKey) {
+        if matches!(result, Ok(true)) {
+            self.jobs.finish(job_id, result);
+        } else {
+            self.finish_job(job_id, result, key);
+        }
+    }
+
+    /// Stop a queued or running job. Its FFmpeg children are killed; the processor then sees
+    /// them fail, removes the partial output and closes the record as cancelled
+    pub fn cancel_job(&self, job_id: &str) -> Result<()> {
+        match self.jobs.cancel(job_id) {
+            None => Err(ServiceError::JobNotFound(format!("Job not found: {}", job_id)).into()),
+            Some(JobStatus::Queued) => {
+                info!("[{}] Cancelled queued job", job_id);
+                Ok(())
+            }
+            Some(JobStatus::Running) => {
+                let killed = children::kill(job_id);
+                info!("[{}] Cancelled running job, killed {} FFmpeg process(es)", job_id, killed);
+                Ok(())
+            }
+            Some(status) => {
+                Err(ServiceError::JobFinished(format!("Job {} is already {:?}", job_id, status).to_lowercase()).into())
+            }
+        }
     }
 
-    pub async fn transcode_video(&self, request: &VideoTranscodeRequest) -> Result<String> {
-        let job_id = Uuid::new_v4().to_string();
+    pub async fn transcode_video(
+        &self,
+        request: &VideoTranscodeRequest,
+        progress: Option<&ProgressSender>,
+    ) -> Result<String> {
+        // Handlers validate too, but gRPC and the CLI reach the processor directly
+        request.validate()?;
+        let job_id = queue::job_id();
         
         info!("Starting video transcode job: {}", job_id);
         
@@ -52,7 +230,7 @@ impl VideoProcessor {
         
         if !input_path.exists() {
             error!("[{}] Input file does not exist: {}", job_id, request.input_path);
-            return Err(anyhow::anyhow!("Input file not found: {}", request.input_path));
+            return Err(ServiceError::FileNotFound(format!("Input file not found: {}", request.input_path)).into());
         }
         
         // Check if file is readable
@@ -66,37 +244,188 @@ impl VideoProcessor {
         // Validate output directory exists
         if let Some(parent) = std::path::Path::new(&request.output_path).parent() {
             if !parent.exists() {
-                return Err(anyhow::anyhow!("Output directory does not exist: {}", parent.display()));
+                return Err(ServiceError::FileNotFound(format!("Output directory does not exist: {}", parent.display())).into());
             }
         }
         
+        // Reject oversized or infected inputs before FFmpeg starts decoding them
+        self.check_input(Some(&job_id), &request.input_path).await?;
+
         // Get video duration first
-        let duration = self.get_video_duration(&request.input_path).await?;
+        let duration = self.get_video_duration(&job_id, &request.input_path).await?;
         info!("[{}] Video duration: {:.2} seconds", job_id, duration);
-        
-        // Build FFmpeg command
-        let mut command = Command::new("ffmpeg");
+
+        // Fail now rather than have FFmpeg die on a full disk halfway through the file. Without
+        // a target bit rate, expect the input's
+        let bits_per_sec = request
+            .bitrate
+            .as_deref()
+            .or(request.rate_control.as_ref().and_then(RateControl::bitrate))
+            .and_then(hls::parse_bitrate)
+            .or_else(|| file_size(&request.input_path).filter(|_| duration > 0.0).map(|bytes| (bytes as f64 * 8.0 / duration) as u64));
+        if let Some(bits_per_sec) = bits_per_sec {
+            let output_dir = std::path::Path::new(&request.output_path).parent().unwrap_or(std::path::Path::new("."));
+            self.temp.check_space(output_dir, TempFileManager::estimate_bytes(duration, bits_per_sec))?;
+        }
+
+        // A bare stream copy needs no encoder, so do it through the linked libraries
+        // unless FFmpeg work has to stay in sandboxed children or metadata must be removed
+        let probed = ProbedOptions {
+            privacy: self.privacy_args(&job_id, &request.input_path).await?,
+            tone_map: self.tone_map_filter(&job_id, request).await?,
+        };
+        let stream_copy = request.codec.as_deref() == Some("copy")
+            && probed.privacy.is_empty()
+            && request.format.is_none()
+            && request.bitrate.is_none()
+            && request.resolution.is_none()
+            && request.fps.is_none()
+            && request.filters.as_ref().is_none_or(Vec::is_empty);
+        if stream_copy && !sandbox::is_enabled() {
+            self.start_job(&job_id, "video.transcode", &request.input_path, &request.output_path)?;
+            let mut result = self.remux(&job_id, &request.input_path, &request.output_path, duration, progress).await;
+            if result.is_ok() {
+                result = self.output_check.verify(&job_id, &request.input_path, &request.output_path, &["video", "audio"]).await;
+            }
+            let key = MetricKey::new("video.transcode", None, Some("copy"), file_size(&request.input_path));
+            self.finish_job(&job_id, &result, key);
+            result?;
+
+            info!("Video remux completed successfully: {}", job_id);
+            return Ok(job_id);
+        }
+
+        let hardware = request.hardware_acceleration.and_then(|accelerator| {
+            let selected = hwaccel::select(accelerator, request.codec.as_deref());
+            if selected.is_none() {
+                warn!("[{}] No usable {:?} hardware encoder, encoding on the CPU", job_id, accelerator);
+            }
+            selected
+        });
+        
+        // Queue behind running jobs rather than overcommit memory
+        let _memory = memory::reserve_video(Some(&job_id), input_path, 1).await?;
+        self.start_job(&job_id, "video.transcode", &request.input_path, &request.output_path)?;
+        let mut result = self.run_transcode(&job_id, request, hardware.as_ref(), &probed, duration, progress).await;
+        let mut encoder = hardware.as_ref().map(|hardware| hardware.name).or(request.codec.as_deref());
+        if let (Err(e), Some(hardware)) = (&result, &hardware) {
+            // A busy or misbehaving GPU fails the encode where the CPU would not
+            if !self.jobs.is_cancelled(&job_id) {
+                warn!("[{}] {} failed, encoding on the CPU instead: {}", job_id, hardware.name, e);
+                result = self.run_transcode(&job_id, request, None, &probed, duration, progress).await;
+                encoder = request.codec.as_deref();
+            }
+        }
+        if result.is_ok() {
+            result = self.output_check.verify(&job_id, &request.input_path, &request.output_path, &["video", "audio"]).await;
+        }
+        let key = MetricKey::new(
+            "video.transcode",
+            request.resolution.as_deref(),
+            encoder,
+            file_size(&request.input_path),
+        );
+        self.finish_job(&job_id, &result, key);
+        result?;
+
+        info!("Video transcode completed successfully: {}", job_id);
+        Ok(job_id)
+    }
+
+    /// Run a transcode, in two passes sharing a statistics file when its rate control asks for it
+    async fn run_transcode(
+        &self,
+        job_id: &str,
+        request: &VideoTranscodeRequest,
+        hardware: Option<&HardwareEncoder>,
+        probed: &ProbedOptions,
+        duration: f64,
+        progress: Option<&ProgressSender>,
+    ) -> Result<()> {
+        if !request.rate_control.as_ref().is_some_and(RateControl::is_two_pass) {
+            let command = Self::transcode_command(request, hardware, probed, None);
+            return self.run_ffmpeg(job_id, command, duration, "Transcode", progress).await;
+        }
+        // Holds the statistics until the second pass has read them
+        let work_dir = self.temp.job_dir(job_id)?;
+        let log_prefix = work_dir.path().join("ffmpeg2pass");
+        let command = Self::transcode_command(request, hardware, probed, Some((1, &log_prefix)));
+        self.run_ffmpeg(job_id, command, duration, "Transcode pass 1", progress).await?;
+        let command = Self::transcode_command(request, hardware, probed, Some((2, &log_prefix)));
+        self.run_ffmpeg(job_id, command, duration, "Transcode pass 2", progress).await
+    }
+
+    /// FFmpeg command for a transcode request, encoding on `hardware` in place of the requested
+    /// software encoder. The first of two passes only writes statistics to `log_prefix`
+    fn transcode_command(
+        request: &VideoTranscodeRequest,
+        hardware: Option<&HardwareEncoder>,
+        probed: &ProbedOptions,
+        pass: Option<(u8, &std::path::Path)>,
+    ) -> Command {
+        let first_pass = matches!(pass, Some((1, _)));
+        let mut command = sandbox::command("ffmpeg");
+        if let Some(hardware) = hardware {
+            command.args(hardware.device_args());
+        }
         
         // Input file
         command.arg("-i").arg(&request.input_path);
         
+        // Filter chain, tone mapping first; mapping its output drops the default stream selection,
+        // so keep the audio. A device upload, which takes over scaling to the resolution, follows the chain
+        let upload = hardware.and_then(|hardware| hardware.upload_filter(request.resolution.as_deref()));
+        let chain: Vec<String> = probed
+            .tone_map
+            .iter()
+            .cloned()
+            .chain(request.filters.as_deref().filter(|filters| !filters.is_empty()).map(filters::chain))
+            .collect();
+        match ((!chain.is_empty()).then(|| chain.join(",")), &upload) {
+            (Some(chain), upload) => {
+                let mut graph = format!("[0:v:0]{}[v]", chain);
+                if let Some(upload) = upload {
+                    graph = format!("{};[v]{}[hw]", graph, upload);
+                }
+                command
+                    .arg("-filter_complex").arg(graph)
+                    .arg("-map").arg(if upload.is_some() { "[hw]" } else { "[v]" })
+                    .arg("-map").arg("0:a?");
+            }
+            (None, Some(upload)) => {
+                command.arg("-vf").arg(upload);
+            }
+            (None, None) => {}
+        }
+        
         // Output format
-        if let Some(format) = &request.format {
+        if let Some(format) = request.format.as_ref().filter(|_| !first_pass) {
             command.arg("-f").arg(format);
         }
         
         // Video codec
-        if let Some(codec) = &request.codec {
+        let codec = hardware.map(|hardware| hardware.name).or(request.codec.as_deref());
+        if let Some(codec) = codec {
             command.arg("-c:v").arg(codec);
         }
         
-        // Bitrate
-        if let Some(bitrate) = &request.bitrate {
-            command.arg("-b:v").arg(bitrate);
+        // Rate control, which a plain bitrate caps; or the bitrate alone
+        match &request.rate_control {
+            Some(rate_control) => {
+                command.args(rate_control.args(codec, request.bitrate.as_deref()));
+            }
+            None => {
+                if let Some(bitrate) = &request.bitrate {
+                    command.arg("-b:v").arg(bitrate);
+                }
+            }
+        }
+        if let Some((pass, log_prefix)) = pass {
+            command.args(ratecontrol::pass_args(codec, pass, log_prefix));
         }
         
         // Resolution
-        if let Some(resolution) = &request.resolution {
+        if let Some(resolution) = request.resolution.as_ref().filter(|_| upload.is_none()) {
             command.arg("-s").arg(resolution);
         }
         
@@ -105,23 +434,139 @@ impl VideoProcessor {
             command.arg("-r").arg(fps.to_string());
         }
         
-        // Output file
-        command.arg(&request.output_path);
-        
-        info!("Executing FFmpeg command: {:?}", command);
-        
-        // Execute FFmpeg command with real-time output monitoring
-        command.stdout(Stdio::piped()).stderr(Stdio::piped());
-        
+        // Output file; the first pass only gathers statistics
+        if first_pass {
+            command.arg("-an").arg("-f").arg("null").arg("-");
+        } else {
+            command.args(&probed.privacy).arg(&request.output_path);
+        }
+        command
+    }
+
+    /// In-process stream copy on the blocking pool, publishing progress from packet timestamps
+    async fn remux(
+        &self,
+        job_id: &str,
+        input_path: &str,
+        output_path: &str,
+        duration: f64,
+        progress: Option<&ProgressSender>,
+    ) -> Result<()> {
+        info!("[{}] Remuxing {} -> {} in-process", job_id, input_path, output_path);
+        let job = job_id.to_string();
+        let input = PathBuf::from(input_path);
+        let output = PathBuf::from(output_path);
+        let progress = progress.cloned();
+        let jobs = self.jobs.clone();
+        let result = tokio::task::spawn_blocking(move || {
+            let mut last_percent = -1i64;
+            libav::remux(&input, &output, |current_time| {
+                // There is no child process to kill, so cancellation is polled between packets
+                if jobs.is_cancelled(&job) {
+                    return false;
+                }
+                let percent = if duration > 0.0 { (current_time / duration * 100.0).min(100.0) } else { 0.0 };
+                // Packets arrive far more often than anyone needs updates
+                if percent as i64 == last_percent {
+                    return true;
+                }
+                last_percent = percent as i64;
+                jobs.set_progress(&job, JobProgress { percent, ..JobProgress::default() });
+                if let Some(sender) = &progress {
+                    let _ = sender.send(ProgressEvent {
+                        job_id: job.clone(),
+                        percent,
+                        current_time,
+                        duration,
+                    });
+                }
+                true
+            })
+        })
+        .await?;
+        result.map_err(|e| {
+            error!("[{}] Remux failed: {}", job_id, e);
+            e
+        })
+    }
+
+    /// Run FFmpeg through `run_ffmpeg_once`, repeating runs that failed for a transient reason
+    /// as the retry policy allows. Each failed attempt is kept on the job record
+    async fn run_ffmpeg(
+        &self,
+        job_id: &str,
+        mut command: Command,
+        duration: f64,
+        operation: &str,
+        progress: Option<&ProgressSender>,
+    ) -> Result<()> {
+        let mut attempt = 1;
+        loop {
+            let next = sandbox::rebuild(&command);
+            let started_at = chrono::Utc::now();
+            let error = match self.run_ffmpeg_once(job_id, command, duration, operation, progress).await {
+                Err(e) if self.retry.should_retry(attempt, &e) && !self.jobs.is_cancelled(job_id) => e,
+                result => return result,
+            };
+            let delay = self.retry.delay(attempt);
+            warn!(
+                "[{}] {} attempt {} of {} failed, retrying in {:?}: {}",
+                job_id, operation, attempt, self.retry.max_attempts, delay, error
+            );
+            self.jobs.record_attempt(job_id, JobAttempt {
+                attempt,
+                operation: operation.to_string(),
+                started_at,
+                failed_at: chrono::Utc::now(),
+                error: error.to_string(),
+            });
+            tokio::time::sleep(delay).await;
+            command = next;
+            attempt += 1;
+        }
+    }
+
+    /// Spawn FFmpeg and monitor its stderr, logging and publishing progress until it exits.
+    /// The pipes are read through tokio, so a long encode parks this task rather than a runtime worker
+    async fn run_ffmpeg_once(
+        &self,
+        job_id: &str,
+        command: Command,
+        duration: f64,
+        operation: &str,
+        progress: Option<&ProgressSender>,
+    ) -> Result<()> {
+        info!("[{}] Executing FFmpeg command: {:?}", job_id, command);
+        let mut audit = CommandRecorder {
+            jobs: &self.jobs,
+            job_id,
+            timer: Some(audit::start(Some(job_id), &command)),
+            status: None,
+            stderr: String::new(),
+        };
+
+        // Execute FFmpeg command with real-time output monitoring. Output goes to files, and an
+        // unread stdout pipe could fill up and stall FFmpeg, so only stderr is piped
+        let mut command = tokio::process::Command::from(command);
+        command.stdout(Stdio::null()).stderr(Stdio::piped()).kill_on_drop(true);
+
         info!("[{}] Spawning FFmpeg process...", job_id);
-        let mut child = command.spawn()?;
-        
+        let mut child = match command.spawn() {
+            Ok(child) => child,
+            Err(e) => {
+                audit.stderr = e.to_string();
+                return Err(e.into());
+            }
+        };
+        let _tracked = children::track(Some(job_id), child.id());
+
         // Check if process started successfully
         match child.try_wait() {
             Ok(Some(status)) => {
                 let error = format!("FFmpeg process terminated immediately with status: {}", status);
                 error!("[{}] {}", job_id, error);
-                return Err(anyhow::anyhow!("FFmpeg processing failed: {}", error));
+                audit.status = Some(status);
+                return Err(anyhow::anyhow!("{} failed: {}", operation, error));
             }
             Ok(None) => {
                 info!("[{}] FFmpeg process started successfully", job_id);
@@ -129,67 +574,137 @@ impl VideoProcessor {
             Err(e) => {
                 let error = format!("Failed to check FFmpeg process status: {}", e);
                 error!("[{}] {}", job_id, error);
-                return Err(anyhow::anyhow!("FFmpeg processing failed: {}", error));
+                return Err(anyhow::anyhow!("{} failed: {}", operation, error));
             }
         }
-        
+
         let stderr = child.stderr.take().unwrap();
-        
+
         // Monitor FFmpeg progress in real-time
-        let reader = BufReader::new(stderr);
+        let mut stderr = BufReader::new(stderr);
         let mut last_progress = 0.0;
-        
-        for line in reader.lines() {
-            if let Ok(line) = line {
-                // Parse FFmpeg progress output
-                if line.contains("time=") && line.contains("bitrate=") {
-                    // Extract time information for progress tracking
-                    if let Some(time_str) = line.split("time=").nth(1) {
-                        if let Some(time_part) = time_str.split_whitespace().next() {
-                            // Parse time format (HH:MM:SS.ms) and calculate percentage
-                            if let Some(current_time) = self.parse_ffmpeg_time(time_part) {
-                                let progress = (current_time / duration) * 100.0;
-                                if progress > last_progress + 5.0 { // Log every 5% progress
-                                    info!("[{}] Transcode progress: {:.1}% ({:.1}s/{:.1}s)", 
-                                          job_id, progress, current_time, duration);
-                                    last_progress = progress;
-                                }
+        let mut stderr_tail = VecDeque::with_capacity(STDERR_TAIL_LINES);
+
+        loop {
+            let line = match Self::next_stderr_line(&mut stderr).await {
+                Ok(Some(line)) => line,
+                Ok(None) => break,
+                Err(e) => {
+                    warn!("[{}] Stopped reading FFmpeg output: {}", job_id, e);
+                    break;
+                }
+            };
+
+            // Parse FFmpeg progress output
+            if line.contains("time=") && line.contains("bitrate=") {
+                // Extract time information for progress tracking
+                if let Some(time_str) = line.split("time=").nth(1) {
+                    if let Some(time_part) = time_str.split_whitespace().next() {
+                        // Parse time format (HH:MM:SS.ms) and calculate percentage
+                        if let Some(current_time) = self.parse_ffmpeg_time(time_part) {
+                            let percent = (current_time / duration) * 100.0;
+                            if percent > last_progress + 5.0 { // Log every 5% progress
+                                info!("[{}] {} progress: {:.1}% ({:.1}s/{:.1}s)",
+                                      job_id, operation, percent, current_time, duration);
+                                last_progress = percent;
+                            }
+                            // `speed=1.98x` is how much faster than real time FFmpeg is encoding
+                            let eta_secs = Self::progress_field(&line, "speed")
+                                .and_then(|speed| speed.trim_end_matches('x').parse::<f64>().ok())
+                                .filter(|&speed| speed > 0.0)
+                                .map(|speed| ((duration - current_time) / speed).max(0.0));
+                            let bitrate = Self::progress_field(&line, "bitrate")
+                                .filter(|&bitrate| bitrate != "N/A")
+                                .map(str::to_string);
+                            self.jobs.set_progress(job_id, JobProgress {
+                                percent: percent.min(100.0),
+                                eta_secs,
+                                bitrate,
+                                ..JobProgress::default()
+                            });
+                            if let Some(sender) = progress {
+                                let _ = sender.send(ProgressEvent {
+                                    job_id: job_id.to_string(),
+                                    percent: percent.min(100.0),
+                                    current_time,
+                                    duration,
+                                });
                             }
                         }
                     }
                 }
-                
-                // Log important FFmpeg messages
-                if line.contains("error") || line.contains("Error") {
-                    warn!("[{}] FFmpeg warning: {}", job_id, line);
-                }
-                
-                // Check if process is still running
-                if let Ok(Some(_)) = child.try_wait() {
-                    break;
-                }
-            } else {
-                // Error reading line, check if process is still running
-                if let Ok(Some(_)) = child.try_wait() {
-                    break;
+            }
+
+            // Log important FFmpeg messages
+            if line.contains("error") || line.contains("Error") {
+                warn!("[{}] FFmpeg warning during {}: {}", job_id, operation.to_lowercase(), line);
+            }
+
+            // Keep the tail of the output for failure classification
+            if !line.contains("time=") {
+                if stderr_tail.len() == STDERR_TAIL_LINES {
+                    stderr_tail.pop_front();
                 }
+                stderr_tail.push_back(line);
             }
         }
-        
+
         // Wait for the process to complete
-        let status = child.wait()?;
-        
+        let stderr_tail: Vec<String> = stderr_tail.into();
+        audit.stderr = stderr_tail.join("\n");
+        let status = child.wait().await?;
+        audit.status = Some(status);
+        let stderr_tail = audit.stderr.clone();
+
         if status.success() {
-            info!("Video transcode completed successfully: {}", job_id);
-            Ok(job_id)
+            Ok(())
         } else {
             let error_msg = if status.code().is_some() {
                 format!("FFmpeg process failed with exit code: {}", status)
             } else {
                 format!("FFmpeg process terminated by signal: {:?}", status.signal())
             };
-            error!("[{}] {}", job_id, error_msg);
-            Err(anyhow::anyhow!("FFmpeg processing failed: {}", error_msg))
+            let kind = match status.signal() {
+                // SIGKILL comes from cancellation or the OOM killer and SIGXCPU from the sandbox's
+                // CPU limit; neither goes away on a second run
+                Some(signal) if signal != libc::SIGKILL && signal != libc::SIGXCPU => FfmpegErrorKind::Transient,
+                _ => FfmpegErrorKind::classify(&stderr_tail),
+            };
+            error!("[{}] {} ({:?})", job_id, error_msg, kind);
+
+            // Surface FFmpeg's own last words, they are far more useful than the exit code
+            let reason = stderr_tail.lines().last().unwrap_or(&error_msg).to_string();
+            Err(FfmpegFailure {
+                kind,
+                message: format!("{} failed: {} - {}", operation, error_msg, reason),
+            }
+            .into())
+        }
+    }
+
+    /// Next line FFmpeg wrote to stderr. Its status lines end in `\r` so terminals redraw them
+    /// in place, so both `\r` and `\n` end a line; blank lines are skipped
+    async fn next_stderr_line(reader: &mut (impl AsyncBufRead + Unpin)) -> std::io::Result<Option<String>> {
+        let mut line = Vec::new();
+        loop {
+            let available = reader.fill_buf().await?;
+            if available.is_empty() {
+                return Ok((!line.is_empty()).then(|| String::from_utf8_lossy(&line).into_owned()));
+            }
+            match available.iter().position(|&byte| byte == b'\r' || byte == b'\n') {
+                Some(end) => {
+                    line.extend_from_slice(&available[..end]);
+                    reader.consume(end + 1);
+                    if !line.is_empty() {
+                        return Ok(Some(String::from_utf8_lossy(&line).into_owned()));
+                    }
+                }
+                None => {
+                    let read = available.len();
+                    line.extend_from_slice(available);
+                    reader.consume(read);
+                }
+            }
         }
     }
 
@@ -208,48 +723,82 @@ impl VideoProcessor {
         }
     }
 
-    /// Get video duration using ffprobe
-    async fn get_video_duration(&self, file_path: &str) -> Result<f64> {
-        let output = Command::new("ffprobe")
-            .arg("-v").arg("quiet")
-            .arg("-show_entries").arg("format=duration")
-            .arg("-of").arg("csv=p=0")
-            .arg(file_path)
-            .output()?;
-            
-        if output.status.success() {
-            let duration_str = String::from_utf8_lossy(&output.stdout).trim().to_string();
-            let duration: f64 = duration_str.parse()
-                .map_err(|_| anyhow::anyhow!("Failed to parse duration: {}", duration_str))?;
-            Ok(duration)
-        } else {
-            let error = String::from_utf8_lossy(&output.stderr);
-            Err(anyhow::anyhow!("Failed to get video duration: {}", error))
+    /// Value of `key=` in an FFmpeg status line, which pads some values (`speed= 1.5x`)
+    fn progress_field<'a>(line: &'a str, key: &str) -> Option<&'a str> {
+        line.split(&format!("{}=", key)).nth(1)?.split_whitespace().next()
+    }
+
+    /// Size of the main video stream, from the shared (cached) ffprobe result
+    async fn video_dimensions(&self, job_id: &str, file_path: &str) -> Result<(u32, u32)> {
+        let probe = probe::probe(Some(job_id), std::path::Path::new(file_path)).await?;
+        Ok(probe::dimensions(&probe).ok_or_else(|| ServiceError::InvalidFormat(format!("No video stream in {}", file_path)))?)
+    }
+
+    /// Output options removing the input's location tags when they fall inside a privacy zone
+    async fn privacy_args(&self, job_id: &str, file_path: &str) -> Result<Vec<String>> {
+        if self.privacy.is_empty() {
+            return Ok(Vec::new());
         }
+        let args = self.privacy.metadata_args(&*probe::probe(Some(job_id), std::path::Path::new(file_path)).await?);
+        if !args.is_empty() {
+            info!("[{}] Removing location tags inside a privacy zone from the output", job_id);
+        }
+        Ok(args)
+    }
+
+    /// Tone mapping chain for an HDR input when the request asks for one
+    async fn tone_map_filter(&self, job_id: &str, request: &VideoTranscodeRequest) -> Result<Option<String>> {
+        let Some(curve) = request.tone_map else {
+            return Ok(None);
+        };
+        match Hdr::detect(&*probe::probe(Some(job_id), std::path::Path::new(&request.input_path)).await?) {
+            Some(hdr) => {
+                info!("[{}] {:?} input, tone mapping to SDR with {:?}", job_id, hdr, curve);
+                Ok(Some(tonemap::filter(hdr, curve)))
+            }
+            None => {
+                info!("[{}] {} is not HDR, leaving its colors alone", job_id, request.input_path);
+                Ok(None)
+            }
+        }
+    }
+
+    /// Container duration from the shared (cached) ffprobe result
+    async fn get_video_duration(&self, job_id: &str, file_path: &str) -> Result<f64> {
+        let info = probe::probe(Some(job_id), std::path::Path::new(file_path)).await?;
+        probe::duration(&info).ok_or_else(|| anyhow::anyhow!("Failed to get video duration: {} has no duration", file_path))
     }
 
-    pub async fn extract_audio(&self, request: &AudioExtractRequest) -> Result<String> {
-        let job_id = Uuid::new_v4().to_string();
+    pub async fn extract_audio(
+        &self,
+        request: &AudioExtractRequest,
+        progress: Option<&ProgressSender>,
+    ) -> Result<String> {
+        request.validate()?;
+        let job_id = queue::job_id();
         
         info!("Starting audio extraction job: {}", job_id);
         
         // Validate input file exists
         if !std::path::Path::new(&request.input_path).exists() {
-            return Err(anyhow::anyhow!("Input file not found: {}", request.input_path));
+            return Err(ServiceError::FileNotFound(format!("Input file not found: {}", request.input_path)).into());
         }
         
         // Validate output directory exists
         if let Some(parent) = std::path::Path::new(&request.output_path).parent() {
             if !parent.exists() {
-                return Err(anyhow::anyhow!("Output directory does not exist: {}", parent.display()));
+                return Err(ServiceError::FileNotFound(format!("Output directory does not exist: {}", parent.display())).into());
             }
         }
         
+        // Reject oversized or infected inputs before FFmpeg starts decoding them
+        self.check_input(Some(&job_id), &request.input_path).await?;
+
         // Get video duration first
-        let duration = self.get_video_duration(&request.input_path).await?;
+        let duration = self.get_video_duration(&job_id, &request.input_path).await?;
         info!("[{}] Video duration: {:.2} seconds", job_id, duration);
         
-        let mut command = Command::new("ffmpeg");
+        let mut command = sandbox::command("ffmpeg");
         
         // Input file
         command.arg("-i").arg(&request.input_path);
@@ -269,146 +818,124 @@ impl VideoProcessor {
         // Output file
         command.arg(&request.output_path);
         
-        info!("Executing FFmpeg command for audio extraction: {:?}", command);
-        
-        // Execute FFmpeg command with real-time output monitoring
-        command.stdout(Stdio::piped()).stderr(Stdio::piped());
-        
-        info!("[{}] Spawning FFmpeg process for audio extraction...", job_id);
-        let mut child = command.spawn()?;
-        
-        // Check if process started successfully
-        match child.try_wait() {
-            Ok(Some(status)) => {
-                let error = format!("FFmpeg process terminated immediately with status: {}", status);
-                error!("[{}] {}", job_id, error);
-                return Err(anyhow::anyhow!("Audio extraction failed: {}", error));
-            }
-            Ok(None) => {
-                info!("[{}] FFmpeg process for audio extraction started successfully", job_id);
-            }
-            Err(e) => {
-                let error = format!("Failed to check FFmpeg process status: {}", e);
-                error!("[{}] {}", job_id, error);
-                return Err(anyhow::anyhow!("Audio extraction failed: {}", error));
-            }
-        }
-        
-        let stderr = child.stderr.take().unwrap();
-        
-        // Monitor FFmpeg progress in real-time
-        let reader = BufReader::new(stderr);
-        let mut last_progress = 0.0;
-        
-        for line in reader.lines() {
-            if let Ok(line) = line {
-                // Parse FFmpeg progress output
-                if line.contains("time=") && line.contains("bitrate=") {
-                    // Extract time information for progress tracking
-                    if let Some(time_str) = line.split("time=").nth(1) {
-                        if let Some(time_part) = time_str.split_whitespace().next() {
-                            // Parse time format (HH:MM:SS.ms) and calculate percentage
-                            if let Some(current_time) = self.parse_ffmpeg_time(time_part) {
-                                let progress = (current_time / duration) * 100.0;
-                                if progress > last_progress + 5.0 { // Log every 5% progress
-                                    info!("[{}] Audio extraction progress: {:.1}% ({:.1}s/{:.1}s)", 
-                                          job_id, progress, current_time, duration);
-                                    last_progress = progress;
-                                }
-                            }
-                        }
-                    }
-                }
-                
-                // Log important FFmpeg messages
-                if line.contains("error") || line.contains("Error") {
-                    warn!("[{}] FFmpeg warning during audio extraction: {}", job_id, line);
-                }
-                
-                // Check if process is still running
-                if let Ok(Some(_)) = child.try_wait() {
-                    break;
-                }
-            } else {
-                // Error reading line, check if process is still running
-                if let Ok(Some(_)) = child.try_wait() {
-                    break;
-                }
-            }
-        }
-        
-        // Wait for the process to complete
-        let status = child.wait()?;
-        
-        if status.success() {
-            info!("Audio extraction completed successfully: {}", job_id);
-            Ok(job_id)
-        } else {
-            let error_msg = if status.code().is_some() {
-                format!("FFmpeg process failed with exit code: {}", status)
-            } else {
-                format!("FFmpeg process terminated by signal: {:?}", status.signal())
-            };
-            error!("[{}] {}", job_id, error_msg);
-            Err(anyhow::anyhow!("Audio extraction failed: {}", error_msg))
+        self.start_job(&job_id, "audio.extract", &request.input_path, &request.output_path)?;
+        let mut result = self.run_ffmpeg(&job_id, command, duration, "Audio extraction", progress).await;
+        if result.is_ok() {
+            result = self.output_check.verify(&job_id, &request.input_path, &request.output_path, &["audio"]).await;
         }
+        let key = MetricKey::new("audio.extract", None, Some(codec), file_size(&request.input_path));
+        self.finish_job(&job_id, &result, key);
+        result?;
+
+        info!("Audio extraction completed successfully: {}", job_id);
+        Ok(job_id)
     }
 
     pub async fn get_video_info(&self, file_path: &str) -> Result<serde_json::Value> {
         info!("Getting video info for: {}", file_path);
+        let mut violations = Violations::new();
+        violations.path("file_path", file_path);
+        violations.into_result()?;
         
         // Validate file exists
         if !std::path::Path::new(file_path).exists() {
-            return Err(anyhow::anyhow!("File not found: {}", file_path));
+            return Err(ServiceError::FileNotFound(format!("File not found: {}", file_path)).into());
         }
         
         // Validate file is readable (try to open it)
-        if let Err(_) = std::fs::File::open(file_path) {
+        if std::fs::File::open(file_path).is_err() {
             return Err(anyhow::anyhow!("File is not readable: {}", file_path));
         }
         
-        let output = Command::new("ffprobe")
-            .arg("-v").arg("quiet")
-            .arg("-print_format").arg("json")
-            .arg("-show_format")
-            .arg("-show_streams")
-            .arg(file_path)
-            .output()?;
-            
-        if output.status.success() {
-            let json_str = String::from_utf8_lossy(&output.stdout);
-            let info: serde_json::Value = serde_json::from_str(&json_str)?;
-            info!("Successfully retrieved video info for: {}", file_path);
-            Ok(info)
-        } else {
-            let error = String::from_utf8_lossy(&output.stderr);
-            error!("FFprobe error: {}", error);
-            Err(anyhow::anyhow!("Failed to get video info: {}", error))
+        match probe::probe(None, std::path::Path::new(file_path)).await {
+            Ok(info) => {
+                info!("Successfully retrieved video info for: {}", file_path);
+                let mut info = info.as_ref().clone();
+                if self.privacy.redact_probe(&mut info) {
+                    info!("Removed location tags inside a privacy zone from the info of {}", file_path);
+                }
+                Ok(info)
+            }
+            Err(e) => {
+                error!("FFprobe error: {}", e);
+                Err(e)
+            }
+        }
+    }
+
+    /// Add the language spoken on each audio stream of `info`, the probe of `file_path`, as
+    /// the stream's `detected_language`; streams Whisper couldn't place are left without one
+    pub async fn detect_languages(&self, file_path: &str, mut info: serde_json::Value) -> Result<serde_json::Value> {
+        let job_id = queue::job_id();
+        info!("Starting language detection job: {}", job_id);
+        let config = WhisperConfig::from_env()
+            .ok_or_else(|| ServiceError::BadRequest("No Whisper model in WHISPER_MODEL or MODEL_DIR".to_string()))?;
+        self.check_input(Some(&job_id), file_path).await?;
+
+        self.start_job(&job_id, "audio.language", file_path, "")?;
+        let input = std::path::Path::new(file_path);
+        let container_duration = probe::duration(&info);
+        let result = async {
+            let Some(streams) = info["streams"].as_array_mut() else {
+                return anyhow::Ok(0);
+            };
+            let work_dir = self.temp.job_dir(&job_id)?;
+            let mut detected = 0;
+            for stream in streams.iter_mut().filter(|stream| stream["codec_type"] == "audio") {
+                let Some(index) = stream["index"].as_u64() else {
+                    continue;
+                };
+                let duration = stream["duration"].as_str().and_then(|d| d.parse().ok()).or(container_duration);
+                if let Some(language) = language::detect(&job_id, input, index, duration, &config, work_dir.path()).await? {
+                    stream["detected_language"] = serde_json::to_value(language)?;
+                    detected += 1;
+                }
+            }
+            Ok(detected)
         }
+        .await;
+        let key = MetricKey::new("audio.language", None, None, file_size(file_path));
+        self.finish_job(&job_id, &result, key);
+        info!("[{}] Detected the language of {} audio streams in {}", job_id, result?, file_path);
+        Ok(info)
     }
 
-    pub async fn transcode_audio(&self, input_path: &str, output_path: &str, format: Option<&str>) -> Result<String> {
-        let job_id = Uuid::new_v4().to_string();
+    pub async fn transcode_audio(
+        &self,
+        input_path: &str,
+        output_path: &str,
+        format: Option<&str>,
+        progress: Option<&ProgressSender>,
+    ) -> Result<String> {
+        let mut violations = Violations::new();
+        violations.path("input_path", input_path);
+        violations.path("output_path", output_path);
+        violations.ffmpeg_token("format", format, CONTAINER_FORMATS);
+        violations.into_result()?;
+        let job_id = queue::job_id();
         
         info!("Starting audio transcode job: {}", job_id);
         
         // Validate input file exists
         if !std::path::Path::new(input_path).exists() {
-            return Err(anyhow::anyhow!("Input file not found: {}", input_path));
+            return Err(ServiceError::FileNotFound(format!("Input file not found: {}", input_path)).into());
         }
         
         // Validate output directory exists
         if let Some(parent) = std::path::Path::new(output_path).parent() {
             if !parent.exists() {
-                return Err(anyhow::anyhow!("Output directory does not exist: {}", parent.display()));
+                return Err(ServiceError::FileNotFound(format!("Output directory does not exist: {}", parent.display())).into());
             }
         }
         
+        // Reject oversized or infected inputs before FFmpeg starts decoding them
+        self.check_input(Some(&job_id), input_path).await?;
+
         // Get audio duration first
-        let duration = self.get_video_duration(input_path).await?;
+        let duration = self.get_video_duration(&job_id, input_path).await?;
         info!("[{}] Audio duration: {:.2} seconds", job_id, duration);
         
-        let mut command = Command::new("ffmpeg");
+        let mut command = sandbox::command("ffmpeg");
         
         // Input file
         command.arg("-i").arg(input_path);
@@ -421,123 +948,1497 @@ impl VideoProcessor {
         // Output file
         command.arg(output_path);
         
-        info!("Executing FFmpeg command for audio transcode: {:?}", command);
-        
-        // Execute FFmpeg command with real-time output monitoring
-        command.stdout(Stdio::piped()).stderr(Stdio::piped());
-        
-        info!("[{}] Spawning FFmpeg process for audio transcode...", job_id);
-        let mut child = command.spawn()?;
-        
-        // Check if process started successfully
-        match child.try_wait() {
-            Ok(Some(status)) => {
-                let error = format!("FFmpeg process terminated immediately with status: {}", status);
-                error!("[{}] {}", job_id, error);
-                return Err(anyhow::anyhow!("Audio transcode failed: {}", error));
-            }
-            Ok(None) => {
-                info!("[{}] FFmpeg process for audio transcode started successfully", job_id);
+        self.start_job(&job_id, "audio.transcode", input_path, output_path)?;
+        let mut result = self.run_ffmpeg(&job_id, command, duration, "Audio transcode", progress).await;
+        if result.is_ok() {
+            result = self.output_check.verify(&job_id, input_path, output_path, &["audio"]).await;
+        }
+        let key = MetricKey::new("audio.transcode", None, format, file_size(input_path));
+        self.finish_job(&job_id, &result, key);
+        result?;
+
+        info!("Audio transcode completed successfully: {}", job_id);
+        Ok(job_id)
+    }
+
+    /// Transcode to every quality profile and package the renditions as HLS next to `output_path`
+    pub async fn transcode_multi_quality_and_hls(&self, request: &VideoTranscodeRequest) -> Result<MultiQualityHlsResponse> {
+        request.validate()?;
+        self.check_input(None, &request.input_path).await?;
+        let output_prefix = request.output_path.trim_end_matches(".mp4");
+        let codec = request.codec.as_deref().unwrap_or("libx264");
+        let output_dir = std::path::Path::new(output_prefix).parent().unwrap_or_else(|| std::path::Path::new("output")).to_str().unwrap_or("output");
+        let master_playlist = "master.m3u8";
+        let master_path = format!("{}/{}", output_dir, master_playlist);
+        let job_id = queue::job_id();
+        let hardware = request.hardware_acceleration.and_then(|accelerator| {
+            let selected = hwaccel::select(accelerator, Some(codec));
+            if selected.is_none() {
+                warn!("[{}] No usable {:?} hardware encoder, encoding on the CPU", job_id, accelerator);
             }
-            Err(e) => {
-                let error = format!("Failed to check FFmpeg process status: {}", e);
-                error!("[{}] {}", job_id, error);
-                return Err(anyhow::anyhow!("Audio transcode failed: {}", error));
+            selected
+        });
+        self.start_job(&job_id, "video.hls", &request.input_path, &master_path)?;
+
+        let result = async {
+            let mut rungs = request.ladder.clone().unwrap_or_else(ladder::default_ladder);
+            if request.per_title.unwrap_or(false) {
+                let (width, height) = self.video_dimensions(&job_id, &request.input_path).await?;
+                rungs = ladder::fit(&rungs, (width, height));
+                let labels: Vec<&str> = rungs.iter().map(|rung| rung.label.as_str()).collect();
+                info!("[{}] Per-title ladder for {}x{}: {}", job_id, width, height, labels.join(", "));
             }
-        }
-        
-        let stderr = child.stderr.take().unwrap();
-        
-        // Monitor FFmpeg progress in real-time
-        let reader = BufReader::new(stderr);
-        let mut last_progress = 0.0;
-        
-        for line in reader.lines() {
-            if let Ok(line) = line {
-                // Parse FFmpeg progress output
-                if line.contains("time=") && line.contains("bitrate=") {
-                    // Extract time information for progress tracking
-                    if let Some(time_str) = line.split("time=").nth(1) {
-                        if let Some(time_part) = time_str.split_whitespace().next() {
-                            // Parse time format (HH:MM:SS.ms) and calculate percentage
-                            if let Some(current_time) = self.parse_ffmpeg_time(time_part) {
-                                let progress = (current_time / duration) * 100.0;
-                                if progress > last_progress + 5.0 { // Log every 5% progress
-                                    info!("[{}] Audio transcode progress: {:.1}% ({:.1}s/{:.1}s)", 
-                                          job_id, progress, current_time, duration);
-                                    last_progress = progress;
-                                }
-                            }
-                        }
-                    }
-                }
-                
-                // Log important FFmpeg messages
-                if line.contains("error") || line.contains("Error") {
-                    warn!("[{}] FFmpeg warning during audio transcode: {}", job_id, line);
-                }
-                
-                // Check if process is still running
-                if let Ok(Some(_)) = child.try_wait() {
-                    break;
-                }
+
+            // 1. Transcode song song nhiều chất lượng
+            let outputs = self.transcode_multi_quality(&job_id, request, output_prefix, &rungs).await?;
+
+            // 2. Đóng gói HLS
+            let options = request.hls.clone().unwrap_or_default();
+            self.package_hls(&job_id, &request.input_path, &outputs, output_dir, master_playlist, &options).await?;
+            let sprites = if options.sprites() {
+                let duration = self.get_video_duration(&job_id, &request.input_path).await?;
+                let dimensions = self.video_dimensions(&job_id, &request.input_path).await?;
+                let layout = SpriteLayout::new(duration, sprites::DEFAULT_INTERVAL, sprites::DEFAULT_TILE_WIDTH, sprites::DEFAULT_COLUMNS, dimensions);
+                Some(self.write_sprites(&job_id, &request.input_path, std::path::Path::new(output_dir), duration, &layout).await?)
             } else {
-                // Error reading line, check if process is still running
-                if let Ok(Some(_)) = child.try_wait() {
-                    break;
-                }
-            }
+                None
+            };
+            Ok((outputs, sprites))
         }
-        
-        // Wait for the process to complete
-        let status = child.wait()?;
-        
-        if status.success() {
-            info!("Audio transcode completed successfully: {}", job_id);
-            Ok(job_id)
+        .await;
+        let encoder = hardware.as_ref().map_or(codec, |hardware| hardware.name);
+        let key = MetricKey::new("video.hls", None, Some(encoder), file_size(&request.input_path));
+        self.finish_job(&job_id, &result, key);
+        let (outputs, sprites) = result?;
+
+        // 3. Trả về metadata
+        Ok(MultiQualityHlsResponse {
+            job_id,
+            outputs,
+            master_playlist: master_path,
+            sprites,
+        })
+    }
+
+    /// Grab a single frame as a still image, optionally scaled to `width` (keeping aspect ratio)
+    pub async fn extract_thumbnail(
+        &self,
+        input_path: &str,
+        output_path: &str,
+        timestamp: Option<f64>,
+        width: Option<u32>,
+    ) -> Result<()> {
+        info!("Extracting thumbnail from: {}", input_path);
+        let mut violations = Violations::new();
+        violations.path("input_path", input_path);
+        violations.path("output_path", output_path);
+        violations.into_result()?;
+
+        if !std::path::Path::new(input_path).exists() {
+            return Err(ServiceError::FileNotFound(format!("Input file not found: {}", input_path)).into());
+        }
+        self.check_input(None, input_path).await?;
+        let (_memory, lowres) = memory::reserve_image(None, std::path::Path::new(input_path)).await?;
+        self.grab_frame(None, input_path, output_path, timestamp, width, lowres).await
+    }
+
+    /// Animated GIF or WebP of a stretch of a video, for previews
+    pub async fn animate(&self, request: &AnimationRequest) -> Result<AnimationResponse> {
+        request.validate()?;
+        let job_id = queue::job_id();
+        info!("Starting animation job: {}", job_id);
+
+        if !std::path::Path::new(&request.input_path).exists() {
+            return Err(ServiceError::FileNotFound(format!("Input file not found: {}", request.input_path)).into());
+        }
+        self.check_input(Some(&job_id), &request.input_path).await?;
+        let format = AnimationFormat::from_path(&request.output_path)
+            .ok_or_else(|| ServiceError::InvalidFormat(format!("{} is not an animation format", request.output_path)))?;
+        let input_duration = self.get_video_duration(&job_id, &request.input_path).await?;
+        let start_time = request.start_time.unwrap_or(0.0);
+        if start_time >= input_duration {
+            return Err(ServiceError::BadRequest(format!(
+                "start_time {:.2}s is past the end of {} ({:.2}s)",
+                start_time, request.input_path, input_duration
+            ))
+            .into());
+        }
+        let duration = request.duration.unwrap_or(animation::DEFAULT_DURATION).min(input_duration - start_time);
+
+        // As input options, so only the range is read and the palette comes from it alone
+        let mut command = sandbox::command("ffmpeg");
+        command
+            .arg("-y")
+            .arg("-ss").arg(start_time.to_string())
+            .arg("-t").arg(duration.to_string())
+            .arg("-i").arg(&request.input_path)
+            .arg("-vf").arg(animation::filter(
+                request.fps.unwrap_or(animation::DEFAULT_FPS),
+                request.width.unwrap_or(animation::DEFAULT_WIDTH),
+                format,
+            ))
+            .args(format.encoder_args(request.quality.unwrap_or(animation::DEFAULT_QUALITY), request.loops.unwrap_or(0)))
+            .arg(&request.output_path);
+
+        self.start_job(&job_id, "video.animation", &request.input_path, &request.output_path)?;
+        let result = async {
+            self.temp.output_parent(std::path::Path::new(&request.output_path))?;
+            let _memory = memory::reserve_video(Some(&job_id), std::path::Path::new(&request.input_path), 1).await?;
+            self.run_ffmpeg(&job_id, command, duration, "Animation", None).await
+        }
+        .await;
+        let codec = match format {
+            AnimationFormat::Gif => "gif",
+            AnimationFormat::Webp => "libwebp_anim",
+        };
+        let key = MetricKey::new("video.animation", None, Some(codec), file_size(&request.input_path));
+        self.finish_job(&job_id, &result, key);
+        result?;
+
+        info!("Animation completed successfully: {}", job_id);
+        Ok(AnimationResponse { job_id, output_path: request.output_path.clone(), start_time, duration })
+    }
+
+    /// Overlay a logo or draw text onto every frame, or only those inside the requested window
+    pub async fn watermark_video(&self, request: &VideoWatermarkRequest) -> Result<VideoWatermarkResponse> {
+        request.validate()?;
+        let job_id = queue::job_id();
+        info!("Starting video watermark job: {}", job_id);
+
+        for path in std::iter::once(&request.input_path).chain(&request.image_path) {
+            if !std::path::Path::new(path).exists() {
+                return Err(ServiceError::FileNotFound(format!("Input file not found: {}", path)).into());
+            }
+            self.check_input(Some(&job_id), path).await?;
+        }
+        let duration = self.get_video_duration(&job_id, &request.input_path).await?;
+        let (width, height) = self.video_dimensions(&job_id, &request.input_path).await?;
+        let placement = Placement {
+            position: request.position.unwrap_or_default(),
+            opacity: request.opacity.unwrap_or(overlay::DEFAULT_OPACITY),
+            margin: request.margin.unwrap_or(overlay::DEFAULT_MARGIN),
+            start: request.start_time,
+            end: request.end_time,
+        };
+
+        self.start_job(&job_id, "video.watermark", &request.input_path, &request.output_path)?;
+        let result = async {
+            self.temp.output_parent(std::path::Path::new(&request.output_path))?;
+            let _memory = memory::reserve_video(Some(&job_id), std::path::Path::new(&request.input_path), 1).await?;
+            let mut command = sandbox::command("ffmpeg");
+            command.arg("-y").arg("-i").arg(&request.input_path);
+            // The text file is kept until FFmpeg has read it
+            let (graph, _work_dir) = match (&request.image_path, &request.text) {
+                (Some(image_path), _) => {
+                    let scale = request.scale.unwrap_or(overlay::DEFAULT_IMAGE_SCALE);
+                    // Even, so 4:2:0 output can hold the scaled logo's edge
+                    let mark_width = ((f64::from(width) * scale).round() as u32 / 2 * 2).max(2);
+                    command.arg("-i").arg(image_path);
+                    (overlay::image_filter(mark_width, &placement), None)
+                }
+                (None, text) => {
+                    let work_dir = self.temp.job_dir(&job_id)?;
+                    let text_file = work_dir.path().join("watermark.txt");
+                    std::fs::write(&text_file, text.as_deref().unwrap_or_default())?;
+                    let scale = request.scale.unwrap_or(overlay::DEFAULT_TEXT_SCALE);
+                    let font_size = ((f64::from(height) * scale).round() as u32).max(8);
+                    let color = request.font_color.as_deref().unwrap_or("white");
+                    (overlay::text_filter(&text_file, font_size, color, &placement), Some(work_dir))
+                }
+            };
+            command
+                .arg("-filter_complex").arg(graph)
+                .arg("-map").arg("[v]")
+                .arg("-map").arg("0:a?")
+                .arg("-c:a").arg("copy");
+            if let Some(codec) = &request.codec {
+                command.arg("-c:v").arg(codec);
+            }
+            command.args(self.privacy_args(&job_id, &request.input_path).await?).arg(&request.output_path);
+            self.run_ffmpeg(&job_id, command, duration, "Video watermark", None).await?;
+            self.output_check.verify(&job_id, &request.input_path, &request.output_path, &["video", "audio"]).await
+        }
+        .await;
+        let key = MetricKey::new("video.watermark", None, request.codec.as_deref(), file_size(&request.input_path));
+        self.finish_job(&job_id, &result, key);
+        result?;
+
+        info!("Video watermark completed successfully: {}", job_id);
+        Ok(VideoWatermarkResponse { job_id, output_path: request.output_path.clone() })
+    }
+
+    /// The `stream`th subtitle track of a probed input, which must be text for `text_only`
+    async fn subtitle_stream(&self, job_id: &str, input_path: &str, stream: u32, text_only: bool) -> Result<SubtitleStream> {
+        let info = probe::probe(Some(job_id), std::path::Path::new(input_path)).await?;
+        let streams = subtitles::streams(&info);
+        let found = streams.get(stream as usize).ok_or_else(|| {
+            ServiceError::BadRequest(format!("{} has {} subtitle tracks, there is no track {}", input_path, streams.len(), stream))
+        })?;
+        if text_only && !found.is_text() {
+            return Err(ServiceError::InvalidFormat(format!(
+                "subtitle track {} of {} is {}, a bitmap format",
+                stream, input_path, found.codec
+            ))
+            .into());
+        }
+        Ok(found.clone())
+    }
+
+    /// Render a subtitle file, or one of the input's text subtitle tracks, onto the frames
+    pub async fn burn_subtitles(&self, request: &SubtitleBurnRequest) -> Result<SubtitleResponse> {
+        request.validate()?;
+        let job_id = queue::job_id();
+        info!("Starting subtitle burn job: {}", job_id);
+
+        for path in std::iter::once(&request.input_path).chain(&request.subtitle_path) {
+            if !std::path::Path::new(path).exists() {
+                return Err(ServiceError::FileNotFound(format!("Input file not found: {}", path)).into());
+            }
+            self.check_input(Some(&job_id), path).await?;
+        }
+        let duration = self.get_video_duration(&job_id, &request.input_path).await?;
+        let filter = match &request.subtitle_path {
+            Some(subtitle_path) => subtitles::burn_filter(subtitle_path, None),
+            None => {
+                let stream = request.stream.unwrap_or(0);
+                // libass renders text tracks only
+                self.subtitle_stream(&job_id, &request.input_path, stream, true).await?;
+                subtitles::burn_filter(&request.input_path, Some(stream))
+            }
+        };
+
+        let mut command = sandbox::command("ffmpeg");
+        command
+            .arg("-y")
+            .arg("-i").arg(&request.input_path)
+            .arg("-vf").arg(filter)
+            .arg("-map").arg("0:v:0")
+            .arg("-map").arg("0:a?")
+            .arg("-c:a").arg("copy");
+        if let Some(codec) = &request.codec {
+            command.arg("-c:v").arg(codec);
+        }
+        command.args(self.privacy_args(&job_id, &request.input_path).await?).arg(&request.output_path);
+
+        self.start_job(&job_id, "video.subtitles_burn", &request.input_path, &request.output_path)?;
+        let result = async {
+            self.temp.output_parent(std::path::Path::new(&request.output_path))?;
+            let _memory = memory::reserve_video(Some(&job_id), std::path::Path::new(&request.input_path), 1).await?;
+            self.run_ffmpeg(&job_id, command, duration, "Subtitle burn", None).await?;
+            self.output_check.verify(&job_id, &request.input_path, &request.output_path, &["video", "audio"]).await
+        }
+        .await;
+        let key = MetricKey::new("video.subtitles_burn", None, request.codec.as_deref(), file_size(&request.input_path));
+        self.finish_job(&job_id, &result, key);
+        result?;
+
+        info!("Subtitle burn completed successfully: {}", job_id);
+        Ok(SubtitleResponse { job_id, output_path: request.output_path.clone() })
+    }
+
+    /// Add subtitle files as tracks of their own; audio, video and existing tracks are copied
+    pub async fn mux_subtitles(&self, request: &SubtitleMuxRequest) -> Result<SubtitleResponse> {
+        request.validate()?;
+        let job_id = queue::job_id();
+        info!("Starting subtitle mux job: {}", job_id);
+
+        for path in std::iter::once(&request.input_path).chain(request.subtitles.iter().map(|track| &track.path)) {
+            if !std::path::Path::new(path).exists() {
+                return Err(ServiceError::FileNotFound(format!("Input file not found: {}", path)).into());
+            }
+            self.check_input(Some(&job_id), path).await?;
+        }
+        let info = probe::probe(Some(&job_id), std::path::Path::new(&request.input_path)).await?;
+        let duration = probe::duration(&info).unwrap_or(0.0);
+        let existing = subtitles::streams(&info).len();
+
+        let mut command = sandbox::command("ffmpeg");
+        command.arg("-y").arg("-i").arg(&request.input_path);
+        for track in &request.subtitles {
+            command.arg("-i").arg(&track.path);
+        }
+        command.arg("-map").arg("0");
+        for input in 1..=request.subtitles.len() {
+            command.arg("-map").arg(format!("{}:s:0", input));
+        }
+        command
+            .arg("-c").arg("copy")
+            .arg("-c:s").arg(subtitles::mux_codec(std::path::Path::new(&request.output_path)));
+        // Added tracks follow the input's own subtitle streams
+        let new_default = request.subtitles.iter().any(|track| track.default.unwrap_or(false));
+        for (offset, track) in request.subtitles.iter().enumerate() {
+            let stream = existing + offset;
+            if let Some(language) = &track.language {
+                command.arg(format!("-metadata:s:s:{}", stream)).arg(format!("language={}", language));
+            }
+            if let Some(title) = &track.title {
+                command.arg(format!("-metadata:s:s:{}", stream)).arg(format!("title={}", title));
+            }
+            let disposition = if track.default.unwrap_or(false) { "default" } else { "0" };
+            command.arg(format!("-disposition:s:{}", stream)).arg(disposition);
+        }
+        // Only one track may be the default, so a new one replaces the input's
+        if new_default {
+            for stream in 0..existing {
+                command.arg(format!("-disposition:s:{}", stream)).arg("0");
+            }
+        }
+        command.args(self.privacy.metadata_args(&info)).arg(&request.output_path);
+
+        self.start_job(&job_id, "video.subtitles_mux", &request.input_path, &request.output_path)?;
+        let result = async {
+            self.temp.output_parent(std::path::Path::new(&request.output_path))?;
+            self.run_ffmpeg(&job_id, command, duration, "Subtitle mux", None).await?;
+            self.output_check.verify(&job_id, &request.input_path, &request.output_path, &["video", "audio", "subtitle"]).await
+        }
+        .await;
+        let key = MetricKey::new("video.subtitles_mux", None, Some("copy"), file_size(&request.input_path));
+        self.finish_job(&job_id, &result, key);
+        result?;
+
+        info!("Subtitle mux completed successfully: {}", job_id);
+        Ok(SubtitleResponse { job_id, output_path: request.output_path.clone() })
+    }
+
+    /// Save a text subtitle track of the input in the format of the output's extension
+    pub async fn extract_subtitles(&self, request: &SubtitleExtractRequest) -> Result<SubtitleExtractResponse> {
+        request.validate()?;
+        let job_id = queue::job_id();
+        info!("Starting subtitle extraction job: {}", job_id);
+
+        if !std::path::Path::new(&request.input_path).exists() {
+            return Err(ServiceError::FileNotFound(format!("Input file not found: {}", request.input_path)).into());
+        }
+        self.check_input(Some(&job_id), &request.input_path).await?;
+        let output = std::path::Path::new(&request.output_path);
+        let codec = subtitles::extract_codec(output)
+            .ok_or_else(|| ServiceError::InvalidFormat(format!("{} is not a subtitle format", request.output_path)))?;
+        let stream = self.subtitle_stream(&job_id, &request.input_path, request.stream.unwrap_or(0), true).await?;
+
+        let mut command = sandbox::command("ffmpeg");
+        command
+            .arg("-y")
+            .arg("-i").arg(&request.input_path)
+            .arg("-map").arg(format!("0:{}", stream.index))
+            .arg("-c:s").arg(codec)
+            .arg(output);
+
+        self.start_job(&job_id, "video.subtitles_extract", &request.input_path, &request.output_path)?;
+        let result = async {
+            self.temp.output_parent(output)?;
+            self.run_ffmpeg(&job_id, command, 0.0, "Subtitle extraction", None).await
+        }
+        .await;
+        let key = MetricKey::new("video.subtitles_extract", None, Some(codec), file_size(&request.input_path));
+        self.finish_job(&job_id, &result, key);
+        result?;
+
+        info!("Subtitle extraction completed successfully: {}", job_id);
+        Ok(SubtitleExtractResponse {
+            job_id,
+            output_path: request.output_path.clone(),
+            codec: stream.codec,
+            language: stream.language,
+        })
+    }
+
+    /// Timestamps and scores of the cuts between scenes
+    pub async fn detect_scenes(&self, request: &SceneDetectRequest) -> Result<SceneDetectResponse> {
+        request.validate()?;
+        let job_id = queue::job_id();
+        info!("Starting scene detection job: {}", job_id);
+
+        let input = std::path::Path::new(&request.input_path);
+        if !input.exists() {
+            return Err(ServiceError::FileNotFound(format!("Input file not found: {}", request.input_path)).into());
+        }
+        self.check_input(Some(&job_id), &request.input_path).await?;
+        let duration = self.get_video_duration(&job_id, &request.input_path).await?;
+
+        self.start_job(&job_id, "video.scenes", &request.input_path, "")?;
+        let result = async {
+            let _memory = memory::reserve_video(Some(&job_id), input, 1).await?;
+            let cuts = scenes::detect(&job_id, input, request.threshold.unwrap_or(scenes::DEFAULT_THRESHOLD)).await?;
+            let max = request.max_scenes.map_or(scenes::MAX_SCENES, |max| max as usize);
+            anyhow::Ok(scenes::thin(cuts, request.min_interval.unwrap_or(1.0), max))
+        }
+        .await;
+        let key = MetricKey::new("video.scenes", None, None, file_size(&request.input_path));
+        self.finish_job(&job_id, &result, key);
+        let scenes = result?;
+        info!("[{}] Found {} scene cuts in {}", job_id, scenes.len(), request.input_path);
+
+        Ok(SceneDetectResponse { job_id, duration, scenes })
+    }
+
+    /// Scrub preview sprite sheets and their WebVTT track, see [`SpriteLayout`]
+    pub async fn generate_sprite_sheet(&self, request: &SpriteSheetRequest) -> Result<SpriteSheetResponse> {
+        request.validate()?;
+        let job_id = queue::job_id();
+        info!("Starting sprite sheet job: {}", job_id);
+
+        if !std::path::Path::new(&request.input_path).exists() {
+            return Err(ServiceError::FileNotFound(format!("Input file not found: {}", request.input_path)).into());
+        }
+        self.check_input(Some(&job_id), &request.input_path).await?;
+        let duration = self.get_video_duration(&job_id, &request.input_path).await?;
+
+        self.start_job(&job_id, "video.sprites", &request.input_path, &request.output_dir)?;
+        let output_dir = std::path::Path::new(&request.output_dir);
+        let result = async {
+            let dimensions = self.video_dimensions(&job_id, &request.input_path).await?;
+            let layout = SpriteLayout::new(
+                duration,
+                request.interval.unwrap_or(sprites::DEFAULT_INTERVAL),
+                request.tile_width.unwrap_or(sprites::DEFAULT_TILE_WIDTH),
+                request.columns.unwrap_or(sprites::DEFAULT_COLUMNS),
+                dimensions,
+            );
+            self.temp.output_dir(output_dir)?;
+            let vtt_path = self.write_sprites(&job_id, &request.input_path, output_dir, duration, &layout).await?;
+            anyhow::Ok((vtt_path, layout))
+        }
+        .await;
+        let key = MetricKey::new("video.sprites", None, None, file_size(&request.input_path));
+        self.finish_job(&job_id, &result, key);
+        let (vtt_path, layout) = result?;
+
+        let sheets = (0..layout.sheets())
+            .map(|index| output_dir.join(SpriteLayout::sheet_name(index)).to_string_lossy().into_owned())
+            .collect();
+        Ok(SpriteSheetResponse { job_id, vtt_path, sheets, tiles: layout.frames })
+    }
+
+    /// Tile `input` into `sprite_000.jpg` and up in `output_dir` in one FFmpeg pass, then write
+    /// `sprite.vtt` beside them. Returns the track's path
+    async fn write_sprites(&self, job_id: &str, input_path: &str, output_dir: &std::path::Path, duration: f64, layout: &SpriteLayout) -> Result<String> {
+        let mut command = sandbox::command("ffmpeg");
+        command
+            .arg("-y")
+            .arg("-i").arg(input_path)
+            .arg("-vf").arg(layout.filter())
+            .arg("-frames:v").arg(layout.sheets().to_string())
+            .arg("-q:v").arg("4")
+            .arg("-start_number").arg("0")
+            .arg(output_dir.join(format!("{}_%03d.jpg", sprites::SPRITE_NAME)));
+        self.run_ffmpeg(job_id, command, duration, "Sprite sheet", None).await?;
+
+        let vtt_path = output_dir.join(format!("{}.vtt", sprites::SPRITE_NAME));
+        std::fs::write(&vtt_path, layout.webvtt(duration))?;
+        info!("[{}] Wrote {} tiles on {} sprite sheets to {}", job_id, layout.frames, layout.sheets(), output_dir.display());
+        Ok(vtt_path.to_string_lossy().into_owned())
+    }
+
+    /// Stills at each of `timestamps`, or at `percentages` of the duration, named by position
+    pub async fn extract_thumbnails(&self, request: &ThumbnailRequest) -> Result<ThumbnailResponse> {
+        request.validate()?;
+        let job_id = queue::job_id();
+        info!("Starting thumbnail job: {}", job_id);
+
+        if !std::path::Path::new(&request.input_path).exists() {
+            return Err(ServiceError::FileNotFound(format!("Input file not found: {}", request.input_path)).into());
+        }
+        self.check_input(Some(&job_id), &request.input_path).await?;
+        let duration = self.get_video_duration(&job_id, &request.input_path).await?;
+        // Nothing decodes at the very end, so the last position is a frame short of it
+        let last = (duration - 0.1).max(0.0);
+        let timestamps: Vec<f64> = match (&request.timestamps, &request.percentages) {
+            (Some(timestamps), _) => {
+                if let Some(past) = timestamps.iter().find(|timestamp| **timestamp > duration) {
+                    return Err(ServiceError::BadRequest(format!(
+                        "timestamp {:.2}s is past the end of {} ({:.2}s)",
+                        past, request.input_path, duration
+                    ))
+                    .into());
+                }
+                timestamps.iter().map(|timestamp| timestamp.min(last)).collect()
+            }
+            (None, Some(percentages)) => percentages.iter().map(|percent| (duration * percent / 100.0).min(last)).collect(),
+            (None, None) => Vec::new(),
+        };
+
+        self.start_job(&job_id, "video.thumbnail", &request.input_path, &request.output_dir)?;
+        let format = request.format.as_deref().unwrap_or("jpg").to_lowercase();
+        let output_dir = std::path::Path::new(&request.output_dir);
+        let result = async {
+            self.temp.output_dir(output_dir)?;
+            let (_memory, lowres) = memory::reserve_image(Some(&job_id), std::path::Path::new(&request.input_path)).await?;
+            let mut thumbnails = Vec::with_capacity(timestamps.len());
+            for (i, timestamp) in timestamps.iter().enumerate() {
+                let path = output_dir.join(format!("thumb_{:03}.{}", i, format)).to_string_lossy().into_owned();
+                self.grab_frame(Some(&job_id), &request.input_path, &path, Some(*timestamp), request.width, lowres).await?;
+                thumbnails.push(Thumbnail { timestamp: *timestamp, path });
+            }
+            anyhow::Ok(thumbnails)
+        }
+        .await;
+        let key = MetricKey::new("video.thumbnail", None, None, file_size(&request.input_path));
+        self.finish_job(&job_id, &result, key);
+        let thumbnails = result?;
+        info!("[{}] Wrote {} thumbnails to {}", job_id, thumbnails.len(), request.output_dir);
+
+        Ok(ThumbnailResponse { job_id, thumbnails })
+    }
+
+    /// One FFmpeg run writing the frame at `timestamp` (default: the first) to `output_path`
+    async fn grab_frame(
+        &self,
+        job_id: Option<&str>,
+        input_path: &str,
+        output_path: &str,
+        timestamp: Option<f64>,
+        width: Option<u32>,
+        lowres: u8,
+    ) -> Result<()> {
+        let mut command = sandbox::command("ffmpeg");
+        command.arg("-y").arg("-v").arg("error");
+
+        // Seek before the input for fast keyframe-based positioning
+        if let Some(timestamp) = timestamp {
+            command.arg("-ss").arg(format!("{:.3}", timestamp));
+        }
+        if lowres > 0 {
+            command.arg("-lowres").arg(lowres.to_string());
+        }
+        command.arg("-i").arg(input_path);
+
+        if let Some(width) = width {
+            command.arg("-vf").arg(format!("scale={}:-2", width));
+        }
+
+        command.arg("-frames:v").arg("1").arg(output_path);
+
+        let output = audit::output_async(job_id, command).await?;
+        if output.status.success() {
+            info!("Thumbnail written to: {}", output_path);
+            Ok(())
         } else {
-            let error_msg = if status.code().is_some() {
-                format!("FFmpeg process failed with exit code: {}", status)
-            } else {
-                format!("FFmpeg process terminated by signal: {:?}", status.signal())
+            let error = String::from_utf8_lossy(&output.stderr);
+            error!("Thumbnail extraction failed: {}", error);
+            Err(FfmpegFailure {
+                kind: FfmpegErrorKind::classify(&error),
+                message: format!("Thumbnail extraction failed: {}", error.trim()),
+            }
+            .into())
+        }
+    }
+
+    /// Save the cover art embedded in an MP4/MKV as an image
+    pub async fn extract_cover(&self, request: &CoverExtractRequest) -> Result<CoverResponse> {
+        request.validate()?;
+        let job_id = queue::job_id();
+        info!("Starting cover extraction job: {}", job_id);
+
+        if !std::path::Path::new(&request.input_path).exists() {
+            return Err(ServiceError::FileNotFound(format!("Input file not found: {}", request.input_path)).into());
+        }
+        self.check_input(Some(&job_id), &request.input_path).await?;
+
+        self.start_job(&job_id, "video.cover_extract", &request.input_path, &request.output_path)?;
+        let result = async {
+            let info = probe::probe(Some(&job_id), std::path::Path::new(&request.input_path)).await?;
+            let index = cover::cover_stream(&info).ok_or_else(|| {
+                ServiceError::InvalidFormat(format!("{} has no embedded cover art", request.input_path))
+            })?;
+            let mut command = sandbox::command("ffmpeg");
+            command
+                .arg("-y")
+                .arg("-i").arg(&request.input_path)
+                .arg("-map").arg(format!("0:{}", index))
+                .arg("-frames:v").arg("1")
+                .arg(&request.output_path);
+            self.run_ffmpeg(&job_id, command, 0.0, "Cover extraction", None).await
+        }
+        .await;
+        let key = MetricKey::new("video.cover_extract", None, None, file_size(&request.input_path));
+        self.finish_job(&job_id, &result, key);
+        result?;
+
+        Ok(CoverResponse { job_id, output_path: request.output_path.clone() })
+    }
+
+    /// Cut a clip out of a video, by stream copy or frame-accurate re-encode
+    pub async fn trim(&self, request: &TrimRequest) -> Result<TrimResponse> {
+        request.validate()?;
+        let job_id = queue::job_id();
+        info!("Starting trim job: {}", job_id);
+
+        if !std::path::Path::new(&request.input_path).exists() {
+            return Err(ServiceError::FileNotFound(format!("Input file not found: {}", request.input_path)).into());
+        }
+        self.check_input(Some(&job_id), &request.input_path).await?;
+        let input_duration = self.get_video_duration(&job_id, &request.input_path).await?;
+        if request.start_time >= input_duration {
+            return Err(ServiceError::BadRequest(format!(
+                "start_time {:.2}s is past the end of {} ({:.2}s)",
+                request.start_time, request.input_path, input_duration
+            ))
+            .into());
+        }
+        let duration = request.clip_duration().min(input_duration - request.start_time);
+        let mode = request.mode.unwrap_or_default();
+
+        // Seeking before `-i` jumps straight to the start instead of decoding up to it, and
+        // resets timestamps there, so the length is given with `-t`: an `-to` would be read
+        // relative to the clip and not to the input
+        let mut command = sandbox::command("ffmpeg");
+        command
+            .arg("-y")
+            .arg("-ss").arg(request.start_time.to_string())
+            .arg("-i").arg(&request.input_path)
+            .arg("-t").arg(duration.to_string());
+        match mode {
+            TrimMode::Copy => {
+                command
+                    .arg("-c").arg("copy")
+                    // Packets before the keyframe would otherwise start at negative timestamps
+                    .arg("-avoid_negative_ts").arg("make_zero");
+            }
+            TrimMode::Reencode => {
+                command
+                    .arg("-c:v").arg(request.codec.as_deref().unwrap_or("libx264"))
+                    .arg("-c:a").arg("aac");
+            }
+        }
+        command.args(self.privacy_args(&job_id, &request.input_path).await?).arg(&request.output_path);
+
+        let codec = match mode {
+            TrimMode::Copy => "copy",
+            TrimMode::Reencode => request.codec.as_deref().unwrap_or("libx264"),
+        };
+        self.start_job(&job_id, "video.trim", &request.input_path, &request.output_path)?;
+        let mut result = self.run_ffmpeg(&job_id, command, duration, "Trim", None).await;
+        if result.is_ok() {
+            // A copied clip starts at the keyframe before `start_time`, so its length is open
+            let expected = (mode == TrimMode::Reencode).then_some(duration);
+            result = self
+                .output_check
+                .verify_clip(&job_id, &request.input_path, &request.output_path, &["video", "audio"], expected)
+                .await;
+        }
+        let key = MetricKey::new("video.trim", None, Some(codec), file_size(&request.input_path));
+        self.finish_job(&job_id, &result, key);
+        result?;
+
+        info!("Trim completed successfully: {}", job_id);
+        Ok(TrimResponse {
+            job_id,
+            output_path: request.output_path.clone(),
+            start_time: request.start_time,
+            duration,
+            mode,
+        })
+    }
+
+    /// Encode one source with two parameter sets and score each against it, so presets can be
+    /// tuned on real footage
+    pub async fn compare_encodes(&self, request: &EncodeCompareRequest) -> Result<EncodeCompareResponse> {
+        request.validate()?;
+        let job_id = queue::job_id();
+        info!("Starting encode comparison job: {}", job_id);
+
+        let input = std::path::Path::new(&request.input_path);
+        if !input.exists() {
+            return Err(ServiceError::FileNotFound(format!("Input file not found: {}", request.input_path)).into());
+        }
+        self.check_input(Some(&job_id), &request.input_path).await?;
+        let dimensions = self.video_dimensions(&job_id, &request.input_path).await?;
+        let input_duration = self.get_video_duration(&job_id, &request.input_path).await?;
+        let duration = request.duration.map_or(input_duration, |duration| duration.min(input_duration));
+        let composite = request.composite.as_deref().unwrap_or("image").to_lowercase();
+
+        self.start_job(&job_id, "video.compare", &request.input_path, &request.output_dir)?;
+        let output_dir = std::path::Path::new(&request.output_dir);
+        let result = async {
+            self.temp.output_dir(output_dir)?;
+            let _memory = memory::reserve_video(Some(&job_id), input, 1).await?;
+            let mut outcomes = Vec::with_capacity(2);
+            // One after the other, so neither encode's timing suffers from the other's load
+            for (label, params) in [("a", &request.a), ("b", &request.b)] {
+                let output = output_dir.join(format!("{}.mkv", label));
+                let mut command = sandbox::command("ffmpeg");
+                command
+                    .arg("-y")
+                    .arg("-i").arg(input)
+                    .arg("-t").arg(duration.to_string())
+                    .arg("-map").arg("0:v:0");
+                params.apply(&mut command);
+                command.arg(&output);
+
+                let started = std::time::Instant::now();
+                self.run_ffmpeg(&job_id, command, duration, &format!("Encode {}", label), None).await?;
+                let encode_ms = started.elapsed().as_millis() as u64;
+                let size_bytes = std::fs::metadata(&output)?.len();
+                let scores = quality::score(&job_id, input, &output, dimensions, duration, request.vmaf.unwrap_or(true)).await?;
+                info!("[{}] Encode {}: {} bytes in {} ms, {:?}", job_id, label, size_bytes, encode_ms, scores);
+                outcomes.push(EncodeOutcome {
+                    output_path: output.to_string_lossy().into_owned(),
+                    size_bytes,
+                    bitrate: (size_bytes as f64 * 8.0 / duration.max(f64::EPSILON)) as u64,
+                    encode_ms,
+                    scores,
+                });
+            }
+
+            let composite_path = match composite.as_str() {
+                "none" => None,
+                kind => {
+                    let (path, at) = match kind {
+                        "video" => (output_dir.join("compare.mp4"), None),
+                        _ => (output_dir.join("compare.png"), Some(duration / 2.0)),
+                    };
+                    let (a, b) = (std::path::Path::new(&outcomes[0].output_path), std::path::Path::new(&outcomes[1].output_path));
+                    // Even, since chroma-subsampled encoders refuse odd heights
+                    quality::composite(&job_id, a, b, dimensions.1 & !1, at, &path).await?;
+                    Some(path.to_string_lossy().into_owned())
+                }
+            };
+            let b = outcomes.pop().expect("two encodes");
+            let a = outcomes.pop().expect("two encodes");
+            anyhow::Ok((a, b, composite_path))
+        }
+        .await;
+        let key = MetricKey::new("video.compare", None, None, file_size(&request.input_path));
+        self.finish_job(&job_id, &result, key);
+        let (a, b, composite_path) = result?;
+
+        info!("Encode comparison completed successfully: {}", job_id);
+        Ok(EncodeCompareResponse { job_id, duration, a, b, composite_path })
+    }
+
+    /// Remux a video with a poster image embedded as its `attached_pic` cover
+    pub async fn attach_cover(&self, request: &CoverAttachRequest) -> Result<CoverResponse> {
+        request.validate()?;
+        let job_id = queue::job_id();
+        info!("Starting cover attach job: {}", job_id);
+
+        for path in [&request.input_path, &request.image_path] {
+            if !std::path::Path::new(path).exists() {
+                return Err(ServiceError::FileNotFound(format!("Input file not found: {}", path)).into());
+            }
+            self.check_input(Some(&job_id), path).await?;
+        }
+
+        self.start_job(&job_id, "video.cover_attach", &request.input_path, &request.output_path)?;
+        let result = async {
+            let info = probe::probe(Some(&job_id), std::path::Path::new(&request.input_path)).await?;
+            let mut command = sandbox::command("ffmpeg");
+            command
+                .arg("-y")
+                .arg("-i").arg(&request.input_path)
+                .arg("-i").arg(&request.image_path)
+                .args(cover::attach_args(&info))
+                .arg(&request.output_path);
+            let duration = probe::duration(&info).unwrap_or(0.0);
+            self.run_ffmpeg(&job_id, command, duration, "Cover attach", None).await
+        }
+        .await;
+        let key = MetricKey::new("video.cover_attach", None, Some("copy"), file_size(&request.input_path));
+        self.finish_job(&job_id, &result, key);
+        result?;
+
+        Ok(CoverResponse { job_id, output_path: request.output_path.clone() })
+    }
+
+    /// Write a video's frames, or every Nth frame of a time range, out as numbered images plus
+    /// an `index.json` mapping each file to its time in the source
+    pub async fn export_frames(&self, request: &FrameExportRequest) -> Result<FrameExportResponse> {
+        request.validate()?;
+        let job_id = queue::job_id();
+        info!("Starting frame export job: {}", job_id);
+
+        let input = std::path::Path::new(&request.input_path);
+        if !input.exists() {
+            return Err(ServiceError::FileNotFound(format!("Input file not found: {}", request.input_path)).into());
+        }
+        self.check_input(Some(&job_id), &request.input_path).await?;
+
+        self.start_job(&job_id, "video.frames", &request.input_path, &request.output_dir)?;
+        let format = request.format.as_deref().unwrap_or("png").to_lowercase();
+        let output_dir = std::path::Path::new(&request.output_dir);
+        let manifest_path = output_dir.join("index.json");
+        let result = async {
+            self.temp.output_dir(output_dir)?;
+            let _memory = memory::reserve_video(Some(&job_id), input, 1).await?;
+            let selection = Selection {
+                start: request.start.unwrap_or(0.0),
+                end: request.end,
+                every: request.every.unwrap_or(1),
+                max_frames: request.max_frames.unwrap_or(frames::DEFAULT_MAX_FRAMES),
             };
-            error!("[{}] {}", job_id, error_msg);
-            Err(anyhow::anyhow!("Audio transcode failed: {}", error_msg))
+            let times = frames::export(&job_id, input, output_dir, &format, selection).await?;
+            let index = FrameIndex::new(&request.input_path, &format, &times);
+            std::fs::write(&manifest_path, serde_json::to_vec_pretty(&index)?)?;
+            anyhow::Ok(times.len())
+        }
+        .await;
+        let key = MetricKey::new("video.frames", None, None, file_size(&request.input_path));
+        self.finish_job(&job_id, &result, key);
+        let count = result?;
+        info!("[{}] Exported {} frames to {}", job_id, count, request.output_dir);
+
+        Ok(FrameExportResponse {
+            job_id,
+            output_dir: request.output_dir.clone(),
+            manifest_path: manifest_path.to_string_lossy().into_owned(),
+            frames: count,
+        })
+    }
+
+    /// Index who appears when: faces detected on sampled frames are linked into tracks, each
+    /// with its time span and a crop of its clearest sighting, listed in `faces.json`
+    pub async fn index_faces(&self, request: &FaceIndexRequest) -> Result<FaceIndexResponse> {
+        request.validate()?;
+        let job_id = queue::job_id();
+        info!("Starting face index job: {}", job_id);
+
+        let input = std::path::Path::new(&request.input_path);
+        if !input.exists() {
+            return Err(ServiceError::FileNotFound(format!("Input file not found: {}", request.input_path)).into());
+        }
+        self.check_input(Some(&job_id), &request.input_path).await?;
+        let model = FaceModel::from_env()
+            .ok_or_else(|| ServiceError::BadRequest("No face detection model in FACE_MODEL or MODEL_DIR".to_string()))?;
+
+        self.start_job(&job_id, "video.faces", &request.input_path, &request.output_dir)?;
+        let interval = request.interval.unwrap_or(faces::DEFAULT_INTERVAL);
+        let output_dir = std::path::Path::new(&request.output_dir);
+        let index_path = output_dir.join("faces.json");
+        let result = async {
+            self.temp.output_dir(output_dir)?;
+            let _memory = memory::reserve_video(Some(&job_id), input, 1).await?;
+            let confidence = request.confidence.unwrap_or(faces::DEFAULT_CONFIDENCE);
+            let detections = faces::detect(&job_id, input, &model, interval, confidence).await?;
+            let tracks = faces::track(&detections, interval, request.min_appearances.unwrap_or(faces::DEFAULT_MIN_APPEARANCES));
+            for track in &tracks {
+                faces::save_crop(&job_id, input, track, output_dir).await?;
+            }
+            let index = FaceIndex { source: request.input_path.clone(), interval, tracks };
+            std::fs::write(&index_path, serde_json::to_vec_pretty(&index)?)?;
+            anyhow::Ok(index.tracks.len())
+        }
+        .await;
+        let key = MetricKey::new("video.faces", None, None, file_size(&request.input_path));
+        self.finish_job(&job_id, &result, key);
+        let tracks = result?;
+        info!("[{}] Indexed {} face tracks in {}", job_id, tracks, request.input_path);
+
+        Ok(FaceIndexResponse {
+            job_id,
+            output_dir: request.output_dir.clone(),
+            index_path: index_path.to_string_lossy().into_owned(),
+            tracks,
+        })
+    }
+
+    /// Hash frames sampled across a video into a fingerprint that survives re-encoding,
+    /// resizing and trimming, for `fingerprint::compare`
+    pub async fn fingerprint(&self, request: &FingerprintRequest) -> Result<FingerprintResponse> {
+        request.validate()?;
+        let job_id = queue::job_id();
+        info!("Starting fingerprint job: {}", job_id);
+
+        let input = std::path::Path::new(&request.input_path);
+        if !input.exists() {
+            return Err(ServiceError::FileNotFound(format!("Input file not found: {}", request.input_path)).into());
+        }
+        self.check_input(Some(&job_id), &request.input_path).await?;
+
+        self.start_job(&job_id, "video.fingerprint", &request.input_path, "")?;
+        let interval = request.interval.unwrap_or(fingerprint::DEFAULT_INTERVAL);
+        let result = async {
+            let _memory = memory::reserve_video(Some(&job_id), input, 1).await?;
+            let fingerprint = fingerprint::fingerprint(&job_id, input, interval).await?;
+            if fingerprint.hashes.is_empty() {
+                return Err(ServiceError::InvalidFormat(format!("No video frames in {}", request.input_path)).into());
+            }
+            anyhow::Ok(fingerprint)
+        }
+        .await;
+        let key = MetricKey::new("video.fingerprint", None, None, file_size(&request.input_path));
+        self.finish_job(&job_id, &result, key);
+        let fingerprint = result?;
+        info!("[{}] Fingerprinted {} from {} frames", job_id, request.input_path, fingerprint.hashes.len());
+
+        Ok(FingerprintResponse { job_id, fingerprint })
+    }
+
+    /// Encode still images into a slideshow or timelapse video, optionally over a music track
+    pub async fn slideshow(&self, request: &SlideshowRequest) -> Result<SlideshowResponse> {
+        request.validate()?;
+        let job_id = queue::job_id();
+        info!("Starting slideshow job: {}", job_id);
+
+        let images = match (&request.images, &request.pattern) {
+            (Some(images), _) => images.clone(),
+            (None, Some(pattern)) => slideshow::expand(pattern)
+                .map_err(|e| ServiceError::FileNotFound(format!("{}: {}", pattern, e)))?,
+            (None, None) => Vec::new(),
+        };
+        if images.is_empty() || images.len() > slideshow::MAX_SLIDES {
+            return Err(ServiceError::BadRequest(format!(
+                "{} images found, a slideshow takes 1 to {}",
+                images.len(),
+                slideshow::MAX_SLIDES
+            ))
+            .into());
+        }
+        for path in images.iter().chain(&request.audio_path) {
+            if !std::path::Path::new(path).exists() {
+                return Err(ServiceError::FileNotFound(format!("Input file not found: {}", path)).into());
+            }
+            self.check_input(Some(&job_id), path).await?;
+        }
+
+        let (width, height) = request
+            .resolution
+            .as_deref()
+            .and_then(crate::utils::validation::parse_resolution)
+            .unwrap_or((1920, 1080));
+        let image_secs = request.image_duration.unwrap_or(slideshow::DEFAULT_IMAGE_SECS);
+        let show = Slideshow {
+            width,
+            height,
+            fps: request.fps.unwrap_or(slideshow::DEFAULT_FPS),
+            image_secs,
+            transition: request
+                .transition
+                .as_deref()
+                .filter(|transition| *transition != "none")
+                .map(|transition| {
+                    let secs = request.transition_duration.unwrap_or(slideshow::DEFAULT_TRANSITION_SECS.min(image_secs / 2.0));
+                    (transition.to_lowercase(), secs)
+                }),
+            ken_burns: request.ken_burns.unwrap_or(false),
+        };
+        let duration = show.duration(images.len());
+
+        self.start_job(&job_id, "video.slideshow", &images[0], &request.output_path)?;
+        let result = async {
+            let _memory = memory::reserve_image(Some(&job_id), std::path::Path::new(&images[0])).await?.0;
+            let mut command = sandbox::command("ffmpeg");
+            command.arg("-y");
+            for input in images.iter().chain(&request.audio_path) {
+                command.arg("-i").arg(input);
+            }
+            command.arg("-filter_complex").arg(show.filter_graph(images.len())).arg("-map").arg("[v]");
+            if request.audio_path.is_some() {
+                let fade_start = (duration - 2.0).max(0.0);
+                command
+                    .arg("-map").arg(format!("{}:a:0", images.len()))
+                    .arg("-af").arg(format!("afade=t=out:st={}:d=2", fade_start))
+                    .arg("-c:a").arg("aac");
+            }
+            command
+                .arg("-c:v").arg(request.codec.as_deref().unwrap_or("libx264"))
+                .arg("-t").arg(duration.to_string())
+                .arg(&request.output_path);
+            self.run_ffmpeg(&job_id, command, duration, "Slideshow", None).await
+        }
+        .await;
+        let key = MetricKey::new("video.slideshow", request.resolution.as_deref(), request.codec.as_deref(), None);
+        self.finish_job(&job_id, &result, key);
+        result?;
+
+        Ok(SlideshowResponse {
+            job_id,
+            output_path: request.output_path.clone(),
+            images: images.len(),
+            duration_secs: duration,
+        })
+    }
+
+    /// Crop uniform borders off an image (`still`) or letterbox bars off a video
+    pub async fn autotrim(&self, request: &AutotrimRequest, still: bool) -> Result<AutotrimResponse> {
+        request.validate()?;
+        let job_id = queue::job_id();
+        let job_type = if still { "image.autotrim" } else { "video.autocrop" };
+        info!("Starting {} job: {}", job_type, job_id);
+
+        if !std::path::Path::new(&request.input_path).exists() {
+            return Err(ServiceError::FileNotFound(format!("Input file not found: {}", request.input_path)).into());
+        }
+        self.check_input(Some(&job_id), &request.input_path).await?;
+        if request.auto_rotate == Some(true) && !still {
+            return Err(ServiceError::BadRequest("auto_rotate only applies to images".to_string()).into());
+        }
+
+        self.start_job(&job_id, job_type, &request.input_path, &request.output_path)?;
+        let result = self.run_autotrim(&job_id, request, still).await;
+        let key = MetricKey::new(job_type, None, None, file_size(&request.input_path));
+        self.finish_job(&job_id, &result, key);
+        let (crop, orientation) = result?;
+
+        Ok(AutotrimResponse { job_id, output_path: request.output_path.clone(), crop, orientation })
+    }
+
+    async fn run_autotrim(
+        &self,
+        job_id: &str,
+        request: &AutotrimRequest,
+        still: bool,
+    ) -> Result<(Option<autotrim::CropRect>, Option<Orientation>)> {
+        let input = std::path::Path::new(&request.input_path);
+        let info = probe::probe(Some(job_id), input).await?;
+        let footprint = memory::Footprint::from_probe(&info)
+            .ok_or_else(|| ServiceError::InvalidFormat(format!("{} has no image or video stream", request.input_path)))?;
+        let _memory = if still {
+            memory::reserve_image(Some(job_id), input).await?.0
+        } else {
+            memory::reserve_video(Some(job_id), input, 1).await?
+        };
+
+        let borders: &[Border] = match request.border.as_deref() {
+            Some("black") => &[Border::Black],
+            Some("white") => &[Border::White],
+            _ => &[Border::Black, Border::White],
+        };
+        let threshold = request.threshold.unwrap_or(autotrim::DEFAULT_THRESHOLD);
+        let mut candidates = Vec::new();
+        for &border in borders {
+            candidates.extend(autotrim::detect(job_id, input, border, threshold, still).await?);
+        }
+        let crop = autotrim::choose(footprint.width, footprint.height, &candidates);
+        match &crop {
+            Some(crop) => info!(
+                "[{}] Trimming {}x{} to {}x{} at {},{}",
+                job_id, footprint.width, footprint.height, crop.width, crop.height, crop.x, crop.y
+            ),
+            None => info!("[{}] No borders found in {}", job_id, request.input_path),
+        }
+
+        // An EXIF orientation or display matrix already says which way is up
+        let orientation = if request.auto_rotate == Some(true) && !probe::has_rotation(&info) {
+            let image = watermark::thumbnail(job_id, input, orientation::ANALYSIS_HEIGHT).await?;
+            let found = tokio::task::spawn_blocking(move || orientation::detect(&image)).await?;
+            match &found {
+                Some(found) => info!("[{}] Text of {} needs {} degrees clockwise", job_id, request.input_path, found.rotation),
+                None => info!("[{}] Could not tell the orientation of {}", job_id, request.input_path),
+            }
+            found.filter(|found| found.rotation != 0)
+        } else {
+            None
+        };
+
+        let mut command = sandbox::command("ffmpeg");
+        command.arg("-y").arg("-i").arg(&request.input_path);
+        let filters: Vec<String> =
+            crop.iter().map(|crop| crop.filter()).chain(orientation.iter().filter_map(Orientation::filter).map(str::to_string)).collect();
+        if !filters.is_empty() {
+            command.arg("-vf").arg(filters.join(","));
+        }
+        if still {
+            command.arg("-frames:v").arg("1");
+        } else {
+            command.arg("-c:a").arg("copy");
         }
+        command.arg(&request.output_path);
+
+        let duration = probe::duration(&info).unwrap_or(0.0);
+        self.run_ffmpeg(job_id, command, duration, "Autotrim", None).await?;
+        Ok((crop, orientation))
     }
 
-    /// Transcode input video to multiple qualities in parallel (for adaptive streaming)
+    /// Blur an image, or with a mask only its background (or only the masked subject)
+    pub async fn blur(&self, request: &BlurRequest) -> Result<BlurResponse> {
+        request.validate()?;
+        let job_id = queue::job_id();
+        info!("Starting blur job: {}", job_id);
+
+        for path in std::iter::once(&request.input_path).chain(&request.mask_path) {
+            if !std::path::Path::new(path).exists() {
+                return Err(ServiceError::FileNotFound(format!("Input file not found: {}", path)).into());
+            }
+            self.check_input(Some(&job_id), path).await?;
+        }
+
+        self.start_job(&job_id, "image.blur", &request.input_path, &request.output_path)?;
+        let inputs: Vec<&str> = std::iter::once(&request.input_path).chain(&request.mask_path).map(String::as_str).collect();
+        let params = serde_json::json!({
+            "sigma": request.sigma,
+            "invert_mask": request.invert_mask,
+            "channels": request.channels,
+        });
+        let result = self
+            .cache
+            .run(&job_id, &inputs, "image.blur", params, &request.output_path, self.run_blur(&job_id, request))
+            .await;
+        let key = MetricKey::new("image.blur", None, None, file_size(&request.input_path));
+        self.finish_cached_job(&job_id, &result, key);
+        result?;
+
+        Ok(BlurResponse { job_id, output_path: request.output_path.clone() })
+    }
+
+    async fn run_blur(&self, job_id: &str, request: &BlurRequest) -> Result<()> {
+        let input = std::path::Path::new(&request.input_path);
+        let _memory = memory::reserve_image(Some(job_id), input).await?.0;
+
+        let coverage = match (&request.mask_path, request.invert_mask.unwrap_or(false)) {
+            (None, _) => Coverage::Full,
+            (Some(_), false) => Coverage::OutsideMask,
+            (Some(_), true) => Coverage::InsideMask,
+        };
+        let planes = request.channels.as_deref().and_then(blur::planes);
+        let graph = blur::filter_graph(request.sigma.unwrap_or(blur::DEFAULT_SIGMA), planes, coverage);
+
+        let mut command = sandbox::command("ffmpeg");
+        command.arg("-y").arg("-i").arg(&request.input_path);
+        if let Some(mask_path) = &request.mask_path {
+            command.arg("-i").arg(mask_path);
+        }
+        command
+            .arg("-filter_complex").arg(graph)
+            .arg("-map").arg("[out]")
+            .arg("-frames:v").arg("1")
+            .arg(&request.output_path);
+        self.run_ffmpeg(job_id, command, 0.0, "Blur", None).await
+    }
+
+    /// Render a looping, transparent-background animation from a video or a frame sequence
+    pub async fn sticker(&self, request: &StickerRequest) -> Result<StickerResponse> {
+        request.validate()?;
+        let job_id = queue::job_id();
+        info!("Starting sticker job: {}", job_id);
+
+        let inputs: Vec<&String> = request.input_path.iter().chain(request.frames.iter().flatten()).collect();
+        for path in &inputs {
+            if !std::path::Path::new(path).exists() {
+                return Err(ServiceError::FileNotFound(format!("Input file not found: {}", path)).into());
+            }
+            self.check_input(Some(&job_id), path).await?;
+        }
+        let job_input = request.input_path.clone().unwrap_or_else(|| inputs[0].clone());
+
+        self.start_job(&job_id, "image.sticker", &job_input, &request.output_path)?;
+        let result = self.run_sticker(&job_id, request).await;
+        let key = MetricKey::new("image.sticker", None, None, file_size(&job_input));
+        self.finish_job(&job_id, &result, key);
+        result?;
+
+        Ok(StickerResponse { job_id, output_path: request.output_path.clone() })
+    }
+
+    async fn run_sticker(&self, job_id: &str, request: &StickerRequest) -> Result<()> {
+        let format = StickerFormat::from_path(&request.output_path)
+            .ok_or_else(|| ServiceError::InvalidFormat(format!("{} is not a sticker format", request.output_path)))?;
+        let output = std::path::Path::new(&request.output_path);
+        let mut command = sandbox::command("ffmpeg");
+        command.arg("-y");
+
+        // The concat script is kept until FFmpeg has read it
+        let (fps, duration, _memory, _script) = match (&request.input_path, request.frames.as_deref()) {
+            (Some(input_path), _) => {
+                let input = std::path::Path::new(input_path);
+                let memory = memory::reserve_video(Some(job_id), input, 1).await?;
+                let info = probe::probe(Some(job_id), input).await?;
+                command.arg("-i").arg(input_path);
+                (request.fps, probe::duration(&info).unwrap_or(0.0), memory, None)
+            }
+            (None, frames) => {
+                let frames = frames.unwrap_or_default();
+                let first = frames.first().ok_or_else(|| ServiceError::BadRequest("no frames given".to_string()))?;
+                let memory = memory::reserve_image(Some(job_id), std::path::Path::new(first)).await?.0;
+                let fps = request.fps.unwrap_or(sticker::DEFAULT_FPS);
+                let entries: Vec<(String, f64)> = frames.iter().map(|frame| (frame.clone(), 1.0 / f64::from(fps))).collect();
+                let script = sequence::write_script(output, &entries)?;
+                command.arg("-f").arg("concat").arg("-safe").arg("0").arg("-i").arg(script.path());
+                (Some(fps), frames.len() as f64 / f64::from(fps), memory, Some(script))
+            }
+        };
+
+        let key = request
+            .key_color
+            .as_deref()
+            .map(|color| (color, request.key_tolerance.unwrap_or(sticker::DEFAULT_TOLERANCE)));
+        let max_dimension = request.max_dimension.unwrap_or(sticker::DEFAULT_MAX_DIMENSION);
+        command
+            .arg("-vf").arg(sticker::filter(fps, key, max_dimension, format))
+            .args(format.encoder_args())
+            .arg(output);
+        self.run_ffmpeg(job_id, command, duration, "Sticker", None).await
+    }
+
+    /// Hide `watermark_id` in an image's luma so leaked copies can be traced back
+    pub async fn embed_watermark(&self, request: &WatermarkEmbedRequest) -> Result<WatermarkEmbedResponse> {
+        request.validate()?;
+        let job_id = queue::job_id();
+        info!("Starting invisible watermark job: {}", job_id);
+
+        if !std::path::Path::new(&request.input_path).exists() {
+            return Err(ServiceError::FileNotFound(format!("Input file not found: {}", request.input_path)).into());
+        }
+        self.check_input(Some(&job_id), &request.input_path).await?;
+
+        self.start_job(&job_id, "image.watermark", &request.input_path, &request.output_path)?;
+        let params = serde_json::json!({ "watermark_id": request.watermark_id, "strength": request.strength });
+        let job = self.run_embed_watermark(&job_id, request);
+        let result = self
+            .cache
+            .run(&job_id, &[&request.input_path], "image.watermark", params, &request.output_path, job)
+            .await;
+        let key = MetricKey::new("image.watermark", None, None, file_size(&request.input_path));
+        self.finish_cached_job(&job_id, &result, key);
+        result?;
+
+        Ok(WatermarkEmbedResponse {
+            job_id,
+            output_path: request.output_path.clone(),
+            watermark_id: request.watermark_id,
+        })
+    }
+
+    async fn run_embed_watermark(&self, job_id: &str, request: &WatermarkEmbedRequest) -> Result<()> {
+        let input = std::path::Path::new(&request.input_path);
+        let _memory = memory::reserve_image(Some(job_id), input).await?.0;
+        let mut image = watermark::decode(job_id, input).await?;
+        if !watermark::fits(&image) {
+            return Err(ServiceError::InvalidFormat(format!(
+                "{}x{} is too small to carry a watermark ({} 8x8 blocks needed)",
+                image.width,
+                image.height,
+                watermark::MIN_BLOCKS
+            ))
+            .into());
+        }
+        let (watermark_id, strength) = (request.watermark_id, request.strength.unwrap_or(watermark::DEFAULT_STRENGTH));
+        let image = tokio::task::spawn_blocking(move || {
+            watermark::embed(&mut image, watermark_id, f64::from(strength));
+            image
+        })
+        .await?;
+        watermark::encode(job_id, &image, std::path::Path::new(&request.output_path)).await
+    }
+
+    /// Look for a watermark written by `embed_watermark`
+    pub async fn detect_watermark(&self, request: &WatermarkDetectRequest) -> Result<WatermarkDetectResponse> {
+        request.validate()?;
+        let job_id = queue::job_id();
+        info!("Starting watermark detection job: {}", job_id);
+
+        let input = std::path::Path::new(&request.input_path);
+        if !input.exists() {
+            return Err(ServiceError::FileNotFound(format!("Input file not found: {}", request.input_path)).into());
+        }
+        self.check_input(Some(&job_id), &request.input_path).await?;
+
+        self.start_job(&job_id, "image.watermark_detect", &request.input_path, "")?;
+        let result = async {
+            let _memory = memory::reserve_image(Some(&job_id), input).await?.0;
+            let image = watermark::decode(&job_id, input).await?;
+            Ok(tokio::task::spawn_blocking(move || watermark::detect(&image)).await?)
+        }
+        .await;
+        let key = MetricKey::new("image.watermark_detect", None, None, file_size(&request.input_path));
+        self.finish_job(&job_id, &result, key);
+        let detection = result?;
+        info!(
+            "[{}] Watermark {:?} in {} (confidence {:.2})",
+            job_id, detection.watermark_id, request.input_path, detection.confidence
+        );
+
+        Ok(WatermarkDetectResponse { job_id, detected: detection.watermark_id.is_some(), detection })
+    }
+
+    /// Order a set of shots into a panorama by how their edges overlap, and rate each seam
+    pub async fn analyze_panorama(&self, request: &PanoramaAnalyzeRequest) -> Result<PanoramaAnalysisResponse> {
+        request.validate()?;
+        let job_id = queue::job_id();
+        info!("Starting panorama analysis job: {}", job_id);
+
+        for path in &request.input_paths {
+            if !std::path::Path::new(path).exists() {
+                return Err(ServiceError::FileNotFound(format!("Input file not found: {}", path)).into());
+            }
+            self.check_input(Some(&job_id), path).await?;
+        }
+        let job_input = &request.input_paths[0];
+
+        self.start_job(&job_id, "image.panorama_analyze", job_input, "")?;
+        let result = async {
+            let mut maps = Vec::with_capacity(request.input_paths.len());
+            for path in &request.input_paths {
+                let input = std::path::Path::new(path);
+                // FFmpeg decodes each at full size before scaling it down
+                let _memory = memory::reserve_image(Some(&job_id), input).await?.0;
+                maps.push(EdgeMap::new(&watermark::thumbnail(&job_id, input, panorama::ANALYSIS_HEIGHT).await?));
+            }
+            let min_score = request.min_score.unwrap_or(panorama::DEFAULT_MIN_SCORE);
+            Ok(tokio::task::spawn_blocking(move || panorama::analyze(&maps, min_score)).await?)
+        }
+        .await;
+        let key = MetricKey::new("image.panorama_analyze", None, None, file_size(job_input));
+        self.finish_job(&job_id, &result, key);
+        let analysis = result?;
+        info!(
+            "[{}] Panorama of {} images is {}stitchable",
+            job_id,
+            request.input_paths.len(),
+            if analysis.stitchable { "" } else { "not " }
+        );
+
+        let path = |index: usize| request.input_paths[index].clone();
+        Ok(PanoramaAnalysisResponse {
+            job_id,
+            stitchable: analysis.stitchable,
+            order: analysis.order.iter().map(|&index| path(index)).collect(),
+            links: analysis
+                .links
+                .iter()
+                .map(|link| PanoramaLink { from: path(link.from), to: path(link.to), overlap: link.overlap })
+                .collect(),
+        })
+    }
+
+    /// Enlarge an image, removing the JPEG block edges the upscale would otherwise magnify first
+    pub async fn upscale(&self, request: &UpscaleRequest) -> Result<UpscaleResponse> {
+        request.validate()?;
+        let job_id = queue::job_id();
+        info!("Starting upscale job: {}", job_id);
+
+        if !std::path::Path::new(&request.input_path).exists() {
+            return Err(ServiceError::FileNotFound(format!("Input file not found: {}", request.input_path)).into());
+        }
+        self.check_input(Some(&job_id), &request.input_path).await?;
+
+        self.start_job(&job_id, "image.upscale", &request.input_path, &request.output_path)?;
+        let result = self.run_upscale(&job_id, request).await;
+        let key = MetricKey::new("image.upscale", None, None, file_size(&request.input_path));
+        self.finish_job(&job_id, &result, key);
+        result
+    }
+
+    async fn run_upscale(&self, job_id: &str, request: &UpscaleRequest) -> Result<UpscaleResponse> {
+        let input = std::path::Path::new(&request.input_path);
+        let scale = request.scale.unwrap_or(upscale::DEFAULT_SCALE);
+        let (width, height) = self.video_dimensions(job_id, &request.input_path).await?;
+        let (width, height) = (width * scale, height * scale);
+        if u64::from(width) * u64::from(height) > self.limits.max_pixels {
+            return Err(ServiceError::BadRequest(format!(
+                "{}x{} output exceeds the {} pixel limit",
+                width, height, self.limits.max_pixels
+            ))
+            .into());
+        }
+        let _memory = memory::reserve_image(Some(job_id), input).await?.0;
+
+        // Measured at full size, the only one the block grid shows at
+        let mode = request.deblock.unwrap_or_default();
+        let blockiness = if mode == Deblock::Never {
+            None
+        } else {
+            let image = watermark::decode(job_id, input).await?;
+            tokio::task::spawn_blocking(move || upscale::blockiness(&image)).await?
+        };
+        let deblock = upscale::deblock_filter(mode, blockiness);
+        if let Some(blockiness) = blockiness {
+            info!(
+                "[{}] Blockiness of {} is {:.2}, {}",
+                job_id,
+                request.input_path,
+                blockiness,
+                if deblock.is_some() { "deblocking" } else { "leaving it as is" }
+            );
+        }
+
+        let mut command = sandbox::command("ffmpeg");
+        command
+            .arg("-y")
+            .arg("-i").arg(&request.input_path)
+            .arg("-vf").arg(upscale::filter(scale, deblock))
+            .arg("-frames:v").arg("1")
+            .arg(&request.output_path);
+        self.run_ffmpeg(job_id, command, 0.0, "Upscale", None).await?;
+
+        Ok(UpscaleResponse {
+            job_id: job_id.to_string(),
+            output_path: request.output_path.clone(),
+            width,
+            height,
+            blockiness,
+            deblocked: deblock.is_some(),
+        })
+    }
+
+    /// Transcode input video to every rendition of `rungs` in parallel (for adaptive streaming),
+    /// each with the request's codec, hardware acceleration and rate control unless it names its own codec
     pub async fn transcode_multi_quality(
         &self,
-        input_path: &str,
+        job_id: &str,
+        request: &VideoTranscodeRequest,
         output_prefix: &str,
-        codec: &str,
-        format: &str,
+        rungs: &[Rung],
     ) -> Result<Vec<String>> {
         use tokio::task;
+        let input_path = request.input_path.as_str();
+        let rate_control = request.rate_control.as_ref();
+        let tone_map = self.tone_map_filter(job_id, request).await?;
+        // All renditions encode at once
+        let _memory = memory::reserve_video(Some(job_id), std::path::Path::new(input_path), rungs.len() as u64).await?;
+        // Holds each rendition's first-pass statistics until its second pass is done
+        let work_dir = match rate_control.filter(|rate_control| rate_control.is_two_pass()) {
+            Some(_) => Some(self.temp.job_dir(job_id)?),
+            None => None,
+        };
         let mut handles = vec![];
-        for profile in QUALITY_PROFILES {
+        for rung in rungs {
             let input = input_path.to_string();
-            let output = format!("{output_prefix}_{}.mp4", profile.label);
-            let codec = codec.to_string();
-            let format = format.to_string();
-            let res = profile.resolution.to_string();
-            let bitrate = profile.bitrate.to_string();
+            let output = format!("{output_prefix}_{}.mp4", rung.label);
+            let codec = rung.codec.as_deref().or(request.codec.as_deref()).unwrap_or("libx264").to_string();
+            let res = rung.resolution.clone();
+            let bitrate = rung.bitrate.clone();
+            let job_id = job_id.to_string();
+            let hardware = request.hardware_acceleration.and_then(|accelerator| hwaccel::select(accelerator, Some(&codec)));
+            let rate_control = rate_control.map(|rate_control| rate_control.at(&bitrate));
+            let log_prefix = work_dir.as_ref().map(|dir| dir.path().join(format!("{}-2pass", rung.label)));
+            let tone_map = tone_map.clone();
 
             handles.push(task::spawn(async move {
-                let mut cmd = Command::new("ffmpeg");
-                cmd.arg("-y")
-                    .arg("-i").arg(&input)
-                    .arg("-s").arg(&res)
-                    .arg("-b:v").arg(&bitrate)
-                    .arg("-c:v").arg(&codec)
-                    .arg(&output);
-                let status = cmd.status().expect("failed to run ffmpeg");
-                if status.success() {
-                    Ok(output)
-                } else {
-                    Err(format!("Transcode failed for {}", output))
+                let command = |hardware: Option<&HardwareEncoder>, pass: Option<u8>| {
+                    let mut cmd = sandbox::command("ffmpeg");
+                    cmd.arg("-y");
+                    if let Some(hardware) = hardware {
+                        cmd.args(hardware.device_args());
+                    }
+                    cmd.arg("-i").arg(&input);
+                    // `-s` scales after the chain; a device upload scales itself
+                    let upload = hardware.and_then(|hardware| hardware.upload_filter(Some(&res)));
+                    let chain: Vec<&str> = tone_map.as_deref().into_iter().chain(upload.as_deref()).collect();
+                    if !chain.is_empty() {
+                        cmd.arg("-vf").arg(chain.join(","));
+                    }
+                    if upload.is_none() {
+                        cmd.arg("-s").arg(&res);
+                    }
+                    let codec = hardware.map_or(codec.as_str(), |hardware| hardware.name);
+                    // CRF renditions are capped at the profile's bit rate
+                    match &rate_control {
+                        Some(rate_control) => cmd.args(rate_control.args(Some(codec), Some(&bitrate))),
+                        None => cmd.arg("-b:v").arg(&bitrate),
+                    };
+                    cmd.arg("-c:v").arg(codec);
+                    if let (Some(pass), Some(log_prefix)) = (pass, &log_prefix) {
+                        cmd.args(ratecontrol::pass_args(Some(codec), pass, log_prefix));
+                    }
+                    if pass == Some(1) {
+                        cmd.arg("-an").arg("-f").arg("null").arg("-");
+                    } else {
+                        cmd.arg(&output);
+                    }
+                    cmd
+                };
+                let passes: &[Option<u8>] = if log_prefix.is_some() { &[Some(1), Some(2)] } else { &[None] };
+                for &pass in passes {
+                    let mut run = audit::output_async(Some(&job_id), command(hardware.as_ref(), pass)).await.expect("failed to run ffmpeg");
+                    if let Some(hardware) = hardware.as_ref().filter(|_| !run.status.success()) {
+                        warn!("[{}] {} failed for {}, encoding on the CPU instead", job_id, hardware.name, output);
+                        run = audit::output_async(Some(&job_id), command(None, pass)).await.expect("failed to run ffmpeg");
+                    }
+                    if !run.status.success() {
+                        return Err(format!("Transcode failed for {}", output));
+                    }
                 }
+                Ok(output)
             }));
         }
         let mut results = vec![];
@@ -551,65 +2452,162 @@ impl VideoProcessor {
         Ok(results)
     }
 
-    /// Package multiple quality files into HLS segments and master playlist
+    /// Package multiple quality files into HLS segments concurrently, then write a master
+    /// playlist describing each rendition from its probed bit rate, resolution and codecs.
+    /// With `separate_audio`, the audio streams of `input_path` become audio renditions instead
     pub async fn package_hls(
         &self,
+        job_id: &str,
+        input_path: &str,
         outputs: &[String],
         output_dir: &str,
         master_playlist: &str,
+        options: &hls::HlsOptions,
     ) -> Result<()> {
-        use std::fs::File;
-        use std::io::Write;
-        let mut variant_playlists = vec![];
-        let mut master_content = String::new();
-        master_content.push_str("#EXTM3U\n");
-
+        let separate_audio = options.separate_audio();
+        let audio_tracks = if separate_audio {
+            hls::audio_tracks(&*probe::probe(Some(job_id), std::path::Path::new(input_path)).await?)
+        } else {
+            Vec::new()
+        };
+        let mut handles = vec![];
         for output in outputs {
             // Tạo tên playlist cho từng chất lượng
             let label = output
-                .split('_')
-                .last()
+                .rsplit('_')
+                .next()
                 .unwrap_or("unknown").replace(".mp4", "");
             let playlist = format!("{}/{}.m3u8", output_dir, label);
-            let segment_pattern = format!("{}/{}_segment_%03d.ts", output_dir, label);
+            let hls_args = options.args(output_dir, &label);
+            let output = output.clone();
+            let job_id = job_id.to_string();
 
-            // Đóng gói từng file thành HLS
-            let status = Command::new("ffmpeg")
-                .arg("-y")
-                .arg("-i").arg(output)
-                .arg("-c:v").arg("copy")
-                .arg("-c:a").arg("aac")
-                .arg("-f").arg("hls")
-                .arg("-hls_time").arg("4")
-                .arg("-hls_playlist_type").arg("vod")
-                .arg("-hls_segment_filename").arg(&segment_pattern)
-                .arg(&playlist)
-                .status()?;
-            if !status.success() {
-                return Err(anyhow::anyhow!("Failed to package HLS for {}", output));
-            }
-            // Thêm vào master playlist
-            master_content.push_str(&format!(
-                "#EXT-X-STREAM-INF:BANDWIDTH={},RESOLUTION={}\n{}\n",
-                match label.as_str() {
-                    "1080p" => 5000000,
-                    "720p" => 2500000,
-                    "480p" => 1000000,
-                    _ => 500000,
-                },
-                match label.as_str() {
-                    "1080p" => "1920x1080",
-                    "720p" => "1280x720",
-                    "480p" => "854x480",
-                    _ => "640x360",
-                },
-                format!("{}.m3u8", label)
-            ));
-            variant_playlists.push(playlist);
+            handles.push(tokio::spawn(async move {
+                // Đóng gói từng file thành HLS
+                let mut command = sandbox::command("ffmpeg");
+                command
+                    .arg("-y")
+                    .arg("-i").arg(&output)
+                    .arg("-c:v").arg("copy");
+                if separate_audio {
+                    command.arg("-an");
+                } else {
+                    command.arg("-c:a").arg("aac");
+                }
+                command
+                    .args(&hls_args)
+                    .arg(&playlist);
+                let run = audit::output_async(Some(&job_id), command).await?;
+                if !run.status.success() {
+                    return Err(anyhow::anyhow!("Failed to package HLS for {}", output));
+                }
+                let mut info = probe::probe(None, std::path::Path::new(&output)).await?;
+                if separate_audio {
+                    info = std::sync::Arc::new(hls::without_audio(&info));
+                }
+                let rendition = hls::Rendition::from_probe(format!("{}.m3u8", label), &info, file_size(&output))?;
+                Ok(rendition.with_segments(std::path::Path::new(&playlist)))
+            }));
+        }
+
+        // One rendition per audio track and group bit rate, encoded straight from the input
+        let mut audio_handles = vec![];
+        for bitrate in options.audio_bitrates().iter().filter(|_| !audio_tracks.is_empty()) {
+            for (n, track) in audio_tracks.iter().enumerate() {
+                let label = format!("audio_{}_{}", n, bitrate.to_lowercase());
+                let playlist = format!("{}/{}.m3u8", output_dir, label);
+                let hls_args = options.args(output_dir, &label);
+                let (input, track, bitrate, job_id) = (input_path.to_string(), track.clone(), bitrate.clone(), job_id.to_string());
+
+                audio_handles.push(tokio::spawn(async move {
+                    let mut command = sandbox::command("ffmpeg");
+                    command
+                        .arg("-y")
+                        .arg("-i").arg(&input)
+                        .arg("-map").arg(format!("0:{}", track.index))
+                        .arg("-vn")
+                        .arg("-c:a").arg("aac")
+                        .arg("-b:a").arg(&bitrate)
+                        .args(&hls_args)
+                        .arg(&playlist);
+                    let run = audit::output_async(Some(&job_id), command).await?;
+                    if !run.status.success() {
+                        return Err(anyhow::anyhow!("Failed to package audio track {} at {}", track.index, bitrate));
+                    }
+                    let group_id = hls::AudioRendition::group_id(&bitrate);
+                    Ok(hls::AudioRendition::new(group_id, &track, n == 0, std::path::Path::new(&playlist), &bitrate))
+                }));
+            }
+        }
+
+        // Thêm vào master playlist
+        let mut renditions = vec![];
+        for handle in handles {
+            match handle.await {
+                Ok(rendition) => renditions.push(rendition?),
+                Err(e) => return Err(anyhow::anyhow!("Task join error: {e}")),
+            }
+        }
+        let mut audio = vec![];
+        for handle in audio_handles {
+            match handle.await {
+                Ok(rendition) => audio.push(rendition?),
+                Err(e) => return Err(anyhow::anyhow!("Task join error: {e}")),
+            }
+        }
+        if !audio.is_empty() {
+            renditions = hls::audio_variants(&renditions, &audio);
         }
         // Ghi master playlist
-        let mut file = File::create(format!("{}/{}", output_dir, master_playlist))?;
-        file.write_all(master_content.as_bytes())?;
+        let playlist = hls::master_playlist(&renditions, &audio, options.independent());
+        std::fs::write(format!("{}/{}", output_dir, master_playlist), playlist)?;
         Ok(())
     }
-} 
\ No newline at end of file
+}
+
+fn file_size(path: &str) -> Option<u64> {
+    std::fs::metadata(path).ok().map(|metadata| metadata.len())
+}
+
+/// Records an FFmpeg run on its job when dropped, so every way out of `run_ffmpeg_once` leaves
+/// an audit entry; `status` stays `None` when FFmpeg did not run to an exit status
+struct CommandRecorder<'a> {
+    jobs: &'a JobStore,
+    job_id: &'a str,
+    timer: Option<audit::AuditTimer>,
+    status: Option<ExitStatus>,
+    stderr: String,
+}
+
+impl Drop for CommandRecorder<'_> {
+    fn drop(&mut self) {
+        if let Some(timer) = self.timer.take() {
+            self.jobs.record_command(self.job_id, timer.finish(self.status.as_ref(), &self.stderr));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_stderr_lines_split_on_carriage_returns() {
+        let stderr: &[u8] = b"Input #0, mov,mp4\n\
+            frame=   24 fps=0.0 q=28.0 size=     256kB time=00:00:01.00 bitrate=2097.2kbits/s speed=1.98x    \r\
+            frame=   48 fps= 47 q=28.0 size=     512kB time=00:00:02.00 bitrate=2097.2kbits/s speed=1.95x    \r\
+            Error while decoding stream #0:0: Invalid data found when processing input\r\n\
+            Conversion failed!";
+        let mut reader = BufReader::new(stderr);
+        let mut lines = Vec::new();
+        while let Some(line) = VideoProcessor::next_stderr_line(&mut reader).await.unwrap() {
+            lines.push(line);
+        }
+        assert_eq!(lines.len(), 5);
+        assert!(lines[1].contains("time=00:00:01.00") && !lines[1].contains("time=00:00:02.00"));
+        assert_eq!(VideoProcessor::progress_field(&lines[2], "speed"), Some("1.95x"));
+        // FFmpeg's error stays a line of its own, out of the status lines the stderr tail skips
+        assert!(!lines[3].contains("time=") && lines[3].starts_with("Error while decoding"));
+        assert_eq!(lines[4], "Conversion failed!");
+    }
+}
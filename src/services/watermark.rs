@@ -0,0 +1,343 @@
+use anyhow::Result;
+use serde::Serialize;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+use crate::utils::error::{FfmpegErrorKind, FfmpegFailure};
+use crate::utils::{audit, sandbox};
+
+/// Gap enforced between the compared DCT coefficients of every block, on the 0-255 luma scale
+pub const DEFAULT_STRENGTH: u32 = 20;
+
+/// Marks a payload as ours; an unmarked image decodes to it by chance once in 65536
+const MAGIC: u16 = 0xB17E;
+/// `MAGIC` followed by the 32-bit watermark id, repeated across the image block by block
+const PAYLOAD_BITS: usize = 48;
+const BLOCK: usize = 8;
+
+/// Mid-frequency pair compared in each block: coarse enough to outlive JPEG quantization at
+/// ordinary qualities, fine enough not to show as a pattern
+const FIRST: (usize, usize) = (2, 3);
+const SECOND: (usize, usize) = (3, 2);
+
+type Block = [[f64; BLOCK]; BLOCK];
+
+/// Smallest image that still carries every payload bit once
+pub const MIN_BLOCKS: usize = PAYLOAD_BITS;
+
+/// A decoded 8-bit RGB frame
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rgb {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u8>,
+}
+
+impl Rgb {
+    /// Parse binary PPM (`P6`, maxval 255) as FFmpeg's `ppm` encoder writes it
+    pub fn from_ppm(data: &[u8]) -> Option<Self> {
+        let mut fields = Vec::with_capacity(4);
+        let mut pos = 0;
+        while fields.len() < 4 {
+            while data.get(pos).is_some_and(u8::is_ascii_whitespace) {
+                pos += 1;
+            }
+            let start = pos;
+            while data.get(pos).is_some_and(|byte| !byte.is_ascii_whitespace()) {
+                pos += 1;
+            }
+            if start == pos {
+                return None;
+            }
+            fields.push(std::str::from_utf8(&data[start..pos]).ok()?);
+        }
+        // A single whitespace byte separates the header from the pixels
+        pos += 1;
+        if fields[0] != "P6" || fields[3] != "255" {
+            return None;
+        }
+        let width: usize = fields[1].parse().ok()?;
+        let height: usize = fields[2].parse().ok()?;
+        // Header sizes are untrusted; one that overflows can't be backed by the data anyway
+        let len = width.checked_mul(height)?.checked_mul(3)?;
+        let pixels = data.get(pos..pos.checked_add(len)?)?.to_vec();
+        Some(Self { width, height, pixels })
+    }
+
+    pub fn to_ppm(&self) -> Vec<u8> {
+        let mut data = format!("P6\n{} {}\n255\n", self.width, self.height).into_bytes();
+        data.extend_from_slice(&self.pixels);
+        data
+    }
+
+    /// Whole 8x8 blocks across and down; a partial strip at the right and bottom is left alone
+    fn blocks(&self) -> (usize, usize) {
+        (self.width / BLOCK, self.height / BLOCK)
+    }
+
+    fn luma_block(&self, bx: usize, by: usize) -> Block {
+        let mut block = [[0.0; BLOCK]; BLOCK];
+        for (y, row) in block.iter_mut().enumerate() {
+            for (x, value) in row.iter_mut().enumerate() {
+                let offset = ((by * BLOCK + y) * self.width + bx * BLOCK + x) * 3;
+                let [r, g, b] = [0, 1, 2].map(|channel| f64::from(self.pixels[offset + channel]));
+                *value = 0.299 * r + 0.587 * g + 0.114 * b;
+            }
+        }
+        block
+    }
+
+    /// Shift R, G and B alike, which moves luma by the same amount and leaves the hue alone
+    fn shift_block(&mut self, bx: usize, by: usize, delta: &Block) {
+        for (y, row) in delta.iter().enumerate() {
+            for (x, &delta) in row.iter().enumerate() {
+                let offset = ((by * BLOCK + y) * self.width + bx * BLOCK + x) * 3;
+                for pixel in &mut self.pixels[offset..offset + 3] {
+                    *pixel = (f64::from(*pixel) + delta).round().clamp(0.0, 255.0) as u8;
+                }
+            }
+        }
+    }
+}
+
+/// What detection found in an image
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Detection {
+    /// `None` when the payload's marker didn't decode, i.e. no watermark or one damaged past repair
+    pub watermark_id: Option<u32>,
+    /// Average share of blocks agreeing with each decoded bit: 0.5 is noise, 1.0 an untouched mark
+    pub confidence: f64,
+}
+
+/// Orthonormal DCT-II basis, `basis[u][x]`
+fn basis() -> Block {
+    let mut basis = [[0.0; BLOCK]; BLOCK];
+    for (u, row) in basis.iter_mut().enumerate() {
+        let scale = if u == 0 { (1.0 / BLOCK as f64).sqrt() } else { (2.0 / BLOCK as f64).sqrt() };
+        for (x, value) in row.iter_mut().enumerate() {
+            *value = scale * (((2 * x + 1) * u) as f64 * std::f64::consts::PI / (2 * BLOCK) as f64).cos();
+        }
+    }
+    basis
+}
+
+/// `basis · block · basisᵀ`
+fn forward(basis: &Block, block: &Block) -> Block {
+    let mut rows = [[0.0; BLOCK]; BLOCK];
+    let mut coeffs = [[0.0; BLOCK]; BLOCK];
+    for u in 0..BLOCK {
+        for x in 0..BLOCK {
+            rows[u][x] = (0..BLOCK).map(|y| basis[u][y] * block[y][x]).sum();
+        }
+    }
+    for u in 0..BLOCK {
+        for v in 0..BLOCK {
+            coeffs[u][v] = (0..BLOCK).map(|x| rows[u][x] * basis[v][x]).sum();
+        }
+    }
+    coeffs
+}
+
+/// `basisᵀ · coeffs · basis`
+fn inverse(basis: &Block, coeffs: &Block) -> Block {
+    let mut rows = [[0.0; BLOCK]; BLOCK];
+    let mut block = [[0.0; BLOCK]; BLOCK];
+    for y in 0..BLOCK {
+        for v in 0..BLOCK {
+            rows[y][v] = (0..BLOCK).map(|u| basis[u][y] * coeffs[u][v]).sum();
+        }
+    }
+    for y in 0..BLOCK {
+        for x in 0..BLOCK {
+            block[y][x] = (0..BLOCK).map(|v| rows[y][v] * basis[v][x]).sum();
+        }
+    }
+    block
+}
+
+fn payload(watermark_id: u32) -> [bool; PAYLOAD_BITS] {
+    let value = (u64::from(MAGIC) << 32) | u64::from(watermark_id);
+    std::array::from_fn(|bit| (value >> (PAYLOAD_BITS - 1 - bit)) & 1 == 1)
+}
+
+/// Whether `image` has room for the full payload
+pub fn fits(image: &Rgb) -> bool {
+    let (columns, rows) = image.blocks();
+    columns * rows >= MIN_BLOCKS
+}
+
+/// Write `watermark_id` into the luma of every whole block: a 1 bit makes the first coefficient
+/// of the pair exceed the second by `strength`, a 0 bit the reverse. Blocks that already agree
+/// are left untouched
+pub fn embed(image: &mut Rgb, watermark_id: u32, strength: f64) {
+    let bits = payload(watermark_id);
+    let basis = basis();
+    let (columns, rows) = image.blocks();
+    for by in 0..rows {
+        for bx in 0..columns {
+            let bit = bits[(by * columns + bx) % PAYLOAD_BITS];
+            let block = image.luma_block(bx, by);
+            let mut coeffs = forward(&basis, &block);
+            let (first, second) = (coeffs[FIRST.0][FIRST.1], coeffs[SECOND.0][SECOND.1]);
+            let gap = if bit { first - second } else { second - first };
+            if gap >= strength {
+                continue;
+            }
+            let mid = (first + second) / 2.0;
+            let half = if bit { strength / 2.0 } else { -strength / 2.0 };
+            coeffs[FIRST.0][FIRST.1] = mid + half;
+            coeffs[SECOND.0][SECOND.1] = mid - half;
+            let marked = inverse(&basis, &coeffs);
+            let mut delta = [[0.0; BLOCK]; BLOCK];
+            for y in 0..BLOCK {
+                for x in 0..BLOCK {
+                    delta[y][x] = marked[y][x] - block[y][x];
+                }
+            }
+            image.shift_block(bx, by, &delta);
+        }
+    }
+}
+
+/// Majority vote over every block carrying each payload bit
+pub fn detect(image: &Rgb) -> Detection {
+    let basis = basis();
+    let (columns, rows) = image.blocks();
+    let mut ones = [0u32; PAYLOAD_BITS];
+    let mut totals = [0u32; PAYLOAD_BITS];
+    for by in 0..rows {
+        for bx in 0..columns {
+            let bit = (by * columns + bx) % PAYLOAD_BITS;
+            let coeffs = forward(&basis, &image.luma_block(bx, by));
+            if coeffs[FIRST.0][FIRST.1] > coeffs[SECOND.0][SECOND.1] {
+                ones[bit] += 1;
+            }
+            totals[bit] += 1;
+        }
+    }
+    if totals.contains(&0) {
+        return Detection { watermark_id: None, confidence: 0.0 };
+    }
+
+    let mut value = 0u64;
+    let mut agreement = 0.0;
+    for (&ones, &total) in ones.iter().zip(&totals) {
+        value = (value << 1) | u64::from(ones * 2 > total);
+        agreement += f64::from(ones.max(total - ones)) / f64::from(total);
+    }
+    let watermark_id = (value >> 32 == u64::from(MAGIC)).then_some(value as u32);
+    Detection { watermark_id, confidence: agreement / PAYLOAD_BITS as f64 }
+}
+
+/// First frame of `input` as 8-bit RGB
+pub async fn decode(job_id: &str, input: &Path) -> Result<Rgb> {
+    decode_with(job_id, input, None).await
+}
+
+/// First frame of `input` scaled to `height` pixels, keeping its aspect ratio
+pub async fn thumbnail(job_id: &str, input: &Path, height: u32) -> Result<Rgb> {
+    decode_with(job_id, input, Some(format!("scale=-2:{}", height))).await
+}
+
+async fn decode_with(job_id: &str, input: &Path, filter: Option<String>) -> Result<Rgb> {
+    let mut command = sandbox::command("ffmpeg");
+    command
+        .arg("-hide_banner")
+        .arg("-nostdin")
+        .arg("-i").arg(input);
+    if let Some(filter) = filter {
+        command.arg("-vf").arg(filter);
+    }
+    command
+        .arg("-frames:v").arg("1")
+        .arg("-f").arg("image2pipe")
+        .arg("-c:v").arg("ppm")
+        .arg("-pix_fmt").arg("rgb24")
+        .arg("-");
+    let output = audit::output_async(Some(job_id), command).await?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(FfmpegFailure {
+            kind: FfmpegErrorKind::classify(&stderr),
+            message: format!("Decoding failed: {}", stderr.trim()),
+        }
+        .into());
+    }
+    Rgb::from_ppm(&output.stdout).ok_or_else(|| anyhow::anyhow!("FFmpeg returned an unreadable frame for {}", input.display()))
+}
+
+/// Encode `image` to `output` in the format its extension names
+pub async fn encode(job_id: &str, image: &Rgb, output: &Path) -> Result<()> {
+    // Next to the output, which is somewhere FFmpeg may already write
+    let dir = output.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let frame = tempfile::Builder::new().prefix(".watermark-").suffix(".ppm").tempfile_in(dir)?;
+    std::fs::write(frame.path(), image.to_ppm())?;
+    // Sandboxed FFmpeg may run as a different uid than the one that created the file
+    std::fs::set_permissions(frame.path(), std::fs::Permissions::from_mode(0o644))?;
+
+    let mut command = sandbox::command("ffmpeg");
+    command
+        .arg("-y")
+        .arg("-hide_banner")
+        .arg("-nostdin")
+        .arg("-i").arg(frame.path())
+        .arg("-frames:v").arg("1")
+        // FFmpeg's default JPEG quality would quantize the mark away before anyone sees the file
+        .arg("-q:v").arg("2")
+        .arg(output);
+    let run = audit::output_async(Some(job_id), command).await?;
+    if !run.status.success() {
+        let stderr = String::from_utf8_lossy(&run.stderr);
+        return Err(FfmpegFailure {
+            kind: FfmpegErrorKind::classify(&stderr),
+            message: format!("Encoding failed: {}", stderr.trim()),
+        }
+        .into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::test_images::Lcg;
+
+    /// Textured test frame from a fixed linear congruential sequence
+    fn frame(width: usize, height: usize) -> Rgb {
+        let mut rng = Lcg::new(12345);
+        let pixels = (0..width * height * 3)
+            .map(|i| {
+                let gradient = (i / 3 % width * 160 / width) as u32;
+                (40 + gradient + rng.below(48)) as u8
+            })
+            .collect();
+        Rgb { width, height, pixels }
+    }
+
+    #[test]
+    fn test_embed_survives_noise_and_ppm_round_trip() {
+        let original = frame(160, 120);
+        assert_eq!(detect(&original).watermark_id, None);
+
+        let mut marked = original.clone();
+        embed(&mut marked, 0xC0FFEE, f64::from(DEFAULT_STRENGTH));
+        let marked = Rgb::from_ppm(&marked.to_ppm()).unwrap();
+        let detection = detect(&marked);
+        assert_eq!(detection.watermark_id, Some(0xC0FFEE));
+        assert!(detection.confidence > 0.95);
+
+        // Changes small enough not to see, large enough to disturb individual blocks
+        let mut rng = Lcg::new(7);
+        let mut noisy = marked.clone();
+        for pixel in &mut noisy.pixels {
+            *pixel = (i32::from(*pixel) + rng.below(9) as i32 - 4).clamp(0, 255) as u8;
+        }
+        assert_eq!(detect(&noisy).watermark_id, Some(0xC0FFEE));
+
+        assert!(!fits(&frame(48, 48)));
+        assert_eq!(detect(&frame(48, 48)).confidence, 0.0);
+
+        // Truncated pixels and sizes that overflow are rejected, not sliced
+        assert_eq!(Rgb::from_ppm(b"P6\n2 2\n255\n\0\0\0"), None);
+        assert_eq!(Rgb::from_ppm(b"P6 18446744073709551615 2 255\n"), None);
+    }
+}
@@ -0,0 +1,205 @@
+use chrono::{DateTime, Utc};
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::os::unix::process::ExitStatusExt;
+use std::process::{Command, ExitStatus, Output, Stdio};
+use std::sync::{Mutex, OnceLock};
+use std::time::Instant;
+use crate::middleware::request_id;
+use crate::utils::children;
+
+/// Bytes of stderr kept per command; the tail is where FFmpeg explains failures
+const STDERR_AUDIT_BYTES: usize = 4096;
+
+static AUDIT_LOG: OnceLock<Mutex<File>> = OnceLock::new();
+
+/// One spawned FFmpeg/ffprobe invocation, enough to rerun it by hand
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommandAudit {
+    pub job_id: Option<String>,
+    pub request_id: Option<String>,
+    pub command_line: String,
+    pub started_at: DateTime<Utc>,
+    pub duration_ms: u64,
+    pub exit_code: Option<i32>,
+    pub signal: Option<i32>,
+    pub stderr: String,
+}
+
+/// Open `<log_dir>/ffmpeg-audit.log` (JSON lines); until then commands are only kept on job records
+pub fn init(log_dir: &str) -> io::Result<()> {
+    std::fs::create_dir_all(log_dir)?;
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(format!("{}/ffmpeg-audit.log", log_dir))?;
+    let _ = AUDIT_LOG.set(Mutex::new(file));
+    Ok(())
+}
+
+/// Started before spawning, finished with the outcome
+pub struct AuditTimer {
+    job_id: Option<String>,
+    command_line: String,
+    started_at: DateTime<Utc>,
+    started: Instant,
+}
+
+pub fn start(job_id: Option<&str>, command: &Command) -> AuditTimer {
+    AuditTimer {
+        job_id: job_id.map(str::to_string),
+        command_line: command_line(command),
+        started_at: Utc::now(),
+        started: Instant::now(),
+    }
+}
+
+impl AuditTimer {
+    /// `status` is `None` when the process could not be spawned; `stderr` then carries the reason
+    pub fn finish(self, status: Option<&ExitStatus>, stderr: &str) -> CommandAudit {
+        let audit = CommandAudit {
+            job_id: self.job_id,
+            request_id: request_id::current(),
+            command_line: self.command_line,
+            started_at: self.started_at,
+            duration_ms: self.started.elapsed().as_millis() as u64,
+            exit_code: status.and_then(|status| status.code()),
+            signal: status.and_then(|status| status.signal()),
+            stderr: truncate_tail(stderr.trim_end(), STDERR_AUDIT_BYTES).to_string(),
+        };
+        debug!(
+            "FFmpeg audit: {} ({} ms, exit {:?})",
+            audit.command_line, audit.duration_ms, audit.exit_code
+        );
+        write(&audit);
+        audit
+    }
+}
+
+fn write(audit: &CommandAudit) {
+    let Some(log) = AUDIT_LOG.get() else {
+        return;
+    };
+    let line = match serde_json::to_string(audit) {
+        Ok(line) => line,
+        Err(e) => {
+            warn!("Failed to serialize FFmpeg audit record: {}", e);
+            return;
+        }
+    };
+    if let Err(e) = writeln!(log.lock().unwrap(), "{}", line) {
+        warn!("Failed to write FFmpeg audit log: {}", e);
+    }
+}
+
+/// `Command::output` with the invocation recorded in the audit log. The child is registered
+/// under `job_id` while it runs, so cancelling the job kills it. Blocks until the child exits,
+/// so it is only for code already off the async runtime, e.g. inside `web::block`; async
+/// callers use [`output_async`]
+pub fn output(job_id: Option<&str>, command: &mut Command) -> io::Result<Output> {
+    let timer = start(job_id, command);
+    command.stdin(Stdio::null()).stdout(Stdio::piped()).stderr(Stdio::piped());
+    let result = command.spawn().and_then(|child| {
+        let _tracked = children::track(job_id, Some(child.id()));
+        child.wait_with_output()
+    });
+    match result {
+        Ok(output) => {
+            timer.finish(Some(&output.status), &String::from_utf8_lossy(&output.stderr));
+            Ok(output)
+        }
+        Err(e) => {
+            timer.finish(None, &e.to_string());
+            Err(e)
+        }
+    }
+}
+
+/// [`output`] for async callers: the child is awaited on tokio's reactor instead of blocking a worker
+pub async fn output_async(job_id: Option<&str>, command: Command) -> io::Result<Output> {
+    let timer = start(job_id, &command);
+    let mut command = tokio::process::Command::from(command);
+    command.stdin(Stdio::null()).stdout(Stdio::piped()).stderr(Stdio::piped()).kill_on_drop(true);
+    let result = match command.spawn() {
+        Ok(child) => {
+            let _tracked = children::track(job_id, child.id());
+            child.wait_with_output().await
+        }
+        Err(e) => Err(e),
+    };
+    match result {
+        Ok(output) => {
+            timer.finish(Some(&output.status), &String::from_utf8_lossy(&output.stderr));
+            Ok(output)
+        }
+        Err(e) => {
+            timer.finish(None, &e.to_string());
+            Err(e)
+        }
+    }
+}
+
+/// Render the command as a copy-pasteable shell line
+fn command_line(command: &Command) -> String {
+    std::iter::once(command.get_program())
+        .chain(command.get_args())
+        .map(|arg| shell_quote(&arg.to_string_lossy()))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn shell_quote(arg: &str) -> String {
+    let safe = !arg.is_empty()
+        && arg
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./:=,%+@".contains(c));
+    if safe {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', "'\\''"))
+    }
+}
+
+fn truncate_tail(text: &str, max_bytes: usize) -> &str {
+    if text.len() <= max_bytes {
+        return text;
+    }
+    let mut start = text.len() - max_bytes;
+    while !text.is_char_boundary(start) {
+        start += 1;
+    }
+    &text[start..]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_command_line_is_reproducible() {
+        let mut command = Command::new("ffmpeg");
+        command.arg("-i").arg("my clip.mp4").arg("-vf").arg("scale=640:-2").arg("it's.mp4");
+        assert_eq!(
+            command_line(&command),
+            "ffmpeg -i 'my clip.mp4' -vf scale=640:-2 'it'\\''s.mp4'"
+        );
+    }
+
+    #[test]
+    fn test_truncate_keeps_tail_on_char_boundary() {
+        assert_eq!(truncate_tail("abcdef", 3), "def");
+        assert_eq!(truncate_tail("ééé", 3), "é");
+        assert_eq!(truncate_tail("short", 100), "short");
+    }
+
+    #[tokio::test]
+    async fn test_output_async_captures_stderr() {
+        let mut command = Command::new("sh");
+        command.arg("-c").arg("echo done >&2; exit 3");
+        let output = output_async(Some("job-1"), command).await.unwrap();
+        assert_eq!(output.status.code(), Some(3));
+        assert_eq!(String::from_utf8_lossy(&output.stderr).trim(), "done");
+    }
+}
@@ -0,0 +1,66 @@
+use log::warn;
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+
+/// PIDs of the FFmpeg processes each job has running
+static CHILDREN: OnceLock<Mutex<HashMap<String, Vec<u32>>>> = OnceLock::new();
+
+fn children() -> &'static Mutex<HashMap<String, Vec<u32>>> {
+    CHILDREN.get_or_init(Default::default)
+}
+
+/// Registration of one running child; dropping it (once the child has been reaped) forgets the PID
+pub struct Tracked {
+    job_id: String,
+    pid: u32,
+}
+
+impl Drop for Tracked {
+    fn drop(&mut self) {
+        let mut children = children().lock().unwrap();
+        if let Some(pids) = children.get_mut(&self.job_id) {
+            pids.retain(|&pid| pid != self.pid);
+            if pids.is_empty() {
+                children.remove(&self.job_id);
+            }
+        }
+    }
+}
+
+/// Remember `pid` as belonging to `job_id` until the returned guard is dropped
+pub fn track(job_id: Option<&str>, pid: Option<u32>) -> Option<Tracked> {
+    let (job_id, pid) = (job_id?, pid?);
+    children().lock().unwrap().entry(job_id.to_string()).or_default().push(pid);
+    Some(Tracked { job_id: job_id.to_string(), pid })
+}
+
+/// SIGKILL every child still running for `job_id`; returns how many were signalled
+pub fn kill(job_id: &str) -> usize {
+    let pids = children().lock().unwrap().get(job_id).cloned().unwrap_or_default();
+    let mut killed = 0;
+    for pid in pids {
+        // Guards are dropped only after the child is reaped, so the PID can't have been reused
+        if unsafe { libc::kill(pid as libc::pid_t, libc::SIGKILL) } == 0 {
+            killed += 1;
+        } else {
+            warn!("[{}] Could not kill FFmpeg process {}: {}", job_id, pid, std::io::Error::last_os_error());
+        }
+    }
+    killed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_kill_tracked_child() {
+        let mut child = tokio::process::Command::new("sleep").arg("30").spawn().unwrap();
+        let tracked = track(Some("job-kill"), child.id());
+        assert_eq!(kill("job-kill"), 1);
+        let status = child.wait().await.unwrap();
+        assert!(!status.success());
+        drop(tracked);
+        assert_eq!(kill("job-kill"), 0);
+    }
+}
@@ -1,57 +1,319 @@
-use actix_web::{error::ResponseError, HttpResponse};
+use actix_web::{error::ResponseError, http::StatusCode, HttpResponse};
 use derive_more::Display;
+use crate::utils::validation::FieldViolation;
 
 #[allow(dead_code)]
 #[derive(Debug, Display)]
 pub enum ServiceError {
     #[display(fmt = "Internal Server Error")]
     InternalError,
-    
+
     #[display(fmt = "BadRequest: {}", _0)]
     BadRequest(String),
-    
+
     #[display(fmt = "FFmpeg Error: {}", _0)]
     FFmpegError(String),
-    
+
     #[display(fmt = "File Not Found: {}", _0)]
     FileNotFound(String),
-    
+
+    #[display(fmt = "Job Not Found: {}", _0)]
+    JobNotFound(String),
+
+    #[display(fmt = "Job Cancelled: {}", _0)]
+    JobCancelled(String),
+
+    #[display(fmt = "Job Finished: {}", _0)]
+    JobFinished(String),
+
     #[display(fmt = "Invalid Format: {}", _0)]
     InvalidFormat(String),
+
+    #[display(fmt = "Validation Failed: {} violation(s)", "_0.len()")]
+    ValidationError(Vec<FieldViolation>),
+
+    #[display(fmt = "Unsupported Codec: {}", _0)]
+    UnsupportedCodec(String),
+
+    #[display(fmt = "Corrupt Input: {}", _0)]
+    CorruptInput(String),
+
+    #[display(fmt = "Insufficient Storage: {}", _0)]
+    InsufficientStorage(String),
+
+    #[display(fmt = "Input Too Large: {}", _0)]
+    InputTooLarge(String),
+
+    #[display(fmt = "Payload Too Large: {}", _0)]
+    PayloadTooLarge(String),
+
+    #[display(fmt = "Infected Input: {}", _0)]
+    InfectedInput(String),
+
+    #[display(fmt = "Scan Unavailable: {}", _0)]
+    ScanUnavailable(String),
+
+    #[display(fmt = "Quota Exceeded: {}", _0)]
+    QuotaExceeded(String),
+
+    #[display(fmt = "Queue Full: {}", _0)]
+    QueueFull(String),
+
+    #[display(fmt = "Unauthorized: {}", _0)]
+    Unauthorized(String),
+
+    #[display(fmt = "Forbidden: {}", _0)]
+    Forbidden(String),
 }
 
-impl ResponseError for ServiceError {
-    fn error_response(&self) -> HttpResponse {
+impl std::error::Error for ServiceError {}
+
+impl ServiceError {
+    /// Stable machine-readable code; clients should match on this rather than on messages
+    pub fn code(&self) -> &'static str {
         match self {
-            ServiceError::InternalError => {
-                HttpResponse::InternalServerError().json(serde_json::json!({
-                    "error": "Internal Server Error"
-                }))
-            }
-            ServiceError::BadRequest(ref message) => {
-                HttpResponse::BadRequest().json(serde_json::json!({
-                    "error": "Bad Request",
-                    "message": message
-                }))
-            }
-            ServiceError::FFmpegError(ref message) => {
-                HttpResponse::InternalServerError().json(serde_json::json!({
-                    "error": "FFmpeg Processing Error",
-                    "message": message
-                }))
-            }
-            ServiceError::FileNotFound(ref message) => {
-                HttpResponse::NotFound().json(serde_json::json!({
-                    "error": "File Not Found",
-                    "message": message
-                }))
-            }
-            ServiceError::InvalidFormat(ref message) => {
-                HttpResponse::BadRequest().json(serde_json::json!({
-                    "error": "Invalid Format",
-                    "message": message
-                }))
+            ServiceError::InternalError => "internal_error",
+            ServiceError::BadRequest(_) => "bad_request",
+            ServiceError::FFmpegError(_) => "ffmpeg_failed",
+            ServiceError::FileNotFound(_) => "file_not_found",
+            ServiceError::JobNotFound(_) => "job_not_found",
+            ServiceError::JobCancelled(_) => "job_cancelled",
+            ServiceError::JobFinished(_) => "job_finished",
+            ServiceError::InvalidFormat(_) => "invalid_format",
+            ServiceError::ValidationError(_) => "validation_failed",
+            ServiceError::UnsupportedCodec(_) => "codec_unsupported",
+            ServiceError::CorruptInput(_) => "corrupt_input",
+            ServiceError::InsufficientStorage(_) => "insufficient_storage",
+            ServiceError::InputTooLarge(_) => "input_too_large",
+            ServiceError::PayloadTooLarge(_) => "payload_too_large",
+            ServiceError::InfectedInput(_) => "infected_input",
+            ServiceError::ScanUnavailable(_) => "scan_unavailable",
+            ServiceError::QuotaExceeded(_) => "quota_exceeded",
+            ServiceError::QueueFull(_) => "queue_full",
+            ServiceError::Unauthorized(_) => "unauthorized",
+            ServiceError::Forbidden(_) => "forbidden",
+        }
+    }
+
+    pub fn title(&self) -> &'static str {
+        match self {
+            ServiceError::InternalError => "Internal Server Error",
+            ServiceError::BadRequest(_) => "Bad Request",
+            ServiceError::FFmpegError(_) => "FFmpeg Processing Error",
+            ServiceError::FileNotFound(_) => "File Not Found",
+            ServiceError::JobNotFound(_) => "Job Not Found",
+            ServiceError::JobCancelled(_) => "Job Cancelled",
+            ServiceError::JobFinished(_) => "Job Already Finished",
+            ServiceError::InvalidFormat(_) => "Invalid Format",
+            ServiceError::ValidationError(_) => "Validation Failed",
+            ServiceError::UnsupportedCodec(_) => "Unsupported Codec",
+            ServiceError::CorruptInput(_) => "Corrupt Input",
+            ServiceError::InsufficientStorage(_) => "Insufficient Storage",
+            ServiceError::InputTooLarge(_) => "Input Too Large",
+            ServiceError::PayloadTooLarge(_) => "Payload Too Large",
+            ServiceError::InfectedInput(_) => "Infected Input",
+            ServiceError::ScanUnavailable(_) => "Virus Scan Unavailable",
+            ServiceError::QuotaExceeded(_) => "Quota Exceeded",
+            ServiceError::QueueFull(_) => "Queue Full",
+            ServiceError::Unauthorized(_) => "Unauthorized",
+            ServiceError::Forbidden(_) => "Forbidden",
+        }
+    }
+
+    fn detail(&self) -> Option<String> {
+        match self {
+            ServiceError::InternalError => None,
+            ServiceError::ValidationError(violations) => {
+                Some(format!("{} field(s) failed validation", violations.len()))
             }
+            ServiceError::BadRequest(message)
+            | ServiceError::FFmpegError(message)
+            | ServiceError::FileNotFound(message)
+            | ServiceError::JobNotFound(message)
+            | ServiceError::JobCancelled(message)
+            | ServiceError::JobFinished(message)
+            | ServiceError::InvalidFormat(message)
+            | ServiceError::UnsupportedCodec(message)
+            | ServiceError::CorruptInput(message)
+            | ServiceError::InsufficientStorage(message)
+            | ServiceError::InputTooLarge(message)
+            | ServiceError::PayloadTooLarge(message)
+            | ServiceError::InfectedInput(message)
+            | ServiceError::ScanUnavailable(message)
+            | ServiceError::QuotaExceeded(message)
+            | ServiceError::QueueFull(message)
+            | ServiceError::Unauthorized(message)
+            | ServiceError::Forbidden(message) => Some(message.clone()),
         }
     }
-} 
\ No newline at end of file
+}
+
+impl ResponseError for ServiceError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            ServiceError::InternalError | ServiceError::FFmpegError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            ServiceError::BadRequest(_) | ServiceError::InvalidFormat(_) => StatusCode::BAD_REQUEST,
+            ServiceError::FileNotFound(_) | ServiceError::JobNotFound(_) => StatusCode::NOT_FOUND,
+            ServiceError::JobCancelled(_) | ServiceError::JobFinished(_) => StatusCode::CONFLICT,
+            ServiceError::ValidationError(_)
+            | ServiceError::UnsupportedCodec(_)
+            | ServiceError::CorruptInput(_)
+            | ServiceError::InfectedInput(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            ServiceError::ScanUnavailable(_) | ServiceError::QueueFull(_) => StatusCode::SERVICE_UNAVAILABLE,
+            ServiceError::InsufficientStorage(_) => StatusCode::INSUFFICIENT_STORAGE,
+            ServiceError::InputTooLarge(_) | ServiceError::PayloadTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            ServiceError::QuotaExceeded(_) => StatusCode::TOO_MANY_REQUESTS,
+            ServiceError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            ServiceError::Forbidden(_) => StatusCode::FORBIDDEN,
+        }
+    }
+
+    /// Render as RFC 7807 `application/problem+json`
+    fn error_response(&self) -> HttpResponse {
+        let status = self.status_code();
+        let mut problem = serde_json::json!({
+            "type": format!("urn:photo-rust:error:{}", self.code()),
+            "title": self.title(),
+            "status": status.as_u16(),
+            "code": self.code(),
+        });
+        if let Some(detail) = self.detail() {
+            problem["detail"] = serde_json::Value::String(detail);
+        }
+        if let ServiceError::ValidationError(violations) = self {
+            problem["violations"] = serde_json::json!(violations);
+        }
+
+        HttpResponse::build(status)
+            .content_type("application/problem+json")
+            .json(problem)
+    }
+}
+
+impl From<anyhow::Error> for ServiceError {
+    /// Recover typed errors raised by the services; anything else is a generic processing failure
+    fn from(error: anyhow::Error) -> Self {
+        let error = match error.downcast::<ServiceError>() {
+            Ok(service_error) => return service_error,
+            Err(error) => error,
+        };
+        if let Some(failure) = error.downcast_ref::<FfmpegFailure>() {
+            return failure.into();
+        }
+        ServiceError::FFmpegError(error.to_string())
+    }
+}
+
+/// Failure categories recognised in FFmpeg/ffprobe stderr
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FfmpegErrorKind {
+    CodecUnsupported,
+    FormatUnsupported,
+    CorruptInput,
+    DiskFull,
+    InputNotFound,
+    /// A busy or briefly unreachable resource, or a stray signal; worth running again
+    Transient,
+    Other,
+}
+
+impl FfmpegErrorKind {
+    /// Classify FFmpeg's stderr output by its well-known messages
+    pub fn classify(stderr: &str) -> Self {
+        const PATTERNS: &[(&str, FfmpegErrorKind)] = &[
+            ("No space left on device", FfmpegErrorKind::DiskFull),
+            ("Disk quota exceeded", FfmpegErrorKind::DiskFull),
+            ("Unknown encoder", FfmpegErrorKind::CodecUnsupported),
+            ("Unknown decoder", FfmpegErrorKind::CodecUnsupported),
+            ("Encoder not found", FfmpegErrorKind::CodecUnsupported),
+            ("Decoder not found", FfmpegErrorKind::CodecUnsupported),
+            ("codec not currently supported", FfmpegErrorKind::CodecUnsupported),
+            ("Could not find tag for codec", FfmpegErrorKind::CodecUnsupported),
+            ("Unable to find a suitable output format", FfmpegErrorKind::FormatUnsupported),
+            ("not a suitable output format", FfmpegErrorKind::FormatUnsupported),
+            ("Unknown input format", FfmpegErrorKind::FormatUnsupported),
+            ("Invalid data found when processing input", FfmpegErrorKind::CorruptInput),
+            ("moov atom not found", FfmpegErrorKind::CorruptInput),
+            ("Error while decoding", FfmpegErrorKind::CorruptInput),
+            ("No such file or directory", FfmpegErrorKind::InputNotFound),
+            ("Resource temporarily unavailable", FfmpegErrorKind::Transient),
+            ("Device or resource busy", FfmpegErrorKind::Transient),
+            ("Text file busy", FfmpegErrorKind::Transient),
+            ("Interrupted system call", FfmpegErrorKind::Transient),
+            ("Connection reset by peer", FfmpegErrorKind::Transient),
+            ("Connection timed out", FfmpegErrorKind::Transient),
+        ];
+
+        PATTERNS
+            .iter()
+            .find(|(pattern, _)| stderr.contains(pattern))
+            .map(|(_, kind)| *kind)
+            .unwrap_or(FfmpegErrorKind::Other)
+    }
+}
+
+/// A failed FFmpeg run, with the category derived from its stderr
+#[derive(Debug, Display)]
+#[display(fmt = "{}", message)]
+pub struct FfmpegFailure {
+    pub kind: FfmpegErrorKind,
+    pub message: String,
+}
+
+impl std::error::Error for FfmpegFailure {}
+
+impl From<&FfmpegFailure> for ServiceError {
+    fn from(failure: &FfmpegFailure) -> Self {
+        let message = failure.message.clone();
+        match failure.kind {
+            FfmpegErrorKind::CodecUnsupported => ServiceError::UnsupportedCodec(message),
+            FfmpegErrorKind::FormatUnsupported => ServiceError::InvalidFormat(message),
+            FfmpegErrorKind::CorruptInput => ServiceError::CorruptInput(message),
+            FfmpegErrorKind::DiskFull => ServiceError::InsufficientStorage(message),
+            FfmpegErrorKind::InputNotFound => ServiceError::FileNotFound(message),
+            FfmpegErrorKind::Transient => ServiceError::FFmpegError(message),
+            FfmpegErrorKind::Other => ServiceError::FFmpegError(message),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify_ffmpeg_stderr() {
+        assert_eq!(
+            FfmpegErrorKind::classify("Unknown encoder 'hevc_nvenc'"),
+            FfmpegErrorKind::CodecUnsupported
+        );
+        assert_eq!(
+            FfmpegErrorKind::classify("in.mp4: Invalid data found when processing input"),
+            FfmpegErrorKind::CorruptInput
+        );
+        assert_eq!(
+            FfmpegErrorKind::classify("av_interleaved_write_frame(): No space left on device"),
+            FfmpegErrorKind::DiskFull
+        );
+        assert_eq!(
+            FfmpegErrorKind::classify("/mnt/share/in.mp4: Device or resource busy"),
+            FfmpegErrorKind::Transient
+        );
+        assert_eq!(FfmpegErrorKind::classify("Conversion failed!"), FfmpegErrorKind::Other);
+    }
+
+    #[test]
+    fn test_typed_errors_survive_anyhow() {
+        let error: anyhow::Error = ServiceError::FileNotFound("in.mp4".to_string()).into();
+        assert_eq!(ServiceError::from(error).code(), "file_not_found");
+
+        let error: anyhow::Error = FfmpegFailure {
+            kind: FfmpegErrorKind::DiskFull,
+            message: "No space left on device".to_string(),
+        }
+        .into();
+        let service_error = ServiceError::from(error);
+        assert_eq!(service_error.code(), "insufficient_storage");
+        assert_eq!(service_error.status_code(), StatusCode::INSUFFICIENT_STORAGE);
+    }
+}
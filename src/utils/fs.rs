@@ -0,0 +1,57 @@
+use serde::Serialize;
+use std::ffi::CString;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+
+/// Scratch space for intermediate files: `WORKSPACE_DIR`, defaulting to the system temp dir
+pub fn workspace_dir() -> PathBuf {
+    std::env::var("WORKSPACE_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| std::env::temp_dir())
+}
+
+/// Total size in bytes and number of files below `path`; unreadable entries are skipped
+pub fn dir_usage(path: &Path) -> (u64, u64) {
+    let Ok(entries) = std::fs::read_dir(path) else {
+        return (0, 0);
+    };
+    let mut bytes = 0;
+    let mut files = 0;
+    for entry in entries.flatten() {
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        if file_type.is_dir() {
+            let (dir_bytes, dir_files) = dir_usage(&entry.path());
+            bytes += dir_bytes;
+            files += dir_files;
+        } else if file_type.is_file() {
+            bytes += entry.metadata().map(|metadata| metadata.len()).unwrap_or(0);
+            files += 1;
+        }
+    }
+    (bytes, files)
+}
+
+/// Size of a filesystem and the bytes unprivileged processes may still write to it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct DiskSpace {
+    pub total_bytes: u64,
+    pub available_bytes: u64,
+}
+
+/// `DiskSpace` of the filesystem holding `path`, or its nearest existing ancestor for paths
+/// not created yet
+pub fn disk_space(path: &Path) -> io::Result<DiskSpace> {
+    let existing = path.ancestors().find(|ancestor| ancestor.exists()).unwrap_or(Path::new("."));
+    let c_path = CString::new(existing.as_os_str().as_bytes())?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // The field types differ between platforms
+    #[allow(clippy::unnecessary_cast)]
+    let (blocks, available, fragment) = (stat.f_blocks as u64, stat.f_bavail as u64, stat.f_frsize as u64);
+    Ok(DiskSpace { total_bytes: blocks * fragment, available_bytes: available * fragment })
+}
@@ -1 +1,6 @@
-pub mod error; 
\ No newline at end of file
+pub mod error;
+pub mod validation;
+pub mod audit;
+pub mod fs;
+pub mod sandbox;
+pub mod children;
\ No newline at end of file
@@ -0,0 +1,181 @@
+use log::info;
+use std::ffi::{CStr, CString};
+use std::io;
+use std::os::unix::process::CommandExt;
+use std::process::Command;
+use std::sync::OnceLock;
+
+static SANDBOX: OnceLock<SandboxConfig> = OnceLock::new();
+
+/// Privileges FFmpeg/ffprobe children are started with; every field is opt-in per deployment
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SandboxConfig {
+    /// Run children as this uid/gid; needs the service itself to start as root
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+    /// Give children an empty network namespace (Linux, needs CAP_SYS_ADMIN)
+    pub no_network: bool,
+    /// RLIMIT_AS per child
+    pub max_memory_bytes: Option<u64>,
+    /// RLIMIT_CPU per child
+    pub max_cpu_secs: Option<u64>,
+    /// Existing cgroup v2 directory children join, e.g. one with `memory.max`/`cpu.max` set
+    pub cgroup: Option<String>,
+}
+
+impl SandboxConfig {
+    /// Read `FFMPEG_UID`, `FFMPEG_GID`, `FFMPEG_NO_NETWORK`, `FFMPEG_MAX_MEMORY_MB`,
+    /// `FFMPEG_MAX_CPU_SECS` and `FFMPEG_CGROUP`
+    pub fn from_env() -> Self {
+        Self::from_lookup(|name| std::env::var(name).ok())
+    }
+
+    fn from_lookup(lookup: impl Fn(&str) -> Option<String>) -> Self {
+        let var = |name: &str| lookup(name).map(|value| value.trim().to_string()).filter(|value| !value.is_empty());
+        let number = |name: &str| var(name).and_then(|value| value.parse::<u64>().ok());
+        Self {
+            uid: number("FFMPEG_UID").and_then(|uid| u32::try_from(uid).ok()),
+            gid: number("FFMPEG_GID").and_then(|gid| u32::try_from(gid).ok()),
+            no_network: var("FFMPEG_NO_NETWORK")
+                .map(|value| matches!(value.to_lowercase().as_str(), "1" | "true" | "yes" | "on"))
+                .unwrap_or(false),
+            max_memory_bytes: number("FFMPEG_MAX_MEMORY_MB").map(|mb| mb * 1024 * 1024),
+            max_cpu_secs: number("FFMPEG_MAX_CPU_SECS"),
+            cgroup: var("FFMPEG_CGROUP"),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        *self != Self::default()
+    }
+
+    /// Build a `Command` for `program` that applies this sandbox between fork and exec
+    pub fn command(&self, program: &str) -> Command {
+        let mut command = Command::new(program);
+        if !self.is_enabled() {
+            return command;
+        }
+
+        // Prepared up front: the pre_exec hook must not allocate
+        let cgroup_procs = self
+            .cgroup
+            .as_ref()
+            .and_then(|dir| CString::new(format!("{}/cgroup.procs", dir.trim_end_matches('/'))).ok());
+        let config = self.clone();
+        // SAFETY: the hook only issues async-signal-safe syscalls on pre-built data
+        unsafe {
+            command.pre_exec(move || config.restrict_child(cgroup_procs.as_deref()));
+        }
+        command
+    }
+
+    /// Runs in the forked child. Privileges are dropped last: joining the cgroup and
+    /// unsharing the network both need the parent's rights
+    fn restrict_child(&self, cgroup_procs: Option<&CStr>) -> io::Result<()> {
+        if let Some(path) = cgroup_procs {
+            // Writing "0" moves the writing process itself
+            let fd = check(unsafe { libc::open(path.as_ptr(), libc::O_WRONLY | libc::O_CLOEXEC) })?;
+            let written = unsafe { libc::write(fd, b"0".as_ptr().cast(), 1) };
+            unsafe { libc::close(fd) };
+            check(written as libc::c_int)?;
+        }
+        if self.no_network {
+            check(unsafe { libc::unshare(libc::CLONE_NEWNET) })?;
+        }
+        if let Some(bytes) = self.max_memory_bytes {
+            check(unsafe { libc::setrlimit(libc::RLIMIT_AS, &rlimit(bytes)) })?;
+        }
+        if let Some(secs) = self.max_cpu_secs {
+            check(unsafe { libc::setrlimit(libc::RLIMIT_CPU, &rlimit(secs)) })?;
+        }
+        if let Some(gid) = self.gid {
+            check(unsafe { libc::setgroups(0, std::ptr::null()) })?;
+            check(unsafe { libc::setgid(gid) })?;
+        }
+        if let Some(uid) = self.uid {
+            check(unsafe { libc::setuid(uid) })?;
+        }
+        Ok(())
+    }
+}
+
+fn rlimit(value: u64) -> libc::rlimit {
+    libc::rlimit { rlim_cur: value as libc::rlim_t, rlim_max: value as libc::rlim_t }
+}
+
+fn check(result: libc::c_int) -> io::Result<libc::c_int> {
+    if result < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(result)
+    }
+}
+
+/// Install the deployment's sandbox; without it children run with the service's own privileges
+pub fn init(config: SandboxConfig) {
+    if config.is_enabled() {
+        info!("FFmpeg sandbox enabled: {:?}", config);
+    }
+    let _ = SANDBOX.set(config);
+}
+
+/// `Command::new` for FFmpeg/ffprobe with the configured sandbox applied
+pub fn command(program: &str) -> Command {
+    SANDBOX.get_or_init(SandboxConfig::from_env).command(program)
+}
+
+/// Whether FFmpeg work must stay in restricted child processes rather than run in-process
+pub fn is_enabled() -> bool {
+    SANDBOX.get_or_init(SandboxConfig::from_env).is_enabled()
+}
+
+/// A fresh copy of a command built by `command`, e.g. to run it again; `Command` can't be
+/// cloned, and the sandbox hook is re-applied rather than copied
+pub fn rebuild(original: &Command) -> Command {
+    let mut copy = command(&original.get_program().to_string_lossy());
+    copy.args(original.get_args());
+    for (key, value) in original.get_envs() {
+        match value {
+            Some(value) => copy.env(key, value),
+            None => copy.env_remove(key),
+        };
+    }
+    if let Some(dir) = original.get_current_dir() {
+        copy.current_dir(dir);
+    }
+    copy
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_env_defaults_to_disabled() {
+        assert!(!SandboxConfig::from_lookup(|_| None).is_enabled());
+
+        let config = SandboxConfig::from_lookup(|name| match name {
+            "FFMPEG_UID" => Some("1000".to_string()),
+            "FFMPEG_NO_NETWORK" => Some("true".to_string()),
+            "FFMPEG_MAX_MEMORY_MB" => Some("512".to_string()),
+            "FFMPEG_CGROUP" => Some(" ".to_string()),
+            _ => None,
+        });
+        assert_eq!(config.uid, Some(1000));
+        assert!(config.no_network);
+        assert_eq!(config.max_memory_bytes, Some(512 * 1024 * 1024));
+        assert_eq!(config.cgroup, None);
+    }
+
+    #[test]
+    fn test_rlimits_reach_the_child() {
+        let config = SandboxConfig {
+            max_memory_bytes: Some(512 * 1024 * 1024),
+            max_cpu_secs: Some(30),
+            ..SandboxConfig::default()
+        };
+        let output = config.command("sh").arg("-c").arg("ulimit -v; ulimit -t").output().unwrap();
+        assert!(output.status.success());
+        assert_eq!(String::from_utf8_lossy(&output.stdout).split_whitespace().collect::<Vec<_>>(), ["524288", "30"]);
+    }
+}
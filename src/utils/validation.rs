@@ -0,0 +1,306 @@
+use actix_web::{error::{JsonPayloadError, QueryPayloadError}, HttpRequest};
+use serde::Serialize;
+use std::path::{Component, Path};
+use crate::middleware::auth;
+use crate::services::storage::{self, LocalFs, Storage, StorageUri};
+use crate::services::{capabilities, remote, tenants};
+use crate::utils::error::ServiceError;
+
+/// Video encoders accepted for `-c:v`
+pub static VIDEO_CODECS: &[&str] = &[
+    "libx264", "libx265", "h264", "hevc", "libvpx", "libvpx-vp9", "libaom-av1", "libsvtav1",
+    "mpeg4", "prores", "prores_ks", "mjpeg", "h264_nvenc", "hevc_nvenc", "h264_vaapi",
+    "hevc_vaapi", "h264_qsv", "hevc_qsv", "h264_videotoolbox", "hevc_videotoolbox", "copy",
+];
+
+/// Audio encoders accepted for `-acodec`
+pub static AUDIO_CODECS: &[&str] = &[
+    "aac", "libfdk_aac", "libmp3lame", "mp3", "libopus", "opus", "libvorbis", "vorbis", "flac",
+    "alac", "ac3", "pcm_s16le", "pcm_s24le", "copy",
+];
+
+/// Muxers accepted for `-f`
+pub static CONTAINER_FORMATS: &[&str] = &[
+    "mp4", "mov", "ipod", "webm", "matroska", "avi", "flv", "mpegts", "hls", "mp3", "wav", "ogg",
+    "opus", "flac", "adts", "ac3",
+];
+
+/// A single failed constraint on a request field
+#[derive(Debug, Clone, Serialize)]
+pub struct FieldViolation {
+    pub field: String,
+    pub message: String,
+}
+
+/// Typed requests check all of their constraints and report every violation at once
+pub trait Validate {
+    fn validate(&self) -> Result<(), ServiceError>;
+}
+
+/// Collects violations so a request is rejected with the full list instead of the first error
+#[derive(Debug, Default)]
+pub struct Violations(Vec<FieldViolation>);
+
+impl Violations {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, field: &str, message: impl Into<String>) {
+        self.0.push(FieldViolation {
+            field: field.to_string(),
+            message: message.into(),
+        });
+    }
+
+    /// Paths must be non-empty, free of NUL bytes and must not look like a command-line flag.
+    /// Requests made with a tenant's API key are also confined to that tenant's workspace
+    pub fn path(&mut self, field: &str, value: &str) {
+        if value.trim().is_empty() {
+            self.add(field, "must not be empty");
+        } else if value.contains('\0') {
+            self.add(field, "must not contain NUL bytes");
+        } else if value.starts_with('-') {
+            self.add(field, "must not start with '-'");
+        } else if let Some(tenant) = auth::current_tenant() {
+            let root = tenants::workspace(&tenant);
+            // Downloaded inputs and staged outputs of the tenant's job live in the temp root
+            if !tenants::is_within(&root, Path::new(value)) && !tenants::is_managed(&tenant, Path::new(value)) {
+                self.add(field, format!("must be inside the tenant workspace {}", root.display()));
+            }
+        }
+    }
+
+    /// Input paths may also be storage URIs, or `https://` URLs when the build has the `remote`
+    /// feature and the host is allowed by `REMOTE_INPUT_HOSTS`; both are downloaded before processing
+    pub fn input(&mut self, field: &str, value: &str) {
+        if storage::has_scheme(value) {
+            self.storage_uri(field, value);
+        } else if !remote::is_url(value) {
+            self.path(field, value);
+        } else if !cfg!(feature = "remote") {
+            self.add(field, "URL inputs need a build with the `remote` feature");
+        } else if remote::host(value).is_none() {
+            self.add(field, "URL inputs must use https://");
+        } else if !remote::RemoteConfig::from_env().allows(value) {
+            self.add(field, "URL host is not in REMOTE_INPUT_HOSTS or is an internal address");
+        }
+    }
+
+    /// Output paths may also be storage URIs, uploaded once the job succeeded
+    pub fn output(&mut self, field: &str, value: &str) {
+        if storage::has_scheme(value) {
+            self.storage_uri(field, value);
+        } else {
+            self.path(field, value);
+        }
+    }
+
+    /// `scheme://bucket/key` with a configured backend; `file://` keys are checked as paths
+    fn storage_uri(&mut self, field: &str, value: &str) {
+        match StorageUri::parse(value) {
+            None => self.add(field, "must look like scheme://bucket/key"),
+            Some(location) if location.scheme != "file" && !tenant_may_use(&location) => {
+                let prefix = tenants::object_prefix(&auth::current_tenant().unwrap_or_default());
+                self.add(field, format!("must be under {}://{}/{}", location.scheme, location.bucket, prefix))
+            }
+            Some(location) if !Storage::from_env().is_configured(&location.scheme) => {
+                self.add(field, format!("no storage backend is configured for {}://", location.scheme))
+            }
+            Some(location) if location.scheme == "file" => match LocalFs::from_env().resolve(&location) {
+                Ok(path) => self.path(field, &path.to_string_lossy()),
+                Err(_) => self.add(field, "must stay inside STORAGE_LOCAL_ROOT"),
+            },
+            Some(_) => {}
+        }
+    }
+
+    pub fn range(&mut self, field: &str, value: Option<u32>, min: u32, max: u32) {
+        if let Some(value) = value {
+            if value < min || value > max {
+                self.add(field, format!("must be between {} and {}", min, max));
+            }
+        }
+    }
+
+    pub fn one_of(&mut self, field: &str, value: Option<&str>, allowed: &[&str]) {
+        if let Some(value) = value {
+            if !allowed.contains(&value.to_lowercase().as_str()) {
+                self.add(field, format!("must be one of: {}", allowed.join(", ")));
+            }
+        }
+    }
+
+    /// Values passed as FFmpeg option arguments must be on an allowlist; anything looking
+    /// like an option (leading `-`) is called out explicitly
+    pub fn ffmpeg_token(&mut self, field: &str, value: Option<&str>, allowed: &[&str]) {
+        if let Some(value) = value {
+            if value.starts_with('-') {
+                self.add(field, "must not start with '-'");
+            } else {
+                self.one_of(field, Some(value), allowed);
+            }
+        }
+    }
+
+    /// An allowlisted encoder that must also be compiled into the local FFmpeg, so a request
+    /// for e.g. `hevc_nvenc` fails here instead of partway through the job
+    pub fn encoder(&mut self, field: &str, value: Option<&str>, allowed: &[&str]) {
+        let before = self.0.len();
+        self.ffmpeg_token(field, value, allowed);
+        if let Some(value) = value.filter(|value| *value != "copy" && self.0.len() == before) {
+            if capabilities::has_encoder(&value.to_lowercase()) == Some(false) {
+                self.add(field, format!("encoder '{}' is not available in this FFmpeg build", value));
+            }
+        }
+    }
+
+    /// Resolutions use FFmpeg's `WIDTHxHEIGHT` form, e.g. `1280x720`
+    pub fn resolution(&mut self, field: &str, value: Option<&str>) {
+        if let Some(value) = value {
+            if parse_resolution(value).is_none() {
+                self.add(field, "must look like WIDTHxHEIGHT, e.g. 1280x720");
+            }
+        }
+    }
+
+    /// Bitrates are a positive number with an optional k/M suffix, e.g. `128k` or `2.5M`
+    pub fn bitrate(&mut self, field: &str, value: Option<&str>) {
+        if let Some(value) = value {
+            if !is_bitrate(value) {
+                self.add(field, "must be a number with optional k/M suffix, e.g. 128k or 2.5M");
+            }
+        }
+    }
+
+    pub fn into_result(self) -> Result<(), ServiceError> {
+        if self.0.is_empty() {
+            Ok(())
+        } else {
+            Err(ServiceError::ValidationError(self.0))
+        }
+    }
+}
+
+pub fn parse_resolution(value: &str) -> Option<(u32, u32)> {
+    let (width, height) = value.split_once('x')?;
+    let width: u32 = width.parse().ok()?;
+    let height: u32 = height.parse().ok()?;
+    if width == 0 || height == 0 {
+        return None;
+    }
+    Some((width, height))
+}
+
+fn is_bitrate(value: &str) -> bool {
+    let number = value
+        .strip_suffix(['k', 'K', 'm', 'M'])
+        .unwrap_or(value);
+    !number.is_empty()
+        && number.chars().all(|c| c.is_ascii_digit() || c == '.')
+        && number.parse::<f64>().map(|n| n > 0.0).unwrap_or(false)
+}
+
+/// Report malformed or mistyped JSON bodies as a 422 violation instead of actix's plain-text 400
+pub fn json_error_handler(err: JsonPayloadError, _req: &HttpRequest) -> actix_web::Error {
+    let message = match &err {
+        JsonPayloadError::Deserialize(e) => e.to_string(),
+        JsonPayloadError::Overflow { .. } | JsonPayloadError::OverflowKnownLength { .. } => {
+            return ServiceError::PayloadTooLarge(err.to_string()).into();
+        }
+        other => other.to_string(),
+    };
+    ServiceError::ValidationError(vec![FieldViolation {
+        field: "body".to_string(),
+        message,
+    }])
+    .into()
+}
+
+/// Same as `json_error_handler`, for query strings (unknown enum values, bad timestamps, ...)
+pub fn query_error_handler(err: QueryPayloadError, _req: &HttpRequest) -> actix_web::Error {
+    let message = match &err {
+        QueryPayloadError::Deserialize(e) => e.to_string(),
+        other => other.to_string(),
+    };
+    ServiceError::ValidationError(vec![FieldViolation {
+        field: "query".to_string(),
+        message,
+    }])
+    .into()
+}
+
+/// Tenant keys only reach objects below their own prefix; `file://` keys are checked as paths
+fn tenant_may_use(location: &StorageUri) -> bool {
+    auth::current_tenant().is_none_or(|tenant| {
+        location.key.starts_with(&tenants::object_prefix(&tenant))
+            && Path::new(&location.key).components().all(|c| matches!(c, Component::Normal(_)))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ffmpeg_token_rejects_option_injection() {
+        let mut violations = Violations::new();
+        violations.ffmpeg_token("codec", Some("libx264"), VIDEO_CODECS);
+        violations.ffmpeg_token("codec", Some("-y"), VIDEO_CODECS);
+        violations.ffmpeg_token("format", Some("image2pipe"), CONTAINER_FORMATS);
+
+        match violations.into_result() {
+            Err(ServiceError::ValidationError(list)) => {
+                assert_eq!(list.len(), 2);
+                assert_eq!(list[0].message, "must not start with '-'");
+                assert!(list[1].message.starts_with("must be one of"));
+            }
+            other => panic!("expected validation error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_collects_every_violation() {
+        let mut violations = Violations::new();
+        violations.path("input_path", "");
+        violations.range("fps", Some(0), 1, 240);
+        violations.resolution("resolution", Some("1280:720"));
+        violations.bitrate("bitrate", Some("fast"));
+        violations.bitrate("audio_bitrate", Some("128k"));
+
+        match violations.into_result() {
+            Err(ServiceError::ValidationError(list)) => {
+                let fields: Vec<&str> = list.iter().map(|v| v.field.as_str()).collect();
+                assert_eq!(fields, vec!["input_path", "fps", "resolution", "bitrate"]);
+            }
+            other => panic!("expected validation error, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_tenant_keys_stay_under_their_prefix() {
+        let result = auth::with_tenant(Some("team-a".to_string()), async {
+            let mut violations = Violations::new();
+            violations.output("own", "s3://media/tenants/team-a/out.mp4");
+            violations.output("other", "s3://media/tenants/team-b/out.mp4");
+            violations.output("climbing", "s3://media/tenants/team-a/../team-b/out.mp4");
+            violations.output("bare", "gs://media/out.mp4");
+            violations.into_result()
+        })
+        .await;
+
+        match result {
+            Err(ServiceError::ValidationError(list)) => {
+                let prefixed: Vec<&str> = list
+                    .iter()
+                    .filter(|v| v.message.starts_with("must be under"))
+                    .map(|v| v.field.as_str())
+                    .collect();
+                assert_eq!(prefixed, vec!["other", "climbing", "bare"]);
+                let other = list.iter().find(|v| v.field == "other").unwrap();
+                assert_eq!(other.message, "must be under s3://media/tenants/team-a/");
+            }
+            other => panic!("expected validation error, got {:?}", other),
+        }
+    }
+}